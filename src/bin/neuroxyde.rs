@@ -0,0 +1,500 @@
+//! `neuroxyde` command-line tool: inspect, run, benchmark, validate, optimize and quantize ONNX models.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use neuroxyde::graph::{find_function, implicit_inputs, Graph};
+use neuroxyde::loader::ModelLoader;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::onnx::onnx_proto::{tensor_shape_proto, type_proto, FunctionProto, GraphProto, NodeProto, ValueInfoProto};
+use neuroxyde::ops::registry::OpRegistry;
+use neuroxyde::quantization::{quantize_static, CalibrationMethod, QuantizationOptions};
+use neuroxyde::runtime::{ExecutionMode, GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::{ElementType, Tensor};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+#[derive(Parser)]
+#[command(name = "neuroxyde", version, about = "Inspect, run and benchmark ONNX models")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show metadata, opsets, inputs/outputs and an operator histogram
+    Inspect {
+        model: PathBuf,
+    },
+    /// Run the model on inputs read from .npy/.pb files and write the outputs
+    Run {
+        model: PathBuf,
+        /// Input files (.npy/.pb), in graph input order or as `name=path`, or one .npz
+        #[arg(short, long = "input", num_args = 1..)]
+        inputs: Vec<String>,
+        /// Directory receiving one file per output
+        #[arg(short, long, default_value = ".")]
+        output_dir: PathBuf,
+        /// Format of the written outputs
+        #[arg(long, value_enum, default_value_t = Format::Npy)]
+        format: Format,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Measure latency percentiles and throughput
+    Bench {
+        model: PathBuf,
+        /// Input files; random inputs matching the declared shapes are used if omitted
+        #[arg(short, long = "input", num_args = 1..)]
+        inputs: Vec<String>,
+        /// Value of the dimensions the model leaves symbolic or unknown
+        #[arg(long, default_value_t = 1)]
+        dim: usize,
+        #[arg(long, default_value_t = 5)]
+        warmup: usize,
+        #[arg(short = 'n', long, default_value_t = 100)]
+        iterations: usize,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Check the graph structure and operator coverage
+    Validate {
+        model: PathBuf,
+    },
+    /// Apply the graph optimizations and write the resulting model
+    Optimize {
+        model: PathBuf,
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = Level::All)]
+        level: Level,
+    },
+    /// Calibrate on sample inputs and write the model with QuantizeLinear/DequantizeLinear pairs
+    Quantize {
+        model: PathBuf,
+        output: PathBuf,
+        /// Calibration samples, one per file: .npz keyed by input name, or .npy/.pb for single-input models
+        #[arg(short, long = "input", num_args = 1.., required = true)]
+        inputs: Vec<String>,
+        #[arg(long, value_enum, default_value_t = Method::MinMax)]
+        method: Method,
+        /// Percentile of |x| kept by `--method percentile`
+        #[arg(long, default_value_t = 99.99)]
+        percentile: f32,
+        /// Quantize weights per output channel
+        #[arg(long)]
+        per_channel: bool,
+        /// Quantize activations to int8 instead of uint8
+        #[arg(long)]
+        signed: bool,
+    },
+}
+
+#[derive(Args)]
+struct SessionArgs {
+    /// Threads available to each operator (0: one per core)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    /// Run independent nodes concurrently
+    #[arg(long)]
+    parallel: bool,
+    #[arg(long, value_enum, default_value_t = Level::All)]
+    level: Level,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Npy,
+    Pb,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Method {
+    MinMax,
+    Percentile,
+    Entropy,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Level {
+    Disable,
+    Basic,
+    Extended,
+    All,
+}
+
+impl From<Level> for GraphOptimizationLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Disable => GraphOptimizationLevel::DisableAll,
+            Level::Basic => GraphOptimizationLevel::Basic,
+            Level::Extended => GraphOptimizationLevel::Extended,
+            Level::All => GraphOptimizationLevel::All,
+        }
+    }
+}
+
+impl SessionArgs {
+    fn options(&self) -> SessionOptions {
+        let mode = if self.parallel { ExecutionMode::Parallel } else { ExecutionMode::Sequential };
+        SessionOptions::new()
+            .with_intra_op_num_threads(self.threads)
+            .with_execution_mode(mode)
+            .with_graph_optimization_level(self.level.into())
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Inspect { model } => inspect(&model),
+        Command::Run { model, inputs, output_dir, format, session } => run(&model, &inputs, &output_dir, format, &session),
+        Command::Bench { model, inputs, dim, warmup, iterations, session } => {
+            bench(&model, &inputs, dim, warmup, iterations, &session)
+        }
+        Command::Validate { model } => validate(&model),
+        Command::Optimize { model, output, level } => optimize(&model, &output, level),
+        Command::Quantize { model, output, inputs, method, percentile, per_channel, signed } => {
+            let calibration = match method {
+                Method::MinMax => CalibrationMethod::MinMax,
+                Method::Percentile => CalibrationMethod::Percentile(percentile),
+                Method::Entropy => CalibrationMethod::Entropy,
+            };
+            let options = QuantizationOptions::new()
+                .with_calibration(calibration)
+                .with_per_channel(per_channel)
+                .with_activation_type(if signed { ElementType::I8 } else { ElementType::U8 });
+            quantize(&model, &output, &inputs, &options)
+        }
+    };
+    match result {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn graph_of(loader: &ModelLoader) -> anyhow::Result<&GraphProto> {
+    loader.model.graph.as_ref().ok_or_else(|| anyhow::anyhow!("Model has no graph"))
+}
+
+/// Inputs that must be fed at run time, in order: declared inputs that are not initializers.
+fn runtime_inputs(graph: &GraphProto) -> Vec<&ValueInfoProto> {
+    let initializers: HashSet<&str> = graph.initializer.iter().map(|t| t.name.as_str()).collect();
+    graph.input.iter().filter(|vi| !initializers.contains(vi.name.as_str())).collect()
+}
+
+/// Element type and dimensions declared for a value (`None` for symbolic or unknown dimensions).
+fn declared_type(vi: &ValueInfoProto) -> (String, Vec<String>, Vec<Option<usize>>) {
+    let Some(type_proto::Value::TensorType(t)) = vi.r#type.as_ref().and_then(|t| t.value.as_ref()) else {
+        return ("?".to_string(), Vec::new(), Vec::new());
+    };
+    let elem = DataType::try_from(t.elem_type).map_or_else(|_| t.elem_type.to_string(), |d| d.as_str_name().to_string());
+    let dims = t.shape.as_ref().map(|s| s.dim.as_slice()).unwrap_or_default();
+    let labels = dims.iter()
+        .map(|d| match &d.value {
+            Some(tensor_shape_proto::dimension::Value::DimValue(v)) => v.to_string(),
+            Some(tensor_shape_proto::dimension::Value::DimParam(p)) if !p.is_empty() => p.clone(),
+            _ => "?".to_string(),
+        })
+        .collect();
+    let sizes = dims.iter()
+        .map(|d| match &d.value {
+            Some(tensor_shape_proto::dimension::Value::DimValue(v)) if *v >= 0 => Some(*v as usize),
+            _ => None,
+        })
+        .collect();
+    (elem, labels, sizes)
+}
+
+fn describe(vi: &ValueInfoProto) -> String {
+    let (elem, dims, _) = declared_type(vi);
+    format!("{}: {}[{}]", vi.name, elem, dims.join(", "))
+}
+
+fn inspect(path: &Path) -> anyhow::Result<ExitCode> {
+    let loader = ModelLoader::load_from_file(path)?;
+    let model = &loader.model;
+    let graph = graph_of(&loader)?;
+
+    println!("Model: {}", path.display());
+    println!("  IR version: {}", model.ir_version);
+    println!("  Producer: {} {}", model.producer_name, model.producer_version);
+    if !model.domain.is_empty() {
+        println!("  Domain: {}", model.domain);
+    }
+    println!("  Model version: {}", model.model_version);
+    for prop in &model.metadata_props {
+        println!("  {}: {}", prop.key, prop.value);
+    }
+    println!("Opsets:");
+    for opset in &model.opset_import {
+        let domain = if opset.domain.is_empty() { "ai.onnx" } else { opset.domain.as_str() };
+        println!("  {} v{}", domain, opset.version);
+    }
+    println!("Inputs:");
+    for vi in runtime_inputs(graph) {
+        println!("  {}", describe(vi));
+    }
+    println!("Outputs:");
+    for vi in &graph.output {
+        println!("  {}", describe(vi));
+    }
+    let bytes: usize = graph.initializer.iter().map(|t| {
+        let count: i64 = t.dims.iter().product();
+        count as usize * if t.data_type == DataType::Double as i32 || t.data_type == DataType::Int64 as i32 { 8 } else { 4 }
+    }).sum();
+    println!("Initializers: {} ({:.2} MiB)", graph.initializer.len(), bytes as f64 / (1024.0 * 1024.0));
+
+    let registry = OpRegistry::new();
+    let mut histogram: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    let mut examples: BTreeMap<(&str, &str), &NodeProto> = BTreeMap::new();
+    for node in &graph.node {
+        *histogram.entry((node.domain.as_str(), node.op_type.as_str())).or_default() += 1;
+        examples.entry((node.domain.as_str(), node.op_type.as_str())).or_insert(node);
+    }
+    println!("Operators ({} nodes):", graph.node.len());
+    let mut unsupported = 0;
+    for (key, count) in &histogram {
+        let (domain, op_type) = key;
+        let name = if domain.is_empty() { op_type.to_string() } else { format!("{}::{}", domain, op_type) };
+        let note = if registry.get_in_domain(domain, op_type).is_some() {
+            ""
+        } else if find_function(&model.functions, examples[key]).is_some() {
+            "  (function)"
+        } else {
+            unsupported += 1;
+            "  UNSUPPORTED"
+        };
+        println!("  {:<32} {:>6}{}", name, count, note);
+    }
+    if unsupported > 0 {
+        println!("{} operator type(s) are not supported", unsupported);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Loads the input files, either all positional, all `name=path` or a single .npz keyed by input name.
+fn load_inputs(graph: &GraphProto, args: &[String]) -> anyhow::Result<Vec<Tensor>> {
+    let expected = runtime_inputs(graph);
+    if let [archive] = args {
+        if archive.ends_with(".npz") {
+            let mut arrays: HashMap<String, Tensor> = Tensor::read_npz(archive)?.into_iter().collect();
+            return expected.iter()
+                .map(|vi| arrays.remove(&vi.name).ok_or_else(|| anyhow::anyhow!("No array '{}' in {}", vi.name, archive)))
+                .collect();
+        }
+    }
+    if args.iter().all(|a| !a.contains('=')) {
+        if args.len() != expected.len() {
+            return Err(anyhow::anyhow!("Model expects {} input(s), got {} file(s)", expected.len(), args.len()));
+        }
+        return args.iter().map(Tensor::load).collect();
+    }
+    let mut by_name: HashMap<&str, &str> = HashMap::new();
+    for arg in args {
+        let (name, path) = arg.split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected `name=path`, got '{}'", arg))?;
+        by_name.insert(name, path);
+    }
+    expected.iter()
+        .map(|vi| {
+            let path = by_name.get(vi.name.as_str())
+                .ok_or_else(|| anyhow::anyhow!("No file given for input '{}'", vi.name))?;
+            Tensor::load(path)
+        })
+        .collect()
+}
+
+/// Deterministic pseudo-random inputs matching the declared shapes.
+fn synthetic_inputs(graph: &GraphProto, dim: usize) -> Vec<Tensor> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    runtime_inputs(graph).into_iter()
+        .map(|vi| {
+            let shape: Vec<usize> = declared_type(vi).2.into_iter().map(|d| d.unwrap_or(dim)).collect();
+            let len = shape.iter().product();
+            let data = (0..len)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    (state >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0
+                })
+                .collect();
+            Tensor::new(data, shape)
+        })
+        .collect()
+}
+
+fn run(path: &Path, inputs: &[String], output_dir: &Path, format: Format, args: &SessionArgs) -> anyhow::Result<ExitCode> {
+    let loader = ModelLoader::load_from_file(path)?;
+    let inputs = load_inputs(graph_of(&loader)?, inputs)?;
+    let session = InferenceSession::with_options(Graph::from_model(&loader)?, args.options())?;
+
+    let start = Instant::now();
+    let outputs = session.run(&inputs)?;
+    let elapsed = start.elapsed();
+
+    std::fs::create_dir_all(output_dir)?;
+    for (name, tensor) in session.graph.outputs.iter().zip(&outputs) {
+        // Value names may contain path separators
+        let file_name: String = name.chars().map(|c| if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' { c } else { '_' }).collect();
+        let file = output_dir.join(match format {
+            Format::Npy => format!("{}.npy", file_name),
+            Format::Pb => format!("{}.pb", file_name),
+        });
+        tensor.save(&file, name)?;
+        println!("{}: {:?} -> {}", name, tensor.shape(), file.display());
+    }
+    println!("Inference took {:.3} ms", elapsed.as_secs_f64() * 1e3);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn bench(
+    path: &Path,
+    inputs: &[String],
+    dim: usize,
+    warmup: usize,
+    iterations: usize,
+    args: &SessionArgs,
+) -> anyhow::Result<ExitCode> {
+    if iterations == 0 {
+        return Err(anyhow::anyhow!("At least one iteration is required"));
+    }
+    let loader = ModelLoader::load_from_file(path)?;
+    let graph = graph_of(&loader)?;
+    let inputs = if inputs.is_empty() { synthetic_inputs(graph, dim) } else { load_inputs(graph, inputs)? };
+
+    let start = Instant::now();
+    let session = InferenceSession::with_options(Graph::from_model(&loader)?, args.options())?;
+    println!("Session created in {:.3} ms ({} nodes, {} kernels, {} intra-op thread(s), {:?})",
+        start.elapsed().as_secs_f64() * 1e3,
+        session.graph.nodes.len(),
+        session.kernel_backend(),
+        session.intra_op_num_threads(),
+        session.execution_mode());
+    for input in &inputs {
+        println!("Input shape: {:?}", input.shape());
+    }
+
+    for _ in 0..warmup {
+        session.run(&inputs)?;
+    }
+    let mut latencies = Vec::with_capacity(iterations);
+    let total = Instant::now();
+    for _ in 0..iterations {
+        let start = Instant::now();
+        session.run(&inputs)?;
+        latencies.push(start.elapsed());
+    }
+    let total = total.elapsed();
+    latencies.sort_unstable();
+
+    let ms = |d: Duration| d.as_secs_f64() * 1e3;
+    let percentile = |p: f64| latencies[((p / 100.0 * iterations as f64).ceil() as usize).clamp(1, iterations) - 1];
+    println!("Iterations: {} (after {} warmup)", iterations, warmup);
+    println!("Latency (ms): min {:.3}  mean {:.3}  max {:.3}",
+        ms(latencies[0]), ms(total) / iterations as f64, ms(latencies[iterations - 1]));
+    println!("Percentiles (ms): p50 {:.3}  p90 {:.3}  p95 {:.3}  p99 {:.3}",
+        ms(percentile(50.0)), ms(percentile(90.0)), ms(percentile(95.0)), ms(percentile(99.0)));
+    println!("Throughput: {:.2} inferences/s", iterations as f64 / total.as_secs_f64());
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Structural problems of a graph, as human-readable messages.
+fn check(graph: &GraphProto, functions: &[FunctionProto]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let registry = OpRegistry::new();
+    let available: HashSet<&str> = graph.input.iter().map(|vi| vi.name.as_str())
+        .chain(graph.initializer.iter().map(|t| t.name.as_str()))
+        .collect();
+
+    let mut produced: HashMap<&str, &str> = HashMap::new();
+    for node in &graph.node {
+        for output in node.output.iter().filter(|o| !o.is_empty()) {
+            if let Some(previous) = produced.insert(output, &node.name) {
+                problems.push(format!("value '{}' is produced by both '{}' and '{}'", output, previous, node.name));
+            }
+        }
+    }
+    for (idx, node) in graph.node.iter().enumerate() {
+        let label = if node.name.is_empty() { format!("#{}", idx) } else { format!("'{}'", node.name) };
+        if node.op_type.is_empty() {
+            problems.push(format!("node {} has no op_type", label));
+        } else if registry.resolve(node).is_none() && find_function(functions, node).is_none() {
+            problems.push(format!("node {}: unsupported operator '{}'", label, node.op_type));
+        }
+        for input in node.input.iter().filter(|i| !i.is_empty()) {
+            if !available.contains(input.as_str()) && !produced.contains_key(input.as_str()) {
+                problems.push(format!("node {}: input '{}' is never defined", label, input));
+            }
+        }
+        for captured in implicit_inputs(node) {
+            if !available.contains(captured.as_str()) && !produced.contains_key(captured.as_str()) {
+                problems.push(format!("node {}: a subgraph reads '{}', which is never defined", label, captured));
+            }
+        }
+        let mut bodies: Vec<&GraphProto> = node.attribute.iter().flat_map(|a| a.g.iter().chain(&a.graphs)).collect();
+        while let Some(body) = bodies.pop() {
+            for inner in &body.node {
+                if registry.resolve(inner).is_none() && find_function(functions, inner).is_none() {
+                    problems.push(format!("node {}: unsupported operator '{}' in subgraph '{}'", label, inner.op_type, body.name));
+                }
+                bodies.extend(inner.attribute.iter().flat_map(|a| a.g.iter().chain(&a.graphs)));
+            }
+        }
+    }
+    for vi in &graph.output {
+        if !available.contains(vi.name.as_str()) && !produced.contains_key(vi.name.as_str()) {
+            problems.push(format!("output '{}' is never produced", vi.name));
+        }
+    }
+    problems
+}
+
+fn validate(path: &Path) -> anyhow::Result<ExitCode> {
+    let loader = ModelLoader::load_from_file(path)?;
+    let mut problems = check(graph_of(&loader)?, &loader.model.functions);
+    if problems.is_empty() {
+        // Also catches cycles
+        if let Err(err) = Graph::from_model(&loader).and_then(|mut g| g.topological_sort()) {
+            problems.push(err.to_string());
+        }
+    }
+    if problems.is_empty() {
+        println!("{}: OK", path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+    for problem in &problems {
+        println!("{}: {}", path.display(), problem);
+    }
+    println!("{} problem(s) found", problems.len());
+    Ok(ExitCode::FAILURE)
+}
+
+fn optimize(path: &Path, output: &Path, level: Level) -> anyhow::Result<ExitCode> {
+    let loader = ModelLoader::load_from_file(path)?;
+    let before = graph_of(&loader)?.node.len();
+    let options = SessionOptions::new().with_graph_optimization_level(level.into());
+    let session = InferenceSession::with_options(Graph::from_model(&loader)?, options)?;
+    ModelLoader { model: session.graph.to_model()? }.save_to_file(output)?;
+    println!("{} -> {}: {} -> {} nodes", path.display(), output.display(), before, session.graph.nodes.len());
+    Ok(ExitCode::SUCCESS)
+}
+
+fn quantize(path: &Path, output: &Path, inputs: &[String], options: &QuantizationOptions) -> anyhow::Result<ExitCode> {
+    let loader = ModelLoader::load_from_file(path)?;
+    let samples = inputs.iter()
+        .map(|file| load_inputs(graph_of(&loader)?, std::slice::from_ref(file)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let graph = quantize_static(&Graph::from_model(&loader)?, samples, options)?;
+    ModelLoader { model: graph.to_model()? }.save_to_file(output)?;
+    let activations = graph.nodes.iter().filter(|n| n.op_type == "QuantizeLinear").count();
+    println!(
+        "{} -> {}: {} activation(s) quantized, calibrated on {} sample(s)",
+        path.display(), output.display(), activations, inputs.len()
+    );
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,338 @@
+//! Programmatic construction of graphs, without an .onnx file.
+//!
+//! Every call is checked against what has been declared so far: node inputs
+//! must already exist, names cannot be produced twice and operators must be
+//! known to the runtime. Nodes are therefore added in execution order.
+
+use super::Graph;
+use crate::loader::ModelLoader;
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use crate::onnx::onnx_proto::{
+    attribute_proto::AttributeType, tensor_shape_proto, type_proto, AttributeProto, GraphProto, ModelProto,
+    NodeProto, OperatorSetIdProto, TensorShapeProto, TypeProto, ValueInfoProto,
+};
+use crate::ops::registry::OpRegistry;
+use crate::tensor::Tensor;
+use std::collections::HashSet;
+
+/// Typed value of a node attribute.
+#[derive(Debug, Clone)]
+pub enum AttributeValue {
+    Int(i64),
+    Float(f32),
+    String(String),
+    Ints(Vec<i64>),
+    Floats(Vec<f32>),
+    Strings(Vec<String>),
+    Tensor(Tensor),
+    /// Body of a control-flow node, e.g. from `GraphBuilder::to_subgraph`.
+    Graph(GraphProto),
+}
+
+impl From<i64> for AttributeValue {
+    fn from(v: i64) -> Self {
+        AttributeValue::Int(v)
+    }
+}
+
+impl From<f32> for AttributeValue {
+    fn from(v: f32) -> Self {
+        AttributeValue::Float(v)
+    }
+}
+
+impl From<&str> for AttributeValue {
+    fn from(v: &str) -> Self {
+        AttributeValue::String(v.to_string())
+    }
+}
+
+impl From<Vec<i64>> for AttributeValue {
+    fn from(v: Vec<i64>) -> Self {
+        AttributeValue::Ints(v)
+    }
+}
+
+impl From<Vec<f32>> for AttributeValue {
+    fn from(v: Vec<f32>) -> Self {
+        AttributeValue::Floats(v)
+    }
+}
+
+impl From<Tensor> for AttributeValue {
+    fn from(v: Tensor) -> Self {
+        AttributeValue::Tensor(v)
+    }
+}
+
+impl From<GraphProto> for AttributeValue {
+    fn from(v: GraphProto) -> Self {
+        AttributeValue::Graph(v)
+    }
+}
+
+impl AttributeValue {
+    /// Encodes the value as the attribute `name` of a node.
+    pub fn to_proto(&self, name: &str) -> AttributeProto {
+        let proto = AttributeProto { name: name.to_string(), ..Default::default() };
+        match self {
+            AttributeValue::Int(i) => AttributeProto { i: *i, r#type: AttributeType::Int as i32, ..proto },
+            AttributeValue::Float(f) => AttributeProto { f: *f, r#type: AttributeType::Float as i32, ..proto },
+            AttributeValue::String(s) => AttributeProto {
+                s: s.as_bytes().to_vec(),
+                r#type: AttributeType::String as i32,
+                ..proto
+            },
+            AttributeValue::Ints(ints) => AttributeProto { ints: ints.clone(), r#type: AttributeType::Ints as i32, ..proto },
+            AttributeValue::Floats(floats) => AttributeProto {
+                floats: floats.clone(),
+                r#type: AttributeType::Floats as i32,
+                ..proto
+            },
+            AttributeValue::Strings(strings) => AttributeProto {
+                strings: strings.iter().map(|s| s.as_bytes().to_vec()).collect(),
+                r#type: AttributeType::Strings as i32,
+                ..proto
+            },
+            AttributeValue::Tensor(t) => AttributeProto {
+                t: Some(t.to_proto("")),
+                r#type: AttributeType::Tensor as i32,
+                ..proto
+            },
+            AttributeValue::Graph(g) => AttributeProto {
+                g: Some(g.clone()),
+                r#type: AttributeType::Graph as i32,
+                ..proto
+            },
+        }
+    }
+}
+
+/// Builds a `Graph` (or a `ModelProto`) one input, initializer and node at a time.
+///
+/// ```
+/// use neuroxyde::graph::Graph;
+/// use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+/// use neuroxyde::tensor::Tensor;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let mut builder = Graph::builder("affine");
+/// builder
+///     .input("x", DataType::Float, &[1, 4])?
+///     .initializer("w", Tensor::new(vec![0.5; 8], vec![4, 2]))?
+///     .node("MatMul", &["x", "w"], &["xw"], &[])?
+///     .node("LeakyRelu", &["xw"], &["y"], &[("alpha", 0.1f32.into())])?
+///     .output("y")?;
+/// let graph = builder.build()?;
+/// # assert_eq!(graph.nodes.len(), 2);
+/// # Ok(())
+/// # }
+/// ```
+pub struct GraphBuilder {
+    registry: OpRegistry,
+    graph: GraphProto,
+    opset_import: Vec<OperatorSetIdProto>,
+    /// Every name that can be read by a node: inputs, initializers and node outputs.
+    defined: HashSet<String>,
+}
+
+impl Graph {
+    /// Starts a `GraphBuilder` for a graph named `name`.
+    pub fn builder(name: &str) -> GraphBuilder {
+        GraphBuilder::new(name)
+    }
+}
+
+impl GraphBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            registry: OpRegistry::new(),
+            graph: GraphProto { name: name.to_string(), ..Default::default() },
+            opset_import: vec![OperatorSetIdProto { domain: String::new(), version: super::DEFAULT_OPSET }],
+            defined: HashSet::new(),
+        }
+    }
+
+    /// Checks nodes against `registry` instead of the built-in operators, so
+    /// that graphs can use custom operators.
+    pub fn with_registry(mut self, registry: OpRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    fn define(&mut self, name: &str) -> anyhow::Result<()> {
+        self.check_new(name)?;
+        self.defined.insert(name.to_string());
+        Ok(())
+    }
+
+    /// Checks that `name` can name a new value.
+    fn check_new(&self, name: &str) -> anyhow::Result<()> {
+        if name.is_empty() {
+            return Err(anyhow::anyhow!("Graph '{}': value names cannot be empty", self.graph.name));
+        }
+        if self.defined.contains(name) {
+            return Err(anyhow::anyhow!("Graph '{}': value '{}' is already defined", self.graph.name, name));
+        }
+        Ok(())
+    }
+
+    /// Declares a graph input. Negative dimensions are left unknown, so any size is accepted at run time.
+    pub fn input(&mut self, name: &str, elem_type: DataType, shape: &[i64]) -> anyhow::Result<&mut Self> {
+        self.define(name)?;
+        let dim = shape.iter()
+            .map(|&d| tensor_shape_proto::Dimension {
+                value: (d >= 0).then_some(tensor_shape_proto::dimension::Value::DimValue(d)),
+                ..Default::default()
+            })
+            .collect();
+        self.graph.input.push(ValueInfoProto {
+            name: name.to_string(),
+            r#type: Some(TypeProto {
+                value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                    elem_type: elem_type as i32,
+                    shape: Some(TensorShapeProto { dim }),
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        Ok(self)
+    }
+
+    /// Adds an initializer stored with the tensor's own element type.
+    pub fn initializer(&mut self, name: &str, tensor: Tensor) -> anyhow::Result<&mut Self> {
+        self.define(name)?;
+        self.graph.initializer.push(tensor.to_proto(name));
+        Ok(self)
+    }
+
+    /// Adds an initializer stored with element type `data_type` (e.g. the INT64 target of a Reshape).
+    pub fn initializer_as(&mut self, name: &str, tensor: Tensor, data_type: DataType) -> anyhow::Result<&mut Self> {
+        self.define(name)?;
+        self.graph.initializer.push(tensor.to_proto_as(name, data_type as i32)?);
+        Ok(self)
+    }
+
+    /// Appends a default-domain node reading `inputs` (empty names mark absent optional inputs).
+    pub fn node(
+        &mut self,
+        op_type: &str,
+        inputs: &[&str],
+        outputs: &[&str],
+        attributes: &[(&str, AttributeValue)],
+    ) -> anyhow::Result<&mut Self> {
+        self.domain_node("", op_type, inputs, outputs, attributes)
+    }
+
+    /// Appends a node of operator set `domain`, which is imported (version 1) if needed.
+    pub fn domain_node(
+        &mut self,
+        domain: &str,
+        op_type: &str,
+        inputs: &[&str],
+        outputs: &[&str],
+        attributes: &[(&str, AttributeValue)],
+    ) -> anyhow::Result<&mut Self> {
+        let name = format!("{}_{}", op_type, self.graph.node.len());
+        if self.registry.get_in_domain(domain, op_type).is_none() {
+            return Err(anyhow::anyhow!("Node '{}': unsupported operator '{}'", name, op_type));
+        }
+        if let Some(missing) = inputs.iter().find(|i| !i.is_empty() && !self.defined.contains(**i)) {
+            return Err(anyhow::anyhow!("Node '{}': input '{}' is not defined yet", name, missing));
+        }
+        let attribute: Vec<_> = attributes.iter().map(|(attr, value)| value.to_proto(attr)).collect();
+        let captured = super::implicit_inputs(&NodeProto { attribute: attribute.clone(), ..Default::default() });
+        if let Some(missing) = captured.iter().find(|c| !self.defined.contains(*c)) {
+            return Err(anyhow::anyhow!("Node '{}': a subgraph reads '{}', which is not defined yet", name, missing));
+        }
+        if outputs.is_empty() {
+            return Err(anyhow::anyhow!("Node '{}': expected at least one output", name));
+        }
+        let mut seen = HashSet::new();
+        if let Some((duplicate, _)) = attributes.iter().find(|(attr, _)| !seen.insert(*attr)) {
+            return Err(anyhow::anyhow!("Node '{}': attribute '{}' given twice", name, duplicate));
+        }
+        // Every output is checked before any is defined, so a failed call changes nothing
+        let mut seen = HashSet::new();
+        for output in outputs.iter().filter(|o| !o.is_empty()) {
+            self.check_new(output)?;
+            if !seen.insert(*output) {
+                return Err(anyhow::anyhow!("Node '{}': output '{}' given twice", name, output));
+            }
+        }
+        self.defined.extend(outputs.iter().filter(|o| !o.is_empty()).map(|o| o.to_string()));
+
+        if !self.opset_import.iter().any(|o| o.domain == domain) {
+            self.opset_import.push(OperatorSetIdProto { domain: domain.to_string(), version: 1 });
+        }
+        self.graph.node.push(NodeProto {
+            input: inputs.iter().map(|s| s.to_string()).collect(),
+            output: outputs.iter().map(|s| s.to_string()).collect(),
+            name,
+            op_type: op_type.to_string(),
+            domain: domain.to_string(),
+            attribute,
+            ..Default::default()
+        });
+        Ok(self)
+    }
+
+    /// Declares `name`, a value defined earlier, as a graph output.
+    pub fn output(&mut self, name: &str) -> anyhow::Result<&mut Self> {
+        if !self.defined.contains(name) {
+            return Err(anyhow::anyhow!("Graph '{}': output '{}' is not defined", self.graph.name, name));
+        }
+        if self.graph.output.iter().any(|o| o.name == name) {
+            return Err(anyhow::anyhow!("Graph '{}': output '{}' declared twice", self.graph.name, name));
+        }
+        self.graph.output.push(ValueInfoProto { name: name.to_string(), ..Default::default() });
+        Ok(self)
+    }
+
+    /// Declares `name`, a value of the enclosing graph, as readable by the
+    /// nodes of a subgraph being built.
+    pub fn capture(&mut self, name: &str) -> anyhow::Result<&mut Self> {
+        self.define(name)?;
+        Ok(self)
+    }
+
+    /// Overrides the version of the operator set imported for `domain` ("" is the default domain).
+    pub fn opset(&mut self, domain: &str, version: i64) -> &mut Self {
+        match self.opset_import.iter_mut().find(|o| o.domain == domain) {
+            Some(o) => o.version = version,
+            None => self.opset_import.push(OperatorSetIdProto { domain: domain.to_string(), version }),
+        }
+        self
+    }
+
+    /// Returns the model holding the graph built so far.
+    pub fn to_model(&self) -> anyhow::Result<ModelProto> {
+        if self.graph.output.is_empty() {
+            return Err(anyhow::anyhow!("Graph '{}' has no output", self.graph.name));
+        }
+        Ok(ModelProto {
+            ir_version: super::DEFAULT_IR_VERSION,
+            opset_import: self.opset_import.clone(),
+            producer_name: "neuroxyde".to_string(),
+            producer_version: env!("CARGO_PKG_VERSION").to_string(),
+            graph: Some(self.graph.clone()),
+            ..Default::default()
+        })
+    }
+
+    /// Returns the graph built so far as the body of a control-flow node
+    /// (an If branch, a Loop or Scan body).
+    pub fn to_subgraph(&self) -> anyhow::Result<GraphProto> {
+        if self.graph.output.is_empty() {
+            return Err(anyhow::anyhow!("Graph '{}' has no output", self.graph.name));
+        }
+        Ok(self.graph.clone())
+    }
+
+    /// Returns a runnable `Graph`.
+    pub fn build(&self) -> anyhow::Result<Graph> {
+        Graph::from_model(&ModelLoader { model: self.to_model()? })
+    }
+}
+
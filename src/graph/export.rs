@@ -0,0 +1,279 @@
+//! Graphviz DOT and Mermaid rendering of a graph, and extraction of the
+//! subgraph around a node to keep large models readable.
+//!
+//! Operators are drawn as boxes, graph inputs and outputs as ellipses and
+//! initializers as notes (DOT) or cylinders (Mermaid). Edges are labeled with
+//! the value name and, when known, its shape. No tensor data is ever printed.
+
+use super::Graph;
+use crate::onnx::onnx_proto::{tensor_shape_proto, type_proto, ValueInfoProto};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write as _;
+
+/// Endpoint of an edge in the rendered graph.
+#[derive(Clone, Copy)]
+enum Vertex {
+    Input(usize),
+    Initializer(usize),
+    Node(usize),
+    Output(usize),
+}
+
+impl Vertex {
+    fn id(self) -> String {
+        match self {
+            Vertex::Input(i) => format!("i{}", i),
+            Vertex::Initializer(i) => format!("w{}", i),
+            Vertex::Node(i) => format!("n{}", i),
+            Vertex::Output(i) => format!("o{}", i),
+        }
+    }
+}
+
+/// Name, label and edges of everything to draw, shared by both formats.
+struct Layout {
+    inputs: Vec<String>,
+    initializers: Vec<String>,
+    nodes: Vec<(String, String)>,
+    outputs: Vec<String>,
+    edges: Vec<(Vertex, Vertex, String)>,
+}
+
+/// Formats declared dimensions, e.g. `[N,3,224,224]` (`?` when unknown).
+fn declared_shape(vi: &ValueInfoProto) -> Option<String> {
+    let Some(type_proto::Value::TensorType(t)) = vi.r#type.as_ref()?.value.as_ref() else {
+        return None;
+    };
+    let dims: Vec<String> = t.shape.as_ref()?.dim.iter()
+        .map(|d| match &d.value {
+            Some(tensor_shape_proto::dimension::Value::DimValue(v)) => v.to_string(),
+            Some(tensor_shape_proto::dimension::Value::DimParam(p)) if !p.is_empty() => p.clone(),
+            _ => "?".to_string(),
+        })
+        .collect();
+    Some(format!("[{}]", dims.join(",")))
+}
+
+impl Graph {
+    /// Shape of `name` as known without running the graph: initializer shapes
+    /// and the shapes declared for inputs, outputs and intermediate values.
+    fn known_shape(&self, name: &str) -> Option<String> {
+        if let Some(t) = self.initializers.get(name) {
+            let dims: Vec<String> = t.shape().iter().map(|d| d.to_string()).collect();
+            return Some(format!("[{}]", dims.join(",")));
+        }
+        self.proto.input.iter()
+            .chain(&self.proto.output)
+            .chain(&self.proto.value_info)
+            .find(|vi| vi.name == name)
+            .and_then(declared_shape)
+    }
+
+    fn layout(&self) -> Layout {
+        let mut inputs: Vec<String> = self.inputs.iter().filter(|n| !self.initializers.contains_key(*n)).cloned().collect();
+        let mut initializers: Vec<String> = Vec::new();
+        let mut sources: HashMap<&str, Vertex> = HashMap::new();
+        for (i, name) in self.inputs.iter().filter(|n| !self.initializers.contains_key(*n)).enumerate() {
+            sources.insert(name, Vertex::Input(i));
+        }
+        for (i, node) in self.nodes.iter().enumerate() {
+            for output in node.output.iter().filter(|o| !o.is_empty()) {
+                sources.insert(output, Vertex::Node(i));
+            }
+        }
+
+        let edge_label = |name: &str| match self.known_shape(name) {
+            Some(shape) => format!("{} {}", name, shape),
+            None => name.to_string(),
+        };
+        let mut edges = Vec::new();
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        for (i, node) in self.nodes.iter().enumerate() {
+            let op = if node.domain.is_empty() { node.op_type.clone() } else { format!("{}::{}", node.domain, node.op_type) };
+            nodes.push((op, node.name.clone()));
+            for input in node.input.iter().filter(|n| !n.is_empty()) {
+                let source = match sources.get(input.as_str()) {
+                    Some(&v) => v,
+                    None if self.initializers.contains_key(input) => {
+                        initializers.push(input.clone());
+                        let v = Vertex::Initializer(initializers.len() - 1);
+                        sources.insert(input, v);
+                        v
+                    }
+                    // Read from outside the (sub)graph
+                    None => {
+                        inputs.push(input.clone());
+                        let v = Vertex::Input(inputs.len() - 1);
+                        sources.insert(input, v);
+                        v
+                    }
+                };
+                edges.push((source, Vertex::Node(i), edge_label(input)));
+            }
+        }
+
+        let outputs = self.outputs.clone();
+        for (i, name) in outputs.iter().enumerate() {
+            if let Some(&source) = sources.get(name.as_str()) {
+                edges.push((source, Vertex::Output(i), edge_label(name)));
+            }
+        }
+        let label = |name: &String| match self.known_shape(name) {
+            Some(shape) => format!("{}\n{}", name, shape),
+            None => name.clone(),
+        };
+        Layout {
+            inputs: inputs.iter().map(label).collect(),
+            initializers: initializers.iter().map(label).collect(),
+            nodes,
+            outputs: outputs.iter().map(label).collect(),
+            edges,
+        }
+    }
+
+    /// Renders the graph in the Graphviz DOT language (`dot -Tsvg graph.dot`).
+    pub fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+        let layout = self.layout();
+        let mut out = String::new();
+        let _ = writeln!(out, "digraph \"{}\" {{", escape(&self.proto.name));
+        let _ = writeln!(out, "  rankdir=TB;");
+        let _ = writeln!(out, "  node [fontname=\"Helvetica\", fontsize=10];");
+        let _ = writeln!(out, "  edge [fontname=\"Helvetica\", fontsize=8];");
+        for (i, label) in layout.inputs.iter().enumerate() {
+            let _ = writeln!(out, "  {} [shape=ellipse, style=filled, fillcolor=\"#cfe2f3\", label=\"{}\"];", Vertex::Input(i).id(), escape(label));
+        }
+        for (i, label) in layout.initializers.iter().enumerate() {
+            let _ = writeln!(out, "  {} [shape=note, style=filled, fillcolor=\"#eeeeee\", fontcolor=\"#555555\", label=\"{}\"];", Vertex::Initializer(i).id(), escape(label));
+        }
+        for (i, (op, name)) in layout.nodes.iter().enumerate() {
+            let label = if name.is_empty() { op.clone() } else { format!("{}\n{}", op, name) };
+            let _ = writeln!(out, "  {} [shape=box, style=\"rounded,filled\", fillcolor=\"#fff2cc\", label=\"{}\"];", Vertex::Node(i).id(), escape(&label));
+        }
+        for (i, label) in layout.outputs.iter().enumerate() {
+            let _ = writeln!(out, "  {} [shape=ellipse, style=filled, fillcolor=\"#d9ead3\", label=\"{}\"];", Vertex::Output(i).id(), escape(label));
+        }
+        for (from, to, label) in &layout.edges {
+            let style = if matches!(from, Vertex::Initializer(_)) { ", style=dashed" } else { "" };
+            let _ = writeln!(out, "  {} -> {} [label=\"{}\"{}];", from.id(), to.id(), escape(label), style);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the graph as a Mermaid flowchart, e.g. for Markdown documentation.
+    pub fn to_mermaid(&self) -> String {
+        // Mermaid has no escaping for quotes inside labels, only HTML entities
+        let escape = |s: &str| s.replace('"', "#quot;").replace('|', "#124;").replace('\n', "<br/>");
+        let layout = self.layout();
+        let mut out = String::from("flowchart TD\n");
+        for (i, label) in layout.inputs.iter().enumerate() {
+            let _ = writeln!(out, "  {}([\"{}\"])", Vertex::Input(i).id(), escape(label));
+        }
+        for (i, label) in layout.initializers.iter().enumerate() {
+            let _ = writeln!(out, "  {}[(\"{}\")]", Vertex::Initializer(i).id(), escape(label));
+        }
+        for (i, (op, name)) in layout.nodes.iter().enumerate() {
+            let label = if name.is_empty() { op.clone() } else { format!("{}\n{}", op, name) };
+            let _ = writeln!(out, "  {}[\"{}\"]", Vertex::Node(i).id(), escape(&label));
+        }
+        for (i, label) in layout.outputs.iter().enumerate() {
+            let _ = writeln!(out, "  {}([\"{}\"])", Vertex::Output(i).id(), escape(label));
+        }
+        for (from, to, label) in &layout.edges {
+            let arrow = if matches!(from, Vertex::Initializer(_)) { "-.->" } else { "-->" };
+            let _ = writeln!(out, "  {} {}|\"{}\"| {}", from.id(), arrow, escape(label), to.id());
+        }
+        let classes = [
+            ("input", "fill:#cfe2f3", layout.inputs.len(), Vertex::Input as fn(usize) -> Vertex),
+            ("initializer", "fill:#eeeeee,color:#555555", layout.initializers.len(), Vertex::Initializer),
+            ("op", "fill:#fff2cc", layout.nodes.len(), Vertex::Node),
+            ("output", "fill:#d9ead3", layout.outputs.len(), Vertex::Output),
+        ];
+        for (class, style, count, vertex) in classes {
+            if count > 0 {
+                let ids: Vec<String> = (0..count).map(|i| vertex(i).id()).collect();
+                let _ = writeln!(out, "  classDef {} {};", class, style);
+                let _ = writeln!(out, "  class {} {};", ids.join(","), class);
+            }
+        }
+        out
+    }
+
+    /// Extracts the nodes at most `depth` edges away from the node named `center`.
+    ///
+    /// Values read from outside the selection become inputs of the returned
+    /// graph, and values used outside it (or returned by this graph) become
+    /// its outputs, so it can be rendered like any other graph.
+    pub fn subgraph(&self, center: &str, depth: usize) -> anyhow::Result<Graph> {
+        let start = self.nodes.iter().position(|n| n.name == center)
+            .ok_or_else(|| anyhow::anyhow!("No node named '{}'", center))?;
+
+        let mut producers: HashMap<&str, usize> = HashMap::new();
+        let mut consumers: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (idx, node) in self.nodes.iter().enumerate() {
+            for output in node.output.iter().filter(|o| !o.is_empty()) {
+                producers.insert(output, idx);
+            }
+            for input in node.input.iter().filter(|i| !i.is_empty()) {
+                consumers.entry(input).or_default().push(idx);
+            }
+        }
+
+        // Breadth-first search in both directions
+        let mut distance: HashMap<usize, usize> = HashMap::from([(start, 0)]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(idx) = queue.pop_front() {
+            let d = distance[&idx];
+            if d == depth {
+                continue;
+            }
+            let node = &self.nodes[idx];
+            let upstream = node.input.iter().filter_map(|i| producers.get(i.as_str()).copied());
+            let downstream = node.output.iter().flat_map(|o| consumers.get(o.as_str()).into_iter().flatten().copied());
+            for next in upstream.chain(downstream).collect::<Vec<_>>() {
+                if let std::collections::hash_map::Entry::Vacant(e) = distance.entry(next) {
+                    e.insert(d + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut selected: Vec<usize> = distance.into_keys().collect();
+        selected.sort_unstable();
+        let nodes: Vec<_> = selected.iter().map(|&i| self.nodes[i].clone()).collect();
+        let chosen: HashSet<usize> = selected.iter().copied().collect();
+        let produced: HashSet<&String> = nodes.iter().flat_map(|n| n.output.iter()).collect();
+
+        let mut inputs: Vec<String> = Vec::new();
+        let mut initializers = HashMap::new();
+        for input in nodes.iter().flat_map(|n| n.input.iter()).filter(|i| !i.is_empty() && !produced.contains(i)) {
+            if let Some(t) = self.initializers.get(input) {
+                initializers.insert(input.clone(), t.clone());
+            } else if !inputs.contains(input) {
+                inputs.push(input.clone());
+            }
+        }
+        let outputs: Vec<String> = nodes.iter()
+            .flat_map(|n| n.output.iter())
+            .filter(|o| {
+                self.outputs.contains(o)
+                    || consumers.get(o.as_str()).is_some_and(|c| c.iter().any(|i| !chosen.contains(i)))
+            })
+            .cloned()
+            .collect();
+
+        let mut proto = self.proto.clone();
+        proto.name = format!("{} around {}", self.proto.name, center);
+        proto.node = nodes.clone();
+        proto.initializer.retain(|t| initializers.contains_key(&t.name));
+        Ok(Graph {
+            proto,
+            model: self.model.clone(),
+            nodes,
+            initializers,
+            inputs,
+            outputs,
+        })
+    }
+}
@@ -0,0 +1,258 @@
+//! Function expansion: a node no kernel runs is replaced by the body of its
+//! `FunctionProto`, taken from the model-local functions or from the
+//! standard operators that ONNX defines as functions.
+
+use super::{AttributeValue, Graph};
+use crate::onnx::onnx_proto::attribute_proto::AttributeType;
+use crate::onnx::onnx_proto::{AttributeProto, FunctionProto, GraphProto, NodeProto};
+use crate::ops::registry::OpRegistry;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// Nesting depth past which a function is taken to call itself.
+const MAX_DEPTH: usize = 32;
+
+fn same_domain(a: &str, b: &str) -> bool {
+    a == b || (a.is_empty() && b == "ai.onnx") || (a == "ai.onnx" && b.is_empty())
+}
+
+/// The function defining `node`, if any: a model-local function with the
+/// same domain, name and overload, then a standard operator body.
+pub fn find_function<'a>(functions: &'a [FunctionProto], node: &NodeProto) -> Option<&'a FunctionProto> {
+    functions.iter()
+        .find(|f| f.name == node.op_type && same_domain(&f.domain, &node.domain) && f.overload == node.overload)
+        .or_else(|| {
+            standard_functions().iter()
+                .find(|f| f.name == node.op_type && same_domain(&f.domain, &node.domain))
+        })
+}
+
+impl Graph {
+    /// Inlines the function body of every node `registry` has no kernel for,
+    /// including the nodes of subgraphs and the calls made by function bodies.
+    ///
+    /// The values internal to a call are prefixed with the node name (or its
+    /// op type) and attribute references are bound to the node's attributes,
+    /// or to the function defaults. Returns the number of calls expanded.
+    pub fn inline_functions(&mut self, registry: &OpRegistry) -> anyhow::Result<usize> {
+        let functions = self.model.functions.clone();
+        let mut inliner = Inliner { functions: &functions, registry, prefixes: HashSet::new(), calls: 0 };
+        self.nodes = inliner.expand_all(std::mem::take(&mut self.nodes), 0)?;
+        Ok(inliner.calls)
+    }
+}
+
+struct Inliner<'a> {
+    functions: &'a [FunctionProto],
+    registry: &'a OpRegistry,
+    prefixes: HashSet<String>,
+    calls: usize,
+}
+
+impl Inliner<'_> {
+    fn expand_all(&mut self, nodes: Vec<NodeProto>, depth: usize) -> anyhow::Result<Vec<NodeProto>> {
+        let mut expanded = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            self.expand(node, depth, &mut expanded)?;
+        }
+        Ok(expanded)
+    }
+
+    fn expand(&mut self, mut node: NodeProto, depth: usize, out: &mut Vec<NodeProto>) -> anyhow::Result<()> {
+        let function = match self.registry.resolve(&node) {
+            Some(_) => None,
+            None => find_function(self.functions, &node),
+        };
+        let Some(function) = function else {
+            for attr in node.attribute.iter_mut() {
+                for body in attr.g.iter_mut().chain(attr.graphs.iter_mut()) {
+                    body.node = self.expand_all(std::mem::take(&mut body.node), depth)?;
+                }
+            }
+            out.push(node);
+            return Ok(());
+        };
+        if depth >= MAX_DEPTH {
+            return Err(anyhow::anyhow!("Node '{}': function '{}' is nested more than {} levels deep", node.name, function.name, MAX_DEPTH));
+        }
+        if node.input.len() > function.input.len() || node.output.len() > function.output.len() {
+            return Err(anyhow::anyhow!(
+                "Node '{}': function '{}' takes {} inputs and {} outputs, the node has {} and {}",
+                node.name, function.name, function.input.len(), function.output.len(), node.input.len(), node.output.len()
+            ));
+        }
+        if let Some(missing) = function.attribute.iter().find(|a| !node.attribute.iter().any(|n| &n.name == *a)) {
+            return Err(anyhow::anyhow!("Node '{}': function '{}' requires attribute '{}'", node.name, function.name, missing));
+        }
+
+        let prefix = self.prefix(&node);
+        let mut names: HashMap<String, String> = HashMap::new();
+        for (i, formal) in function.input.iter().enumerate() {
+            names.insert(formal.clone(), node.input.get(i).cloned().unwrap_or_default());
+        }
+        for (i, formal) in function.output.iter().enumerate() {
+            let actual = node.output.get(i).filter(|o| !o.is_empty()).cloned();
+            names.insert(formal.clone(), actual.unwrap_or_else(|| format!("{}/{}", prefix, formal)));
+        }
+        for value in function.node.iter().flat_map(|n| n.output.iter()).filter(|o| !o.is_empty()) {
+            names.entry(value.clone()).or_insert_with(|| format!("{}/{}", prefix, value));
+        }
+
+        self.calls += 1;
+        for (idx, body_node) in function.node.iter().enumerate() {
+            let mut inner = body_node.clone();
+            inner.name = match body_node.name.as_str() {
+                "" => format!("{}/{}_{}", prefix, body_node.op_type, idx),
+                name => format!("{}/{}", prefix, name),
+            };
+            bind(&mut inner, &names, &node, function);
+            self.expand(inner, depth + 1, out)?;
+        }
+        Ok(())
+    }
+
+    /// A name for the values of one call, unique among the calls expanded so far.
+    fn prefix(&mut self, node: &NodeProto) -> String {
+        let label = if node.name.is_empty() { node.op_type.clone() } else { node.name.clone() };
+        let mut prefix = label.clone();
+        let mut n = 1;
+        while !self.prefixes.insert(prefix.clone()) {
+            prefix = format!("{}_{}", label, n);
+            n += 1;
+        }
+        prefix
+    }
+}
+
+/// Instantiates a body node for one call: renames the function's values
+/// (also where its subgraphs read them) and resolves attribute references.
+fn bind(node: &mut NodeProto, names: &HashMap<String, String>, call: &NodeProto, function: &FunctionProto) {
+    let rename = |name: &mut String| {
+        if let Some(actual) = names.get(name.as_str()) {
+            *name = actual.clone();
+        }
+    };
+    node.input.iter_mut().for_each(rename);
+    node.output.iter_mut().for_each(rename);
+    node.attribute = node.attribute.iter()
+        .filter_map(|attr| {
+            if attr.ref_attr_name.is_empty() {
+                return Some(attr.clone());
+            }
+            // An optional attribute the call leaves out and without a default is dropped
+            let value = call.attribute.iter().find(|a| a.name == attr.ref_attr_name)
+                .or_else(|| function.attribute_proto.iter().find(|a| a.name == attr.ref_attr_name))?;
+            Some(AttributeProto { name: attr.name.clone(), ..value.clone() })
+        })
+        .collect();
+    for attr in node.attribute.iter_mut() {
+        for body in attr.g.iter_mut().chain(attr.graphs.iter_mut()) {
+            bind_subgraph(body, names, call, function);
+        }
+    }
+}
+
+fn bind_subgraph(body: &mut GraphProto, names: &HashMap<String, String>, call: &NodeProto, function: &FunctionProto) {
+    let rename = |name: &mut String| {
+        if let Some(actual) = names.get(name.as_str()) {
+            *name = actual.clone();
+        }
+    };
+    body.input.iter_mut().chain(body.output.iter_mut()).for_each(|vi| rename(&mut vi.name));
+    body.initializer.iter_mut().for_each(|t| rename(&mut t.name));
+    for node in body.node.iter_mut() {
+        bind(node, names, call, function);
+    }
+}
+
+fn op(op_type: &str, inputs: &[&str], outputs: &[&str], attribute: Vec<AttributeProto>) -> NodeProto {
+    NodeProto {
+        op_type: op_type.to_string(),
+        input: inputs.iter().map(|s| s.to_string()).collect(),
+        output: outputs.iter().map(|s| s.to_string()).collect(),
+        attribute,
+        ..Default::default()
+    }
+}
+
+fn constant(output: &str, value: f32) -> NodeProto {
+    op("Constant", &[], &[output], vec![AttributeValue::from(value).to_proto("value_float")])
+}
+
+/// `name` taking the value of the function attribute `referenced`.
+fn reference(name: &str, ty: AttributeType, referenced: &str) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        r#type: ty as i32,
+        ref_attr_name: referenced.to_string(),
+        ..Default::default()
+    }
+}
+
+fn function(name: &str, defaults: &[(&str, AttributeValue)], node: Vec<NodeProto>) -> FunctionProto {
+    FunctionProto {
+        name: name.to_string(),
+        input: vec!["X".to_string()],
+        output: vec!["Y".to_string()],
+        attribute_proto: defaults.iter().map(|(attr, value)| value.to_proto(attr)).collect(),
+        node,
+        ..Default::default()
+    }
+}
+
+/// Bodies of the standard operators that are defined as functions and
+/// have no kernel of their own, written with the operators we do run.
+fn standard_functions() -> &'static [FunctionProto] {
+    static FUNCTIONS: OnceLock<Vec<FunctionProto>> = OnceLock::new();
+    FUNCTIONS.get_or_init(|| {
+        let alpha = || reference("value_float", AttributeType::Float, "alpha");
+        vec![
+            // max(0, min(1, alpha * x + beta))
+            function("HardSigmoid", &[("alpha", 0.2f32.into()), ("beta", 0.5f32.into())], vec![
+                op("Constant", &[], &["Alpha"], vec![alpha()]),
+                op("Constant", &[], &["Beta"], vec![reference("value_float", AttributeType::Float, "beta")]),
+                constant("Zero", 0.0),
+                constant("One", 1.0),
+                op("Mul", &["X", "Alpha"], &["AX"], vec![]),
+                op("Add", &["AX", "Beta"], &["AXB"], vec![]),
+                op("Clip", &["AXB", "Zero", "One"], &["Y"], vec![]),
+            ]),
+            // x * HardSigmoid<alpha = 1/6, beta = 1/2>(x)
+            function("HardSwish", &[], vec![
+                op("HardSigmoid", &["X"], &["HS"], vec![
+                    AttributeValue::from(1.0f32 / 6.0).to_proto("alpha"),
+                    AttributeValue::from(0.5f32).to_proto("beta"),
+                ]),
+                op("Mul", &["X", "HS"], &["Y"], vec![]),
+            ]),
+            // x / (1 + |x|)
+            function("Softsign", &[], vec![
+                constant("One", 1.0),
+                op("Abs", &["X"], &["AbsX"], vec![]),
+                op("Add", &["One", "AbsX"], &["Denominator"], vec![]),
+                op("Div", &["X", "Denominator"], &["Y"], vec![]),
+            ]),
+            // x * sigmoid(alpha * x)
+            function("Swish", &[("alpha", 1.0f32.into())], vec![
+                op("Constant", &[], &["Alpha"], vec![alpha()]),
+                op("Mul", &["Alpha", "X"], &["AX"], vec![]),
+                op("Sigmoid", &["AX"], &["Gate"], vec![]),
+                op("Mul", &["X", "Gate"], &["Y"], vec![]),
+            ]),
+            // (x - E[x]) / (sqrt(E[x^2] - E[x]^2) + 1e-9)
+            function("MeanVarianceNormalization", &[("axes", vec![0i64, 2, 3].into())], vec![
+                constant("Exponent", 2.0),
+                constant("Epsilon", 1e-9),
+                op("ReduceMean", &["X"], &["Mean"], vec![reference("axes", AttributeType::Ints, "axes")]),
+                op("Pow", &["Mean", "Exponent"], &["MeanSquared"], vec![]),
+                op("Pow", &["X", "Exponent"], &["XSquared"], vec![]),
+                op("ReduceMean", &["XSquared"], &["MeanOfSquares"], vec![reference("axes", AttributeType::Ints, "axes")]),
+                op("Sub", &["MeanOfSquares", "MeanSquared"], &["Variance"], vec![]),
+                op("Sqrt", &["Variance"], &["Deviation"], vec![]),
+                op("Sub", &["X", "Mean"], &["Centered"], vec![]),
+                op("Add", &["Deviation", "Epsilon"], &["Denominator"], vec![]),
+                op("Div", &["Centered", "Denominator"], &["Y"], vec![]),
+            ]),
+        ]
+    })
+}
@@ -1,15 +1,28 @@
 //! Graph module: represents the computational graph.
 
+pub mod builder;
+mod export;
+mod function;
+pub mod optimizer;
+
+pub use builder::{AttributeValue, GraphBuilder};
+pub use function::find_function;
+
 use crate::tensor;
-#[allow(unused_imports)]
-use std::collections::HashMap;
-use crate::onnx::onnx_proto::{NodeProto, GraphProto};
+use std::collections::{HashMap, HashSet};
+use crate::onnx::onnx_proto::{NodeProto, GraphProto, ModelProto, OperatorSetIdProto, TensorProto, ValueInfoProto};
 use std::fmt;
 use crate::loader::ModelLoader;
 
+/// Cloning a graph is cheap for its weights: the cloned `initializers` share
+/// their buffers with the original, so several sessions can run one model.
+#[derive(Clone, Default)]
 pub struct Graph {
     // Placeholder for graph structure
     pub proto: GraphProto,
+    /// Model-level fields (opset imports, metadata, functions...) of the model
+    /// the graph comes from; its `graph` is left empty.
+    pub model: ModelProto,
     pub nodes: Vec<NodeProto>,
     pub initializers: HashMap<String, tensor::Tensor>,
     pub inputs: Vec<String>,
@@ -17,22 +30,75 @@ pub struct Graph {
 }
 
 impl Graph {
+    /// Builds the graph of a model. Calls to functions are kept as they are:
+    /// sessions inline those that their registry has no kernel for.
     pub fn from_model(model: &ModelLoader) -> anyhow::Result<Self> {
+        let _span = tracing::info_span!("graph_from_model").entered();
         let g = model.model.graph.as_ref().ok_or(anyhow::anyhow!("Model has no graph"))?;
 
+        // Only initializers read by a node (or one of its subgraphs), returned as
+        // outputs or standing in for an input are worth converting
+        let captured: Vec<String> = g.node.iter().flat_map(implicit_inputs).collect();
+        let referenced: HashSet<&str> = g.node.iter()
+            .flat_map(|n| n.input.iter())
+            .chain(&captured)
+            .chain(g.output.iter().chain(&g.input).map(|vi| &vi.name))
+            .map(String::as_str)
+            .collect();
+
         // extract initializers into HashMap<String, Tensor>
         let mut inits = HashMap::new();
-        for init in &g.initializer {
+        for init in g.initializer.iter().filter(|init| referenced.contains(init.name.as_str())) {
             let name = init.name.clone();
-            let tensor = tensor::Tensor::from_proto(init);
+            let tensor = tensor::Tensor::from_proto(init)?;
             inits.insert(name, tensor);
         }
 
+        // The proto keeps the initializer metadata but not a second copy of their data
+        let proto = GraphProto {
+            node: g.node.clone(),
+            name: g.name.clone(),
+            initializer: g.initializer.iter()
+                .filter(|init| referenced.contains(init.name.as_str()))
+                .map(|init| TensorProto {
+                    name: init.name.clone(),
+                    dims: init.dims.clone(),
+                    data_type: init.data_type,
+                    doc_string: init.doc_string.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            sparse_initializer: g.sparse_initializer.clone(),
+            doc_string: g.doc_string.clone(),
+            input: g.input.clone(),
+            output: g.output.clone(),
+            value_info: g.value_info.clone(),
+            quantization_annotation: g.quantization_annotation.clone(),
+            metadata_props: g.metadata_props.clone(),
+        };
+
         let inputs = g.input.iter().map(|vi| vi.name.clone()).collect();
         let outputs = g.output.iter().map(|vi| vi.name.clone()).collect();
 
+        let m = &model.model;
+        let model = ModelProto {
+            ir_version: m.ir_version,
+            opset_import: m.opset_import.clone(),
+            producer_name: m.producer_name.clone(),
+            producer_version: m.producer_version.clone(),
+            domain: m.domain.clone(),
+            model_version: m.model_version,
+            doc_string: m.doc_string.clone(),
+            graph: None,
+            metadata_props: m.metadata_props.clone(),
+            training_info: m.training_info.clone(),
+            functions: m.functions.clone(),
+            configuration: m.configuration.clone(),
+        };
+
         Ok(Self {
-            proto: g.clone(),
+            proto,
+            model,
             nodes: g.node.clone(),
             initializers: inits,
             inputs,
@@ -41,6 +107,190 @@ impl Graph {
     }
 }
 
+/// Values of the enclosing scopes read by the subgraphs of `node` (the
+/// branches of If, the body of Loop or Scan), in order of first use.
+///
+/// They are inputs of the node as far as scheduling is concerned, and the
+/// executors pass them to the operator after its declared inputs.
+pub fn implicit_inputs(node: &NodeProto) -> Vec<String> {
+    let mut names = Vec::new();
+    for body in node.attribute.iter().flat_map(|a| a.g.iter().chain(&a.graphs)) {
+        let defined: HashSet<&str> = body.input.iter().map(|vi| vi.name.as_str())
+            .chain(body.initializer.iter().map(|t| t.name.as_str()))
+            .chain(body.node.iter().flat_map(|n| n.output.iter()).map(String::as_str))
+            .collect();
+        let reads = body.node.iter()
+            .flat_map(|n| n.input.iter().cloned().chain(implicit_inputs(n)))
+            .chain(body.output.iter().map(|vi| vi.name.clone()));
+        for name in reads {
+            if !name.is_empty() && !defined.contains(name.as_str()) && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Whether `node` carries subgraph attributes.
+pub fn has_subgraphs(node: &NodeProto) -> bool {
+    node.attribute.iter().any(|a| a.g.is_some() || !a.graphs.is_empty())
+}
+
+impl Graph {
+    /// Prepares the body of a control-flow node to be run on its own: its
+    /// initializers are converted and its nodes sorted.
+    pub(crate) fn from_subgraph(body: &GraphProto) -> anyhow::Result<Self> {
+        let initializers = body.initializer.iter()
+            .map(|init| Ok((init.name.clone(), tensor::Tensor::from_proto(init)?)))
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+        let mut graph = Self {
+            proto: GraphProto {
+                name: body.name.clone(),
+                input: body.input.clone(),
+                output: body.output.clone(),
+                ..Default::default()
+            },
+            model: ModelProto::default(),
+            nodes: body.node.clone(),
+            initializers,
+            inputs: body.input.iter().map(|vi| vi.name.clone()).collect(),
+            outputs: body.output.iter().map(|vi| vi.name.clone()).collect(),
+        };
+        graph.topological_sort()?;
+        Ok(graph)
+    }
+}
+
+/// IR version written for graphs that were not loaded from a model.
+const DEFAULT_IR_VERSION: i64 = 8;
+/// Default-domain opset written for graphs that were not loaded from a model.
+pub(crate) const DEFAULT_OPSET: i64 = 17;
+
+impl Graph {
+    /// Re-encodes the graph, with its current nodes and initializers, into a `ModelProto`.
+    ///
+    /// Model-level fields such as `metadata_props` and `opset_import` are
+    /// preserved, and the domains introduced by fused operators are imported.
+    pub fn to_model(&self) -> anyhow::Result<ModelProto> {
+        // Keep the original initializer order and element types where known
+        let elem_types: HashMap<&str, i32> = self.proto.initializer.iter().map(|t| (t.name.as_str(), t.data_type)).collect();
+        let mut names: Vec<&String> = self.proto.initializer.iter()
+            .map(|t| &t.name)
+            .filter(|n| self.initializers.contains_key(*n))
+            .collect();
+        let mut extra: Vec<&String> = self.initializers.keys().filter(|n| !elem_types.contains_key(n.as_str())).collect();
+        extra.sort();
+        names.extend(extra);
+        let initializer = names.into_iter()
+            .map(|name| {
+                let tensor = &self.initializers[name];
+                let data_type = elem_types.get(name.as_str()).copied()
+                    .unwrap_or(tensor.element_type().data_type() as i32);
+                tensor.to_proto_as(name, data_type)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let produced: HashSet<&String> = self.nodes.iter().flat_map(|n| n.output.iter()).collect();
+        let value_infos = |names: &[String], declared: &[ValueInfoProto]| {
+            names.iter()
+                .map(|name| declared.iter().find(|vi| &vi.name == name).cloned().unwrap_or_else(|| {
+                    ValueInfoProto { name: name.clone(), ..Default::default() }
+                }))
+                .collect::<Vec<_>>()
+        };
+        let graph = GraphProto {
+            node: self.nodes.clone(),
+            name: self.proto.name.clone(),
+            initializer,
+            sparse_initializer: self.proto.sparse_initializer.clone(),
+            doc_string: self.proto.doc_string.clone(),
+            input: value_infos(&self.inputs, &self.proto.input),
+            output: value_infos(&self.outputs, &self.proto.output),
+            // Intermediate values removed by the optimizer lose their annotations
+            value_info: self.proto.value_info.iter().filter(|vi| produced.contains(&vi.name)).cloned().collect(),
+            quantization_annotation: self.proto.quantization_annotation.clone(),
+            metadata_props: self.proto.metadata_props.clone(),
+        };
+
+        let mut model = self.model.clone();
+        model.graph = Some(graph);
+        if model.ir_version == 0 {
+            model.ir_version = DEFAULT_IR_VERSION;
+        }
+        if model.opset_import.is_empty() {
+            model.opset_import.push(OperatorSetIdProto { domain: String::new(), version: DEFAULT_OPSET });
+        }
+        for node in &self.nodes {
+            let imported = model.opset_import.iter()
+                .any(|o| o.domain == node.domain || (o.domain.is_empty() && node.domain == "ai.onnx"));
+            if !imported {
+                model.opset_import.push(OperatorSetIdProto { domain: node.domain.clone(), version: 1 });
+            }
+        }
+        Ok(model)
+    }
+
+    /// Reorders `nodes` so that every node comes after the producers of its inputs.
+    ///
+    /// The sort is stable: already-ordered graphs are left untouched. Fails if
+    /// the graph contains a cycle.
+    pub fn topological_sort(&mut self) -> anyhow::Result<()> {
+        let mut producers: HashMap<&str, usize> = HashMap::new();
+        for (idx, node) in self.nodes.iter().enumerate() {
+            for output in node.output.iter().filter(|o| !o.is_empty()) {
+                producers.insert(output.as_str(), idx);
+            }
+        }
+
+        let mut pending = vec![0usize; self.nodes.len()];
+        let mut consumers = vec![Vec::new(); self.nodes.len()];
+        for (idx, node) in self.nodes.iter().enumerate() {
+            for input in node.input.iter().cloned().chain(implicit_inputs(node)) {
+                if let Some(&producer) = producers.get(input.as_str()) {
+                    pending[idx] += 1;
+                    consumers[producer].push(idx);
+                }
+            }
+        }
+
+        // Kahn's algorithm, always picking the ready node that appears first
+        let mut ready: std::collections::BTreeSet<usize> = (0..self.nodes.len()).filter(|&i| pending[i] == 0).collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(idx) = ready.pop_first() {
+            order.push(idx);
+            for &consumer in &consumers[idx] {
+                pending[consumer] -= 1;
+                if pending[consumer] == 0 {
+                    ready.insert(consumer);
+                }
+            }
+        }
+        if order.len() != self.nodes.len() {
+            return Err(anyhow::anyhow!("Graph contains a cycle"));
+        }
+
+        let mut slots: Vec<Option<NodeProto>> = std::mem::take(&mut self.nodes).into_iter().map(Some).collect();
+        self.nodes = order.into_iter().map(|i| slots[i].take().unwrap()).collect();
+        Ok(())
+    }
+}
+
+/// Entries listed per section by `Display`, unless the alternate flag (`{:#}`) is set.
+const DISPLAY_LIMIT: usize = 32;
+
+/// Writes the note replacing the entries of a section past `DISPLAY_LIMIT`.
+pub(crate) fn write_elided(f: &mut fmt::Formatter<'_>, total: usize) -> fmt::Result {
+    if !f.alternate() && total > DISPLAY_LIMIT {
+        writeln!(f, "    ... {} more (use {{:#}} to list all)", total - DISPLAY_LIMIT)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn display_limit(f: &fmt::Formatter<'_>) -> usize {
+    if f.alternate() { usize::MAX } else { DISPLAY_LIMIT }
+}
+
+/// Lists inputs, outputs, initializer shapes and nodes; tensor data is never printed.
 impl fmt::Display for Graph {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Graph:")?;
@@ -50,14 +300,15 @@ impl fmt::Display for Graph {
         writeln!(f, "  Initializers:")?;
         let mut init_names: Vec<_> = self.initializers.keys().collect();
         init_names.sort();
-        for name in init_names {
-            if let Some(tensor) = self.initializers.get(name) {
+        for name in init_names.iter().take(display_limit(f)) {
+            if let Some(tensor) = self.initializers.get(*name) {
                 writeln!(f, "    - {}: {:?}", name, tensor.shape())?;
             }
         }
+        write_elided(f, init_names.len())?;
 
         writeln!(f, "  Nodes:")?;
-        for (i, node) in self.nodes.iter().enumerate() {
+        for (i, node) in self.nodes.iter().enumerate().take(display_limit(f)) {
             let name = if node.name.is_empty() {
                 format!("node_{}", i)
             } else {
@@ -72,6 +323,6 @@ impl fmt::Display for Graph {
                 node.output.join(", ")
             )?;
         }
-        Ok(())
+        write_elided(f, self.nodes.len())
     }
 }
@@ -0,0 +1,126 @@
+//! Evaluates nodes whose inputs are all known at session creation.
+
+use super::rewrite::GraphIndex;
+use super::{GraphPass, PassContext};
+use crate::graph::{has_subgraphs, Graph};
+use crate::onnx::onnx_proto::{type_proto, tensor_shape_proto, NodeProto, TensorProto};
+use crate::ops::{operator, shape};
+use crate::tensor::Tensor;
+use std::collections::{HashMap, HashSet};
+
+/// Replaces constant subgraphs with initializers.
+///
+/// A node is folded when every input is an initializer (including the
+/// results of previously folded nodes), or when it is a `Shape` of a graph
+/// input whose dimensions are all fixed or overridden. Graph outputs are never folded.
+pub struct ConstantFolding;
+
+/// Dimensions of the graph inputs whose shape is fully static, symbolic
+/// dimensions counting as static when `overrides` pins them.
+fn static_input_shapes(graph: &Graph, overrides: &HashMap<String, usize>) -> HashMap<String, Vec<usize>> {
+    let mut shapes = HashMap::new();
+    for input in &graph.proto.input {
+        let Some(type_proto::Value::TensorType(tensor_type)) = input.r#type.as_ref().and_then(|t| t.value.as_ref()) else {
+            continue;
+        };
+        let Some(shape) = &tensor_type.shape else { continue };
+        let dims: Option<Vec<usize>> = shape.dim.iter()
+            .map(|d| match d.value {
+                Some(tensor_shape_proto::dimension::Value::DimValue(v)) if v >= 0 => Some(v as usize),
+                Some(tensor_shape_proto::dimension::Value::DimParam(ref name)) => overrides.get(name).copied(),
+                _ => None,
+            })
+            .collect();
+        if let Some(dims) = dims {
+            shapes.insert(input.name.clone(), dims);
+        }
+    }
+    shapes
+}
+
+impl ConstantFolding {
+    fn fold(
+        &self,
+        node: &NodeProto,
+        graph: &Graph,
+        ctx: &PassContext,
+        static_shapes: &HashMap<String, Vec<usize>>,
+        outputs: &HashSet<&String>,
+    ) -> anyhow::Result<Option<Tensor>> {
+        let default_domain = node.domain.is_empty() || node.domain == "ai.onnx";
+        if !default_domain || node.output.len() != 1 || outputs.contains(&node.output[0]) {
+            return Ok(None);
+        }
+
+        if node.op_type == "Shape" {
+            if let Some(dims) = node.input.first().and_then(|name| static_shapes.get(name)) {
+                return Ok(Some(shape::shape_of(node, dims)));
+            }
+        }
+
+        // Dequantized weights stay quantized so that QDQ patterns can be fused, and
+        // control-flow nodes may read values of the graph that are not known yet
+        if node.op_type == "DequantizeLinear" || has_subgraphs(node) {
+            return Ok(None);
+        }
+        let inputs: Option<Vec<&Tensor>> = node.input.iter()
+            .map(|name| if name.is_empty() { Some(operator::absent_input()) } else { graph.initializers.get(name) })
+            .collect();
+        let (Some(inputs), Some(op)) = (inputs, ctx.registry.resolve(node)) else {
+            return Ok(None);
+        };
+        // A failing kernel keeps the node, which reports the error if it is ever run
+        let tensor = match operator::invoke(op, &inputs, node, &ctx.op_ctx) {
+            Ok(mut outputs) => outputs.swap_remove(0),
+            Err(e) => {
+                tracing::warn!(node = %node.name, op_type = %node.op_type, "constant folding failed, keeping the node: {}", e);
+                return Ok(None);
+            }
+        };
+        // Initializers are read with `data()` by later passes and kernels
+        Ok(Some(tensor.to_contiguous()))
+    }
+}
+
+impl GraphPass for ConstantFolding {
+    fn name(&self) -> &'static str {
+        "ConstantFolding"
+    }
+
+    fn apply(&self, graph: &mut Graph, ctx: &PassContext) -> anyhow::Result<usize> {
+        // Initializers listed as graph inputs may be overridden by the caller in
+        // ONNX, but this runtime always binds them to their initializer.
+        let static_shapes = static_input_shapes(graph, ctx.free_dimension_overrides);
+        let outputs: HashSet<String> = graph.outputs.iter().cloned().collect();
+        let outputs: HashSet<&String> = outputs.iter().collect();
+
+        // Element types are worked out on the unfolded graph, to be kept with the new initializers
+        let index = GraphIndex::new(graph);
+        let elem_types: HashMap<String, i32> = graph.nodes.iter()
+            .filter_map(|n| n.output.first())
+            .filter_map(|o| index.elem_type_of(graph, o).map(|t| (o.clone(), t)))
+            .collect();
+
+        // Nodes are topologically sorted, so folded results are visible to their consumers
+        let mut kept = Vec::with_capacity(graph.nodes.len());
+        let mut folded = 0;
+        for node in std::mem::take(&mut graph.nodes) {
+            match self.fold(&node, graph, ctx, &static_shapes, &outputs)? {
+                Some(tensor) => {
+                    let name = node.output[0].clone();
+                    graph.proto.initializer.push(TensorProto {
+                        name: name.clone(),
+                        dims: tensor.shape().iter().map(|&d| d as i64).collect(),
+                        data_type: elem_types.get(&name).copied().unwrap_or(tensor.element_type().data_type() as i32),
+                        ..Default::default()
+                    });
+                    graph.initializers.insert(name, tensor);
+                    folded += 1;
+                }
+                None => kept.push(node),
+            }
+        }
+        graph.nodes = kept;
+        Ok(folded)
+    }
+}
@@ -0,0 +1,196 @@
+//! Passes removing nodes and initializers that do not change the outputs.
+
+use super::rewrite::{is_onnx, GraphIndex};
+use super::{GraphPass, PassContext};
+use crate::graph::{implicit_inputs, Graph};
+use crate::onnx::onnx_proto::NodeProto;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Follows `aliases` from `name` to the value that finally replaces it.
+fn resolve<'a>(aliases: &'a HashMap<String, String>, mut name: &'a str) -> &'a str {
+    while let Some(next) = aliases.get(name) {
+        name = next;
+    }
+    name
+}
+
+/// Removes an initializer, along with its metadata and its entry among the graph inputs.
+fn remove_initializer(graph: &mut Graph, name: &str) {
+    graph.initializers.remove(name);
+    graph.proto.initializer.retain(|t| t.name != name);
+    graph.proto.input.retain(|vi| vi.name != name);
+    graph.inputs.retain(|i| i != name);
+}
+
+/// Removes Identity, inference-mode Dropout and Cast-to-same-type nodes,
+/// rewiring their consumers to the forwarded value.
+pub struct NoOpElimination;
+
+impl NoOpElimination {
+    /// Whether `node` returns its first input unchanged.
+    fn is_no_op(graph: &Graph, index: &GraphIndex, node: &NodeProto) -> bool {
+        if node.input.first().is_none_or(|i| i.is_empty()) || node.output.first().is_none_or(|o| o.is_empty()) {
+            return false;
+        }
+        let unused = |name: &String| name.is_empty() || (index.consumers(name).is_empty() && !index.is_graph_output(name));
+        if is_onnx(node, "Identity") {
+            true
+        } else if is_onnx(node, "Dropout") {
+            // The mask must be unused and `training_mode` absent or false
+            let inference = match node.input.get(2).filter(|t| !t.is_empty()) {
+                Some(name) => graph.initializers.get(name).is_some_and(|t| t.values().iter().all(|&v| v == 0.0)),
+                None => true,
+            };
+            inference && node.output[1..].iter().all(unused)
+        } else if is_onnx(node, "Cast") {
+            let to = node.attribute.iter().find(|a| a.name == "to").map(|a| a.i as i32);
+            to.is_some() && index.elem_type_of(graph, &node.input[0]) == to
+        } else {
+            false
+        }
+    }
+}
+
+impl GraphPass for NoOpElimination {
+    fn name(&self) -> &'static str {
+        "NoOpElimination"
+    }
+
+    fn apply(&self, graph: &mut Graph, _ctx: &PassContext) -> anyhow::Result<usize> {
+        let index = GraphIndex::new(graph);
+        let mut aliases: HashMap<String, String> = HashMap::new();
+        let mut removed = vec![false; graph.nodes.len()];
+
+        for (idx, node) in graph.nodes.iter().enumerate() {
+            if !Self::is_no_op(graph, &index, node) {
+                continue;
+            }
+            let input = resolve(&aliases, &node.input[0]).to_string();
+            let output = &node.output[0];
+            // Subgraphs refer to the values they capture by name
+            if index.is_captured(output) {
+                continue;
+            }
+            if !index.is_graph_output(output) {
+                aliases.insert(output.clone(), input);
+            } else if index.producer(graph, &input).is_some() && !index.is_graph_output(&input) && !index.is_captured(&input) {
+                // The output name must survive: the producer of the input takes it over
+                aliases.insert(input, output.clone());
+            } else {
+                continue;
+            }
+            removed[idx] = true;
+        }
+
+        let count = removed.iter().filter(|&&r| r).count();
+        let nodes = std::mem::take(&mut graph.nodes);
+        graph.nodes = nodes.into_iter()
+            .zip(removed)
+            .filter(|(_, removed)| !removed)
+            .map(|(mut node, _)| {
+                for name in node.input.iter_mut().chain(node.output.iter_mut()) {
+                    let resolved = resolve(&aliases, name);
+                    if resolved != name {
+                        *name = resolved.to_string();
+                    }
+                }
+                node
+            })
+            .collect();
+        Ok(count)
+    }
+}
+
+/// Removes the nodes that no graph output depends on, then the initializers nothing reads.
+pub struct DeadNodeElimination;
+
+impl GraphPass for DeadNodeElimination {
+    fn name(&self) -> &'static str {
+        "DeadNodeElimination"
+    }
+
+    fn apply(&self, graph: &mut Graph, _ctx: &PassContext) -> anyhow::Result<usize> {
+        let mut needed: HashSet<String> = graph.outputs.iter().cloned().collect();
+        let mut live = vec![false; graph.nodes.len()];
+        // Nodes are topologically sorted: walking backwards sees every consumer before its producers
+        for (idx, node) in graph.nodes.iter().enumerate().rev() {
+            if node.output.iter().any(|o| needed.contains(o)) {
+                live[idx] = true;
+                needed.extend(node.input.iter().filter(|i| !i.is_empty()).cloned());
+                needed.extend(implicit_inputs(node));
+            }
+        }
+
+        let before = graph.nodes.len();
+        let nodes = std::mem::take(&mut graph.nodes);
+        graph.nodes = nodes.into_iter().zip(live).filter(|(_, live)| *live).map(|(n, _)| n).collect();
+        let mut removed = before - graph.nodes.len();
+
+        let unused: Vec<String> = graph.initializers.keys().filter(|name| !needed.contains(*name)).cloned().collect();
+        removed += unused.len();
+        for name in unused {
+            remove_initializer(graph, &name);
+        }
+        Ok(removed)
+    }
+}
+
+/// Makes the nodes share a single copy of initializers that hold the same values.
+pub struct InitializerDeduplication;
+
+impl GraphPass for InitializerDeduplication {
+    fn name(&self) -> &'static str {
+        "InitializerDeduplication"
+    }
+
+    fn apply(&self, graph: &mut Graph, _ctx: &PassContext) -> anyhow::Result<usize> {
+        // Initializers that callers can see or override, or that subgraphs capture, keep their identity
+        let captured: Vec<String> = graph.nodes.iter().flat_map(implicit_inputs).collect();
+        let pinned: HashSet<&String> = graph.outputs.iter().chain(&graph.inputs).chain(&captured).collect();
+        let elem_types: HashMap<&str, i32> = graph.proto.initializer.iter().map(|t| (t.name.as_str(), t.data_type)).collect();
+
+        let mut names: Vec<&String> = graph.initializers.keys().filter(|n| !pinned.contains(n)).collect();
+        names.sort();
+        let mut buckets: HashMap<u64, Vec<&String>> = HashMap::new();
+        let mut replacements: HashMap<String, String> = HashMap::new();
+        for name in names {
+            let tensor = &graph.initializers[name];
+            let elem_type = elem_types.get(name.as_str()).copied();
+            let mut hasher = DefaultHasher::new();
+            tensor.shape().hash(&mut hasher);
+            elem_type.hash(&mut hasher);
+            let values = tensor.values();
+            for v in values.iter() {
+                v.to_bits().hash(&mut hasher);
+            }
+
+            let bucket = buckets.entry(hasher.finish()).or_default();
+            let same = bucket.iter().find(|other| {
+                let o = &graph.initializers[**other];
+                o.shape() == tensor.shape()
+                    && elem_types.get(other.as_str()).copied() == elem_type
+                    && o.values().iter().zip(values.iter()).all(|(a, b)| a.to_bits() == b.to_bits())
+            });
+            match same {
+                Some(canonical) => {
+                    replacements.insert(name.clone(), (*canonical).clone());
+                }
+                None => bucket.push(name),
+            }
+        }
+
+        for node in &mut graph.nodes {
+            for input in &mut node.input {
+                if let Some(canonical) = replacements.get(input) {
+                    *input = canonical.clone();
+                }
+            }
+        }
+        for name in replacements.keys() {
+            remove_initializer(graph, name);
+        }
+        Ok(replacements.len())
+    }
+}
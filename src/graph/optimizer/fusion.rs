@@ -0,0 +1,365 @@
+//! Operator fusion rules.
+
+use super::rewrite::{is_onnx, other_input, scalar_initializer, unique_name, GraphIndex, Rewrite, RewriteRule};
+use crate::graph::Graph;
+use crate::onnx::onnx_proto::{attribute_proto, AttributeProto, NodeProto};
+use crate::ops::activation::Activation;
+use crate::ops::attributes;
+use crate::tensor::Tensor;
+
+/// Domain of the fused operators that have no ONNX equivalent.
+pub const MS_DOMAIN: &str = "com.microsoft";
+
+fn approx(value: Option<f32>, expected: f32) -> bool {
+    value.is_some_and(|v| (v - expected).abs() <= 1e-4 * expected.abs().max(1.0))
+}
+
+fn float_attr(name: &str, value: f32) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        f: value,
+        r#type: attribute_proto::AttributeType::Float as i32,
+        ..Default::default()
+    }
+}
+
+fn int_attr(name: &str, value: i64) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        i: value,
+        r#type: attribute_proto::AttributeType::Int as i32,
+        ..Default::default()
+    }
+}
+
+/// Only the first output of `node` is used (optional outputs are absent).
+fn single_output(node: &NodeProto) -> bool {
+    node.output.iter().skip(1).all(|o| o.is_empty())
+}
+
+/// Folds `BatchNormalization(Conv(x, W, B))` into the Conv weights and bias.
+pub struct ConvBatchNormFusion;
+
+impl RewriteRule for ConvBatchNormFusion {
+    fn name(&self) -> &'static str {
+        "ConvBatchNormFusion"
+    }
+
+    fn try_match(&self, graph: &Graph, index: &GraphIndex, idx: usize) -> anyhow::Result<Option<Rewrite>> {
+        let conv = &graph.nodes[idx];
+        if !is_onnx(conv, "Conv") || !single_output(conv) {
+            return Ok(None);
+        }
+        let Some((bn_idx, bn)) = index.sole_consumer(graph, &conv.output[0]) else { return Ok(None) };
+        if !is_onnx(bn, "BatchNormalization") || bn.input.len() != 5 || bn.input[0] != conv.output[0] || !single_output(bn) {
+            return Ok(None);
+        }
+
+        let init = |name: &String| graph.initializers.get(name);
+        let Some(w) = conv.input.get(1).and_then(init).filter(|w| !w.shape().is_empty()) else { return Ok(None) };
+        let bias = match conv.input.get(2).filter(|b| !b.is_empty()) {
+            Some(name) => match init(name) {
+                Some(b) => Some(b),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+        let params: Option<Vec<&Tensor>> = bn.input[1..].iter().map(init).collect();
+        let Some(params) = params else { return Ok(None) };
+        let channels = w.shape()[0];
+        if params.iter().any(|t| t.len() != channels) || bias.is_some_and(|b| b.len() != channels) {
+            return Ok(None);
+        }
+
+        let (scale, shift, mean, var) = (params[0].values(), params[1].values(), params[2].values(), params[3].values());
+        let epsilon = attributes::get_float(bn, "epsilon", 1e-5);
+        let factor: Vec<f32> = (0..channels).map(|c| scale[c] / (var[c] + epsilon).sqrt()).collect();
+
+        let per_channel = w.len() / channels.max(1);
+        let weights: Vec<f32> = w.values().iter().enumerate().map(|(i, &v)| v * factor[i / per_channel]).collect();
+        let bias = bias.map(|b| b.values());
+        let biases: Vec<f32> = (0..channels)
+            .map(|c| (bias.as_ref().map_or(0.0, |b| b[c]) - mean[c]) * factor[c] + shift[c])
+            .collect();
+
+        let w_name = unique_name(graph, index, &format!("{}_bn_weight", bn.output[0]));
+        let b_name = unique_name(graph, index, &format!("{}_bn_bias", bn.output[0]));
+        let mut fused = conv.clone();
+        fused.input = vec![conv.input[0].clone(), w_name.clone(), b_name.clone()];
+        fused.output = vec![bn.output[0].clone()];
+        Ok(Some(Rewrite {
+            remove: vec![idx, bn_idx],
+            insert: vec![fused],
+            initializers: vec![
+                // Folded parameters keep the precision of the original weights
+                (w_name, Tensor::new(weights, w.shape().to_vec()).to_element_type(w.element_type())),
+                (b_name, Tensor::new(biases, vec![channels]).to_element_type(w.element_type())),
+            ],
+        }))
+    }
+}
+
+/// Rewrites `Add(MatMul(A, B), C)` as `Gemm(A, B, C)` when A and B are matrices.
+pub struct MatMulAddFusion;
+
+impl RewriteRule for MatMulAddFusion {
+    fn name(&self) -> &'static str {
+        "MatMulAddFusion"
+    }
+
+    fn try_match(&self, graph: &Graph, index: &GraphIndex, idx: usize) -> anyhow::Result<Option<Rewrite>> {
+        let matmul = &graph.nodes[idx];
+        if !is_onnx(matmul, "MatMul") || matmul.input.len() != 2 {
+            return Ok(None);
+        }
+        let Some((add_idx, add)) = index.sole_consumer(graph, &matmul.output[0]) else { return Ok(None) };
+        if !is_onnx(add, "Add") {
+            return Ok(None);
+        }
+        let Some(c_name) = other_input(add, &matmul.output[0]) else { return Ok(None) };
+        let (Some(b), Some(c)) = (graph.initializers.get(&matmul.input[1]), graph.initializers.get(c_name)) else {
+            return Ok(None);
+        };
+        if b.shape().len() != 2 || index.rank_of(graph, &matmul.input[0]) != Some(2) {
+            return Ok(None);
+        }
+
+        // C must broadcast to (M, N) for any M
+        let n = b.shape()[1];
+        let broadcastable = match c.shape() {
+            [] => true,
+            [cn] => *cn == n || *cn == 1,
+            [1, cn] => *cn == n || *cn == 1,
+            _ => false,
+        };
+        if !broadcastable {
+            return Ok(None);
+        }
+
+        let gemm = NodeProto {
+            op_type: "Gemm".to_string(),
+            name: if matmul.name.is_empty() { String::new() } else { format!("{}_gemm", matmul.name) },
+            input: vec![matmul.input[0].clone(), matmul.input[1].clone(), c_name.to_string()],
+            output: vec![add.output[0].clone()],
+            ..Default::default()
+        };
+        Ok(Some(Rewrite { remove: vec![idx, add_idx], insert: vec![gemm], ..Default::default() }))
+    }
+}
+
+/// Merges an activation into the preceding Conv or Gemm (FusedConv/FusedGemm).
+pub struct ActivationFusion;
+
+impl RewriteRule for ActivationFusion {
+    fn name(&self) -> &'static str {
+        "ActivationFusion"
+    }
+
+    fn try_match(&self, graph: &Graph, index: &GraphIndex, idx: usize) -> anyhow::Result<Option<Rewrite>> {
+        let node = &graph.nodes[idx];
+        let fused_type = if is_onnx(node, "Conv") {
+            "FusedConv"
+        } else if is_onnx(node, "Gemm") {
+            "FusedGemm"
+        } else {
+            return Ok(None);
+        };
+        if !single_output(node) {
+            return Ok(None);
+        }
+        let Some((act_idx, act_node)) = index.sole_consumer(graph, &node.output[0]) else { return Ok(None) };
+        if !(act_node.domain.is_empty() || act_node.domain == "ai.onnx") || act_node.input.first() != node.output.first() {
+            return Ok(None);
+        }
+
+        // Clip bounds given as inputs must be constants
+        let mut known = vec![None];
+        for name in &act_node.input[1..] {
+            if name.is_empty() {
+                known.push(None);
+            } else if let Some(t) = graph.initializers.get(name) {
+                known.push(Some(t));
+            } else {
+                return Ok(None);
+            }
+        }
+        let Some(activation) = Activation::from_node(act_node, &known) else { return Ok(None) };
+
+        let mut fused = node.clone();
+        fused.op_type = fused_type.to_string();
+        fused.domain = MS_DOMAIN.to_string();
+        fused.output = vec![act_node.output[0].clone()];
+        fused.attribute.extend(activation.to_attributes());
+        Ok(Some(Rewrite { remove: vec![idx, act_idx], insert: vec![fused], ..Default::default() }))
+    }
+}
+
+/// Replaces the erf-based GELU decomposition `x * 0.5 * (1 + Erf(x / sqrt(2)))`
+/// emitted by PyTorch with a single `Gelu`.
+pub struct GeluFusion;
+
+impl RewriteRule for GeluFusion {
+    fn name(&self) -> &'static str {
+        "GeluFusion"
+    }
+
+    fn try_match(&self, graph: &Graph, index: &GraphIndex, idx: usize) -> anyhow::Result<Option<Rewrite>> {
+        let erf = &graph.nodes[idx];
+        if !is_onnx(erf, "Erf") {
+            return Ok(None);
+        }
+        let (Some(erf_input), Some(erf_output)) = (erf.input.first(), erf.output.first()) else { return Ok(None) };
+
+        // x / sqrt(2), or x * (1 / sqrt(2))
+        let Some((div_idx, div)) = index.producer(graph, erf_input) else { return Ok(None) };
+        if div.input.len() != 2 || index.sole_consumer(graph, &div.output[0]).map(|(i, _)| i) != Some(idx) {
+            return Ok(None);
+        }
+        let x = if is_onnx(div, "Div") && approx(scalar_initializer(graph, &div.input[1]), std::f32::consts::SQRT_2) {
+            div.input[0].as_str()
+        } else if is_onnx(div, "Mul") {
+            match div.input.iter().position(|i| approx(scalar_initializer(graph, i), std::f32::consts::FRAC_1_SQRT_2)) {
+                Some(c) => div.input[1 - c].as_str(),
+                None => return Ok(None),
+            }
+        } else {
+            return Ok(None);
+        };
+
+        // 1 + erf
+        let Some((add_idx, add)) = index.sole_consumer(graph, erf_output) else { return Ok(None) };
+        if !is_onnx(add, "Add") || !approx(other_input(add, erf_output).and_then(|c| scalar_initializer(graph, c)), 1.0) {
+            return Ok(None);
+        }
+
+        // Either (x * (1 + erf)) * 0.5 or (x * 0.5) * (1 + erf)
+        let Some((mul_idx, mul)) = index.sole_consumer(graph, &add.output[0]) else { return Ok(None) };
+        let Some(lhs) = other_input(mul, &add.output[0]).filter(|_| is_onnx(mul, "Mul")) else { return Ok(None) };
+        let (remove, output) = if lhs == x {
+            let Some((half_idx, half)) = index.sole_consumer(graph, &mul.output[0]) else { return Ok(None) };
+            if !is_onnx(half, "Mul") || !approx(other_input(half, &mul.output[0]).and_then(|c| scalar_initializer(graph, c)), 0.5) {
+                return Ok(None);
+            }
+            (vec![div_idx, idx, add_idx, mul_idx, half_idx], half.output[0].clone())
+        } else {
+            let Some((half_idx, half)) = index.producer(graph, lhs) else { return Ok(None) };
+            let halves_x = is_onnx(half, "Mul")
+                && other_input(half, x).is_some_and(|c| approx(scalar_initializer(graph, c), 0.5))
+                && index.sole_consumer(graph, lhs).map(|(i, _)| i) == Some(mul_idx);
+            if !halves_x {
+                return Ok(None);
+            }
+            (vec![div_idx, idx, add_idx, half_idx, mul_idx], mul.output[0].clone())
+        };
+
+        let gelu = NodeProto {
+            op_type: "Gelu".to_string(),
+            domain: MS_DOMAIN.to_string(),
+            name: format!("{}_gelu", output),
+            input: vec![x.to_string()],
+            output: vec![output],
+            ..Default::default()
+        };
+        Ok(Some(Rewrite { remove, insert: vec![gelu], ..Default::default() }))
+    }
+}
+
+/// Replaces the decomposed layer normalization over the last axis,
+/// `(x - mean) / sqrt(var + eps) * gamma + beta`, with `LayerNormalization`.
+pub struct LayerNormFusion;
+
+impl LayerNormFusion {
+    /// Whether a ReduceMean node averages over the last axis only, keeping dimensions.
+    fn reduces_last_axis(graph: &Graph, index: &GraphIndex, node: &NodeProto) -> bool {
+        if !is_onnx(node, "ReduceMean") || attributes::get_int(node, "keepdims", 1) == 0 {
+            return false;
+        }
+        let axes: Vec<i64> = match node.input.get(1).filter(|a| !a.is_empty()) {
+            Some(name) => match graph.initializers.get(name) {
+                Some(t) => t.values().iter().map(|&v| v as i64).collect(),
+                None => return false,
+            },
+            None => attributes::get_ints(node, "axes"),
+        };
+        match axes.as_slice() {
+            [-1] => true,
+            [axis] => index.rank_of(graph, &node.input[0]) == Some(*axis as usize + 1),
+            _ => false,
+        }
+    }
+}
+
+impl RewriteRule for LayerNormFusion {
+    fn name(&self) -> &'static str {
+        "LayerNormFusion"
+    }
+
+    fn try_match(&self, graph: &Graph, index: &GraphIndex, idx: usize) -> anyhow::Result<Option<Rewrite>> {
+        let mean = &graph.nodes[idx];
+        if !Self::reduces_last_axis(graph, index, mean) {
+            return Ok(None);
+        }
+        let x = &mean.input[0];
+
+        // d = x - mean, read by Pow and Div only
+        let Some((sub_idx, sub)) = index.sole_consumer(graph, &mean.output[0]) else { return Ok(None) };
+        if !is_onnx(sub, "Sub") || sub.input != [x.clone(), mean.output[0].clone()] {
+            return Ok(None);
+        }
+        let d = &sub.output[0];
+        let readers = index.consumers(d);
+        if readers.len() != 2 || index.is_graph_output(d) {
+            return Ok(None);
+        }
+        let (pow_idx, div_idx) = if is_onnx(&graph.nodes[readers[0]], "Pow") { (readers[0], readers[1]) } else { (readers[1], readers[0]) };
+        let (pow, div) = (&graph.nodes[pow_idx], &graph.nodes[div_idx]);
+        if !is_onnx(pow, "Pow") || pow.input[0] != *d || !approx(scalar_initializer(graph, &pow.input[1]), 2.0) {
+            return Ok(None);
+        }
+
+        // sqrt(mean(d^2) + eps)
+        let Some((var_idx, var)) = index.sole_consumer(graph, &pow.output[0]) else { return Ok(None) };
+        if !Self::reduces_last_axis(graph, index, var) {
+            return Ok(None);
+        }
+        let Some((eps_idx, eps_add)) = index.sole_consumer(graph, &var.output[0]) else { return Ok(None) };
+        let epsilon = other_input(eps_add, &var.output[0]).and_then(|c| scalar_initializer(graph, c));
+        let Some(epsilon) = epsilon.filter(|_| is_onnx(eps_add, "Add")) else { return Ok(None) };
+        let Some((sqrt_idx, sqrt)) = index.sole_consumer(graph, &eps_add.output[0]) else { return Ok(None) };
+        if !is_onnx(sqrt, "Sqrt") || index.sole_consumer(graph, &sqrt.output[0]).map(|(i, _)| i) != Some(div_idx) {
+            return Ok(None);
+        }
+        if !is_onnx(div, "Div") || div.input != [d.clone(), sqrt.output[0].clone()] {
+            return Ok(None);
+        }
+
+        // * gamma (required, it gives the normalized size), then optionally + beta
+        let one_d = |name: Option<&str>| name.and_then(|n| graph.initializers.get(n)).filter(|t| t.shape().len() == 1);
+        let Some((mul_idx, mul)) = index.sole_consumer(graph, &div.output[0]) else { return Ok(None) };
+        let gamma = other_input(mul, &div.output[0]).filter(|_| is_onnx(mul, "Mul"));
+        if one_d(gamma).is_none() {
+            return Ok(None);
+        }
+        let mut remove = vec![idx, sub_idx, pow_idx, var_idx, eps_idx, sqrt_idx, div_idx, mul_idx];
+        let mut inputs = vec![x.clone(), gamma.unwrap().to_string()];
+        let mut output = mul.output[0].clone();
+        if let Some((add_idx, add)) = index.sole_consumer(graph, &mul.output[0]) {
+            let beta = other_input(add, &mul.output[0]).filter(|_| is_onnx(add, "Add"));
+            if one_d(beta).is_some() {
+                remove.push(add_idx);
+                inputs.push(beta.unwrap().to_string());
+                output = add.output[0].clone();
+            }
+        }
+
+        let layer_norm = NodeProto {
+            op_type: "LayerNormalization".to_string(),
+            name: format!("{}_layer_norm", output),
+            input: inputs,
+            output: vec![output],
+            attribute: vec![int_attr("axis", -1), float_attr("epsilon", epsilon)],
+            ..Default::default()
+        };
+        Ok(Some(Rewrite { remove, insert: vec![layer_norm], ..Default::default() }))
+    }
+}
@@ -0,0 +1,78 @@
+//! Graph optimizer: rewrites the graph at session creation.
+//!
+//! Passes are grouped by `GraphOptimizationLevel` and run in registration
+//! order; each one must leave the graph computing the same outputs.
+
+mod constant_folding;
+mod elimination;
+mod fusion;
+mod quantization;
+pub mod rewrite;
+
+pub use constant_folding::ConstantFolding;
+pub use elimination::{DeadNodeElimination, InitializerDeduplication, NoOpElimination};
+pub use fusion::{ActivationFusion, ConvBatchNormFusion, GeluFusion, LayerNormFusion, MatMulAddFusion, MS_DOMAIN};
+pub use quantization::QdqFusion;
+pub use rewrite::{PatternRewriter, RewriteRule};
+
+use crate::graph::Graph;
+use crate::ops::operator::OpContext;
+use crate::ops::registry::OpRegistry;
+use crate::runtime::GraphOptimizationLevel;
+use std::collections::HashMap;
+
+/// What passes may use to evaluate nodes ahead of time.
+pub struct PassContext<'a> {
+    pub registry: &'a OpRegistry,
+    pub op_ctx: OpContext<'a>,
+    /// Values pinned for symbolic input dimensions, by dimension name.
+    pub free_dimension_overrides: &'a HashMap<String, usize>,
+}
+
+pub trait GraphPass {
+    fn name(&self) -> &'static str;
+
+    /// Rewrites `graph` in place and returns the number of rewrites applied.
+    fn apply(&self, graph: &mut Graph, ctx: &PassContext) -> anyhow::Result<usize>;
+}
+
+/// Ordered list of passes, each enabled from a given optimization level.
+pub struct GraphOptimizer {
+    passes: Vec<(GraphOptimizationLevel, Box<dyn GraphPass>)>,
+}
+
+impl GraphOptimizer {
+    /// The built-in passes enabled at `level`.
+    pub fn new(level: GraphOptimizationLevel) -> Self {
+        let mut optimizer = Self { passes: Vec::new() };
+        optimizer.add(GraphOptimizationLevel::Basic, NoOpElimination);
+        optimizer.add(GraphOptimizationLevel::Basic, DeadNodeElimination);
+        optimizer.add(GraphOptimizationLevel::Basic, ConstantFolding);
+        optimizer.add(GraphOptimizationLevel::Basic, PatternRewriter::new(ConvBatchNormFusion));
+        optimizer.add(GraphOptimizationLevel::Basic, PatternRewriter::new(MatMulAddFusion));
+        optimizer.add(GraphOptimizationLevel::Extended, PatternRewriter::new(QdqFusion));
+        optimizer.add(GraphOptimizationLevel::Extended, PatternRewriter::new(GeluFusion));
+        optimizer.add(GraphOptimizationLevel::Extended, PatternRewriter::new(LayerNormFusion));
+        // After the rewrites above so that their Conv/Gemm outputs can absorb the activation
+        optimizer.add(GraphOptimizationLevel::Extended, PatternRewriter::new(ActivationFusion));
+        // Folding and fusions leave behind initializers that nothing reads anymore
+        optimizer.add(GraphOptimizationLevel::Basic, DeadNodeElimination);
+        optimizer.add(GraphOptimizationLevel::Basic, InitializerDeduplication);
+        optimizer.passes.retain(|(min_level, _)| *min_level <= level);
+        optimizer
+    }
+
+    /// Appends a pass that runs when the optimizer level is at least `level`.
+    pub fn add<P: GraphPass + 'static>(&mut self, level: GraphOptimizationLevel, pass: P) {
+        self.passes.push((level, Box::new(pass)));
+    }
+
+    pub fn run(&self, graph: &mut Graph, ctx: &PassContext) -> anyhow::Result<()> {
+        for (_, pass) in &self.passes {
+            let _span = tracing::debug_span!("pass", name = pass.name()).entered();
+            let rewrites = pass.apply(graph, ctx)?;
+            tracing::debug!(rewrites, nodes = graph.nodes.len(), "pass done");
+        }
+        Ok(())
+    }
+}
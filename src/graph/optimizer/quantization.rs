@@ -0,0 +1,169 @@
+//! Fusion of QDQ (QuantizeLinear/DequantizeLinear) patterns into integer kernels.
+
+use super::rewrite::{is_onnx, unique_name, GraphIndex, Rewrite, RewriteRule};
+use crate::graph::Graph;
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use crate::onnx::onnx_proto::NodeProto;
+use crate::ops::attributes;
+use crate::tensor::Tensor;
+
+/// Rewrites `QuantizeLinear(MatMul(DequantizeLinear(a), DequantizeLinear(b)))`
+/// as `QLinearMatMul`, and the same pattern around Conv as `QLinearConv`.
+///
+/// A Conv bias is kept when it is the dequantized int32 tensor that
+/// QLinearConv expects (scale `x_scale * w_scale`, zero point 0). The
+/// DequantizeLinear nodes are removed when nothing else reads their output.
+pub struct QdqFusion;
+
+/// A DequantizeLinear feeding the matched operator.
+struct Dequantized<'g> {
+    idx: usize,
+    node: &'g NodeProto,
+    /// Quantized input, scale and zero point names.
+    x: &'g str,
+    scale: &'g str,
+    zero_point: Option<&'g str>,
+}
+
+impl<'g> Dequantized<'g> {
+    fn of(graph: &'g Graph, index: &GraphIndex, value: &str) -> Option<Self> {
+        let (idx, node) = index.producer(graph, value)?;
+        if !is_onnx(node, "DequantizeLinear") || node.input.len() < 2 {
+            return None;
+        }
+        graph.initializers.get(&node.input[1])?;
+        let zero_point = node.input.get(2).filter(|z| !z.is_empty()).map(String::as_str);
+        if zero_point.is_some_and(|z| !graph.initializers.contains_key(z)) {
+            return None;
+        }
+        Some(Self { idx, node, x: &node.input[0], scale: &node.input[1], zero_point })
+    }
+
+    /// Scale values, a single one or one per slice along the quantization axis.
+    fn scales(&self, graph: &Graph) -> Vec<f32> {
+        graph.initializers[self.scale].values().into_owned()
+    }
+
+    /// Whether the scale is per tensor, or per slice along `axis` of a rank-`rank` input.
+    fn quantized_along(&self, graph: &Graph, axis: usize, rank: usize) -> bool {
+        if graph.initializers[self.scale].len() == 1 {
+            return true;
+        }
+        let declared = attributes::get_int(self.node, "axis", 1);
+        (if declared < 0 { declared + rank as i64 } else { declared }) == axis as i64
+    }
+}
+
+/// Names the zero point of a quantized value, creating a zero initializer of
+/// the value's element type when the pattern leaves it implicit.
+fn zero_point(
+    graph: &Graph,
+    index: &GraphIndex,
+    given: Option<&str>,
+    quantized: &str,
+    scale: &Tensor,
+    initializers: &mut Vec<(String, Tensor)>,
+) -> String {
+    if let Some(name) = given {
+        return name.to_string();
+    }
+    let name = unique_name(graph, index, &format!("{}_zero_point", quantized));
+    let shape = scale.shape().to_vec();
+    let zeros = match index.elem_type_of(graph, quantized).and_then(|t| DataType::try_from(t).ok()) {
+        Some(DataType::Int8) => Tensor::from_i8(vec![0; scale.len()], shape),
+        _ => Tensor::from_u8(vec![0; scale.len()], shape),
+    };
+    initializers.push((name.clone(), zeros));
+    name
+}
+
+impl RewriteRule for QdqFusion {
+    fn name(&self) -> &'static str {
+        "QdqFusion"
+    }
+
+    fn try_match(&self, graph: &Graph, index: &GraphIndex, idx: usize) -> anyhow::Result<Option<Rewrite>> {
+        let node = &graph.nodes[idx];
+        let conv = is_onnx(node, "Conv");
+        if !(conv || is_onnx(node, "MatMul")) || node.input.len() < 2 || node.output.len() != 1 {
+            return Ok(None);
+        }
+        let Some((q_idx, q)) = index.sole_consumer(graph, &node.output[0]) else { return Ok(None) };
+        if !is_onnx(q, "QuantizeLinear") || q.input.len() < 2 || q.input[0] != node.output[0] {
+            return Ok(None);
+        }
+        let Some(y_scale) = graph.initializers.get(&q.input[1]).filter(|s| s.len() == 1) else { return Ok(None) };
+        let y_zero = q.input.get(2).filter(|z| !z.is_empty()).map(String::as_str);
+        if y_zero.is_some_and(|z| !graph.initializers.contains_key(z)) {
+            return Ok(None);
+        }
+
+        let (Some(a), Some(b)) = (Dequantized::of(graph, index, &node.input[0]), Dequantized::of(graph, index, &node.input[1])) else {
+            return Ok(None);
+        };
+        if a.idx == b.idx || graph.initializers[a.scale].len() != 1 {
+            return Ok(None);
+        }
+        // B may be quantized per column (MatMul) or per output channel (Conv)
+        let b_rank = index.rank_of(graph, b.x);
+        let b_per_slice = match (conv, b_rank) {
+            (true, Some(4)) => b.quantized_along(graph, 0, 4),
+            (false, Some(rank)) if rank >= 2 => b.quantized_along(graph, rank - 1, rank),
+            _ => false,
+        };
+        if !b_per_slice {
+            return Ok(None);
+        }
+
+        // The Conv bias must already be the int32 tensor QLinearConv adds to its accumulators
+        let bias = match node.input.get(2).filter(|b| !b.is_empty()) {
+            None => None,
+            Some(value) => {
+                let Some(bias) = Dequantized::of(graph, index, value) else { return Ok(None) };
+                let int32 = index.elem_type_of(graph, bias.x) == Some(DataType::Int32 as i32);
+                let zero = bias.zero_point.is_none_or(|z| graph.initializers[z].values().iter().all(|&v| v == 0.0));
+                let a_scale = a.scales(graph)[0];
+                let b_scales = b.scales(graph);
+                let bias_scales = bias.scales(graph);
+                let expected = |c: usize| a_scale * b_scales[if b_scales.len() == 1 { 0 } else { c }];
+                let matches = bias_scales.iter().enumerate()
+                    .all(|(c, &s)| (s - expected(c)).abs() <= 1e-6 * expected(c).abs().max(f32::MIN_POSITIVE));
+                if !int32 || !zero || !matches {
+                    return Ok(None);
+                }
+                Some(bias)
+            }
+        };
+
+        let mut initializers = Vec::new();
+        let a_zero = zero_point(graph, index, a.zero_point, a.x, &graph.initializers[a.scale], &mut initializers);
+        let b_zero = zero_point(graph, index, b.zero_point, b.x, &graph.initializers[b.scale], &mut initializers);
+        let y_zero = zero_point(graph, index, y_zero, &q.output[0], y_scale, &mut initializers);
+
+        let mut inputs = vec![
+            a.x.to_string(), a.scale.to_string(), a_zero,
+            b.x.to_string(), b.scale.to_string(), b_zero,
+            q.input[1].clone(), y_zero,
+        ];
+        if let Some(bias) = &bias {
+            inputs.push(bias.x.to_string());
+        }
+        let fused = NodeProto {
+            op_type: if conv { "QLinearConv" } else { "QLinearMatMul" }.to_string(),
+            name: format!("{}_quantized", node.name),
+            input: inputs,
+            output: vec![q.output[0].clone()],
+            attribute: if conv { node.attribute.clone() } else { Vec::new() },
+            ..Default::default()
+        };
+
+        // Dequantized values read elsewhere are kept
+        let mut remove = vec![idx, q_idx];
+        for dq in [Some(&a), Some(&b), bias.as_ref()].into_iter().flatten() {
+            if index.sole_consumer(graph, &dq.node.output[0]).is_some_and(|(i, _)| i == idx) {
+                remove.push(dq.idx);
+            }
+        }
+        Ok(Some(Rewrite { remove, insert: vec![fused], initializers }))
+    }
+}
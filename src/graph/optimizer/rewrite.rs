@@ -0,0 +1,254 @@
+//! Pattern-matching rewrite engine.
+//!
+//! A `RewriteRule` inspects the graph around an anchor node and, when its
+//! pattern matches, describes the nodes to remove and what replaces them.
+//! `PatternRewriter` sweeps the graph applying non-overlapping matches until
+//! none is left.
+
+use super::{GraphPass, PassContext};
+use crate::graph::{implicit_inputs, Graph};
+use crate::onnx::onnx_proto::{type_proto, NodeProto};
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use crate::tensor::Tensor;
+use std::collections::{HashMap, HashSet};
+
+/// Producers and consumers of every value, built once per sweep.
+pub struct GraphIndex {
+    producers: HashMap<String, usize>,
+    consumers: HashMap<String, Vec<usize>>,
+    outputs: HashSet<String>,
+    /// Values read by subgraphs, which refer to them by name.
+    captured: HashSet<String>,
+}
+
+impl GraphIndex {
+    pub fn new(graph: &Graph) -> Self {
+        let mut producers = HashMap::new();
+        let mut consumers: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut captured = HashSet::new();
+        for (idx, node) in graph.nodes.iter().enumerate() {
+            for output in node.output.iter().filter(|o| !o.is_empty()) {
+                producers.insert(output.clone(), idx);
+            }
+            let implicit = implicit_inputs(node);
+            for input in node.input.iter().chain(&implicit).filter(|i| !i.is_empty()) {
+                let readers = consumers.entry(input.clone()).or_default();
+                if readers.last() != Some(&idx) {
+                    readers.push(idx);
+                }
+            }
+            captured.extend(implicit);
+        }
+        Self { producers, consumers, outputs: graph.outputs.iter().cloned().collect(), captured }
+    }
+
+    /// Node producing `value`, if any.
+    pub fn producer<'g>(&self, graph: &'g Graph, value: &str) -> Option<(usize, &'g NodeProto)> {
+        self.producers.get(value).map(|&idx| (idx, &graph.nodes[idx]))
+    }
+
+    /// Indices of the nodes reading `value`.
+    pub fn consumers(&self, value: &str) -> &[usize] {
+        self.consumers.get(value).map_or(&[], |c| c.as_slice())
+    }
+
+    pub fn is_graph_output(&self, value: &str) -> bool {
+        self.outputs.contains(value)
+    }
+
+    /// Whether a subgraph reads `value` from the enclosing scope, so that it cannot be renamed.
+    pub fn is_captured(&self, value: &str) -> bool {
+        self.captured.contains(value)
+    }
+
+    /// The only node reading `value`, provided nothing else (including the graph outputs) needs it.
+    pub fn sole_consumer<'g>(&self, graph: &'g Graph, value: &str) -> Option<(usize, &'g NodeProto)> {
+        match self.consumers(value) {
+            [idx] if !self.is_graph_output(value) => Some((*idx, &graph.nodes[*idx])),
+            _ => None,
+        }
+    }
+
+    /// Element type (`TensorProto.DataType`) of `value` when it can be told without running the graph.
+    pub fn elem_type_of(&self, graph: &Graph, value: &str) -> Option<i32> {
+        if let Some(init) = graph.proto.initializer.iter().find(|t| t.name == value) {
+            return Some(init.data_type);
+        }
+        let declared = graph.proto.input.iter()
+            .chain(&graph.proto.value_info)
+            .chain(&graph.proto.output)
+            .find(|vi| vi.name == value)
+            .and_then(|vi| match vi.r#type.as_ref()?.value.as_ref()? {
+                type_proto::Value::TensorType(t) if t.elem_type != 0 => Some(t.elem_type),
+                _ => None,
+            });
+        if declared.is_some() {
+            return declared;
+        }
+        let (_, node) = self.producer(graph, value)?;
+        match node.op_type.as_str() {
+            "Cast" => node.attribute.iter().find(|a| a.name == "to").map(|a| a.i as i32),
+            "Shape" => Some(DataType::Int64 as i32),
+            "Constant" => node.attribute.first().map(|a| match a.name.as_str() {
+                "value" => a.t.as_ref().map_or(DataType::Float as i32, |t| t.data_type),
+                "value_int" | "value_ints" => DataType::Int64 as i32,
+                _ => DataType::Float as i32,
+            }),
+            "QuantizeLinear" => Some(zero_point_type(graph, node.input.get(2))),
+            "QLinearMatMul" | "QLinearConv" => Some(zero_point_type(graph, node.input.get(7))),
+            "DynamicQuantizeLinear" if node.output.get(1).is_some_and(|o| o == value) => Some(DataType::Float as i32),
+            "DynamicQuantizeLinear" => Some(DataType::Uint8 as i32),
+            "DequantizeLinear" => Some(DataType::Float as i32),
+            "MatMulInteger" => Some(DataType::Int32 as i32),
+            // Ops whose output has the type of their first input
+            "Identity" | "Dropout" | "Reshape" | "Flatten" | "Squeeze" | "Unsqueeze" | "Transpose" | "Concat"
+            | "Gather" | "Add" | "Sub" | "Mul" | "Div" | "Neg" | "Abs" | "Max" | "Min" | "ReduceSum"
+            | "ReduceMean" | "ReduceMax" | "ReduceMin" => {
+                node.input.first().and_then(|i| self.elem_type_of(graph, i))
+            }
+            _ => None,
+        }
+    }
+
+    /// Rank of `value` when it can be told without running the graph.
+    pub fn rank_of(&self, graph: &Graph, value: &str) -> Option<usize> {
+        if let Some(t) = graph.initializers.get(value) {
+            return Some(t.shape().len());
+        }
+        let declared = graph.proto.input.iter()
+            .chain(&graph.proto.value_info)
+            .chain(&graph.proto.output)
+            .find(|vi| vi.name == value)
+            .and_then(|vi| match vi.r#type.as_ref()?.value.as_ref()? {
+                type_proto::Value::TensorType(t) => t.shape.as_ref().map(|s| s.dim.len()),
+                _ => None,
+            });
+        if declared.is_some() {
+            return declared;
+        }
+        let (_, node) = self.producer(graph, value)?;
+        match node.op_type.as_str() {
+            "Flatten" | "Gemm" | "FusedGemm" => Some(2),
+            "Reshape" => node.input.get(1).and_then(|s| graph.initializers.get(s)).map(|s| s.len()),
+            "Relu" | "Sigmoid" | "Tanh" | "LeakyRelu" | "Clip" | "Erf" | "Gelu" | "Abs" | "Neg" | "Sqrt" => {
+                self.rank_of(graph, &node.input[0])
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Element type of a quantized output, given by its zero point (UINT8 when there is none).
+fn zero_point_type(graph: &Graph, zero_point: Option<&String>) -> i32 {
+    zero_point.and_then(|name| graph.proto.initializer.iter().find(|t| &t.name == name))
+        .map_or(DataType::Uint8 as i32, |t| t.data_type)
+}
+
+/// Replacement produced by a matching rule.
+#[derive(Default)]
+pub struct Rewrite {
+    /// Indices of the matched nodes.
+    pub remove: Vec<usize>,
+    /// Nodes inserted in place of the last matched node.
+    pub insert: Vec<NodeProto>,
+    /// New initializers referenced by the inserted nodes.
+    pub initializers: Vec<(String, Tensor)>,
+}
+
+pub trait RewriteRule {
+    fn name(&self) -> &'static str;
+
+    /// Tries to match the pattern anchored at node `idx`.
+    fn try_match(&self, graph: &Graph, index: &GraphIndex, idx: usize) -> anyhow::Result<Option<Rewrite>>;
+}
+
+/// Runs a `RewriteRule` over the whole graph as a `GraphPass`.
+pub struct PatternRewriter<R> {
+    rule: R,
+}
+
+impl<R: RewriteRule> PatternRewriter<R> {
+    pub fn new(rule: R) -> Self {
+        Self { rule }
+    }
+}
+
+impl<R: RewriteRule> GraphPass for PatternRewriter<R> {
+    fn name(&self) -> &'static str {
+        self.rule.name()
+    }
+
+    fn apply(&self, graph: &mut Graph, _ctx: &PassContext) -> anyhow::Result<usize> {
+        let mut total = 0;
+        loop {
+            let index = GraphIndex::new(graph);
+            let mut claimed = vec![false; graph.nodes.len()];
+            let mut rewrites: HashMap<usize, Rewrite> = HashMap::new();
+            for idx in 0..graph.nodes.len() {
+                if claimed[idx] {
+                    continue;
+                }
+                let Some(rewrite) = self.rule.try_match(graph, &index, idx)? else { continue };
+                if rewrite.remove.iter().any(|&i| claimed[i]) {
+                    continue;
+                }
+                for &i in &rewrite.remove {
+                    claimed[i] = true;
+                }
+                let last = *rewrite.remove.iter().max().unwrap_or(&idx);
+                rewrites.insert(last, rewrite);
+            }
+            if rewrites.is_empty() {
+                return Ok(total);
+            }
+            total += rewrites.len();
+
+            // Replacements take the position of the last matched node, after
+            // the producers of all the pattern inputs.
+            let nodes = std::mem::take(&mut graph.nodes);
+            for (idx, node) in nodes.into_iter().enumerate() {
+                if let Some(rewrite) = rewrites.remove(&idx) {
+                    graph.nodes.extend(rewrite.insert);
+                    graph.initializers.extend(rewrite.initializers);
+                } else if !claimed[idx] {
+                    graph.nodes.push(node);
+                }
+            }
+        }
+    }
+}
+
+/// Returns `base`, suffixed if needed so that it names no existing initializer or value.
+pub fn unique_name(graph: &Graph, index: &GraphIndex, base: &str) -> String {
+    let taken = |name: &str| graph.initializers.contains_key(name) || index.producers.contains_key(name)
+        || index.consumers.contains_key(name);
+    let mut name = base.to_string();
+    let mut suffix = 1;
+    while taken(&name) {
+        name = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+    name
+}
+
+/// Value of a single-element initializer.
+pub fn scalar_initializer(graph: &Graph, name: &str) -> Option<f32> {
+    match *graph.initializers.get(name)?.values() {
+        [v] => Some(v),
+        _ => None,
+    }
+}
+
+/// The input of a binary `node` other than `value`.
+pub fn other_input<'n>(node: &'n NodeProto, value: &str) -> Option<&'n str> {
+    match node.input.as_slice() {
+        [a, b] if a == value => Some(b),
+        [a, b] if b == value => Some(a),
+        _ => None,
+    }
+}
+
+/// Whether `node` belongs to the default ONNX domain.
+pub fn is_onnx(node: &NodeProto, op_type: &str) -> bool {
+    node.op_type == op_type && (node.domain.is_empty() || node.domain == "ai.onnx")
+}
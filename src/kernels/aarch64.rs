@@ -0,0 +1,34 @@
+//! aarch64 backend: NEON.
+
+pub(crate) use neon::KERNELS as NEON_KERNELS;
+
+mod neon {
+    use std::arch::aarch64::*;
+
+    #[target_feature(enable = "neon")]
+    #[inline]
+    unsafe fn store(ptr: *mut f32, v: float32x4_t) {
+        vst1q_f32(ptr, v)
+    }
+
+    // `fmaxnm`/`fminnm` return the non-NaN operand, which keeps Relu(NaN) == 0
+    // like the scalar and x86 backends.
+    crate::kernels::simd_kernels! {
+        backend: crate::kernels::KernelBackend::Neon,
+        feature: "neon",
+        vector: float32x4_t,
+        lanes: 4,
+        load: vld1q_f32,
+        store: store,
+        splat: vdupq_n_f32,
+        add: vaddq_f32,
+        sub: vsubq_f32,
+        mul: vmulq_f32,
+        div: vdivq_f32,
+        max: vmaxnmq_f32,
+        min: vminnmq_f32,
+        sqrt: vsqrtq_f32,
+        neg: vnegq_f32,
+        abs: vabsq_f32,
+    }
+}
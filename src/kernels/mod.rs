@@ -0,0 +1,344 @@
+//! Kernels module: vectorized f32 primitives used by the operators.
+//!
+//! Each backend provides the same set of unary, binary and reduction
+//! primitives. A backend is picked once (at session creation) and handed to
+//! operators through a `Kernels` dispatch table, so the hot loops never
+//! re-check CPU features.
+
+mod scalar;
+#[cfg(target_arch = "x86_64")]
+mod x86;
+#[cfg(target_arch = "aarch64")]
+mod aarch64;
+
+use std::fmt;
+
+/// Instruction set used by the elementwise and reduction kernels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KernelBackend {
+    /// Portable Rust loops, available everywhere.
+    Scalar,
+    /// 128-bit SSE (x86_64 baseline).
+    Sse,
+    /// 256-bit AVX2.
+    Avx2,
+    /// 512-bit AVX-512F.
+    Avx512,
+    /// 128-bit NEON (aarch64 baseline).
+    Neon,
+}
+
+impl KernelBackend {
+    /// Returns the widest backend supported by the running CPU.
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if std::arch::is_x86_feature_detected!("avx512f") {
+                return KernelBackend::Avx512;
+            }
+            if std::arch::is_x86_feature_detected!("avx2") {
+                return KernelBackend::Avx2;
+            }
+            KernelBackend::Sse
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            KernelBackend::Neon
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            KernelBackend::Scalar
+        }
+    }
+
+    /// Whether this backend can run on the current CPU.
+    pub fn is_supported(self) -> bool {
+        match self {
+            KernelBackend::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            KernelBackend::Sse => std::arch::is_x86_feature_detected!("sse"),
+            #[cfg(target_arch = "x86_64")]
+            KernelBackend::Avx2 => std::arch::is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            KernelBackend::Avx512 => std::arch::is_x86_feature_detected!("avx512f"),
+            #[cfg(target_arch = "aarch64")]
+            KernelBackend::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Every backend usable on the current CPU, scalar first.
+    pub fn available() -> Vec<Self> {
+        [
+            KernelBackend::Scalar,
+            KernelBackend::Sse,
+            KernelBackend::Avx2,
+            KernelBackend::Avx512,
+            KernelBackend::Neon,
+        ]
+        .into_iter()
+        .filter(|b| b.is_supported())
+        .collect()
+    }
+
+    /// Returns the dispatch table for this backend, or an error if the CPU lacks the required features.
+    pub fn kernels(self) -> anyhow::Result<&'static Kernels> {
+        if !self.is_supported() {
+            return Err(anyhow::anyhow!("Kernel backend {} is not supported on this CPU", self));
+        }
+        Ok(match self {
+            KernelBackend::Scalar => &scalar::KERNELS,
+            #[cfg(target_arch = "x86_64")]
+            KernelBackend::Sse => &x86::SSE_KERNELS,
+            #[cfg(target_arch = "x86_64")]
+            KernelBackend::Avx2 => &x86::AVX2_KERNELS,
+            #[cfg(target_arch = "x86_64")]
+            KernelBackend::Avx512 => &x86::AVX512_KERNELS,
+            #[cfg(target_arch = "aarch64")]
+            KernelBackend::Neon => &aarch64::NEON_KERNELS,
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        })
+    }
+}
+
+impl fmt::Display for KernelBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            KernelBackend::Scalar => "scalar",
+            KernelBackend::Sse => "sse",
+            KernelBackend::Avx2 => "avx2",
+            KernelBackend::Avx512 => "avx512",
+            KernelBackend::Neon => "neon",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Relu,
+    Neg,
+    Abs,
+    Sqrt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Max,
+    Min,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReduceOp {
+    Sum,
+    Max,
+    Min,
+}
+
+/// Dispatch table for one backend.
+///
+/// All slices passed to a single call must have the same length.
+pub struct Kernels {
+    pub backend: KernelBackend,
+    unary: fn(UnaryOp, &[f32], &mut [f32]),
+    binary: fn(BinaryOp, &[f32], &[f32], &mut [f32]),
+    binary_scalar_rhs: fn(BinaryOp, &[f32], f32, &mut [f32]),
+    binary_scalar_lhs: fn(BinaryOp, f32, &[f32], &mut [f32]),
+    reduce: fn(ReduceOp, &[f32]) -> f32,
+}
+
+impl Kernels {
+    /// `dst[i] = op(src[i])`
+    pub fn unary(&self, op: UnaryOp, src: &[f32], dst: &mut [f32]) {
+        assert_eq!(src.len(), dst.len());
+        (self.unary)(op, src, dst)
+    }
+
+    /// `dst[i] = op(a[i], b[i])`
+    pub fn binary(&self, op: BinaryOp, a: &[f32], b: &[f32], dst: &mut [f32]) {
+        assert_eq!(a.len(), dst.len());
+        assert_eq!(b.len(), dst.len());
+        (self.binary)(op, a, b, dst)
+    }
+
+    /// `dst[i] = op(a[i], b)`
+    pub fn binary_scalar_rhs(&self, op: BinaryOp, a: &[f32], b: f32, dst: &mut [f32]) {
+        assert_eq!(a.len(), dst.len());
+        (self.binary_scalar_rhs)(op, a, b, dst)
+    }
+
+    /// `dst[i] = op(a, b[i])`
+    pub fn binary_scalar_lhs(&self, op: BinaryOp, a: f32, b: &[f32], dst: &mut [f32]) {
+        assert_eq!(b.len(), dst.len());
+        (self.binary_scalar_lhs)(op, a, b, dst)
+    }
+
+    /// Folds `src` with `op`. Returns the identity of `op` for an empty slice.
+    pub fn reduce(&self, op: ReduceOp, src: &[f32]) -> f32 {
+        (self.reduce)(op, src)
+    }
+}
+
+impl fmt::Debug for Kernels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Kernels").field("backend", &self.backend).finish()
+    }
+}
+
+impl ReduceOp {
+    fn identity(self) -> f32 {
+        match self {
+            ReduceOp::Sum => 0.0,
+            ReduceOp::Max => f32::NEG_INFINITY,
+            ReduceOp::Min => f32::INFINITY,
+        }
+    }
+}
+
+/// Generates the dispatch table for one SIMD instruction set.
+///
+/// The `unsafe` entry points are only reachable through the generated
+/// `KERNELS` table, which `KernelBackend::kernels` hands out after checking
+/// that the CPU supports `feature`.
+#[allow(unused_macros)]
+macro_rules! simd_kernels {
+    (
+        backend: $backend:expr,
+        feature: $feature:literal,
+        vector: $v:ty,
+        lanes: $lanes:expr,
+        load: $load:path,
+        store: $store:path,
+        splat: $splat:path,
+        add: $add:path,
+        sub: $sub:path,
+        mul: $mul:path,
+        div: $div:path,
+        max: $max:path,
+        min: $min:path,
+        sqrt: $sqrt:path,
+        neg: $neg:path,
+        abs: $abs:path $(,)?
+    ) => {
+        use $crate::kernels::{scalar, BinaryOp, Kernels, ReduceOp, UnaryOp};
+
+        const LANES: usize = $lanes;
+
+        pub(crate) static KERNELS: Kernels = Kernels {
+            backend: $backend,
+            unary: |op, src, dst| unsafe { unary(op, src, dst) },
+            binary: |op, a, b, dst| unsafe { binary(op, a, b, dst) },
+            binary_scalar_rhs: |op, a, b, dst| unsafe { binary_scalar_rhs(op, a, b, dst) },
+            binary_scalar_lhs: |op, a, b, dst| unsafe { binary_scalar_lhs(op, a, b, dst) },
+            reduce: |op, src| unsafe { reduce(op, src) },
+        };
+
+        #[target_feature(enable = $feature)]
+        #[inline]
+        unsafe fn apply(op: BinaryOp, x: $v, y: $v) -> $v {
+            match op {
+                BinaryOp::Add => $add(x, y),
+                BinaryOp::Sub => $sub(x, y),
+                BinaryOp::Mul => $mul(x, y),
+                BinaryOp::Div => $div(x, y),
+                BinaryOp::Max => $max(x, y),
+                BinaryOp::Min => $min(x, y),
+            }
+        }
+
+        #[target_feature(enable = $feature)]
+        unsafe fn unary(op: UnaryOp, src: &[f32], dst: &mut [f32]) {
+            let n = src.len();
+            let body = n - n % LANES;
+            let zero = $splat(0.0);
+            let mut i = 0;
+            while i < body {
+                let v = $load(src.as_ptr().add(i));
+                let r = match op {
+                    UnaryOp::Relu => $max(v, zero),
+                    UnaryOp::Neg => $neg(v),
+                    UnaryOp::Abs => $abs(v),
+                    UnaryOp::Sqrt => $sqrt(v),
+                };
+                $store(dst.as_mut_ptr().add(i), r);
+                i += LANES;
+            }
+            scalar::unary(op, &src[body..], &mut dst[body..]);
+        }
+
+        #[target_feature(enable = $feature)]
+        unsafe fn binary(op: BinaryOp, a: &[f32], b: &[f32], dst: &mut [f32]) {
+            let n = a.len();
+            let body = n - n % LANES;
+            let mut i = 0;
+            while i < body {
+                let x = $load(a.as_ptr().add(i));
+                let y = $load(b.as_ptr().add(i));
+                $store(dst.as_mut_ptr().add(i), apply(op, x, y));
+                i += LANES;
+            }
+            scalar::binary(op, &a[body..], &b[body..], &mut dst[body..]);
+        }
+
+        #[target_feature(enable = $feature)]
+        unsafe fn binary_scalar_rhs(op: BinaryOp, a: &[f32], b: f32, dst: &mut [f32]) {
+            let n = a.len();
+            let body = n - n % LANES;
+            let y = $splat(b);
+            let mut i = 0;
+            while i < body {
+                let x = $load(a.as_ptr().add(i));
+                $store(dst.as_mut_ptr().add(i), apply(op, x, y));
+                i += LANES;
+            }
+            scalar::binary_scalar_rhs(op, &a[body..], b, &mut dst[body..]);
+        }
+
+        #[target_feature(enable = $feature)]
+        unsafe fn binary_scalar_lhs(op: BinaryOp, a: f32, b: &[f32], dst: &mut [f32]) {
+            let n = b.len();
+            let body = n - n % LANES;
+            let x = $splat(a);
+            let mut i = 0;
+            while i < body {
+                let y = $load(b.as_ptr().add(i));
+                $store(dst.as_mut_ptr().add(i), apply(op, x, y));
+                i += LANES;
+            }
+            scalar::binary_scalar_lhs(op, a, &b[body..], &mut dst[body..]);
+        }
+
+        #[target_feature(enable = $feature)]
+        unsafe fn reduce(op: ReduceOp, src: &[f32]) -> f32 {
+            let n = src.len();
+            if n < LANES {
+                return scalar::reduce(op, src);
+            }
+            let body = n - n % LANES;
+            let mut acc = $load(src.as_ptr());
+            let mut i = LANES;
+            while i < body {
+                let v = $load(src.as_ptr().add(i));
+                acc = match op {
+                    ReduceOp::Sum => $add(acc, v),
+                    ReduceOp::Max => $max(acc, v),
+                    ReduceOp::Min => $min(acc, v),
+                };
+                i += LANES;
+            }
+            let mut lanes = [0.0f32; LANES];
+            $store(lanes.as_mut_ptr(), acc);
+            let partial = scalar::reduce(op, &lanes);
+            scalar::combine(op, partial, scalar::reduce(op, &src[body..]))
+        }
+    };
+}
+
+#[allow(unused_imports)]
+pub(crate) use simd_kernels;
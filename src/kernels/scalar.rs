@@ -0,0 +1,80 @@
+//! Portable reference kernels. SIMD backends fall back to these for tails.
+
+use super::{BinaryOp, KernelBackend, Kernels, ReduceOp, UnaryOp};
+
+pub(crate) static KERNELS: Kernels = Kernels {
+    backend: KernelBackend::Scalar,
+    unary,
+    binary,
+    binary_scalar_rhs,
+    binary_scalar_lhs,
+    reduce,
+};
+
+// Max/Min mirror the x86 `maxps`/`minps` semantics (the second operand wins
+// when the comparison is false) so that every backend agrees on NaN-free data
+// and Relu maps NaN to 0.
+#[inline(always)]
+fn max(x: f32, y: f32) -> f32 {
+    if x > y { x } else { y }
+}
+
+#[inline(always)]
+fn min(x: f32, y: f32) -> f32 {
+    if x < y { x } else { y }
+}
+
+#[inline(always)]
+fn apply(op: BinaryOp, x: f32, y: f32) -> f32 {
+    match op {
+        BinaryOp::Add => x + y,
+        BinaryOp::Sub => x - y,
+        BinaryOp::Mul => x * y,
+        BinaryOp::Div => x / y,
+        BinaryOp::Max => max(x, y),
+        BinaryOp::Min => min(x, y),
+    }
+}
+
+pub(crate) fn unary(op: UnaryOp, src: &[f32], dst: &mut [f32]) {
+    let f: fn(f32) -> f32 = match op {
+        UnaryOp::Relu => |x| max(x, 0.0),
+        UnaryOp::Neg => |x| -x,
+        UnaryOp::Abs => f32::abs,
+        UnaryOp::Sqrt => f32::sqrt,
+    };
+    for (d, &s) in dst.iter_mut().zip(src) {
+        *d = f(s);
+    }
+}
+
+pub(crate) fn binary(op: BinaryOp, a: &[f32], b: &[f32], dst: &mut [f32]) {
+    for ((d, &x), &y) in dst.iter_mut().zip(a).zip(b) {
+        *d = apply(op, x, y);
+    }
+}
+
+pub(crate) fn binary_scalar_rhs(op: BinaryOp, a: &[f32], b: f32, dst: &mut [f32]) {
+    for (d, &x) in dst.iter_mut().zip(a) {
+        *d = apply(op, x, b);
+    }
+}
+
+pub(crate) fn binary_scalar_lhs(op: BinaryOp, a: f32, b: &[f32], dst: &mut [f32]) {
+    for (d, &y) in dst.iter_mut().zip(b) {
+        *d = apply(op, a, y);
+    }
+}
+
+pub(crate) fn reduce(op: ReduceOp, src: &[f32]) -> f32 {
+    src.iter().fold(op.identity(), |acc, &x| combine(op, acc, x))
+}
+
+#[inline(always)]
+pub(crate) fn combine(op: ReduceOp, acc: f32, x: f32) -> f32 {
+    match op {
+        ReduceOp::Sum => acc + x,
+        ReduceOp::Max => max(acc, x),
+        ReduceOp::Min => min(acc, x),
+    }
+}
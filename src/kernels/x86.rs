@@ -0,0 +1,124 @@
+//! x86_64 backends: SSE, AVX2 and AVX-512F.
+
+pub(crate) use avx2::KERNELS as AVX2_KERNELS;
+pub(crate) use avx512::KERNELS as AVX512_KERNELS;
+pub(crate) use sse::KERNELS as SSE_KERNELS;
+
+mod sse {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "sse")]
+    #[inline]
+    unsafe fn neg(v: __m128) -> __m128 {
+        _mm_xor_ps(v, _mm_set1_ps(-0.0))
+    }
+
+    #[target_feature(enable = "sse")]
+    #[inline]
+    unsafe fn abs(v: __m128) -> __m128 {
+        _mm_andnot_ps(_mm_set1_ps(-0.0), v)
+    }
+
+    crate::kernels::simd_kernels! {
+        backend: crate::kernels::KernelBackend::Sse,
+        feature: "sse",
+        vector: __m128,
+        lanes: 4,
+        load: _mm_loadu_ps,
+        store: _mm_storeu_ps,
+        splat: _mm_set1_ps,
+        add: _mm_add_ps,
+        sub: _mm_sub_ps,
+        mul: _mm_mul_ps,
+        div: _mm_div_ps,
+        max: _mm_max_ps,
+        min: _mm_min_ps,
+        sqrt: _mm_sqrt_ps,
+        neg: neg,
+        abs: abs,
+    }
+}
+
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn neg(v: __m256) -> __m256 {
+        _mm256_xor_ps(v, _mm256_set1_ps(-0.0))
+    }
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn abs(v: __m256) -> __m256 {
+        _mm256_andnot_ps(_mm256_set1_ps(-0.0), v)
+    }
+
+    crate::kernels::simd_kernels! {
+        backend: crate::kernels::KernelBackend::Avx2,
+        feature: "avx2",
+        vector: __m256,
+        lanes: 8,
+        load: _mm256_loadu_ps,
+        store: _mm256_storeu_ps,
+        splat: _mm256_set1_ps,
+        add: _mm256_add_ps,
+        sub: _mm256_sub_ps,
+        mul: _mm256_mul_ps,
+        div: _mm256_div_ps,
+        max: _mm256_max_ps,
+        min: _mm256_min_ps,
+        sqrt: _mm256_sqrt_ps,
+        neg: neg,
+        abs: abs,
+    }
+}
+
+mod avx512 {
+    use std::arch::x86_64::*;
+
+    // AVX-512F has no float bitwise ops (those are AVX-512DQ), so the sign
+    // bit is manipulated through the integer domain.
+    #[target_feature(enable = "avx512f")]
+    #[inline]
+    unsafe fn neg(v: __m512) -> __m512 {
+        _mm512_castsi512_ps(_mm512_xor_si512(_mm512_castps_si512(v), _mm512_set1_epi32(i32::MIN)))
+    }
+
+    #[target_feature(enable = "avx512f")]
+    #[inline]
+    unsafe fn abs(v: __m512) -> __m512 {
+        _mm512_abs_ps(v)
+    }
+
+    #[target_feature(enable = "avx512f")]
+    #[inline]
+    unsafe fn load(ptr: *const f32) -> __m512 {
+        _mm512_loadu_ps(ptr)
+    }
+
+    #[target_feature(enable = "avx512f")]
+    #[inline]
+    unsafe fn store(ptr: *mut f32, v: __m512) {
+        _mm512_storeu_ps(ptr, v)
+    }
+
+    crate::kernels::simd_kernels! {
+        backend: crate::kernels::KernelBackend::Avx512,
+        feature: "avx512f",
+        vector: __m512,
+        lanes: 16,
+        load: load,
+        store: store,
+        splat: _mm512_set1_ps,
+        add: _mm512_add_ps,
+        sub: _mm512_sub_ps,
+        mul: _mm512_mul_ps,
+        div: _mm512_div_ps,
+        max: _mm512_max_ps,
+        min: _mm512_min_ps,
+        sqrt: _mm512_sqrt_ps,
+        neg: neg,
+        abs: abs,
+    }
+}
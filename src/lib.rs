@@ -4,9 +4,11 @@
 
 pub mod loader;
 pub mod tensor;
+pub mod kernels;
 pub mod ops;
 pub mod graph;
 pub mod runtime;
+pub mod quantization;
 
 // Re-export generated ONNX bindings
 // The generated file name depends on the package name in the proto file.
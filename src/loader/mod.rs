@@ -8,6 +8,7 @@ use std::io::Read;
 use prost::Message;
 use crate::onnx::onnx_proto::ModelProto;
 use std::fmt;
+use crate::graph::{display_limit, write_elided};
 
 #[derive(Error, Debug)]
 pub enum LoaderError {
@@ -23,6 +24,7 @@ pub struct ModelLoader {
 
 impl ModelLoader {
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let _span = tracing::info_span!("load", path = %path.as_ref().display()).entered();
         // Read ONNX file into bytes
         let mut file = File::open(path)?;
         let mut buffer = Vec::new();
@@ -30,27 +32,53 @@ impl ModelLoader {
         
         // Parse the ONNX protobuf
         let model = ModelProto::decode(&*buffer)?;
+        tracing::debug!(bytes = buffer.len(), "model decoded");
 
         Ok(Self { model })
     }
+
+    /// Encodes `model` and writes it to `path`.
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let _span = tracing::info_span!("save", path = %path.as_ref().display()).entered();
+        let buffer = self.model.encode_to_vec();
+        std::fs::write(path, &buffer)?;
+        tracing::debug!(bytes = buffer.len(), "model encoded");
+        Ok(())
+    }
 }
 
+/// Summarizes the model: header fields, opsets, metadata and the node list.
+/// Initializers are reported by count and size only.
 impl fmt::Display for ModelLoader {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Model IR version: {:?}", self.model.ir_version)?;
-        writeln!(f, "Producer: {:?}", self.model.producer_name)?;
+        writeln!(f, "Producer: {:?} {:?}", self.model.producer_name, self.model.producer_version)?;
         writeln!(f, "Domain: {:?}", self.model.domain)?;
         writeln!(f, "Model version: {:?}", self.model.model_version)?;
-        writeln!(f, "Model: {:#?}", self.model)?;
-        // model.
+        for opset in &self.model.opset_import {
+            let domain = if opset.domain.is_empty() { "ai.onnx" } else { opset.domain.as_str() };
+            writeln!(f, "Opset: {} v{}", domain, opset.version)?;
+        }
+        for prop in &self.model.metadata_props {
+            writeln!(f, "Metadata: {} = {:?}", prop.key, prop.value)?;
+        }
 
         // Access the graph
         if let Some(graph) = &self.model.graph.as_ref() {
+            let bytes: usize = graph.initializer.iter()
+                .map(|t| t.raw_data.len() + 4 * (t.float_data.len() + t.int32_data.len())
+                    + 8 * (t.int64_data.len() + t.double_data.len() + t.uint64_data.len()))
+                .sum();
+            writeln!(f, "Graph: {:?}", graph.name)?;
+            writeln!(f, "Inputs: {:?}", graph.input.iter().map(|vi| &vi.name).collect::<Vec<_>>())?;
+            writeln!(f, "Outputs: {:?}", graph.output.iter().map(|vi| &vi.name).collect::<Vec<_>>())?;
+            writeln!(f, "Initializers: {} ({} bytes)", graph.initializer.len(), bytes)?;
             writeln!(f, "Nodes in graph: {}", graph.node.len())?;
-            for (i, node) in graph.node.iter().enumerate() {
+            for (i, node) in graph.node.iter().enumerate().take(display_limit(f)) {
                 writeln!(f, "Node {} → op_type: {}", i, node.op_type)?;
             }
+            write_elided(f, graph.node.len())?;
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,13 @@
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::elementwise;
+use crate::kernels::UnaryOp;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Abs;
+
+impl Operator for Abs {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(elementwise::unary(ctx, inputs[0], UnaryOp::Abs))
+    }
+}
@@ -0,0 +1,133 @@
+//! Activation operators (Sigmoid, Tanh, LeakyRelu, Clip) and the activations
+//! that FusedConv/FusedGemm apply to their output.
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::attributes;
+use crate::ops::elementwise;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::{attribute_proto, AttributeProto, NodeProto};
+
+pub struct Sigmoid;
+pub struct Tanh;
+pub struct LeakyRelu;
+pub struct Clip;
+
+/// Elementwise activation that can be fused into the preceding node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Activation {
+    Relu,
+    Sigmoid,
+    Tanh,
+    LeakyRelu { alpha: f32 },
+    Clip { min: f32, max: f32 },
+}
+
+impl Activation {
+    /// Parses a standalone activation node. `inputs` holds the node inputs
+    /// that are known (Clip reads its bounds from inputs since opset 11).
+    pub(crate) fn from_node(node: &NodeProto, inputs: &[Option<&Tensor>]) -> Option<Self> {
+        let scalar = |i: usize| inputs.get(i).copied().flatten().and_then(|t| t.data().first().copied());
+        match node.op_type.as_str() {
+            "Relu" => Some(Self::Relu),
+            "Sigmoid" => Some(Self::Sigmoid),
+            "Tanh" => Some(Self::Tanh),
+            "LeakyRelu" => Some(Self::LeakyRelu { alpha: attributes::get_float(node, "alpha", 0.01) }),
+            "Clip" => Some(Self::Clip {
+                min: scalar(1).unwrap_or_else(|| attributes::get_float(node, "min", f32::NEG_INFINITY)),
+                max: scalar(2).unwrap_or_else(|| attributes::get_float(node, "max", f32::INFINITY)),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Reads the `activation`/`activation_params` attributes of a fused node.
+    pub(crate) fn from_fused(node: &NodeProto) -> anyhow::Result<Option<Self>> {
+        let name = attributes::get_string(node, "activation");
+        let params = attributes::get_attr(node, "activation_params").map(|a| a.floats.clone()).unwrap_or_default();
+        let param = |i: usize, default: f32| params.get(i).copied().unwrap_or(default);
+        Ok(match name.as_str() {
+            "" => None,
+            "Relu" => Some(Self::Relu),
+            "Sigmoid" => Some(Self::Sigmoid),
+            "Tanh" => Some(Self::Tanh),
+            "LeakyRelu" => Some(Self::LeakyRelu { alpha: param(0, 0.01) }),
+            "Clip" => Some(Self::Clip { min: param(0, f32::NEG_INFINITY), max: param(1, f32::INFINITY) }),
+            other => return Err(anyhow::anyhow!("{}: unsupported fused activation '{}'", node.op_type, other)),
+        })
+    }
+
+    /// Attributes describing this activation on a fused node.
+    pub(crate) fn to_attributes(self) -> Vec<AttributeProto> {
+        let (name, params) = match self {
+            Self::Relu => ("Relu", vec![]),
+            Self::Sigmoid => ("Sigmoid", vec![]),
+            Self::Tanh => ("Tanh", vec![]),
+            Self::LeakyRelu { alpha } => ("LeakyRelu", vec![alpha]),
+            Self::Clip { min, max } => ("Clip", vec![min, max]),
+        };
+        let mut attrs = vec![AttributeProto {
+            name: "activation".to_string(),
+            s: name.as_bytes().to_vec(),
+            r#type: attribute_proto::AttributeType::String as i32,
+            ..Default::default()
+        }];
+        if !params.is_empty() {
+            attrs.push(AttributeProto {
+                name: "activation_params".to_string(),
+                floats: params,
+                r#type: attribute_proto::AttributeType::Floats as i32,
+                ..Default::default()
+            });
+        }
+        attrs
+    }
+
+    #[inline]
+    pub(crate) fn apply(self, x: f32) -> f32 {
+        match self {
+            // NaN maps to 0, like the Relu kernels
+            Self::Relu => if x > 0.0 { x } else { 0.0 },
+            Self::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Self::Tanh => x.tanh(),
+            Self::LeakyRelu { alpha } => if x >= 0.0 { x } else { alpha * x },
+            Self::Clip { min, max } => x.max(min).min(max),
+        }
+    }
+
+    pub(crate) fn apply_slice(self, data: &mut [f32]) {
+        for v in data {
+            *v = self.apply(*v);
+        }
+    }
+}
+
+fn activation(inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+    let known: Vec<Option<&Tensor>> = inputs.iter().map(|&t| Some(t)).collect();
+    let act = Activation::from_node(node, &known)
+        .ok_or_else(|| anyhow::anyhow!("{}: not an activation", node.op_type))?;
+    Ok(elementwise::map(ctx, inputs[0], move |x| act.apply(x)))
+}
+
+impl Operator for Sigmoid {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        activation(inputs, node, ctx)
+    }
+}
+
+impl Operator for Tanh {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        activation(inputs, node, ctx)
+    }
+}
+
+impl Operator for LeakyRelu {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        activation(inputs, node, ctx)
+    }
+}
+
+impl Operator for Clip {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        activation(inputs, node, ctx)
+    }
+}
@@ -0,0 +1,644 @@
+//! Attention operators of transformer models optimized by ONNX Runtime
+//! (com.microsoft): Attention, MultiHeadAttention, GroupQueryAttention and
+//! RotaryEmbedding.
+//!
+//! Query, key and value heads are gathered into `Heads`, laid out
+//! [batch, heads, sequence, head_size], then `attend` computes the scaled
+//! dot-product attention with the masks of each operator folded into one
+//! additive bias.
+
+use crate::ops::attributes;
+use crate::ops::matmul::MatMul;
+use crate::ops::operator::{self, Operator, OpContext};
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Attention;
+pub struct MultiHeadAttention;
+pub struct GroupQueryAttention;
+pub struct RotaryEmbedding;
+
+/// Values of a query, key or value, laid out [batch, heads, seq, size].
+struct Heads {
+    data: Vec<f32>,
+    batch: usize,
+    heads: usize,
+    seq: usize,
+    size: usize,
+}
+
+impl Heads {
+    fn zeros(batch: usize, heads: usize, seq: usize, size: usize) -> Self {
+        Self { data: vec![0.0; batch * heads * seq * size], batch, heads, seq, size }
+    }
+
+    /// Gathers heads from [batch, seq, stride] rows, head `n` starting at
+    /// column `offset(n)` of its row.
+    fn from_rows(src: &[f32], batch: usize, seq: usize, heads: usize, size: usize, stride: usize, offset: &dyn Fn(usize) -> usize) -> Self {
+        let mut out = Self::zeros(batch, heads, seq, size);
+        for b in 0..batch {
+            for s in 0..seq {
+                let row = &src[(b * seq + s) * stride..(b * seq + s + 1) * stride];
+                for n in 0..heads {
+                    out.row_mut(b, n, s).copy_from_slice(&row[offset(n)..offset(n) + size]);
+                }
+            }
+        }
+        out
+    }
+
+    /// Heads already laid out [batch, heads, seq, size].
+    fn from_tensor(op: &str, name: &str, t: &Tensor) -> anyhow::Result<Self> {
+        let &[batch, heads, seq, size] = t.shape() else {
+            return Err(anyhow::anyhow!("{}: {} must be [batch, heads, sequence, head_size], got {:?}", op, name, t.shape()));
+        };
+        Ok(Self { data: t.data().to_vec(), batch, heads, seq, size })
+    }
+
+    fn offset(&self, b: usize, n: usize, s: usize) -> usize {
+        ((b * self.heads + n) * self.seq + s) * self.size
+    }
+
+    fn row(&self, b: usize, n: usize, s: usize) -> &[f32] {
+        let start = self.offset(b, n, s);
+        &self.data[start..start + self.size]
+    }
+
+    fn row_mut(&mut self, b: usize, n: usize, s: usize) -> &mut [f32] {
+        let start = self.offset(b, n, s);
+        &mut self.data[start..start + self.size]
+    }
+
+    /// Adds `bias`, laid out [heads, size], to every row.
+    fn add_bias(&mut self, bias: &[f32]) {
+        for (i, v) in self.data.iter_mut().enumerate() {
+            let (n, d) = ((i / (self.size * self.seq)) % self.heads, i % self.size);
+            *v += bias[n * self.size + d];
+        }
+    }
+
+    /// `past` followed by these values along the sequence axis.
+    fn after(self, op: &str, past: &Heads) -> anyhow::Result<Self> {
+        if (past.batch, past.heads, past.size) != (self.batch, self.heads, self.size) {
+            return Err(anyhow::anyhow!(
+                "{}: past state [{}, {}, _, {}] does not match [{}, {}, _, {}]",
+                op, past.batch, past.heads, past.size, self.batch, self.heads, self.size
+            ));
+        }
+        let mut out = Self::zeros(self.batch, self.heads, past.seq + self.seq, self.size);
+        for b in 0..self.batch {
+            for n in 0..self.heads {
+                for s in 0..past.seq {
+                    out.row_mut(b, n, s).copy_from_slice(past.row(b, n, s));
+                }
+                for s in 0..self.seq {
+                    out.row_mut(b, n, past.seq + s).copy_from_slice(self.row(b, n, s));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn into_tensor(self) -> Tensor {
+        Tensor::new(self.data, vec![self.batch, self.heads, self.seq, self.size])
+    }
+}
+
+/// Additive bias of the score of query `i` for key `j`, by batch `b` and head `n`.
+type ScoreBias<'a> = dyn Fn(usize, usize, usize, usize) -> f32 + Sync + 'a;
+
+/// Scaled dot-product attention of `q` over `k` and `v`, query heads sharing
+/// the key/value heads in equal groups. Keys biased to `-inf` get no weight.
+/// Returns [batch, seq, heads * v.size].
+fn attend(ctx: &OpContext, q: &Heads, k: &Heads, v: &Heads, scale: f32, softcap: f32, bias: &ScoreBias) -> Vec<f32> {
+    let (heads, seq, total, width) = (q.heads, q.seq, k.seq, q.heads * v.size);
+    let group = heads / k.heads.max(1);
+    let mut out = ctx.alloc(q.batch * seq * width);
+    ctx.pool.for_each_chunk(&mut out, width.max(1), heads * total * (q.size + v.size), |row, dst| {
+        let (b, i) = (row / seq, row % seq);
+        let mut scores = vec![0.0; total];
+        for n in 0..heads {
+            let kn = n / group;
+            let query = q.row(b, n, i);
+            for (j, score) in scores.iter_mut().enumerate() {
+                let mut dot = query.iter().zip(k.row(b, kn, j)).map(|(a, b)| a * b).sum::<f32>() * scale;
+                if softcap > 0.0 {
+                    dot = softcap * (dot / softcap).tanh();
+                }
+                *score = dot + bias(b, n, i, j);
+            }
+            softmax(&mut scores);
+            let o = &mut dst[n * v.size..(n + 1) * v.size];
+            o.fill(0.0);
+            for (j, &w) in scores.iter().enumerate().filter(|(_, &w)| w != 0.0) {
+                for (o, &x) in o.iter_mut().zip(v.row(b, kn, j)) {
+                    *o += w * x;
+                }
+            }
+        }
+    });
+    out
+}
+
+/// In-place softmax; a row masked out entirely gets no weight at all.
+fn softmax(scores: &mut [f32]) {
+    let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    if max == f32::NEG_INFINITY {
+        scores.fill(0.0);
+        return;
+    }
+    let mut sum = 0.0;
+    for s in scores.iter_mut() {
+        *s = (*s - max).exp();
+        sum += *s;
+    }
+    scores.iter_mut().for_each(|s| *s /= sum);
+}
+
+fn num_heads(node: &NodeProto, name: &str) -> anyhow::Result<usize> {
+    match attributes::get_int(node, name, 0) {
+        n if n > 0 => Ok(n as usize),
+        _ => Err(anyhow::anyhow!("{}: the {} attribute is required", node.op_type, name)),
+    }
+}
+
+/// The `scale` attribute, 1/sqrt(head_size) when left out.
+fn scale_of(node: &NodeProto, size: usize) -> f32 {
+    match attributes::get_float(node, "scale", 0.0) {
+        0.0 => 1.0 / (size as f32).sqrt(),
+        scale => scale,
+    }
+}
+
+/// Whether key `j` is hidden from query `i` of batch `b`.
+type KeyMask<'a> = Box<dyn Fn(usize, usize, usize) -> bool + Sync + 'a>;
+
+/// Reads a key mask in the formats of Attention's `mask_index` and
+/// MultiHeadAttention's `key_padding_mask`: sequence ends [batch], ends then
+/// starts [2 * batch], or 0/1 masks [batch, total], [batch, seq, total] and
+/// [batch, 1, max, max].
+fn key_mask<'a>(node: &NodeProto, mask: Option<&'a Tensor>, batch: usize, seq: usize, total: usize, past: usize) -> anyhow::Result<KeyMask<'a>> {
+    let Some(mask) = mask else {
+        return Ok(Box::new(|_, _, _| false));
+    };
+    let m = mask.data();
+    Ok(match *mask.shape() {
+        [n] if n == batch => Box::new(move |b, _, j| j as f32 >= m[b]),
+        [n] if n == 2 * batch => Box::new(move |b, _, j| j as f32 >= m[b] || (j as f32) < m[batch + b]),
+        [b_, t] if b_ == batch && t == total => Box::new(move |b, _, j| m[b * total + j] == 0.0),
+        [b_, s, t] if b_ == batch && s == seq && t == total => Box::new(move |b, i, j| m[(b * seq + i) * total + j] == 0.0),
+        [b_, 1, rows, max] if b_ == batch && rows == max && max >= total => {
+            Box::new(move |b, i, j| m[(b * max + past + i) * max + j] == 0.0)
+        }
+        _ => return Err(anyhow::anyhow!("{}: unsupported mask shape {:?}", node.op_type, mask.shape())),
+    })
+}
+
+/// Reads an `attention_bias` input, [batch or 1, heads or 1, seq, total].
+fn attention_bias<'a>(node: &NodeProto, bias: Option<&'a Tensor>, batch: usize, heads: usize, seq: usize, total: usize) -> anyhow::Result<Box<ScoreBias<'a>>> {
+    let Some(bias) = bias else {
+        return Ok(Box::new(|_, _, _, _| 0.0));
+    };
+    let &[bb, bn, s, t] = bias.shape() else {
+        return Err(anyhow::anyhow!("{}: attention_bias must have rank 4, got {:?}", node.op_type, bias.shape()));
+    };
+    if !(bb == 1 || bb == batch) || !(bn == 1 || bn == heads) || s != seq || t != total {
+        return Err(anyhow::anyhow!("{}: attention_bias {:?} does not broadcast to [{}, {}, {}, {}]", node.op_type, bias.shape(), batch, heads, seq, total));
+    }
+    let data = bias.data();
+    Ok(Box::new(move |b, n, i, j| data[(((b % bb) * bn + n % bn) * seq + i) * total + j]))
+}
+
+/// Rotates the first `2 * cos.len()` values of `head` by the angles whose
+/// cosines and sines are given, pairing adjacent values when `interleaved`,
+/// and the two halves otherwise.
+fn rotate(head: &mut [f32], cos: &[f32], sin: &[f32], interleaved: bool) {
+    let half = cos.len();
+    for d in 0..half {
+        let (a, b) = if interleaved { (2 * d, 2 * d + 1) } else { (d, d + half) };
+        let (x, y) = (head[a], head[b]);
+        head[a] = x * cos[d] - y * sin[d];
+        head[b] = y * cos[d] + x * sin[d];
+    }
+}
+
+/// Cosine and sine caches, [max_position, rotary_dim / 2].
+struct RotaryCache<'a> {
+    cos: &'a [f32],
+    sin: &'a [f32],
+    half: usize,
+    positions: usize,
+}
+
+impl<'a> RotaryCache<'a> {
+    fn new(node: &NodeProto, cos: &'a Tensor, sin: &'a Tensor, size: usize) -> anyhow::Result<Self> {
+        let &[positions, half] = cos.shape() else {
+            return Err(anyhow::anyhow!("{}: cos_cache must be [max_position, rotary_dim / 2], got {:?}", node.op_type, cos.shape()));
+        };
+        if sin.shape() != cos.shape() || 2 * half > size {
+            return Err(anyhow::anyhow!(
+                "{}: caches {:?} and {:?} do not fit heads of size {}", node.op_type, cos.shape(), sin.shape(), size
+            ));
+        }
+        Ok(Self { cos: cos.data(), sin: sin.data(), half, positions })
+    }
+
+    fn apply(&self, node: &NodeProto, head: &mut [f32], position: usize, interleaved: bool) -> anyhow::Result<()> {
+        if position >= self.positions {
+            return Err(anyhow::anyhow!("{}: position {} is beyond the {} cached positions", node.op_type, position, self.positions));
+        }
+        let range = position * self.half..(position + 1) * self.half;
+        rotate(head, &self.cos[range.clone()], &self.sin[range], interleaved);
+        Ok(())
+    }
+}
+
+impl Operator for Attention {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.remove(0))
+    }
+
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let x = inputs[0];
+        let weights = inputs.get(1).ok_or_else(|| anyhow::anyhow!("Attention: missing weights input"))?;
+        let bias = operator::optional_input(inputs, 2);
+        let mask = operator::optional_input(inputs, 3);
+        let past = operator::optional_input(inputs, 4);
+        let extra = operator::optional_input(inputs, 5);
+        if attributes::get_int(node, "do_rotary", 0) != 0 || attributes::get_int(node, "past_present_share_buffer", 0) != 0 {
+            return Err(anyhow::anyhow!("Attention: do_rotary and past_present_share_buffer are not supported"));
+        }
+
+        let heads = num_heads(node, "num_heads")?;
+        let &[batch, seq, input_hidden] = x.shape() else {
+            return Err(anyhow::anyhow!("Attention: input must be [batch, sequence, hidden], got {:?}", x.shape()));
+        };
+        let &[w_rows, width] = weights.shape() else {
+            return Err(anyhow::anyhow!("Attention: weights must be 2-D, got {:?}", weights.shape()));
+        };
+        let (dq, dk, dv) = match attributes::get_ints(node, "qkv_hidden_sizes").as_slice() {
+            [] => (width / 3, width / 3, width / 3),
+            &[q, k, v] => (q as usize, k as usize, v as usize),
+            other => return Err(anyhow::anyhow!("Attention: qkv_hidden_sizes must have 3 values, got {:?}", other)),
+        };
+        if w_rows != input_hidden || dq + dk + dv != width || dq != dk || [dq, dk, dv].iter().any(|d| d % heads != 0) {
+            return Err(anyhow::anyhow!(
+                "Attention: weights {:?} do not split into query/key/value of sizes {}/{}/{} with {} heads for input {:?}",
+                weights.shape(), dq, dk, dv, heads, x.shape()
+            ));
+        }
+        if bias.is_some_and(|b| b.len() != width) {
+            return Err(anyhow::anyhow!("Attention: bias must have {} elements", width));
+        }
+
+        // One projection for the query, key and value of every token
+        let mut qkv = ctx.alloc(batch * seq * width);
+        MatMul::gemm(ctx, x.data(), weights.data(), &mut qkv, batch * seq, input_hidden, width);
+        if let Some(bias) = bias {
+            let bias = bias.data();
+            for row in qkv.chunks_mut(width.max(1)) {
+                row.iter_mut().zip(bias).for_each(|(v, b)| *v += b);
+            }
+        }
+        let (hq, hv) = (dq / heads, dv / heads);
+        // past and present stack keys and values in one tensor
+        let present = node.output.len() > 1;
+        if hq != hv && (past.is_some() || present) {
+            return Err(anyhow::anyhow!("Attention: past and present need key and value heads of one size"));
+        }
+        let q = Heads::from_rows(&qkv, batch, seq, heads, hq, width, &|n| n * hq);
+        let mut k = Heads::from_rows(&qkv, batch, seq, heads, hq, width, &|n| dq + n * hq);
+        let mut v = Heads::from_rows(&qkv, batch, seq, heads, hv, width, &|n| dq + dk + n * hv);
+
+        // past: [2, batch, heads, past, head_size], keys then values
+        let mut past_len = 0;
+        if let Some(past) = past {
+            let &[2, pb, pn, p, ps] = past.shape() else {
+                return Err(anyhow::anyhow!("Attention: past must be [2, batch, heads, past, head_size], got {:?}", past.shape()));
+            };
+            let (keys, values) = past.data().split_at(past.len() / 2);
+            let past_k = Heads { data: keys.to_vec(), batch: pb, heads: pn, seq: p, size: ps };
+            let past_v = Heads { data: values.to_vec(), batch: pb, heads: pn, seq: p, size: ps };
+            k = k.after("Attention", &past_k)?;
+            v = v.after("Attention", &past_v)?;
+            past_len = p;
+        }
+        let total = past_len + seq;
+
+        let masked = key_mask(node, mask, batch, seq, total, past_len)?;
+        let extra = attention_bias(node, extra, batch, heads, seq, total)?;
+        let causal = attributes::get_int(node, "unidirectional", 0) != 0;
+        let filter = attributes::get_float(node, "mask_filter_value", -10000.0);
+        let out = attend(ctx, &q, &k, &v, scale_of(node, hq), 0.0, &|b, n, i, j| {
+            let hidden = masked(b, i, j) || (causal && j > past_len + i);
+            if hidden { filter + extra(b, n, i, j) } else { extra(b, n, i, j) }
+        });
+
+        let mut outputs = vec![Tensor::new(out, vec![batch, seq, dv])];
+        if present {
+            let mut present = k.data;
+            present.extend_from_slice(&v.data);
+            outputs.push(Tensor::new(present, vec![2, batch, heads, total, hq]));
+        }
+        Ok(outputs)
+    }
+}
+
+impl Operator for MultiHeadAttention {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.remove(0))
+    }
+
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let query = inputs[0];
+        let key = operator::optional_input(inputs, 1);
+        let value = operator::optional_input(inputs, 2);
+        let bias = operator::optional_input(inputs, 3);
+        let padding = operator::optional_input(inputs, 4);
+        let extra = operator::optional_input(inputs, 5);
+        let past_key = operator::optional_input(inputs, 6);
+        let past_value = operator::optional_input(inputs, 7);
+        let heads = num_heads(node, "num_heads")?;
+        let op = "MultiHeadAttention";
+
+        // Query, key and value packed together, keys and values packed
+        // together, separate, or keys and values already split into heads
+        let shape = |t: &Tensor| t.shape().to_vec();
+        let (mut q, mut k, mut v, split) = match (shape(query).as_slice(), key.map(shape).as_deref(), value.map(shape).as_deref()) {
+            (&[batch, seq, n, 3, size], None, None) if n == heads => {
+                let stride = heads * 3 * size;
+                (
+                    Heads::from_rows(query.data(), batch, seq, heads, size, stride, &|n| n * 3 * size),
+                    Heads::from_rows(query.data(), batch, seq, heads, size, stride, &|n| n * 3 * size + size),
+                    Heads::from_rows(query.data(), batch, seq, heads, size, stride, &|n| n * 3 * size + 2 * size),
+                    false,
+                )
+            }
+            (&[batch, seq, dq], Some(&[kb, len, n, 2, size]), None) if kb == batch && n == heads && dq == heads * size => {
+                let key = key.unwrap().data();
+                let stride = heads * 2 * size;
+                (
+                    Heads::from_rows(query.data(), batch, seq, heads, size, dq, &|n| n * size),
+                    Heads::from_rows(key, batch, len, heads, size, stride, &|n| n * 2 * size),
+                    Heads::from_rows(key, batch, len, heads, size, stride, &|n| n * 2 * size + size),
+                    false,
+                )
+            }
+            (&[batch, seq, dq], Some(&[kb, len, dk]), Some(&[vb, vlen, dv]))
+                if kb == batch && vb == batch && vlen == len && dq % heads == 0 && dk == dq && dv % heads == 0 =>
+            {
+                let (hq, hv) = (dq / heads, dv / heads);
+                (
+                    Heads::from_rows(query.data(), batch, seq, heads, hq, dq, &|n| n * hq),
+                    Heads::from_rows(key.unwrap().data(), batch, len, heads, hq, dk, &|n| n * hq),
+                    Heads::from_rows(value.unwrap().data(), batch, len, heads, hv, dv, &|n| n * hv),
+                    false,
+                )
+            }
+            (&[batch, seq, dq], Some(&[_, _, _, _]), Some(&[_, _, _, _])) if dq % heads == 0 => (
+                Heads::from_rows(query.data(), batch, seq, heads, dq / heads, dq, &|n| n * (dq / heads)),
+                Heads::from_tensor(op, "key", key.unwrap())?,
+                Heads::from_tensor(op, "value", value.unwrap())?,
+                true,
+            ),
+            (q, k, v) => {
+                return Err(anyhow::anyhow!("MultiHeadAttention: unsupported query/key/value shapes {:?}/{:?}/{:?} for {} heads", q, k, v, heads));
+            }
+        };
+        let (batch, seq) = (q.batch, q.seq);
+        if (k.batch, k.heads, k.size) != (batch, heads, q.size) || (v.batch, v.heads, v.seq) != (batch, heads, k.seq) {
+            return Err(anyhow::anyhow!("MultiHeadAttention: key/value heads do not match the query"));
+        }
+
+        // bias: [query | key | value], the key and value parts unused once split into heads
+        if let Some(bias) = bias {
+            let (dq, dv) = (heads * q.size, heads * v.size);
+            let bias = bias.data();
+            if bias.len() != 2 * dq + dv {
+                return Err(anyhow::anyhow!("MultiHeadAttention: bias must have {} elements, got {}", 2 * dq + dv, bias.len()));
+            }
+            q.add_bias(&bias[..dq]);
+            if !split {
+                k.add_bias(&bias[dq..2 * dq]);
+                v.add_bias(&bias[2 * dq..]);
+            }
+        }
+        let mut past_len = 0;
+        if let (Some(past_key), Some(past_value), false) = (past_key, past_value, split) {
+            let past_key = Heads::from_tensor(op, "past_key", past_key)?;
+            past_len = past_key.seq;
+            k = k.after(op, &past_key)?;
+            v = v.after(op, &Heads::from_tensor(op, "past_value", past_value)?)?;
+        }
+        let total = k.seq;
+
+        let masked = key_mask(node, padding, batch, seq, total, past_len)?;
+        let extra = attention_bias(node, extra, batch, heads, seq, total)?;
+        let causal = attributes::get_int(node, "unidirectional", 0) != 0;
+        let filter = attributes::get_float(node, "mask_filter_value", -10000.0);
+        let out = attend(ctx, &q, &k, &v, scale_of(node, q.size), 0.0, &|b, n, i, j| {
+            let hidden = masked(b, i, j) || (causal && j > past_len + i);
+            if hidden { filter + extra(b, n, i, j) } else { extra(b, n, i, j) }
+        });
+
+        let width = heads * v.size;
+        let mut outputs = vec![Tensor::new(out, vec![batch, seq, width]), k.into_tensor(), v.into_tensor()];
+        outputs.truncate(node.output.len().max(1));
+        Ok(outputs)
+    }
+}
+
+impl Operator for GroupQueryAttention {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.remove(0))
+    }
+
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let op = "GroupQueryAttention";
+        let query = inputs[0];
+        let key = operator::optional_input(inputs, 1);
+        let value = operator::optional_input(inputs, 2);
+        let past_key = operator::optional_input(inputs, 3);
+        let past_value = operator::optional_input(inputs, 4);
+        let (Some(seqlens_k), Some(total_length)) = (operator::optional_input(inputs, 5), operator::optional_input(inputs, 6)) else {
+            return Err(anyhow::anyhow!("GroupQueryAttention: seqlens_k and total_sequence_length are required"));
+        };
+        if attributes::get_int(node, "smooth_softmax", 0) != 0 {
+            return Err(anyhow::anyhow!("GroupQueryAttention: smooth_softmax is not supported"));
+        }
+        let heads = num_heads(node, "num_heads")?;
+        let kv_heads = num_heads(node, "kv_num_heads")?;
+        if heads % kv_heads != 0 {
+            return Err(anyhow::anyhow!("GroupQueryAttention: {} heads cannot share {} key/value heads", heads, kv_heads));
+        }
+        let &[batch, seq, width] = query.shape() else {
+            return Err(anyhow::anyhow!("GroupQueryAttention: query must be [batch, sequence, hidden], got {:?}", query.shape()));
+        };
+
+        // Separate projections, or query, key and value packed in one
+        let (mut q, mut k, v) = match (key, value) {
+            (Some(key), Some(value)) => {
+                let size = width / heads;
+                let kv_width = kv_heads * size;
+                if width % heads != 0 || key.shape() != [batch, seq, kv_width] || value.shape() != key.shape() {
+                    return Err(anyhow::anyhow!(
+                        "GroupQueryAttention: key {:?}/value {:?} do not match query {:?} with {}/{} heads",
+                        key.shape(), value.shape(), query.shape(), heads, kv_heads
+                    ));
+                }
+                (
+                    Heads::from_rows(query.data(), batch, seq, heads, size, width, &|n| n * size),
+                    Heads::from_rows(key.data(), batch, seq, kv_heads, size, kv_width, &|n| n * size),
+                    Heads::from_rows(value.data(), batch, seq, kv_heads, size, kv_width, &|n| n * size),
+                )
+            }
+            (None, None) if width % (heads + 2 * kv_heads) == 0 => {
+                let size = width / (heads + 2 * kv_heads);
+                (
+                    Heads::from_rows(query.data(), batch, seq, heads, size, width, &|n| n * size),
+                    Heads::from_rows(query.data(), batch, seq, kv_heads, size, width, &|n| (heads + n) * size),
+                    Heads::from_rows(query.data(), batch, seq, kv_heads, size, width, &|n| (heads + kv_heads + n) * size),
+                )
+            }
+            _ => return Err(anyhow::anyhow!("GroupQueryAttention: expected key and value, or a packed query of {} heads", heads + 2 * kv_heads)),
+        };
+        let size = q.size;
+
+        let past = match (past_key, past_value) {
+            (Some(pk), Some(pv)) => {
+                let (pk, pv) = (Heads::from_tensor(op, "past_key", pk)?, Heads::from_tensor(op, "past_value", pv)?);
+                if (pk.batch, pk.heads, pk.size) != (batch, kv_heads, size) || pv.data.len() != pk.data.len() {
+                    return Err(anyhow::anyhow!("GroupQueryAttention: past_key/past_value must be [{}, {}, past, {}]", batch, kv_heads, size));
+                }
+                Some((pk, pv))
+            }
+            _ => None,
+        };
+        let past_capacity = past.as_ref().map_or(0, |(pk, _)| pk.seq);
+        let total_length = total_length.data().first().copied().unwrap_or(0.0) as usize;
+        let present_len = total_length.max(past_capacity);
+        let is_prompt = past_capacity == 0;
+
+        // Valid keys of each sequence, and how many of them were cached before this step
+        let lens = seqlens_k.data();
+        if lens.len() != batch {
+            return Err(anyhow::anyhow!("GroupQueryAttention: seqlens_k must have {} values, got {}", batch, lens.len()));
+        }
+        let mut totals = Vec::with_capacity(batch);
+        let mut pasts = Vec::with_capacity(batch);
+        for &len in lens {
+            let total = len as usize + 1;
+            let past = if is_prompt { Some(0) } else { total.checked_sub(seq) };
+            match past {
+                Some(past) if total <= present_len && past <= past_capacity => {
+                    totals.push(total);
+                    pasts.push(past);
+                }
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "GroupQueryAttention: sequence length {} does not fit a {}-token cache with {} new tokens", total, present_len, seq
+                    ));
+                }
+            }
+        }
+
+        if attributes::get_int(node, "do_rotary", 0) != 0 {
+            let (Some(cos), Some(sin)) = (operator::optional_input(inputs, 7), operator::optional_input(inputs, 8)) else {
+                return Err(anyhow::anyhow!("GroupQueryAttention: do_rotary needs cos_cache and sin_cache"));
+            };
+            let cache = RotaryCache::new(node, cos, sin, size)?;
+            let interleaved = attributes::get_int(node, "rotary_interleaved", 0) != 0;
+            for (b, &past) in pasts.iter().enumerate() {
+                for i in 0..seq {
+                    for n in 0..heads {
+                        cache.apply(node, q.row_mut(b, n, i), past + i, interleaved)?;
+                    }
+                    for n in 0..kv_heads {
+                        cache.apply(node, k.row_mut(b, n, i), past + i, interleaved)?;
+                    }
+                }
+            }
+        }
+
+        // The cached keys and values, then the new ones right after the valid past
+        let mut present_k = Heads::zeros(batch, kv_heads, present_len, size);
+        let mut present_v = Heads::zeros(batch, kv_heads, present_len, size);
+        for (b, &cached) in pasts.iter().enumerate() {
+            for n in 0..kv_heads {
+                if let Some((pk, pv)) = &past {
+                    for s in 0..cached {
+                        present_k.row_mut(b, n, s).copy_from_slice(pk.row(b, n, s));
+                        present_v.row_mut(b, n, s).copy_from_slice(pv.row(b, n, s));
+                    }
+                }
+                for i in (0..seq).filter(|i| cached + i < present_len) {
+                    present_k.row_mut(b, n, cached + i).copy_from_slice(k.row(b, n, i));
+                    present_v.row_mut(b, n, cached + i).copy_from_slice(v.row(b, n, i));
+                }
+            }
+        }
+
+        let window = attributes::get_int(node, "local_window_size", -1);
+        let softcap = attributes::get_float(node, "softcap", 0.0);
+        let out = attend(ctx, &q, &present_k, &present_v, scale_of(node, size), softcap, &|b, _, i, j| {
+            let causal = pasts[b] + i + 1;
+            let outside_window = window > 0 && j + (window as usize) < causal;
+            if j >= causal || j >= totals[b] || outside_window { f32::NEG_INFINITY } else { 0.0 }
+        });
+
+        let mut outputs = vec![Tensor::new(out, vec![batch, seq, heads * size]), present_k.into_tensor(), present_v.into_tensor()];
+        outputs.truncate(node.output.len().max(1));
+        Ok(outputs)
+    }
+}
+
+impl Operator for RotaryEmbedding {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        if inputs.len() < 4 {
+            return Err(anyhow::anyhow!("RotaryEmbedding: expected input, position_ids, cos_cache and sin_cache"));
+        }
+        let (x, position_ids, cos, sin) = (inputs[0], inputs[1], inputs[2], inputs[3]);
+        let interleaved = attributes::get_int(node, "interleaved", 0) != 0;
+        let half = cos.shape().get(1).copied().unwrap_or(0);
+
+        // [batch, seq, hidden] with heads side by side, or [batch, heads, seq, head_size]
+        let (batch, heads, seq, size, bnsh) = match *x.shape() {
+            [batch, heads, seq, size] => (batch, heads, seq, size, true),
+            [batch, seq, hidden] => {
+                let size = match attributes::get_int(node, "num_heads", 0) {
+                    n if n > 0 => hidden / n as usize,
+                    _ => 2 * half,
+                };
+                if size == 0 || hidden % size != 0 {
+                    return Err(anyhow::anyhow!("RotaryEmbedding: hidden size {} does not split into heads of {}", hidden, size));
+                }
+                (batch, hidden / size, seq, size, false)
+            }
+            _ => return Err(anyhow::anyhow!("RotaryEmbedding: input must have rank 3 or 4, got {:?}", x.shape())),
+        };
+        let cache = RotaryCache::new(node, cos, sin, size)?;
+
+        // One position per token, or the position of the first token
+        let positions = position_ids.data();
+        let position = |b: usize, s: usize| -> anyhow::Result<usize> {
+            let p = match positions.len() {
+                1 => positions[0] as usize + s,
+                n if n == batch * seq => positions[b * seq + s] as usize,
+                n => return Err(anyhow::anyhow!("RotaryEmbedding: {} position ids for {} tokens", n, batch * seq)),
+            };
+            Ok(p)
+        };
+
+        let mut out = x.data().to_vec();
+        for b in 0..batch {
+            for s in 0..seq {
+                let p = position(b, s)?;
+                for n in 0..heads {
+                    let start = if bnsh { ((b * heads + n) * seq + s) * size } else { ((b * seq + s) * heads + n) * size };
+                    cache.apply(node, &mut out[start..start + size], p, interleaved)?;
+                }
+            }
+        }
+        Ok(Tensor::new(out, x.shape().to_vec()))
+    }
+}
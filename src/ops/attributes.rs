@@ -0,0 +1,29 @@
+//! Helpers to read `NodeProto` attributes with ONNX defaults.
+
+use crate::onnx::onnx_proto::{AttributeProto, NodeProto};
+
+pub(crate) fn get_attr<'a>(node: &'a NodeProto, name: &str) -> Option<&'a AttributeProto> {
+    node.attribute.iter().find(|a| a.name == name)
+}
+
+/// Extract an i64 attribute from the node
+pub(crate) fn get_int(node: &NodeProto, name: &str, default: i64) -> i64 {
+    get_attr(node, name).map(|a| a.i).unwrap_or(default)
+}
+
+/// Extract an f32 attribute from the node
+pub(crate) fn get_float(node: &NodeProto, name: &str, default: f32) -> f32 {
+    get_attr(node, name).map(|a| a.f).unwrap_or(default)
+}
+
+/// Extract a list of i64 attributes from the node
+pub(crate) fn get_ints(node: &NodeProto, name: &str) -> Vec<i64> {
+    get_attr(node, name).map(|a| a.ints.clone()).unwrap_or_default()
+}
+
+/// Extract a string attribute from the node
+pub(crate) fn get_string(node: &NodeProto, name: &str) -> String {
+    get_attr(node, name)
+        .map(|a| String::from_utf8_lossy(&a.s).to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,45 @@
+//! BatchNormalization operator implementation (inference mode)
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::attributes;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct BatchNormalization;
+
+impl Operator for BatchNormalization {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        if inputs.len() != 5 {
+            return Err(anyhow::anyhow!("BatchNormalization: expected 5 inputs, got {}", inputs.len()));
+        }
+        let (x, scale, bias, mean, var) = (inputs[0], inputs[1], inputs[2], inputs[3], inputs[4]);
+        let shape = x.shape();
+        if shape.len() < 2 {
+            return Err(anyhow::anyhow!("BatchNormalization: expected at least 2D input, got {:?}", shape));
+        }
+        let channels = shape[1];
+        if [scale, bias, mean, var].iter().any(|t| t.data().len() != channels) {
+            return Err(anyhow::anyhow!("BatchNormalization: parameters must have {} elements", channels));
+        }
+        let epsilon = attributes::get_float(node, "epsilon", 1e-5);
+        let spatial: usize = shape[2..].iter().product();
+
+        // y = x * factor + shift, per channel
+        let factor: Vec<f32> = (0..channels)
+            .map(|c| scale.data()[c] / (var.data()[c] + epsilon).sqrt())
+            .collect();
+        let shift: Vec<f32> = (0..channels)
+            .map(|c| bias.data()[c] - mean.data()[c] * factor[c])
+            .collect();
+
+        let src = x.data();
+        let mut out = ctx.alloc(src.len());
+        ctx.pool.for_each_chunk(&mut out, spatial.max(1), spatial, |idx, plane| {
+            let c = idx % channels;
+            for (o, &v) in plane.iter_mut().zip(&src[idx * spatial..(idx + 1) * spatial]) {
+                *o = v * factor[c] + shift[c];
+            }
+        });
+        Ok(Tensor::new(out, shape.to_vec()))
+    }
+}
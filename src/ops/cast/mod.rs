@@ -0,0 +1,49 @@
+//! Cast operator implementation
+//!
+//! Casts to FLOAT, FLOAT16 and BFLOAT16 change the element type of the tensor.
+//! Integer targets wrap like a C cast, then UINT8, INT8 and INT32 are stored
+//! natively; other types are held as f32 values, so casting to them only
+//! reproduces the value changes of the target type (truncation, booleans).
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::attributes;
+use crate::ops::elementwise;
+use crate::tensor::{ElementType, Tensor};
+use crate::onnx::onnx_proto::NodeProto;
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+
+pub struct Cast;
+
+impl Operator for Cast {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let to = attributes::get_int(node, "to", 0) as i32;
+        let target = DataType::try_from(to);
+        let native = target.as_ref().ok().and_then(|&t| ElementType::from_data_type(t));
+        if let Some(element_type) = native.filter(|&t| t.is_float() || t == inputs[0].element_type()) {
+            return Ok(inputs[0].to_element_type(element_type));
+        }
+        let x = &inputs[0].to_element_type(ElementType::F32);
+        let convert: fn(f32) -> f32 = match target {
+            Ok(DataType::Double) => return Ok(elementwise::map(ctx, x, |v| v)),
+            Ok(DataType::Bool) => |v| if v != 0.0 { 1.0 } else { 0.0 },
+            Ok(DataType::Int8) => |v| v as i64 as i8 as f32,
+            Ok(DataType::Uint8) => |v| v as i64 as u8 as f32,
+            Ok(DataType::Int16) => |v| v as i64 as i16 as f32,
+            Ok(DataType::Uint16) => |v| v as i64 as u16 as f32,
+            Ok(DataType::Int32) => |v| v as i64 as i32 as f32,
+            Ok(DataType::Uint32) => |v| v as i64 as u32 as f32,
+            Ok(DataType::Int64 | DataType::Uint64) => |v| v.trunc(),
+            _ => return Err(anyhow::anyhow!("Cast: unsupported target type {}", to)),
+        };
+        let y = elementwise::map(ctx, x, convert);
+        // Integer targets wrap like a C cast, then store natively when a layout exists
+        Ok(match native {
+            Some(element_type) => y.to_element_type(element_type),
+            None => y,
+        })
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
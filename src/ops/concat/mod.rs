@@ -0,0 +1,42 @@
+//! Concat operator implementation
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::attributes;
+use crate::ops::shape::normalize_axis;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Concat;
+
+impl Operator for Concat {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let first = inputs.first().ok_or_else(|| anyhow::anyhow!("Concat: no inputs"))?;
+        let rank = first.shape().len();
+        let axis = normalize_axis(node, attributes::get_int(node, "axis", 0), rank)?;
+
+        let mut shape = first.shape().to_vec();
+        shape[axis] = 0;
+        for x in inputs {
+            let compatible = x.shape().len() == rank
+                && x.shape().iter().zip(first.shape()).enumerate().all(|(i, (a, b))| i == axis || a == b);
+            if !compatible {
+                return Err(anyhow::anyhow!("Concat: incompatible shapes {:?} and {:?} on axis {}", first.shape(), x.shape(), axis));
+            }
+            shape[axis] += x.shape()[axis];
+        }
+
+        // Each input contributes a contiguous block of `dim * inner` values per outer index
+        let outer: usize = shape[..axis].iter().product();
+        let inner: usize = shape[axis + 1..].iter().product();
+        let mut out = ctx.alloc(shape.iter().product());
+        let mut offset = 0;
+        for o in 0..outer {
+            for x in inputs {
+                let block = x.shape()[axis] * inner;
+                out[offset..offset + block].copy_from_slice(&x.data()[o * block..(o + 1) * block]);
+                offset += block;
+            }
+        }
+        Ok(Tensor::new(out, shape))
+    }
+}
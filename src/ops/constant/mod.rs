@@ -0,0 +1,26 @@
+//! Constant operator implementation
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Constant;
+
+impl Operator for Constant {
+    fn run(&self, _inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let attr = node.attribute.first()
+            .ok_or_else(|| anyhow::anyhow!("Constant '{}': missing value attribute", node.name))?;
+        match attr.name.as_str() {
+            "value" => {
+                let t = attr.t.as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("Constant '{}': empty tensor attribute", node.name))?;
+                Tensor::from_proto(t)
+            }
+            "value_float" => Ok(Tensor::new(vec![attr.f], vec![])),
+            "value_floats" => Ok(Tensor::new(attr.floats.clone(), vec![attr.floats.len()])),
+            "value_int" => Ok(Tensor::new(vec![attr.i as f32], vec![])),
+            "value_ints" => Ok(Tensor::new(attr.ints.iter().map(|&i| i as f32).collect(), vec![attr.ints.len()])),
+            other => Err(anyhow::anyhow!("Constant '{}': unsupported attribute '{}'", node.name, other)),
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! Control-flow operators: If, Loop and Scan.
+//!
+//! Their bodies are `GraphProto` attributes run by `Body`, which dispatches
+//! each node to the operators of `OpContext::registry`. Sessions build the
+//! bodies of their nodes once, as `Bodies`; nodes run outside a session
+//! build theirs on every call. Values of the enclosing scopes that a body
+//! reads are passed to the operator after its declared inputs, in the order
+//! given by `graph::implicit_inputs`.
+
+use crate::graph::{implicit_inputs, Graph};
+use crate::onnx::onnx_proto::NodeProto;
+use crate::ops::attributes;
+use crate::ops::operator::{self, optional_input, OpContext, Operator};
+use crate::tensor::{ElementType, Tensor};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Outer-scope values visible to a body, by name.
+type Scope<'a> = HashMap<&'a str, &'a Tensor>;
+
+/// Splits the inputs of a control-flow node into its declared inputs and the
+/// scope made of the values its subgraphs capture.
+fn split_inputs<'a>(inputs: &[&'a Tensor], node: &NodeProto, captured: &'a [String]) -> anyhow::Result<(Vec<&'a Tensor>, Scope<'a>)> {
+    let declared = node.input.len().min(inputs.len());
+    if inputs.len() != declared + captured.len() {
+        return Err(anyhow::anyhow!(
+            "{} '{}': expected {} captured values, got {}", node.op_type, node.name, captured.len(), inputs.len() - declared
+        ));
+    }
+    let scope = captured.iter().map(String::as_str).zip(inputs[declared..].iter().copied()).collect();
+    Ok((inputs[..declared].to_vec(), scope))
+}
+
+/// Truth value of a boolean scalar (stored as 0/1).
+fn truthy(node: &NodeProto, tensor: &Tensor) -> anyhow::Result<bool> {
+    let values = tensor.values();
+    let first = values.first().ok_or_else(|| anyhow::anyhow!("{} '{}': empty condition", node.op_type, node.name))?;
+    Ok(*first != 0.0)
+}
+
+fn scalar(value: f32) -> Tensor {
+    Tensor::new(vec![value], vec![])
+}
+
+/// Stacks tensors of one shape along a new `axis`. With no tensor at all the
+/// element shape is unknown and an empty `[0]` tensor is returned.
+fn stack(items: &[Tensor], axis: usize) -> anyhow::Result<Tensor> {
+    let Some(first) = items.first() else { return Ok(Tensor::new(Vec::new(), vec![0])) };
+    if let Some(other) = items.iter().find(|t| t.shape() != first.shape()) {
+        return Err(anyhow::anyhow!("Scan outputs change shape across iterations: {:?} and {:?}", first.shape(), other.shape()));
+    }
+    let rank = first.shape().len();
+    if axis > rank {
+        return Err(anyhow::anyhow!("Axis {} out of range to stack tensors of rank {}", axis, rank));
+    }
+    let mut data = Vec::with_capacity(first.len() * items.len());
+    for item in items {
+        data.extend_from_slice(&item.values());
+    }
+    let mut shape = vec![items.len()];
+    shape.extend_from_slice(first.shape());
+    let mut stacked = Tensor::new(data, shape);
+    if axis > 0 {
+        // Move the new leading axis into place
+        let mut perm: Vec<usize> = (1..=rank).collect();
+        perm.insert(axis, 0);
+        stacked = stacked.permute(&perm)?.to_contiguous();
+    }
+    Ok(match first.element_type() {
+        ElementType::F32 => stacked,
+        element_type => stacked.to_element_type(element_type),
+    })
+}
+
+/// Normalizes a possibly negative `axis` of a rank-`rank` tensor.
+fn normalize(node: &NodeProto, axis: i64, rank: usize) -> anyhow::Result<usize> {
+    let normalized = if axis < 0 { axis + rank as i64 } else { axis };
+    if !(0..rank as i64).contains(&normalized) {
+        return Err(anyhow::anyhow!("{} '{}': axis {} out of range for rank {}", node.op_type, node.name, axis, rank));
+    }
+    Ok(normalized as usize)
+}
+
+/// The subgraphs of the control-flow nodes of a graph, built once.
+///
+/// Bodies are keyed by the first output of their node, which is unique
+/// within the graph, and by the attribute holding them.
+#[derive(Clone, Default)]
+pub struct Bodies {
+    bodies: HashMap<(String, String), Body>,
+}
+
+impl Bodies {
+    /// Builds the subgraph attributes of `nodes`, and those nested in them.
+    pub fn new(nodes: &[NodeProto]) -> anyhow::Result<Self> {
+        let mut bodies = HashMap::new();
+        for node in nodes {
+            let Some(output) = node.output.first() else { continue };
+            for attribute in node.attribute.iter().filter(|a| a.g.is_some()) {
+                bodies.insert((output.clone(), attribute.name.clone()), Body::new(node, &attribute.name)?);
+            }
+        }
+        Ok(Self { bodies })
+    }
+
+    /// The body in `attribute` of `node`, from `ctx` when the caller built it
+    /// ahead of time, otherwise decoded now.
+    fn get<'a>(ctx: &OpContext<'a>, node: &NodeProto, attribute: &str) -> anyhow::Result<Cow<'a, Body>> {
+        let cached = ctx.bodies.zip(node.output.first())
+            .and_then(|(bodies, output)| bodies.bodies.get(&(output.clone(), attribute.to_string())));
+        match cached {
+            Some(body) => Ok(Cow::Borrowed(body)),
+            None => Body::new(node, attribute).map(Cow::Owned),
+        }
+    }
+}
+
+/// A subgraph attribute made ready to run.
+#[derive(Clone)]
+struct Body {
+    graph: Graph,
+    /// Subgraphs of the body's own control-flow nodes.
+    nested: Bodies,
+}
+
+impl Body {
+    fn new(node: &NodeProto, attribute: &str) -> anyhow::Result<Self> {
+        let proto = attributes::get_attr(node, attribute)
+            .and_then(|a| a.g.as_ref())
+            .ok_or_else(|| anyhow::anyhow!("{} '{}': missing graph attribute '{}'", node.op_type, node.name, attribute))?;
+        let graph = Graph::from_subgraph(proto)?;
+        let nested = Bodies::new(&graph.nodes)?;
+        Ok(Self { graph, nested })
+    }
+
+    /// Runs the body on `inputs`, bound to its declared inputs in order, and
+    /// returns its outputs. Other names are looked up in the body, then in `scope`.
+    fn run(&self, inputs: Vec<Tensor>, scope: &Scope, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let graph = &self.graph;
+        if inputs.len() != graph.inputs.len() {
+            return Err(anyhow::anyhow!(
+                "Subgraph '{}' expects {} inputs, got {}", graph.proto.name, graph.inputs.len(), inputs.len()
+            ));
+        }
+        let registry = ctx.registry
+            .ok_or_else(|| anyhow::anyhow!("Subgraph '{}' needs an operator registry to run", graph.proto.name))?;
+        let ctx = &OpContext { bodies: Some(&self.nested), ..*ctx };
+        let mut values: HashMap<String, Tensor> = graph.inputs.iter().cloned().zip(inputs).collect();
+
+        for node in &graph.nodes {
+            let captured = implicit_inputs(node);
+            let node_inputs = node.input.iter().chain(&captured)
+                .map(|name| {
+                    if name.is_empty() {
+                        return Ok(operator::absent_input());
+                    }
+                    self.resolve(&values, scope, name)
+                        .ok_or_else(|| anyhow::anyhow!("Missing input '{}' for node '{}' in subgraph '{}'", name, node.name, graph.proto.name))
+                })
+                .collect::<anyhow::Result<Vec<&Tensor>>>()?;
+            let op = registry.resolve(node)
+                .ok_or_else(|| anyhow::anyhow!("Unsupported operator: {}", node.op_type))?;
+            let outputs = operator::invoke(op, &node_inputs, node, ctx)?;
+            for (name, output) in node.output.iter().zip(outputs) {
+                if !name.is_empty() {
+                    values.insert(name.clone(), output);
+                }
+            }
+        }
+
+        // Outputs may also name a body input, an initializer or a captured value
+        graph.outputs.iter()
+            .map(|name| {
+                self.resolve(&values, scope, name).cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Output '{}' of subgraph '{}' not produced", name, graph.proto.name))
+            })
+            .collect()
+    }
+
+    fn resolve<'v>(&'v self, values: &'v HashMap<String, Tensor>, scope: &Scope<'v>, name: &str) -> Option<&'v Tensor> {
+        values.get(name).or_else(|| self.graph.initializers.get(name)).or_else(|| scope.get(name).copied())
+    }
+}
+
+/// `If`: runs `then_branch` or `else_branch` depending on a boolean scalar.
+pub struct If;
+
+impl Operator for If {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.swap_remove(0))
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let captured = implicit_inputs(node);
+        let (declared, scope) = split_inputs(inputs, node, &captured)?;
+        let cond = declared.first().ok_or_else(|| anyhow::anyhow!("If '{}': missing condition", node.name))?;
+        let branch = if truthy(node, cond)? { "then_branch" } else { "else_branch" };
+        let outputs = Bodies::get(ctx, node, branch)?.run(Vec::new(), &scope, ctx)?;
+        if outputs.len() != node.output.len() {
+            return Err(anyhow::anyhow!(
+                "If '{}': {} has {} outputs, the node {}", node.name, branch, outputs.len(), node.output.len()
+            ));
+        }
+        Ok(outputs)
+    }
+}
+
+/// `Loop`: runs `body` while the trip count `M` is not reached and the
+/// condition holds, threading the loop-carried values through the iterations.
+///
+/// The body reads `(iteration, condition, carried...)` and returns
+/// `(condition, carried..., scan...)`; the node returns the final carried
+/// values, then each scan output stacked over the iterations.
+pub struct Loop;
+
+impl Operator for Loop {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.swap_remove(0))
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let captured = implicit_inputs(node);
+        let (declared, scope) = split_inputs(inputs, node, &captured)?;
+        let body = Bodies::get(ctx, node, "body")?;
+        let trip_count = optional_input(&declared, 0).map(|m| m.values().first().map_or(0, |&m| m as i64));
+        let mut cond = match optional_input(&declared, 1) {
+            Some(cond) => truthy(node, cond)?,
+            None => true,
+        };
+        let mut carried: Vec<Tensor> = declared.iter().skip(2).map(|t| (*t).clone()).collect();
+        let carried_count = carried.len();
+        let scan_count = body.graph.outputs.len().checked_sub(1 + carried_count)
+            .ok_or_else(|| anyhow::anyhow!("Loop '{}': the body returns too few outputs", node.name))?;
+
+        let mut scans: Vec<Vec<Tensor>> = vec![Vec::new(); scan_count];
+        let mut iteration = 0i64;
+        while cond && trip_count.is_none_or(|m| iteration < m) {
+            let mut body_inputs = vec![scalar(iteration as f32), scalar(if cond { 1.0 } else { 0.0 })];
+            body_inputs.append(&mut carried);
+            let mut outputs = body.run(body_inputs, &scope, ctx)?.into_iter();
+            cond = truthy(node, &outputs.next().expect("the body returns the condition"))?;
+            carried = outputs.by_ref().take(carried_count).collect();
+            for (scan, output) in scans.iter_mut().zip(outputs) {
+                scan.push(output);
+            }
+            iteration += 1;
+        }
+
+        let mut outputs = carried;
+        for scan in &scans {
+            outputs.push(stack(scan, 0)?);
+        }
+        Ok(outputs)
+    }
+}
+
+/// `Scan` (opset 9 and later): runs `body` once per slice of the scan inputs,
+/// threading the state values through the iterations.
+///
+/// The body reads `(state..., slices...)` and returns `(state..., scan...)`;
+/// the node returns the final state values, then each scan output stacked
+/// along its axis. Directions of 1 walk an axis backwards.
+pub struct Scan;
+
+impl Operator for Scan {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.swap_remove(0))
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let captured = implicit_inputs(node);
+        let (declared, scope) = split_inputs(inputs, node, &captured)?;
+        let body = Bodies::get(ctx, node, "body")?;
+        let scan_inputs = attributes::get_int(node, "num_scan_inputs", 0);
+        if scan_inputs < 1 || scan_inputs as usize > declared.len() {
+            return Err(anyhow::anyhow!("Scan '{}': invalid num_scan_inputs {}", node.name, scan_inputs));
+        }
+        let state_count = declared.len() - scan_inputs as usize;
+        let scan_count = body.graph.outputs.len().checked_sub(state_count)
+            .ok_or_else(|| anyhow::anyhow!("Scan '{}': the body returns too few outputs", node.name))?;
+        let setting = |name: &str, i: usize| attributes::get_ints(node, name).get(i).copied().unwrap_or(0);
+
+        // Scanned axis and direction of every scan input, which must agree on the sequence length
+        let mut sequences = Vec::new();
+        for (i, x) in declared[state_count..].iter().enumerate() {
+            let axis = normalize(node, setting("scan_input_axes", i), x.shape().len())?;
+            sequences.push((*x, axis, setting("scan_input_directions", i) == 1));
+        }
+        let length = sequences[0].0.shape()[sequences[0].1];
+        if let Some((x, axis, _)) = sequences.iter().find(|(x, axis, _)| x.shape()[*axis] != length) {
+            return Err(anyhow::anyhow!(
+                "Scan '{}': scan inputs of {} and {} steps ({:?} on axis {})", node.name, length, x.shape()[*axis], x.shape(), axis
+            ));
+        }
+
+        let mut state: Vec<Tensor> = declared[..state_count].iter().map(|t| (*t).clone()).collect();
+        let mut scans: Vec<Vec<Tensor>> = vec![Vec::new(); scan_count];
+        for step in 0..length {
+            let mut body_inputs = std::mem::take(&mut state);
+            for (x, axis, reverse) in &sequences {
+                let index = if *reverse { length - 1 - step } else { step };
+                let mut shape = x.shape().to_vec();
+                shape.remove(*axis);
+                body_inputs.push(x.slice_axis(*axis, index, 1, 1)?.reshape(shape)?);
+            }
+            let mut outputs = body.run(body_inputs, &scope, ctx)?;
+            let elements = outputs.split_off(state_count);
+            state = outputs;
+            for (scan, element) in scans.iter_mut().zip(elements) {
+                scan.push(element);
+            }
+        }
+
+        let mut outputs = state;
+        for (i, mut scan) in scans.into_iter().enumerate() {
+            if setting("scan_output_directions", i) == 1 {
+                scan.reverse();
+            }
+            let rank = scan.first().map_or(0, |t| t.shape().len()) + 1;
+            outputs.push(stack(&scan, normalize(node, setting("scan_output_axes", i), rank)?)?);
+        }
+        Ok(outputs)
+    }
+}
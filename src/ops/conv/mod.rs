@@ -1,38 +1,18 @@
 //! Conv operator implementation (2D convolution)
 
-use crate::ops::operator::Operator;
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::activation::Activation;
+use crate::ops::attributes;
 use crate::tensor::Tensor;
 use crate::onnx::onnx_proto::NodeProto;
 
 pub struct Conv;
+pub struct FusedConv;
 
 impl Conv {
-    /// Extract an i64 attribute from the node
-    fn get_int_attr(node: &NodeProto, name: &str, default: i64) -> i64 {
-        node.attribute.iter()
-            .find(|a| a.name == name)
-            .map(|a| a.i)
-            .unwrap_or(default)
-    }
-
-    /// Extract a list of i64 attributes from the node
-    fn get_ints_attr(node: &NodeProto, name: &str) -> Vec<i64> {
-        node.attribute.iter()
-            .find(|a| a.name == name)
-            .map(|a| a.ints.clone())
-            .unwrap_or_default()
-    }
-
-    /// Extract a string attribute from the node
-    fn get_string_attr(node: &NodeProto, name: &str) -> String {
-        node.attribute.iter()
-            .find(|a| a.name == name)
-            .map(|a| String::from_utf8_lossy(&a.s).to_string())
-            .unwrap_or_default()
-    }
-
     /// Compute padding based on auto_pad setting
-    fn compute_auto_pad(
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn compute_auto_pad(
         auto_pad: &str,
         input_h: usize,
         input_w: usize,
@@ -49,16 +29,16 @@ impl Conv {
                 let effective_kh = (kernel_h - 1) * dilation_h + 1;
                 let effective_kw = (kernel_w - 1) * dilation_w + 1;
 
-                let out_h = (input_h + stride_h - 1) / stride_h;
-                let out_w = (input_w + stride_w - 1) / stride_w;
+                let out_h = input_h.div_ceil(stride_h);
+                let out_w = input_w.div_ceil(stride_w);
 
                 let pad_h = ((out_h - 1) * stride_h + effective_kh).saturating_sub(input_h);
                 let pad_w = ((out_w - 1) * stride_w + effective_kw).saturating_sub(input_w);
 
                 if auto_pad == "SAME_UPPER" {
-                    (pad_h / 2, pad_w / 2, (pad_h + 1) / 2, (pad_w + 1) / 2)
+                    (pad_h / 2, pad_w / 2, pad_h.div_ceil(2), pad_w.div_ceil(2))
                 } else {
-                    ((pad_h + 1) / 2, (pad_w + 1) / 2, pad_h / 2, pad_w / 2)
+                    (pad_h.div_ceil(2), pad_w.div_ceil(2), pad_h / 2, pad_w / 2)
                 }
             }
             "VALID" => (0, 0, 0, 0),
@@ -67,25 +47,31 @@ impl Conv {
     }
 }
 
-impl Operator for Conv {
-    fn run(&self, inputs: &[&Tensor], node: &NodeProto) -> anyhow::Result<Tensor> {
-        // Input X: (N, C, H, W)
-        // Weight W: (M, C/group, kH, kW)
-        // Optional Bias B: (M,)
-        let x = inputs[0];
-        let w = inputs[1];
-        let bias = inputs.get(2).copied();
-
-        let x_shape = x.shape();
-        let w_shape = w.shape();
-
-        // println!("Conv: x_shape: {:?}, w_shape: {:?}", x_shape, w_shape);
-        // println!("X data: {:?}", x.data());
-        // println!("W data: {:?}", w.data());
-        // println!("Bias data: {:?}", bias.map(|b| b.data()));
+/// Shapes, attributes and padding of a 2D convolution, shared by Conv and QLinearConv.
+pub(crate) struct ConvGeometry {
+    pub batch: usize,
+    pub in_channels: usize,
+    pub in_h: usize,
+    pub in_w: usize,
+    pub out_channels: usize,
+    pub kernel_h: usize,
+    pub kernel_w: usize,
+    pub group: usize,
+    pub stride_h: usize,
+    pub stride_w: usize,
+    pub dilation_h: usize,
+    pub dilation_w: usize,
+    pub pad_top: usize,
+    pub pad_left: usize,
+    pub out_h: usize,
+    pub out_w: usize,
+}
 
+impl ConvGeometry {
+    /// Reads the attributes of `node` for input X: (N, C, H, W) and weight W: (M, C/group, kH, kW).
+    pub(crate) fn new(node: &NodeProto, x_shape: &[usize], w_shape: &[usize]) -> anyhow::Result<Self> {
         if x_shape.len() != 4 || w_shape.len() != 4 {
-            return Err(anyhow::anyhow!("Conv: only 2D convolution (4D tensors) supported"));
+            return Err(anyhow::anyhow!("{}: only 2D convolution (4D tensors) supported", node.op_type));
         }
 
         let batch = x_shape[0];
@@ -98,11 +84,11 @@ impl Operator for Conv {
         let kernel_w = w_shape[3];
 
         // Parse attributes
-        let group = Self::get_int_attr(node, "group", 1) as usize;
-        let strides = Self::get_ints_attr(node, "strides");
-        let dilations = Self::get_ints_attr(node, "dilations");
-        let pads = Self::get_ints_attr(node, "pads");
-        let auto_pad = Self::get_string_attr(node, "auto_pad");
+        let group = attributes::get_int(node, "group", 1) as usize;
+        let strides = attributes::get_ints(node, "strides");
+        let dilations = attributes::get_ints(node, "dilations");
+        let pads = attributes::get_ints(node, "pads");
+        let auto_pad = attributes::get_string(node, "auto_pad");
 
         let stride_h = strides.first().copied().unwrap_or(1) as usize;
         let stride_w = strides.get(1).copied().unwrap_or(stride_h as i64) as usize;
@@ -112,7 +98,7 @@ impl Operator for Conv {
 
         // Compute padding
         let (pad_top, pad_left, pad_bottom, pad_right) = if !auto_pad.is_empty() && auto_pad != "NOTSET" {
-            Self::compute_auto_pad(&auto_pad, in_h, in_w, kernel_h, kernel_w, stride_h, stride_w, dilation_h, dilation_w)
+            Conv::compute_auto_pad(&auto_pad, in_h, in_w, kernel_h, kernel_w, stride_h, stride_w, dilation_h, dilation_w)
         } else if pads.len() >= 4 {
             (pads[0] as usize, pads[1] as usize, pads[2] as usize, pads[3] as usize)
         } else {
@@ -125,8 +111,31 @@ impl Operator for Conv {
         let out_h = (in_h + pad_top + pad_bottom - effective_kh) / stride_h + 1;
         let out_w = (in_w + pad_left + pad_right - effective_kw) / stride_w + 1;
 
+        Ok(Self {
+            batch, in_channels, in_h, in_w, out_channels, kernel_h, kernel_w, group,
+            stride_h, stride_w, dilation_h, dilation_w, pad_top, pad_left, out_h, out_w,
+        })
+    }
+
+    pub(crate) fn output_shape(&self) -> Vec<usize> {
+        vec![self.batch, self.out_channels, self.out_h, self.out_w]
+    }
+}
+
+impl Conv {
+    fn compute(inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext, activation: Option<Activation>) -> anyhow::Result<Tensor> {
+        // Optional Bias B: (M,)
+        let x = inputs[0];
+        let w = inputs[1];
+        let bias = inputs.get(2).copied();
+
+        let ConvGeometry {
+            batch, in_channels, in_h, in_w, out_channels, kernel_h, kernel_w, group,
+            stride_h, stride_w, dilation_h, dilation_w, pad_top, pad_left, out_h, out_w,
+        } = ConvGeometry::new(node, x.shape(), w.shape())?;
+
         // Allocate output
-        let mut output = vec![0.0f32; batch * out_channels * out_h * out_w];
+        let mut output = ctx.alloc(batch * out_channels * out_h * out_w);
 
         let x_data = x.data();
         let w_data = w.data();
@@ -134,59 +143,69 @@ impl Operator for Conv {
         let in_channels_per_group = in_channels / group;
         let out_channels_per_group = out_channels / group;
 
-        // Perform convolution
-        for n in 0..batch {
-            for g in 0..group {
-                for oc in 0..out_channels_per_group {
-                    let abs_oc = g * out_channels_per_group + oc;
-                    for oh in 0..out_h {
-                        for ow in 0..out_w {
-                            let mut sum = 0.0f32;
-
-                            for ic in 0..in_channels_per_group {
-                                let abs_ic = g * in_channels_per_group + ic;
-                                for kh in 0..kernel_h {
-                                    for kw in 0..kernel_w {
-                                        let ih = (oh * stride_h + kh * dilation_h) as isize - pad_top as isize;
-                                        let iw = (ow * stride_w + kw * dilation_w) as isize - pad_left as isize;
-
-                                        if ih >= 0 && ih < in_h as isize && iw >= 0 && iw < in_w as isize {
-                                            let ih = ih as usize;
-                                            let iw = iw as usize;
-
-                                            let x_idx = n * in_channels * in_h * in_w
-                                                + abs_ic * in_h * in_w
-                                                + ih * in_w
-                                                + iw;
-
-                                            let w_idx = abs_oc * (in_channels_per_group * kernel_h * kernel_w)
-                                                + ic * kernel_h * kernel_w
-                                                + kh * kernel_w
-                                                + kw;
-
-                                            sum += x_data[x_idx] * w_data[w_idx];
-                                        }
-                                    }
+        // Perform convolution, one (batch, output channel) plane per task
+        let plane = out_h * out_w;
+        let work = plane * in_channels_per_group * kernel_h * kernel_w;
+        ctx.pool.for_each_chunk(&mut output, plane, work, |idx, out_plane| {
+            let n = idx / out_channels;
+            let abs_oc = idx % out_channels;
+            let g = abs_oc / out_channels_per_group;
+            for oh in 0..out_h {
+                for ow in 0..out_w {
+                    let mut sum = 0.0f32;
+
+                    for ic in 0..in_channels_per_group {
+                        let abs_ic = g * in_channels_per_group + ic;
+                        for kh in 0..kernel_h {
+                            for kw in 0..kernel_w {
+                                let ih = (oh * stride_h + kh * dilation_h) as isize - pad_top as isize;
+                                let iw = (ow * stride_w + kw * dilation_w) as isize - pad_left as isize;
+
+                                if ih >= 0 && ih < in_h as isize && iw >= 0 && iw < in_w as isize {
+                                    let ih = ih as usize;
+                                    let iw = iw as usize;
+
+                                    let x_idx = n * in_channels * in_h * in_w
+                                        + abs_ic * in_h * in_w
+                                        + ih * in_w
+                                        + iw;
+
+                                    let w_idx = abs_oc * (in_channels_per_group * kernel_h * kernel_w)
+                                        + ic * kernel_h * kernel_w
+                                        + kh * kernel_w
+                                        + kw;
+
+                                    sum += x_data[x_idx] * w_data[w_idx];
                                 }
                             }
-
-                            // Add bias if present
-                            if let Some(b) = bias {
-                                sum += b.data()[abs_oc];
-                            }
-
-                            let out_idx = n * out_channels * out_h * out_w
-                                + abs_oc * out_h * out_w
-                                + oh * out_w
-                                + ow;
-                            output[out_idx] = sum;
                         }
                     }
+
+                    // Add bias if present
+                    if let Some(b) = bias {
+                        sum += b.data()[abs_oc];
+                    }
+
+                    out_plane[oh * out_w + ow] = sum;
                 }
             }
-        }
+            if let Some(act) = activation {
+                act.apply_slice(out_plane);
+            }
+        });
 
         Ok(Tensor::new(output, vec![batch, out_channels, out_h, out_w]))
     }
 }
 
+impl Operator for Conv {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Self::compute(inputs, node, ctx, None)
+    }
+}
+
+impl Operator for FusedConv {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Conv::compute(inputs, node, ctx, Activation::from_fused(node)?)
+    }
+}
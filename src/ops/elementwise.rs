@@ -0,0 +1,195 @@
+//! Shared plumbing for elementwise operators: numpy-style broadcasting on top
+//! of the session's `Kernels`.
+
+use crate::kernels::{BinaryOp, UnaryOp};
+use crate::ops::operator::OpContext;
+use crate::tensor::Tensor;
+
+/// Computes the multidirectional (numpy) broadcast of two shapes.
+pub(crate) fn broadcast_shape(a: &[usize], b: &[usize]) -> anyhow::Result<Vec<usize>> {
+    let rank = a.len().max(b.len());
+    let a = pad_shape(a, rank);
+    let b = pad_shape(b, rank);
+    a.iter()
+        .zip(b.iter())
+        .map(|(&x, &y)| match (x, y) {
+            _ if x == y => Ok(x),
+            (1, _) => Ok(y),
+            (_, 1) => Ok(x),
+            _ => Err(anyhow::anyhow!("shapes {:?} and {:?} are not broadcastable", a, b)),
+        })
+        .collect()
+}
+
+/// Left-pads `shape` with ones up to `rank`.
+pub(crate) fn pad_shape(shape: &[usize], rank: usize) -> Vec<usize> {
+    let mut padded = vec![1; rank - shape.len()];
+    padded.extend_from_slice(shape);
+    padded
+}
+
+/// Row-major strides of `shape`, with 0 for the axes broadcast to `out`.
+fn broadcast_strides(shape: &[usize], out: &[usize]) -> Vec<usize> {
+    let mut strides = vec![0; shape.len()];
+    let mut acc = 1;
+    for i in (0..shape.len()).rev() {
+        strides[i] = if shape[i] == 1 && out[i] != 1 { 0 } else { acc };
+        acc *= shape[i];
+    }
+    strides
+}
+
+/// Elements per task when a flat elementwise op is split across the intra-op pool.
+const CHUNK: usize = 4096;
+
+pub(crate) fn unary(ctx: &OpContext, x: &Tensor, op: UnaryOp) -> Tensor {
+    let src = x.data();
+    let mut out = ctx.alloc(src.len());
+    ctx.pool.for_each_chunk(&mut out, CHUNK, CHUNK, |i, dst| {
+        let start = i * CHUNK;
+        ctx.kernels.unary(op, &src[start..start + dst.len()], dst);
+    });
+    Tensor::new(out, x.shape().to_vec())
+}
+
+/// Applies `f` to every element, for the functions the `Kernels` do not provide.
+pub(crate) fn map<F: Fn(f32) -> f32 + Sync + Send>(ctx: &OpContext, x: &Tensor, f: F) -> Tensor {
+    let src = x.data();
+    let mut out = ctx.alloc(src.len());
+    ctx.pool.for_each_chunk(&mut out, CHUNK, CHUNK, |i, dst| {
+        let start = i * CHUNK;
+        let end = start + dst.len();
+        for (d, &s) in dst.iter_mut().zip(&src[start..end]) {
+            *d = f(s);
+        }
+    });
+    Tensor::new(out, x.shape().to_vec())
+}
+
+/// Broadcasting binary op with an arbitrary scalar function, one element at a time.
+pub(crate) fn binary_map<F: Fn(f32, f32) -> f32 + Sync + Send>(
+    ctx: &OpContext,
+    a: &Tensor,
+    b: &Tensor,
+    f: F,
+) -> anyhow::Result<Tensor> {
+    let out_shape = broadcast_shape(a.shape(), b.shape())?;
+    let rank = out_shape.len();
+    let sa = broadcast_strides(&pad_shape(a.shape(), rank), &out_shape);
+    let sb = broadcast_strides(&pad_shape(b.shape(), rank), &out_shape);
+    let (ad, bd) = (a.data(), b.data());
+
+    let mut out = ctx.alloc(out_shape.iter().product());
+    ctx.pool.for_each_chunk(&mut out, CHUNK, CHUNK, |c, chunk| {
+        for (j, o) in chunk.iter_mut().enumerate() {
+            let (mut a_off, mut b_off, mut rem) = (0, 0, c * CHUNK + j);
+            for axis in (0..rank).rev() {
+                let i = rem % out_shape[axis];
+                rem /= out_shape[axis];
+                a_off += i * sa[axis];
+                b_off += i * sb[axis];
+            }
+            *o = f(ad[a_off], bd[b_off]);
+        }
+    });
+    Ok(Tensor::new(out, out_shape))
+}
+
+/// How the innermost contiguous block of a broadcast binary op is computed.
+enum Inner {
+    Both,
+    ScalarLhs,
+    ScalarRhs,
+}
+
+pub(crate) fn binary(ctx: &OpContext, a: &Tensor, b: &Tensor, op: BinaryOp) -> anyhow::Result<Tensor> {
+    let kernels = ctx.kernels;
+    let out_shape = broadcast_shape(a.shape(), b.shape())?;
+    let count = out_shape.iter().product::<usize>();
+    let mut out = ctx.alloc(count);
+
+    let (ad, bd) = (a.data(), b.data());
+
+    if a.shape() == b.shape() {
+        ctx.pool.for_each_chunk(&mut out, CHUNK, CHUNK, |i, dst| {
+            let range = i * CHUNK..i * CHUNK + dst.len();
+            kernels.binary(op, &ad[range.clone()], &bd[range], dst);
+        });
+        return Ok(Tensor::new(out, out_shape));
+    }
+    if bd.len() == 1 && ad.len() == count {
+        ctx.pool.for_each_chunk(&mut out, CHUNK, CHUNK, |i, dst| {
+            let start = i * CHUNK;
+            kernels.binary_scalar_rhs(op, &ad[start..start + dst.len()], bd[0], dst);
+        });
+        return Ok(Tensor::new(out, out_shape));
+    }
+    if ad.len() == 1 && bd.len() == count {
+        ctx.pool.for_each_chunk(&mut out, CHUNK, CHUNK, |i, dst| {
+            let start = i * CHUNK;
+            kernels.binary_scalar_lhs(op, ad[0], &bd[start..start + dst.len()], dst);
+        });
+        return Ok(Tensor::new(out, out_shape));
+    }
+    if count == 0 {
+        return Ok(Tensor::new(out, out_shape));
+    }
+
+    let rank = out_shape.len();
+    let pa = pad_shape(a.shape(), rank);
+    let pb = pad_shape(b.shape(), rank);
+
+    // Split the output into outer axes (walked one by one) and an inner block
+    // handled by a single kernel call.
+    let mut split = rank;
+    while split > 0 && pa[split - 1] == out_shape[split - 1] && pb[split - 1] == out_shape[split - 1] {
+        split -= 1;
+    }
+    let mode = if split < rank {
+        Inner::Both
+    } else if pb[rank - 1] == 1 {
+        while split > 0 && pa[split - 1] == out_shape[split - 1] && pb[split - 1] == 1 {
+            split -= 1;
+        }
+        Inner::ScalarRhs
+    } else {
+        while split > 0 && pb[split - 1] == out_shape[split - 1] && pa[split - 1] == 1 {
+            split -= 1;
+        }
+        Inner::ScalarLhs
+    };
+    let inner: usize = out_shape[split..].iter().product();
+
+    let sa = broadcast_strides(&pa, &out_shape);
+    let sb = broadcast_strides(&pb, &out_shape);
+    let outer_shape = &out_shape[..split];
+    ctx.pool.for_each_chunk(&mut out, inner, inner, |c, chunk| {
+        // Unravel the chunk index over the outer axes
+        let (mut a_off, mut b_off, mut rem) = (0, 0, c);
+        for axis in (0..split).rev() {
+            let i = rem % outer_shape[axis];
+            rem /= outer_shape[axis];
+            a_off += i * sa[axis];
+            b_off += i * sb[axis];
+        }
+        match mode {
+            Inner::Both => kernels.binary(op, &ad[a_off..a_off + inner], &bd[b_off..b_off + inner], chunk),
+            Inner::ScalarRhs => kernels.binary_scalar_rhs(op, &ad[a_off..a_off + inner], bd[b_off], chunk),
+            Inner::ScalarLhs => kernels.binary_scalar_lhs(op, ad[a_off], &bd[b_off..b_off + inner], chunk),
+        }
+    });
+
+    Ok(Tensor::new(out, out_shape))
+}
+
+/// Folds any number of inputs pairwise, as required by the variadic ONNX ops (Max, Min, Sum...).
+pub(crate) fn variadic(ctx: &OpContext, inputs: &[&Tensor], op: BinaryOp) -> anyhow::Result<Tensor> {
+    let (first, rest) = inputs
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("{:?}: expected at least one input", op))?;
+    let mut acc = (*first).clone();
+    for t in rest {
+        acc = binary(ctx, &acc, t, op)?;
+    }
+    Ok(acc)
+}
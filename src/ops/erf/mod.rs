@@ -0,0 +1,25 @@
+//! Erf operator implementation
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::elementwise;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Erf;
+
+/// Gauss error function (Abramowitz & Stegun 7.1.26, evaluated in f64; |error| < 1.5e-7).
+pub(crate) fn erf(x: f32) -> f32 {
+    const A: [f64; 5] = [0.254829592, -0.284496736, 1.421413741, -1.453152027, 1.061405429];
+    const P: f64 = 0.3275911;
+    let x = x as f64;
+    let t = 1.0 / (1.0 + P * x.abs());
+    let poly = t * (A[0] + t * (A[1] + t * (A[2] + t * (A[3] + t * A[4]))));
+    let y = 1.0 - poly * (-x * x).exp();
+    (if x < 0.0 { -y } else { y }) as f32
+}
+
+impl Operator for Erf {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(elementwise::map(ctx, inputs[0], erf))
+    }
+}
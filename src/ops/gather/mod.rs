@@ -0,0 +1,46 @@
+//! Gather operator implementation
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::attributes;
+use crate::ops::shape::normalize_axis;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Gather;
+
+impl Operator for Gather {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let (data, indices) = (inputs[0], inputs[1]);
+        let rank = data.shape().len();
+        let axis = normalize_axis(node, attributes::get_int(node, "axis", 0), rank)?;
+        let dim = data.shape()[axis];
+
+        let indices: Vec<usize> = indices.data().iter()
+            .map(|&v| {
+                let i = v as i64;
+                let j = if i < 0 { i + dim as i64 } else { i };
+                if j < 0 || j >= dim as i64 {
+                    Err(anyhow::anyhow!("Gather: index {} out of range for dimension {}", i, dim))
+                } else {
+                    Ok(j as usize)
+                }
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let outer: usize = data.shape()[..axis].iter().product();
+        let inner: usize = data.shape()[axis + 1..].iter().product();
+        let mut out = ctx.alloc(outer * indices.len() * inner);
+        for o in 0..outer {
+            for (k, &i) in indices.iter().enumerate() {
+                let src = (o * dim + i) * inner;
+                let dst = (o * indices.len() + k) * inner;
+                out[dst..dst + inner].copy_from_slice(&data.data()[src..src + inner]);
+            }
+        }
+
+        let mut shape = data.shape()[..axis].to_vec();
+        shape.extend_from_slice(inputs[1].shape());
+        shape.extend_from_slice(&data.shape()[axis + 1..]);
+        Ok(Tensor::new(out, shape))
+    }
+}
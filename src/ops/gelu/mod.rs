@@ -0,0 +1,50 @@
+//! Gelu operator implementation (com.microsoft Gelu and ONNX opset 20 Gelu),
+//! and the com.microsoft BiasGelu and FastGelu variants.
+
+use crate::ops::operator::{self, Operator, OpContext};
+use crate::ops::attributes;
+use crate::ops::elementwise;
+use crate::ops::erf::erf;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Gelu;
+/// `Gelu(A + B)`, with the bias `B` broadcast over the last axis.
+pub struct BiasGelu;
+/// The tanh approximation of Gelu, with an optional bias added first.
+pub struct FastGelu;
+
+fn gelu(x: f32) -> f32 {
+    0.5 * x * (1.0 + erf(x * std::f32::consts::FRAC_1_SQRT_2))
+}
+
+fn gelu_tanh(x: f32) -> f32 {
+    let k = (2.0 / std::f32::consts::PI).sqrt();
+    0.5 * x * (1.0 + (k * (x + 0.044715 * x * x * x)).tanh())
+}
+
+impl Operator for Gelu {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        match attributes::get_string(node, "approximate").as_str() {
+            "" | "none" => Ok(elementwise::map(ctx, inputs[0], gelu)),
+            "tanh" => Ok(elementwise::map(ctx, inputs[0], gelu_tanh)),
+            other => Err(anyhow::anyhow!("Gelu: unsupported approximate mode '{}'", other)),
+        }
+    }
+}
+
+impl Operator for BiasGelu {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let bias = inputs.get(1).ok_or_else(|| anyhow::anyhow!("BiasGelu: missing bias input"))?;
+        elementwise::binary_map(ctx, inputs[0], bias, |a, b| gelu(a + b))
+    }
+}
+
+impl Operator for FastGelu {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        match operator::optional_input(inputs, 1) {
+            Some(bias) => elementwise::binary_map(ctx, inputs[0], bias, |a, b| gelu_tanh(a + b)),
+            None => Ok(elementwise::map(ctx, inputs[0], gelu_tanh)),
+        }
+    }
+}
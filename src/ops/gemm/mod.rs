@@ -0,0 +1,73 @@
+//! Gemm operator implementation, and FusedGemm (com.microsoft) which also applies an activation
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::activation::Activation;
+use crate::ops::attributes;
+use crate::ops::elementwise;
+use crate::ops::matmul::MatMul;
+use crate::ops::transpose::permute;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Gemm;
+pub struct FusedGemm;
+
+/// Returns the row-major (rows, cols) matrix, transposing it first if requested.
+fn matrix(t: &Tensor, transpose: bool, name: &str) -> anyhow::Result<(Vec<f32>, usize, usize)> {
+    let &[rows, cols] = t.shape() else {
+        return Err(anyhow::anyhow!("Gemm: {} must be 2D, got {:?}", name, t.shape()));
+    };
+    if !transpose {
+        return Ok((t.data().to_vec(), rows, cols));
+    }
+    let mut out = vec![0.0; t.data().len()];
+    permute(t.data(), t.shape(), &[1, 0], &mut out);
+    Ok((out, cols, rows))
+}
+
+fn gemm(inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext, activation: Option<Activation>) -> anyhow::Result<Tensor> {
+    let alpha = attributes::get_float(node, "alpha", 1.0);
+    let beta = attributes::get_float(node, "beta", 1.0);
+    let (a, m, k) = matrix(inputs[0], attributes::get_int(node, "transA", 0) != 0, "A")?;
+    let (b, k2, n) = matrix(inputs[1], attributes::get_int(node, "transB", 0) != 0, "B")?;
+    if k != k2 {
+        return Err(anyhow::anyhow!("Gemm: inner dimensions mismatch {:?} x {:?}", inputs[0].shape(), inputs[1].shape()));
+    }
+
+    let mut out = ctx.alloc(m * n);
+    MatMul::gemm(ctx, &a, &b, &mut out, m, k, n);
+
+    // C is unidirectionally broadcast to (M, N)
+    let c = match inputs.get(2) {
+        Some(c) if beta != 0.0 => {
+            if elementwise::broadcast_shape(c.shape(), &[m, n])? != [m, n] {
+                return Err(anyhow::anyhow!("Gemm: C of shape {:?} does not broadcast to [{}, {}]", c.shape(), m, n));
+            }
+            Some((c.data(), elementwise::pad_shape(c.shape(), 2)))
+        }
+        _ => None,
+    };
+    ctx.pool.for_each_chunk(&mut out, n.max(1), n, |i, row| {
+        for (j, o) in row.iter_mut().enumerate() {
+            let mut v = alpha * *o;
+            if let Some((c, shape)) = &c {
+                let ci = if shape[0] == 1 { 0 } else { i } * shape[1] + if shape[1] == 1 { 0 } else { j };
+                v += beta * c[ci];
+            }
+            *o = activation.map_or(v, |act| act.apply(v));
+        }
+    });
+    Ok(Tensor::new(out, vec![m, n]))
+}
+
+impl Operator for Gemm {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        gemm(inputs, node, ctx, None)
+    }
+}
+
+impl Operator for FusedGemm {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        gemm(inputs, node, ctx, Activation::from_fused(node)?)
+    }
+}
@@ -0,0 +1,40 @@
+//! Identity and Dropout (inference mode) operators: both forward their input,
+//! sharing its buffer.
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Identity;
+pub struct Dropout;
+
+impl Operator for Identity {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(inputs[0].clone())
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for Dropout {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        if inputs.get(2).is_some_and(|t| t.values().iter().any(|&v| v != 0.0)) {
+            return Err(anyhow::anyhow!("Dropout '{}': training mode is not supported", node.name));
+        }
+        Ok(inputs[0].clone())
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,196 @@
+//! LayerNormalization operator implementation (opset 17), and the
+//! com.microsoft SkipLayerNormalization and EmbedLayerNormalization of
+//! transformer models.
+
+use crate::ops::operator::{self, Operator, OpContext};
+use crate::ops::attributes;
+use crate::ops::shape::normalize_axis;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct LayerNormalization;
+/// `LayerNormalization(input + skip + bias)` over the last axis, also
+/// returning the sum when asked for.
+pub struct SkipLayerNormalization;
+/// BERT embeddings: word, position and segment embeddings summed and
+/// normalized, along with the number of unmasked tokens of each sequence.
+pub struct EmbedLayerNormalization;
+
+/// Normalizes `xs` into `out`, returning its mean and inverse standard deviation.
+fn normalize_row(xs: &[f32], out: &mut [f32], gamma: &[f32], beta: Option<&[f32]>, epsilon: f32) -> (f32, f32) {
+    let inner = xs.len() as f32;
+    let mean = xs.iter().sum::<f32>() / inner;
+    let var = xs.iter().map(|&v| (v - mean) * (v - mean)).sum::<f32>() / inner;
+    let inv_std = 1.0 / (var + epsilon).sqrt();
+    for (j, (o, &v)) in out.iter_mut().zip(xs).enumerate() {
+        *o = (v - mean) * inv_std * gamma[j] + beta.map_or(0.0, |b| b[j]);
+    }
+    (mean, inv_std)
+}
+
+/// Normalizes every `hidden`-long row of `src`, in parallel.
+fn normalize_rows(ctx: &OpContext, src: &[f32], hidden: usize, gamma: &[f32], beta: Option<&[f32]>, epsilon: f32) -> Vec<f32> {
+    let mut out = ctx.alloc(src.len());
+    ctx.pool.for_each_chunk(&mut out, hidden.max(1), 4 * hidden, |i, row| {
+        normalize_row(&src[i * hidden..(i + 1) * hidden], row, gamma, beta, epsilon);
+    });
+    out
+}
+
+impl Operator for LayerNormalization {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let x = inputs[0];
+        let scale = inputs.get(1).ok_or_else(|| anyhow::anyhow!("LayerNormalization: missing scale input"))?;
+        let bias = inputs.get(2);
+        let axis = normalize_axis(node, attributes::get_int(node, "axis", -1), x.shape().len())?;
+        let epsilon = attributes::get_float(node, "epsilon", 1e-5);
+
+        // Normalize over the trailing axes starting at `axis`
+        let inner: usize = x.shape()[axis..].iter().product();
+        if scale.data().len() != inner || bias.is_some_and(|b| b.data().len() != inner) {
+            return Err(anyhow::anyhow!(
+                "LayerNormalization: scale/bias must have {} elements for input {:?}", inner, x.shape()
+            ));
+        }
+        let out = normalize_rows(ctx, x.data(), inner, scale.data(), bias.map(|b| b.data()), epsilon);
+        Ok(Tensor::new(out, x.shape().to_vec()))
+    }
+}
+
+/// Checks that the optional per-channel inputs of `node` hold `hidden` values.
+fn check_channels(node: &NodeProto, named: &[(&str, Option<&Tensor>)], hidden: usize) -> anyhow::Result<()> {
+    for (name, tensor) in named {
+        if let Some(t) = tensor.filter(|t| t.len() != hidden) {
+            return Err(anyhow::anyhow!("{}: {} has {} elements, expected the hidden size {}", node.op_type, name, t.len(), hidden));
+        }
+    }
+    Ok(())
+}
+
+impl Operator for SkipLayerNormalization {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.remove(0))
+    }
+
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        if inputs.len() < 3 {
+            return Err(anyhow::anyhow!("SkipLayerNormalization: expected input, skip and gamma, got {} inputs", inputs.len()));
+        }
+        let (x, skip, gamma) = (inputs[0], inputs[1], inputs[2]);
+        let beta = operator::optional_input(inputs, 3);
+        let bias = operator::optional_input(inputs, 4);
+        let epsilon = attributes::get_float(node, "epsilon", 1e-12);
+        let shape = x.shape().to_vec();
+        let hidden = *shape.last().ok_or_else(|| anyhow::anyhow!("SkipLayerNormalization: input must have at least 1 dimension"))?;
+        check_channels(node, &[("gamma", Some(gamma)), ("beta", beta), ("bias", bias)], hidden)?;
+
+        // The skip covers the whole input or repeats over its leading axes
+        let (src, skip_data) = (x.data(), skip.data());
+        if skip_data.is_empty() || src.len() % skip_data.len() != 0 || skip.shape().last() != Some(&hidden) {
+            return Err(anyhow::anyhow!("SkipLayerNormalization: skip {:?} does not match input {:?}", skip.shape(), shape));
+        }
+        let bias = bias.map(|b| b.data());
+        let sum: Vec<f32> = src.iter().enumerate()
+            .map(|(i, &v)| v + skip_data[i % skip_data.len()] + bias.map_or(0.0, |b| b[i % hidden]))
+            .collect();
+
+        let (gamma, beta) = (gamma.data(), beta.map(|b| b.data()));
+        let out = normalize_rows(ctx, &sum, hidden, gamma, beta, epsilon);
+        let mut outputs = vec![Tensor::new(out, shape.clone())];
+        if node.output.len() > 1 {
+            // mean and inv_std_var, one per row
+            let mut scratch = vec![0.0; hidden];
+            let (means, inv_stds): (Vec<f32>, Vec<f32>) = sum.chunks(hidden.max(1))
+                .map(|xs| normalize_row(xs, &mut scratch, gamma, None, epsilon))
+                .unzip();
+            let mut stats_shape = shape.clone();
+            *stats_shape.last_mut().unwrap() = 1;
+            outputs.push(Tensor::new(means, stats_shape.clone()));
+            outputs.push(Tensor::new(inv_stds, stats_shape));
+            outputs.push(Tensor::new(sum, shape));
+        }
+        outputs.truncate(node.output.len().max(1));
+        Ok(outputs)
+    }
+}
+
+impl Operator for EmbedLayerNormalization {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.remove(0))
+    }
+
+    /// mask_index counts tokens
+    fn narrows_output(&self, index: usize) -> bool {
+        index != 1
+    }
+
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        if inputs.len() < 7 {
+            return Err(anyhow::anyhow!("EmbedLayerNormalization: expected at least 7 inputs, got {}", inputs.len()));
+        }
+        let input_ids = inputs[0];
+        let segment_ids = operator::optional_input(inputs, 1);
+        let (word, position) = (inputs[2], inputs[3]);
+        let segment = operator::optional_input(inputs, 4);
+        let (gamma, beta) = (inputs[5], operator::optional_input(inputs, 6));
+        let mask = operator::optional_input(inputs, 7);
+        let position_ids = operator::optional_input(inputs, 8);
+        let epsilon = attributes::get_float(node, "epsilon", 1e-12);
+
+        let [batch, seq] = input_ids.shape() else {
+            return Err(anyhow::anyhow!("EmbedLayerNormalization: input_ids must be [batch, sequence], got {:?}", input_ids.shape()));
+        };
+        let (batch, seq) = (*batch, *seq);
+        let hidden = *word.shape().last().unwrap_or(&0);
+        check_channels(node, &[("gamma", Some(gamma)), ("beta", beta)], hidden)?;
+        if segment_ids.is_some() != segment.is_some() {
+            return Err(anyhow::anyhow!("EmbedLayerNormalization: segment_ids and segment_embedding go together"));
+        }
+
+        // Row `id` of `table`, checked against its number of rows
+        let lookup = |table: &Tensor, name: &str, id: f32| -> anyhow::Result<std::ops::Range<usize>> {
+            let rows = table.len() / hidden.max(1);
+            if id < 0.0 || id as usize >= rows {
+                return Err(anyhow::anyhow!("EmbedLayerNormalization: {} index {} out of range for {} rows", name, id, rows));
+            }
+            Ok(id as usize * hidden..(id as usize + 1) * hidden)
+        };
+        let ids = input_ids.data();
+        let segments = segment_ids.map(|s| s.data());
+        let positions = position_ids.map(|p| p.data());
+        let (word_data, position_data, segment_data) = (word.data(), position.data(), segment.map(|s| s.data()));
+        let mut sum = vec![0.0; batch * seq * hidden];
+        for (token, row) in sum.chunks_mut(hidden.max(1)).enumerate() {
+            let s = token % seq;
+            // Position ids are per token, or one row shared by the batch
+            let pos = match &positions {
+                Some(p) if p.len() == batch * seq => p[token],
+                Some(p) => p[s],
+                None => s as f32,
+            };
+            let w = &word_data[lookup(word, "word", ids[token])?];
+            let p = &position_data[lookup(position, "position", pos)?];
+            for (j, o) in row.iter_mut().enumerate() {
+                *o = w[j] + p[j];
+            }
+            if let (Some(segments), Some(table), Some(data)) = (&segments, segment, &segment_data) {
+                let g = &data[lookup(table, "segment", segments[token])?];
+                row.iter_mut().zip(g).for_each(|(o, &v)| *o += v);
+            }
+        }
+
+        let (gamma, beta) = (gamma.data(), beta.map(|b| b.data()));
+        let out = normalize_rows(ctx, &sum, hidden, gamma, beta, epsilon);
+        let mask_index = match mask {
+            Some(mask) => mask.data().chunks(seq.max(1)).map(|m| m.iter().filter(|&&v| v != 0.0).count() as f32).collect(),
+            None => vec![0.0; batch],
+        };
+        let mut outputs = vec![
+            Tensor::new(out, vec![batch, seq, hidden]),
+            Tensor::new(mask_index, vec![batch]),
+            Tensor::new(sum, vec![batch, seq, hidden]),
+        ];
+        outputs.truncate(node.output.len().max(1));
+        Ok(outputs)
+    }
+}
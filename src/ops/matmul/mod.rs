@@ -0,0 +1,96 @@
+//! MatMul operator implementation (numpy matmul semantics)
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::elementwise;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct MatMul;
+
+impl MatMul {
+    /// Computes `a @ b` for row-major `a: (M, K)` and `b: (K, N)` into `out: (M, N)`,
+    /// splitting the rows across the intra-op pool.
+    pub(crate) fn gemm(ctx: &OpContext, a: &[f32], b: &[f32], out: &mut [f32], m: usize, k: usize, n: usize) {
+        debug_assert_eq!(out.len(), m * n);
+        if n == 0 {
+            return;
+        }
+        ctx.pool.for_each_chunk(out, n, k * n, |i, row| {
+            row.fill(0.0);
+            let a_row = &a[i * k..(i + 1) * k];
+            for (kk, &a_ik) in a_row.iter().enumerate() {
+                let b_row = &b[kk * n..(kk + 1) * n];
+                for (o, &b_kj) in row.iter_mut().zip(b_row) {
+                    *o += a_ik * b_kj;
+                }
+            }
+        });
+    }
+}
+
+impl Operator for MatMul {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let a = inputs[0];
+        let b = inputs[1];
+
+        // Promote 1-D operands to matrices, as numpy does
+        let mut a_shape = a.shape().to_vec();
+        let mut b_shape = b.shape().to_vec();
+        let a_vector = a_shape.len() == 1;
+        let b_vector = b_shape.len() == 1;
+        if a_vector {
+            a_shape.insert(0, 1);
+        }
+        if b_vector {
+            b_shape.push(1);
+        }
+        if a_shape.len() < 2 || b_shape.len() < 2 {
+            return Err(anyhow::anyhow!("MatMul: scalar operands are not supported"));
+        }
+
+        let (m, k) = (a_shape[a_shape.len() - 2], a_shape[a_shape.len() - 1]);
+        let (k2, n) = (b_shape[b_shape.len() - 2], b_shape[b_shape.len() - 1]);
+        if k != k2 {
+            return Err(anyhow::anyhow!("MatMul: inner dimensions mismatch {:?} x {:?}", a.shape(), b.shape()));
+        }
+
+        let a_batch = &a_shape[..a_shape.len() - 2];
+        let b_batch = &b_shape[..b_shape.len() - 2];
+        let batch_shape = elementwise::broadcast_shape(a_batch, b_batch)?;
+        let batch: usize = batch_shape.iter().product();
+        let rank = batch_shape.len();
+        let pa = elementwise::pad_shape(a_batch, rank);
+        let pb = elementwise::pad_shape(b_batch, rank);
+
+        let mut out = ctx.alloc(batch * m * n);
+        for (bi, out_mat) in out.chunks_mut((m * n).max(1)).enumerate().take(batch) {
+            // Map the output batch index back to each (possibly broadcast) operand
+            let (mut a_idx, mut b_idx, mut rem) = (0, 0, bi);
+            let (mut a_stride, mut b_stride) = (1, 1);
+            for axis in (0..rank).rev() {
+                let i = rem % batch_shape[axis];
+                rem /= batch_shape[axis];
+                if pa[axis] != 1 {
+                    a_idx += i * a_stride;
+                }
+                if pb[axis] != 1 {
+                    b_idx += i * b_stride;
+                }
+                a_stride *= pa[axis];
+                b_stride *= pb[axis];
+            }
+            let a_mat = &a.data()[a_idx * m * k..(a_idx + 1) * m * k];
+            let b_mat = &b.data()[b_idx * k * n..(b_idx + 1) * k * n];
+            Self::gemm(ctx, a_mat, b_mat, out_mat, m, k, n);
+        }
+
+        let mut out_shape = batch_shape;
+        if !a_vector {
+            out_shape.push(m);
+        }
+        if !b_vector {
+            out_shape.push(n);
+        }
+        Ok(Tensor::new(out, out_shape))
+    }
+}
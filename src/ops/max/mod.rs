@@ -0,0 +1,14 @@
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::elementwise;
+use crate::kernels::BinaryOp;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+/// Variadic elementwise Max with broadcasting.
+pub struct Max;
+
+impl Operator for Max {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        elementwise::variadic(ctx, inputs, BinaryOp::Max)
+    }
+}
@@ -0,0 +1,14 @@
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::elementwise;
+use crate::kernels::BinaryOp;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+/// Variadic elementwise Min with broadcasting.
+pub struct Min;
+
+impl Operator for Min {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        elementwise::variadic(ctx, inputs, BinaryOp::Min)
+    }
+}
@@ -2,7 +2,39 @@
 
 pub mod operator;
 pub mod registry;
+pub(crate) mod attributes;
+pub(crate) mod elementwise;
 
 pub mod add;
+pub mod sub;
+pub mod mul;
+pub mod div;
+pub mod max;
+pub mod min;
 pub mod relu;
-pub mod conv;
\ No newline at end of file
+pub mod abs;
+pub mod neg;
+pub mod sqrt;
+pub mod reduce;
+pub mod conv;
+pub mod matmul;
+pub mod pool;
+pub mod shape;
+pub mod transpose;
+pub mod slice;
+pub mod concat;
+pub mod gather;
+pub mod constant;
+pub mod activation;
+pub mod erf;
+pub mod pow;
+pub mod gemm;
+pub mod batch_norm;
+pub mod gelu;
+pub mod layer_norm;
+pub mod cast;
+pub mod identity;
+pub mod quantization;
+pub mod control_flow;
+pub mod rnn;
+pub mod attention;
@@ -0,0 +1,13 @@
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::elementwise;
+use crate::kernels::BinaryOp;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Mul;
+
+impl Operator for Mul {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        elementwise::binary(ctx, inputs[0], inputs[1], BinaryOp::Mul)
+    }
+}
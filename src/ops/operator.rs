@@ -1,6 +1,111 @@
-use crate::tensor::Tensor;
+use crate::tensor::{ElementType, Tensor};
+use crate::kernels::Kernels;
+use crate::ops::control_flow::Bodies;
+use crate::ops::registry::OpRegistry;
+use crate::runtime::arena::TensorArena;
+use crate::runtime::thread_pool::ThreadPool;
 use crate::onnx::onnx_proto::NodeProto;
+use std::sync::OnceLock;
+
+/// Per-session state made available to every operator invocation.
+pub struct OpContext<'a> {
+    /// Elementwise/reduction primitives selected at session creation.
+    pub kernels: &'a Kernels,
+    /// Intra-op pool that kernels may split their work across.
+    pub pool: &'a ThreadPool,
+    /// Buffer arena, when the session has it enabled.
+    pub arena: Option<&'a TensorArena>,
+    /// Operators that control-flow nodes dispatch the nodes of their subgraphs to.
+    pub registry: Option<&'a OpRegistry>,
+    /// Subgraphs of the control-flow nodes being run, built at session creation.
+    pub bodies: Option<&'a Bodies>,
+}
+
+impl<'a> OpContext<'a> {
+    pub fn new(kernels: &'a Kernels, pool: &'a ThreadPool) -> Self {
+        Self { kernels, pool, arena: None, registry: None, bodies: None }
+    }
+
+    /// Allocates a zero-filled output buffer, from the arena if there is one.
+    pub fn alloc(&self, len: usize) -> Vec<f32> {
+        match self.arena {
+            Some(arena) => arena.alloc(len),
+            None => vec![0.0; len],
+        }
+    }
+}
 
 pub trait Operator {
-    fn run(&self, inputs: &[&Tensor], node: &NodeProto) -> anyhow::Result<Tensor>;
-}
\ No newline at end of file
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor>;
+
+    /// Whether `run` handles non-contiguous views itself. Operators that keep
+    /// the default read `Tensor::data` and get their inputs made contiguous first.
+    fn accepts_views(&self) -> bool {
+        false
+    }
+
+    /// Whether `run` handles inputs of every element type itself. Other operators
+    /// compute in f32: their inputs are widened and their outputs are narrowed
+    /// back to the element type of the first f16, bf16, u8 or i8 input. Int32
+    /// inputs, such as indices and lengths, do not set the output type.
+    fn accepts_any_element_type(&self) -> bool {
+        false
+    }
+
+    /// Whether output `index` is narrowed back after computing in f32. Outputs
+    /// holding integers, such as counts or indices, opt out and stay exact.
+    fn narrows_output(&self, _index: usize) -> bool {
+        true
+    }
+
+    /// Runs an operator with several outputs, returned in `node.output` order.
+    ///
+    /// The default runs `run` for single-output operators.
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        Ok(vec![self.run(inputs, node, ctx)?])
+    }
+}
+
+/// Placeholder bound to optional inputs left out with an empty name.
+pub(crate) fn absent_input() -> &'static Tensor {
+    static ABSENT: OnceLock<Tensor> = OnceLock::new();
+    ABSENT.get_or_init(|| Tensor::new(Vec::new(), vec![0]))
+}
+
+/// Input `index` of a node, or `None` when the optional input was left out
+/// (an empty name, or fewer inputs than `index + 1`).
+pub fn optional_input<'t>(inputs: &[&'t Tensor], index: usize) -> Option<&'t Tensor> {
+    inputs.get(index).copied().filter(|t| !std::ptr::eq(*t, absent_input()))
+}
+
+/// Runs `op`, materializing strided inputs and widening non-f32 ones unless
+/// the operator handles them. Returns every output of the node.
+pub fn invoke(op: &dyn Operator, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+    let views = op.accepts_views() || inputs.iter().all(|t| t.is_contiguous());
+    let types = op.accepts_any_element_type() || inputs.iter().all(|t| t.element_type() == ElementType::F32);
+    if views && types {
+        return op.run_outputs(inputs, node, ctx);
+    }
+    // Left-out inputs keep the shared placeholder, which `optional_input` tells apart
+    let owned: Vec<Option<Tensor>> = inputs.iter()
+        .map(|&t| {
+            if std::ptr::eq(t, absent_input()) {
+                return None;
+            }
+            let t = if types { t.clone() } else { t.to_element_type(ElementType::F32) };
+            Some(if views { t } else { t.to_contiguous() })
+        })
+        .collect();
+    let refs: Vec<&Tensor> = owned.iter().map(|t| t.as_ref().unwrap_or_else(|| absent_input())).collect();
+    let mut outputs = op.run_outputs(&refs, node, ctx)?;
+    // e.g. f16 weights looked up by integer ids, which are stored as f32
+    let narrowed = inputs.iter().map(|t| t.element_type()).find(|&t| !matches!(t, ElementType::F32 | ElementType::I32));
+    if let (Some(element_type), false) = (narrowed, types) {
+        for (i, output) in outputs.iter_mut().enumerate() {
+            if output.element_type() == ElementType::F32 && op.narrows_output(i) {
+                *output = output.to_element_type(element_type);
+            }
+        }
+    }
+    Ok(outputs)
+}
@@ -0,0 +1,186 @@
+//! Pooling operators (2D): MaxPool, AveragePool, GlobalMaxPool, GlobalAveragePool
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::attributes;
+use crate::ops::conv::Conv;
+use crate::kernels::ReduceOp;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct MaxPool;
+pub struct AveragePool;
+pub struct GlobalMaxPool;
+pub struct GlobalAveragePool;
+
+#[derive(Clone, Copy, PartialEq)]
+enum PoolKind {
+    Max,
+    Average { count_include_pad: bool },
+}
+
+/// Window geometry shared by MaxPool and AveragePool.
+struct Window {
+    kernel: (usize, usize),
+    stride: (usize, usize),
+    dilation: (usize, usize),
+    pad_begin: (usize, usize),
+    pad_end: (usize, usize),
+    output: (usize, usize),
+}
+
+impl Window {
+    fn from_node(node: &NodeProto, in_h: usize, in_w: usize) -> anyhow::Result<Self> {
+        let kernel_shape = attributes::get_ints(node, "kernel_shape");
+        if kernel_shape.len() != 2 {
+            return Err(anyhow::anyhow!("{}: only 2D pooling supported, kernel_shape = {:?}", node.op_type, kernel_shape));
+        }
+        let (kernel_h, kernel_w) = (kernel_shape[0] as usize, kernel_shape[1] as usize);
+
+        let strides = attributes::get_ints(node, "strides");
+        let dilations = attributes::get_ints(node, "dilations");
+        let pads = attributes::get_ints(node, "pads");
+        let auto_pad = attributes::get_string(node, "auto_pad");
+        let ceil_mode = attributes::get_int(node, "ceil_mode", 0) != 0;
+
+        let stride_h = strides.first().copied().unwrap_or(1) as usize;
+        let stride_w = strides.get(1).copied().unwrap_or(stride_h as i64) as usize;
+        let dilation_h = dilations.first().copied().unwrap_or(1) as usize;
+        let dilation_w = dilations.get(1).copied().unwrap_or(dilation_h as i64) as usize;
+
+        let (pad_top, pad_left, pad_bottom, pad_right) = if !auto_pad.is_empty() && auto_pad != "NOTSET" {
+            Conv::compute_auto_pad(&auto_pad, in_h, in_w, kernel_h, kernel_w, stride_h, stride_w, dilation_h, dilation_w)
+        } else if pads.len() >= 4 {
+            (pads[0] as usize, pads[1] as usize, pads[2] as usize, pads[3] as usize)
+        } else {
+            (0, 0, 0, 0)
+        };
+
+        let output_dim = |input: usize, pad_begin: usize, pad_end: usize, kernel: usize, stride: usize, dilation: usize| {
+            let effective = (kernel - 1) * dilation + 1;
+            let span = (input + pad_begin + pad_end).saturating_sub(effective);
+            let mut out = if ceil_mode { span.div_ceil(stride) } else { span / stride } + 1;
+            // The last window must start inside the input or the leading padding
+            if ceil_mode && (out - 1) * stride >= input + pad_begin {
+                out -= 1;
+            }
+            out
+        };
+
+        Ok(Self {
+            kernel: (kernel_h, kernel_w),
+            stride: (stride_h, stride_w),
+            dilation: (dilation_h, dilation_w),
+            pad_begin: (pad_top, pad_left),
+            pad_end: (pad_bottom, pad_right),
+            output: (
+                output_dim(in_h, pad_top, pad_bottom, kernel_h, stride_h, dilation_h),
+                output_dim(in_w, pad_left, pad_right, kernel_w, stride_w, dilation_w),
+            ),
+        })
+    }
+}
+
+fn pool(inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext, kind: PoolKind) -> anyhow::Result<Tensor> {
+    let x = inputs[0];
+    let shape = x.shape();
+    if shape.len() != 4 {
+        return Err(anyhow::anyhow!("{}: only 2D pooling (4D tensors) supported, got {:?}", node.op_type, shape));
+    }
+    let (batch, channels, in_h, in_w) = (shape[0], shape[1], shape[2], shape[3]);
+    let win = Window::from_node(node, in_h, in_w)?;
+    let (out_h, out_w) = win.output;
+    let (kernel_h, kernel_w) = win.kernel;
+
+    let x_data = x.data();
+    let mut output = ctx.alloc(batch * channels * out_h * out_w);
+    let plane = out_h * out_w;
+
+    // One (batch, channel) plane per task
+    ctx.pool.for_each_chunk(&mut output, plane, plane * kernel_h * kernel_w, |idx, out_plane| {
+        let in_plane = &x_data[idx * in_h * in_w..(idx + 1) * in_h * in_w];
+        for oh in 0..out_h {
+            for ow in 0..out_w {
+                let mut max = f32::NEG_INFINITY;
+                let mut sum = 0.0f32;
+                let mut count = 0usize;
+                let mut padded_count = 0usize;
+
+                for kh in 0..kernel_h {
+                    let ih = (oh * win.stride.0 + kh * win.dilation.0) as isize - win.pad_begin.0 as isize;
+                    for kw in 0..kernel_w {
+                        let iw = (ow * win.stride.1 + kw * win.dilation.1) as isize - win.pad_begin.1 as isize;
+                        // Padded positions count only if they fall within the declared pads
+                        if ih < (in_h + win.pad_end.0) as isize && iw < (in_w + win.pad_end.1) as isize {
+                            padded_count += 1;
+                        }
+                        if ih >= 0 && ih < in_h as isize && iw >= 0 && iw < in_w as isize {
+                            let v = in_plane[ih as usize * in_w + iw as usize];
+                            if v > max {
+                                max = v;
+                            }
+                            sum += v;
+                            count += 1;
+                        }
+                    }
+                }
+
+                out_plane[oh * out_w + ow] = match kind {
+                    PoolKind::Max => max,
+                    PoolKind::Average { count_include_pad: true } => sum / padded_count.max(1) as f32,
+                    PoolKind::Average { count_include_pad: false } => sum / count.max(1) as f32,
+                };
+            }
+        }
+    });
+
+    Ok(Tensor::new(output, vec![batch, channels, out_h, out_w]))
+}
+
+fn global_pool(inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext, kind: PoolKind) -> anyhow::Result<Tensor> {
+    let x = inputs[0];
+    let shape = x.shape();
+    if shape.len() < 3 {
+        return Err(anyhow::anyhow!("{}: expected at least 3D input, got {:?}", node.op_type, shape));
+    }
+    let spatial: usize = shape[2..].iter().product();
+    let planes = shape[0] * shape[1];
+    let x_data = x.data();
+
+    let mut output = ctx.alloc(planes);
+    ctx.pool.for_each_chunk(&mut output, 1, spatial, |idx, out| {
+        let src = &x_data[idx * spatial..(idx + 1) * spatial];
+        out[0] = match kind {
+            PoolKind::Max => ctx.kernels.reduce(ReduceOp::Max, src),
+            PoolKind::Average { .. } => ctx.kernels.reduce(ReduceOp::Sum, src) / spatial as f32,
+        };
+    });
+
+    let mut out_shape = vec![shape[0], shape[1]];
+    out_shape.resize(shape.len(), 1);
+    Ok(Tensor::new(output, out_shape))
+}
+
+impl Operator for MaxPool {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        pool(inputs, node, ctx, PoolKind::Max)
+    }
+}
+
+impl Operator for AveragePool {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let count_include_pad = attributes::get_int(node, "count_include_pad", 0) != 0;
+        pool(inputs, node, ctx, PoolKind::Average { count_include_pad })
+    }
+}
+
+impl Operator for GlobalMaxPool {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        global_pool(inputs, node, ctx, PoolKind::Max)
+    }
+}
+
+impl Operator for GlobalAveragePool {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        global_pool(inputs, node, ctx, PoolKind::Average { count_include_pad: false })
+    }
+}
@@ -0,0 +1,19 @@
+//! Pow operator implementation
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::elementwise;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Pow;
+
+impl Operator for Pow {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let (x, y) = (inputs[0], inputs[1]);
+        // Squares dominate in practice (variance computations)
+        if y.data() == [2.0] && y.shape().iter().all(|&d| d == 1) && y.shape().len() <= x.shape().len() {
+            return Ok(elementwise::map(ctx, x, |v| v * v));
+        }
+        elementwise::binary_map(ctx, x, y, f32::powf)
+    }
+}
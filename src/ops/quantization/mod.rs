@@ -0,0 +1,421 @@
+//! Linear quantization operators: QuantizeLinear, DequantizeLinear,
+//! DynamicQuantizeLinear, MatMulInteger, QLinearMatMul and QLinearConv.
+//!
+//! Quantized tensors hold u8 or i8 elements, with `real = (q - zero_point) * scale`.
+//! MatMulInteger returns its int32 accumulators in an i32 tensor, and
+//! QLinearConv adds its int32 bias to the accumulators exactly.
+
+use crate::ops::attributes;
+use crate::ops::conv::ConvGeometry;
+use crate::ops::elementwise;
+use crate::ops::operator::{optional_input, Operator, OpContext};
+use crate::ops::shape::normalize_axis;
+use crate::tensor::{ElementType, Tensor};
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct QuantizeLinear;
+pub struct DequantizeLinear;
+pub struct DynamicQuantizeLinear;
+pub struct MatMulInteger;
+pub struct QLinearMatMul;
+pub struct QLinearConv;
+
+/// Scales and zero points, either one pair for the whole tensor or one per
+/// index along `axis`.
+struct QuantParams {
+    scales: Vec<f32>,
+    zero_points: Vec<i32>,
+    /// Product of the dimensions after the quantization axis.
+    inner: usize,
+}
+
+impl QuantParams {
+    /// Reads `scale` and the optional `zero_point` of a tensor shaped `shape`,
+    /// quantized along `axis` when the scale has more than one element.
+    fn new(node: &NodeProto, scale: &Tensor, zero_point: Option<&Tensor>, shape: &[usize], axis: i64) -> anyhow::Result<Self> {
+        let scales = scale.values().into_owned();
+        let zero_points = match zero_point {
+            Some(zp) => zp.values().iter().map(|&v| v as i32).collect(),
+            None => vec![0; scales.len()],
+        };
+        if zero_points.len() != scales.len() {
+            return Err(anyhow::anyhow!(
+                "{}: zero point {:?} does not match scale {:?}", node.op_type, zero_point.map(Tensor::shape), scale.shape()
+            ));
+        }
+        if scales.len() == 1 {
+            return Ok(Self { scales, zero_points, inner: usize::MAX });
+        }
+        if attributes::get_int(node, "block_size", 0) != 0 {
+            return Err(anyhow::anyhow!("{}: blocked quantization is not supported", node.op_type));
+        }
+        let axis = normalize_axis(node, axis, shape.len())?;
+        if scale.shape().len() != 1 || scales.len() != shape[axis] {
+            return Err(anyhow::anyhow!(
+                "{}: scale {:?} does not match axis {} of {:?}", node.op_type, scale.shape(), axis, shape
+            ));
+        }
+        Ok(Self { scales, zero_points, inner: shape[axis + 1..].iter().product() })
+    }
+
+    /// Zero points alone, for operators without scales.
+    fn zero_points(node: &NodeProto, zero_point: Option<&Tensor>, shape: &[usize], axis: i64) -> anyhow::Result<Self> {
+        let scale = match zero_point {
+            Some(zp) => Tensor::new(vec![1.0; zp.len()], zp.shape().to_vec()),
+            None => Tensor::new(vec![1.0], vec![]),
+        };
+        Self::new(node, &scale, zero_point, shape, axis)
+    }
+
+    /// Scale of slice `c` along the quantization axis.
+    fn scale(&self, c: usize) -> f32 {
+        self.scales[if self.scales.len() == 1 { 0 } else { c }]
+    }
+
+    /// Index of the (scale, zero point) pair of flat element `i`.
+    fn channel(&self, i: usize) -> usize {
+        if self.scales.len() == 1 { 0 } else { (i / self.inner) % self.scales.len() }
+    }
+}
+
+/// Saturation bounds of a quantized element type.
+fn bounds(element_type: ElementType) -> (f32, f32) {
+    match element_type {
+        ElementType::I8 => (-128.0, 127.0),
+        _ => (0.0, 255.0),
+    }
+}
+
+/// Rounds `v` half to even, offsets it and saturates it to `bounds`.
+fn quantize(v: f32, zero_point: i32, (lo, hi): (f32, f32)) -> f32 {
+    (v.round_ties_even() + zero_point as f32).clamp(lo, hi)
+}
+
+/// Packs saturated values into a tensor of quantized `element_type`.
+fn quantized_tensor(values: Vec<f32>, shape: Vec<usize>, element_type: ElementType) -> Tensor {
+    match element_type {
+        ElementType::I8 => Tensor::from_i8(values.into_iter().map(|v| v as i8).collect(), shape),
+        _ => Tensor::from_u8(values.into_iter().map(|v| v as u8).collect(), shape),
+    }
+}
+
+/// Element type of an output quantized with `zero_point`: u8 when there is none.
+fn output_type(node: &NodeProto, zero_point: Option<&Tensor>) -> anyhow::Result<ElementType> {
+    match zero_point.map_or(ElementType::U8, Tensor::element_type) {
+        t if t.is_integer() => Ok(t),
+        t => Err(anyhow::anyhow!("{}: unsupported zero point type {:?}", node.op_type, t)),
+    }
+}
+
+/// Checks that input `name` holds u8 or i8 elements.
+fn expect_quantized(node: &NodeProto, t: &Tensor, name: &str) -> anyhow::Result<()> {
+    if !t.element_type().is_integer() {
+        return Err(anyhow::anyhow!("{}: {} must be uint8 or int8, got {:?}", node.op_type, name, t.element_type()));
+    }
+    Ok(())
+}
+
+/// Integer values of `t` with their zero point subtracted.
+fn centered(t: &Tensor, params: &QuantParams) -> Vec<i32> {
+    t.values().iter().enumerate()
+        .map(|(i, &v)| v as i32 - params.zero_points[params.channel(i)])
+        .collect()
+}
+
+impl Operator for QuantizeLinear {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let x = inputs[0];
+        let zero_point = optional_input(inputs, 2);
+        let element_type = output_type(node, zero_point)?;
+        let params = QuantParams::new(node, inputs[1], zero_point, x.shape(), attributes::get_int(node, "axis", 1))?;
+        let bounds = bounds(element_type);
+        let y = x.values().iter().enumerate()
+            .map(|(i, &v)| {
+                let c = params.channel(i);
+                quantize(v / params.scales[c], params.zero_points[c], bounds)
+            })
+            .collect();
+        Ok(quantized_tensor(y, x.shape().to_vec(), element_type))
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for DequantizeLinear {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let x = inputs[0];
+        let scale = inputs[1];
+        let params = QuantParams::new(node, scale, optional_input(inputs, 2), x.shape(), attributes::get_int(node, "axis", 1))?;
+        let y = x.values().iter().enumerate()
+            .map(|(i, &v)| {
+                let c = params.channel(i);
+                (v - params.zero_points[c] as f32) * params.scales[c]
+            })
+            .collect();
+        // The output takes the type of the scale (float16 scales give float16 outputs)
+        Ok(Tensor::new(y, x.shape().to_vec()).to_element_type(scale.element_type()))
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for DynamicQuantizeLinear {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.swap_remove(0))
+    }
+
+    /// Returns `y` (u8), `y_scale` and `y_zero_point` (u8), with a range widened to include 0.
+    fn run_outputs(&self, inputs: &[&Tensor], _node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let x = inputs[0].values();
+        let (min, max) = x.iter().fold((0.0f32, 0.0f32), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+        let scale = (max - min) / 255.0;
+        let bounds = bounds(ElementType::U8);
+        let (zero_point, y) = if scale > 0.0 {
+            let zero_point = quantize(-min / scale, 0, bounds) as i32;
+            (zero_point, x.iter().map(|&v| quantize(v / scale, zero_point, bounds)).collect())
+        } else {
+            (0, vec![0.0; x.len()])
+        };
+        Ok(vec![
+            quantized_tensor(y, inputs[0].shape().to_vec(), ElementType::U8),
+            Tensor::new(vec![scale], vec![]),
+            Tensor::from_u8(vec![zero_point as u8], vec![]),
+        ])
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
+
+/// Integer product of quantized `a` and `b` with numpy matmul broadcasting.
+///
+/// `a_zero` holds one zero point per row of `a` (or a single one) and `b_zero`
+/// one per column of `b`. Returns the int32 accumulators, the output shape and
+/// the number of columns.
+fn integer_matmul(
+    node: &NodeProto,
+    ctx: &OpContext,
+    a: &Tensor,
+    b: &Tensor,
+    a_zero: Option<&Tensor>,
+    b_zero: Option<&Tensor>,
+) -> anyhow::Result<(Vec<i32>, Vec<usize>, usize)> {
+    expect_quantized(node, a, "A")?;
+    expect_quantized(node, b, "B")?;
+
+    // Promote 1-D operands to matrices, as numpy does
+    let mut a_shape = a.shape().to_vec();
+    let mut b_shape = b.shape().to_vec();
+    let a_vector = a_shape.len() == 1;
+    let b_vector = b_shape.len() == 1;
+    if a_vector {
+        a_shape.insert(0, 1);
+    }
+    if b_vector {
+        b_shape.push(1);
+    }
+    if a_shape.len() < 2 || b_shape.len() < 2 {
+        return Err(anyhow::anyhow!("{}: scalar operands are not supported", node.op_type));
+    }
+
+    let (m, k) = (a_shape[a_shape.len() - 2], a_shape[a_shape.len() - 1]);
+    let (k2, n) = (b_shape[b_shape.len() - 2], b_shape[b_shape.len() - 1]);
+    if k != k2 {
+        return Err(anyhow::anyhow!("{}: inner dimensions mismatch {:?} x {:?}", node.op_type, a.shape(), b.shape()));
+    }
+
+    // Zero points are per row of A (axis -2) and per column of B (axis -1)
+    let a_params = QuantParams::zero_points(node, a_zero, &a_shape, -2)?;
+    let b_params = QuantParams::zero_points(node, b_zero, &b_shape, -1)?;
+    let a_data = centered(a, &a_params);
+    let b_data = centered(b, &b_params);
+
+    let a_batch = &a_shape[..a_shape.len() - 2];
+    let b_batch = &b_shape[..b_shape.len() - 2];
+    let batch_shape = elementwise::broadcast_shape(a_batch, b_batch)?;
+    let batch: usize = batch_shape.iter().product();
+    let rank = batch_shape.len();
+    let pa = elementwise::pad_shape(a_batch, rank);
+    let pb = elementwise::pad_shape(b_batch, rank);
+
+    let mut out = vec![0i32; batch * m * n];
+    for (bi, out_mat) in out.chunks_mut((m * n).max(1)).enumerate().take(batch) {
+        // Map the output batch index back to each (possibly broadcast) operand
+        let (mut a_idx, mut b_idx, mut rem) = (0, 0, bi);
+        let (mut a_stride, mut b_stride) = (1, 1);
+        for axis in (0..rank).rev() {
+            let i = rem % batch_shape[axis];
+            rem /= batch_shape[axis];
+            if pa[axis] != 1 {
+                a_idx += i * a_stride;
+            }
+            if pb[axis] != 1 {
+                b_idx += i * b_stride;
+            }
+            a_stride *= pa[axis];
+            b_stride *= pb[axis];
+        }
+        let a_mat = &a_data[a_idx * m * k..(a_idx + 1) * m * k];
+        let b_mat = &b_data[b_idx * k * n..(b_idx + 1) * k * n];
+        if n == 0 {
+            continue;
+        }
+        ctx.pool.for_each_chunk(out_mat, n, k * n, |i, row| {
+            let a_row = &a_mat[i * k..(i + 1) * k];
+            for (kk, &a_ik) in a_row.iter().enumerate() {
+                let b_row = &b_mat[kk * n..(kk + 1) * n];
+                for (o, &b_kj) in row.iter_mut().zip(b_row) {
+                    *o += a_ik * b_kj;
+                }
+            }
+        });
+    }
+
+    let mut out_shape = batch_shape;
+    if !a_vector {
+        out_shape.push(m);
+    }
+    if !b_vector {
+        out_shape.push(n);
+    }
+    Ok((out, out_shape, n))
+}
+
+impl Operator for MatMulInteger {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let (acc, shape, _) = integer_matmul(
+            node, ctx, inputs[0], inputs[1], optional_input(inputs, 2), optional_input(inputs, 3),
+        )?;
+        Ok(Tensor::from_i32(acc, shape))
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for QLinearMatMul {
+    /// Inputs: a, a_scale, a_zero_point, b, b_scale, b_zero_point, y_scale, y_zero_point.
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        if inputs.len() < 8 {
+            return Err(anyhow::anyhow!("QLinearMatMul: expected 8 inputs, got {}", inputs.len()));
+        }
+        let (a_scale, b_scale, y_scale) = (inputs[1], inputs[4], inputs[6]);
+        let y_zero = optional_input(inputs, 7);
+        let element_type = output_type(node, y_zero)?;
+        let (acc, shape, n) = integer_matmul(
+            node, ctx, inputs[0], inputs[3], optional_input(inputs, 2), optional_input(inputs, 5),
+        )?;
+
+        // One multiplier per output column when B is quantized per column
+        let a_scale = a_scale.values()[0];
+        let y = QuantParams::new(node, y_scale, y_zero, &shape, -1)?;
+        let b = QuantParams::new(node, b_scale, None, &shape, -1)?;
+        if y.scales.len() != 1 || b.scales.len() != 1 && b.scales.len() != n {
+            return Err(anyhow::anyhow!("QLinearMatMul: b_scale {:?} does not match {} columns", b_scale.shape(), n));
+        }
+        let multipliers: Vec<f32> = (0..n).map(|j| a_scale * b.scale(j) / y.scales[0]).collect();
+        let bounds = bounds(element_type);
+        let values = acc.iter().enumerate()
+            .map(|(i, &v)| quantize(v as f32 * multipliers[i % n.max(1)], y.zero_points[0], bounds))
+            .collect();
+        Ok(quantized_tensor(values, shape, element_type))
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for QLinearConv {
+    /// Inputs: x, x_scale, x_zero_point, w, w_scale, w_zero_point, y_scale,
+    /// y_zero_point and an optional int32 bias B.
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        if inputs.len() < 8 {
+            return Err(anyhow::anyhow!("QLinearConv: expected at least 8 inputs, got {}", inputs.len()));
+        }
+        let (x, w) = (inputs[0], inputs[3]);
+        expect_quantized(node, x, "x")?;
+        expect_quantized(node, w, "w")?;
+        let y_zero = optional_input(inputs, 7);
+        let element_type = output_type(node, y_zero)?;
+        let bias = optional_input(inputs, 8).map(|b| match b.element_type() {
+            ElementType::I32 => b.to_contiguous().data_i32().to_vec(),
+            _ => b.values().iter().map(|&v| v as i32).collect(),
+        });
+
+        let geometry = ConvGeometry::new(node, x.shape(), w.shape())?;
+        let out_shape = geometry.output_shape();
+        let ConvGeometry {
+            batch, in_channels, in_h, in_w, out_channels, kernel_h, kernel_w, group,
+            stride_h, stride_w, dilation_h, dilation_w, pad_top, pad_left, out_h, out_w,
+        } = geometry;
+
+        let x_params = QuantParams::new(node, inputs[1], optional_input(inputs, 2), x.shape(), 1)?;
+        if x_params.scales.len() != 1 {
+            return Err(anyhow::anyhow!("QLinearConv: x must be quantized per tensor"));
+        }
+        // The weight may be quantized per output channel
+        let w_params = QuantParams::new(node, inputs[4], optional_input(inputs, 5), w.shape(), 0)?;
+        let y = QuantParams::new(node, inputs[6], y_zero, &out_shape, 1)?;
+        if y.scales.len() != 1 {
+            return Err(anyhow::anyhow!("QLinearConv: y must be quantized per tensor"));
+        }
+        if bias.as_ref().is_some_and(|b| b.len() != out_channels) {
+            return Err(anyhow::anyhow!("QLinearConv: bias must have {} elements", out_channels));
+        }
+
+        // Padding holds the zero point, which is 0 once centered
+        let x_data = centered(x, &x_params);
+        let w_data = centered(w, &w_params);
+
+        let in_channels_per_group = in_channels / group;
+        let out_channels_per_group = out_channels / group;
+
+        // One (batch, output channel) plane per task
+        let plane = out_h * out_w;
+        let work = plane * in_channels_per_group * kernel_h * kernel_w;
+        let mut acc = vec![0i32; batch * out_channels * plane];
+        ctx.pool.for_each_chunk(&mut acc, plane, work, |idx, out_plane| {
+            let n = idx / out_channels;
+            let abs_oc = idx % out_channels;
+            let g = abs_oc / out_channels_per_group;
+            for oh in 0..out_h {
+                for ow in 0..out_w {
+                    let mut sum = bias.as_ref().map_or(0, |b| b[abs_oc]);
+                    for ic in 0..in_channels_per_group {
+                        let abs_ic = g * in_channels_per_group + ic;
+                        for kh in 0..kernel_h {
+                            for kw in 0..kernel_w {
+                                let ih = (oh * stride_h + kh * dilation_h) as isize - pad_top as isize;
+                                let iw = (ow * stride_w + kw * dilation_w) as isize - pad_left as isize;
+                                if ih >= 0 && ih < in_h as isize && iw >= 0 && iw < in_w as isize {
+                                    let x_idx = ((n * in_channels + abs_ic) * in_h + ih as usize) * in_w + iw as usize;
+                                    let w_idx = ((abs_oc * in_channels_per_group + ic) * kernel_h + kh) * kernel_w + kw;
+                                    sum += x_data[x_idx] * w_data[w_idx];
+                                }
+                            }
+                        }
+                    }
+                    out_plane[oh * out_w + ow] = sum;
+                }
+            }
+        });
+
+        let multipliers: Vec<f32> = (0..out_channels).map(|oc| x_params.scales[0] * w_params.scale(oc) / y.scales[0]).collect();
+        let bounds = bounds(element_type);
+        let values = acc.iter().enumerate()
+            .map(|(i, &v)| quantize(v as f32 * multipliers[(i / plane.max(1)) % out_channels], y.zero_points[0], bounds))
+            .collect();
+        Ok(quantized_tensor(values, out_shape, element_type))
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
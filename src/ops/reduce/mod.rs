@@ -0,0 +1,111 @@
+//! Reduce operators: ReduceSum, ReduceMean, ReduceMax, ReduceMin.
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::attributes;
+use crate::ops::transpose::permute;
+use crate::kernels::ReduceOp;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct ReduceSum;
+pub struct ReduceMean;
+pub struct ReduceMax;
+pub struct ReduceMin;
+
+/// Resolves the reduced axes, from the `axes` input (opset >= 13/18) or attribute.
+///
+/// Returns `None` when the node is a no-op (`noop_with_empty_axes` and no axes).
+fn resolve_axes(inputs: &[&Tensor], node: &NodeProto, rank: usize) -> anyhow::Result<Option<Vec<usize>>> {
+    let raw: Vec<i64> = match inputs.get(1) {
+        Some(t) => t.data().iter().map(|&v| v as i64).collect(),
+        None => attributes::get_ints(node, "axes"),
+    };
+    if raw.is_empty() {
+        if attributes::get_int(node, "noop_with_empty_axes", 0) != 0 {
+            return Ok(None);
+        }
+        return Ok(Some((0..rank).collect()));
+    }
+    let mut axes = raw
+        .iter()
+        .map(|&a| {
+            let axis = if a < 0 { a + rank as i64 } else { a };
+            if axis < 0 || axis >= rank as i64 {
+                Err(anyhow::anyhow!("{}: axis {} out of range for rank {}", node.op_type, a, rank))
+            } else {
+                Ok(axis as usize)
+            }
+        })
+        .collect::<anyhow::Result<Vec<usize>>>()?;
+    axes.sort_unstable();
+    axes.dedup();
+    Ok(Some(axes))
+}
+
+fn reduce(inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext, op: ReduceOp, mean: bool) -> anyhow::Result<Tensor> {
+    let x = inputs[0];
+    let shape = x.shape();
+    let rank = shape.len();
+    let keepdims = attributes::get_int(node, "keepdims", 1) != 0;
+
+    let axes = match resolve_axes(inputs, node, rank)? {
+        Some(axes) => axes,
+        None => return Ok(x.clone()),
+    };
+
+    let kept: Vec<usize> = (0..rank).filter(|a| !axes.contains(a)).collect();
+    let inner: usize = axes.iter().map(|&a| shape[a]).product();
+    let outer: usize = kept.iter().map(|&a| shape[a]).product();
+
+    // Reduced axes must be innermost so that every output is a contiguous slice.
+    let trailing = axes.iter().enumerate().all(|(i, &a)| a == rank - axes.len() + i);
+    let permuted;
+    let data = if trailing {
+        x.data()
+    } else {
+        let perm: Vec<usize> = kept.iter().chain(axes.iter()).copied().collect();
+        permuted = {
+            let mut buf = vec![0.0; x.data().len()];
+            permute(x.data(), shape, &perm, &mut buf);
+            buf
+        };
+        &permuted
+    };
+
+    let mut out = ctx.alloc(outer);
+    ctx.pool.for_each_chunk(&mut out, 1, inner, |i, dst| {
+        let v = ctx.kernels.reduce(op, &data[i * inner..(i + 1) * inner]);
+        dst[0] = if mean { v / inner as f32 } else { v };
+    });
+
+    let out_shape: Vec<usize> = if keepdims {
+        (0..rank).map(|a| if axes.contains(&a) { 1 } else { shape[a] }).collect()
+    } else {
+        kept.iter().map(|&a| shape[a]).collect()
+    };
+    Ok(Tensor::new(out, out_shape))
+}
+
+impl Operator for ReduceSum {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        reduce(inputs, node, ctx, ReduceOp::Sum, false)
+    }
+}
+
+impl Operator for ReduceMean {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        reduce(inputs, node, ctx, ReduceOp::Sum, true)
+    }
+}
+
+impl Operator for ReduceMax {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        reduce(inputs, node, ctx, ReduceOp::Max, false)
+    }
+}
+
+impl Operator for ReduceMin {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        reduce(inputs, node, ctx, ReduceOp::Min, false)
+    }
+}
@@ -1,29 +1,195 @@
 use std::collections::HashMap;
 use crate::ops::operator::Operator;
 use crate::ops::add::Add;
+use crate::ops::sub::Sub;
+use crate::ops::mul::Mul;
+use crate::ops::div::Div;
+use crate::ops::max::Max;
+use crate::ops::min::Min;
 use crate::ops::relu::Relu;
-use crate::ops::conv::Conv;
+use crate::ops::abs::Abs;
+use crate::ops::neg::Neg;
+use crate::ops::sqrt::Sqrt;
+use crate::ops::reduce::{ReduceSum, ReduceMean, ReduceMax, ReduceMin};
+use crate::ops::conv::{Conv, FusedConv};
+use crate::ops::matmul::MatMul;
+use crate::ops::pool::{MaxPool, AveragePool, GlobalMaxPool, GlobalAveragePool};
+use crate::ops::shape::{Shape, Reshape, Flatten, Squeeze, Unsqueeze};
+use crate::ops::transpose::Transpose;
+use crate::ops::slice::Slice;
+use crate::ops::concat::Concat;
+use crate::ops::gather::Gather;
+use crate::ops::constant::Constant;
+use crate::ops::cast::Cast;
+use crate::ops::identity::{Identity, Dropout};
+use crate::ops::activation::{Sigmoid, Tanh, LeakyRelu, Clip};
+use crate::ops::erf::Erf;
+use crate::ops::pow::Pow;
+use crate::ops::gemm::{Gemm, FusedGemm};
+use crate::ops::batch_norm::BatchNormalization;
+use crate::ops::gelu::{BiasGelu, FastGelu, Gelu};
+use crate::ops::layer_norm::{EmbedLayerNormalization, LayerNormalization, SkipLayerNormalization};
+use crate::ops::attention::{Attention, GroupQueryAttention, MultiHeadAttention, RotaryEmbedding};
+use crate::ops::control_flow::{If, Loop, Scan};
+use crate::ops::rnn::{Gru, Lstm, Rnn};
+use crate::ops::quantization::{
+    QuantizeLinear, DequantizeLinear, DynamicQuantizeLinear, MatMulInteger, QLinearMatMul, QLinearConv,
+};
+use crate::graph::optimizer::MS_DOMAIN;
+use crate::onnx::onnx_proto::NodeProto;
 
+/// Operators by domain and op type. Nodes are dispatched to the operator
+/// registered for their own domain, `"ai.onnx"` being the default domain `""`.
+///
+/// Custom operators implement `Operator` and are added to the registry a
+/// session is created with:
+///
+/// ```
+/// use neuroxyde::onnx::onnx_proto::NodeProto;
+/// use neuroxyde::ops::operator::{OpContext, Operator};
+/// use neuroxyde::ops::registry::OpRegistry;
+/// use neuroxyde::tensor::Tensor;
+///
+/// struct Square;
+///
+/// impl Operator for Square {
+///     fn run(&self, inputs: &[&Tensor], _node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+///         let data = inputs[0].data().iter().map(|v| v * v).collect();
+///         Ok(Tensor::new(data, inputs[0].shape().to_vec()))
+///     }
+/// }
+///
+/// let mut registry = OpRegistry::new();
+/// registry.register_in_domain("com.example", "Square", Square);
+/// assert!(registry.get_in_domain("com.example", "Square").is_some());
+/// assert!(registry.get("Square").is_none());
+/// ```
 pub struct OpRegistry {
-  ops: HashMap<String, Box<dyn Operator + Send + Sync>>,
+  ops: HashMap<(String, String), Box<dyn Operator + Send + Sync>>,
+}
+
+/// `"ai.onnx"` and `""` both name the default domain.
+fn canonical(domain: &str) -> &str {
+  if domain == "ai.onnx" { "" } else { domain }
 }
 
 impl OpRegistry {
+  /// A registry without any operator.
+  pub fn empty() -> Self {
+      Self { ops: HashMap::new() }
+  }
+
+  /// The built-in operators: the default ONNX domain, plus the `com.microsoft`
+  /// contrib operators.
   pub fn new() -> Self {
-      let mut registry = Self {
-          ops: HashMap::new(),
-      };
+      let mut registry = Self::empty();
       registry.register("Add", Add);
+      registry.register("Sub", Sub);
+      registry.register("Mul", Mul);
+      registry.register("Div", Div);
+      registry.register("Max", Max);
+      registry.register("Min", Min);
       registry.register("Relu", Relu);
+      registry.register("Abs", Abs);
+      registry.register("Neg", Neg);
+      registry.register("Sqrt", Sqrt);
+      registry.register("ReduceSum", ReduceSum);
+      registry.register("ReduceMean", ReduceMean);
+      registry.register("ReduceMax", ReduceMax);
+      registry.register("ReduceMin", ReduceMin);
       registry.register("Conv", Conv);
+      registry.register("MatMul", MatMul);
+      registry.register("MaxPool", MaxPool);
+      registry.register("AveragePool", AveragePool);
+      registry.register("GlobalMaxPool", GlobalMaxPool);
+      registry.register("GlobalAveragePool", GlobalAveragePool);
+      registry.register("Shape", Shape);
+      registry.register("Reshape", Reshape);
+      registry.register("Flatten", Flatten);
+      registry.register("Squeeze", Squeeze);
+      registry.register("Unsqueeze", Unsqueeze);
+      registry.register("Transpose", Transpose);
+      registry.register("Slice", Slice);
+      registry.register("Concat", Concat);
+      registry.register("Gather", Gather);
+      registry.register("Constant", Constant);
+      registry.register("Cast", Cast);
+      registry.register("Identity", Identity);
+      registry.register("Dropout", Dropout);
+      registry.register("Sigmoid", Sigmoid);
+      registry.register("Tanh", Tanh);
+      registry.register("LeakyRelu", LeakyRelu);
+      registry.register("Clip", Clip);
+      registry.register("Erf", Erf);
+      registry.register("Pow", Pow);
+      registry.register("Gemm", Gemm);
+      registry.register("BatchNormalization", BatchNormalization);
+      registry.register("LayerNormalization", LayerNormalization);
+      registry.register("QuantizeLinear", QuantizeLinear);
+      registry.register("DequantizeLinear", DequantizeLinear);
+      registry.register("DynamicQuantizeLinear", DynamicQuantizeLinear);
+      registry.register("MatMulInteger", MatMulInteger);
+      registry.register("QLinearMatMul", QLinearMatMul);
+      registry.register("QLinearConv", QLinearConv);
+      registry.register("If", If);
+      registry.register("Loop", Loop);
+      registry.register("Scan", Scan);
+      registry.register("LSTM", Lstm);
+      registry.register("GRU", Gru);
+      registry.register("RNN", Rnn);
+      registry.register("Gelu", Gelu);
+      // com.microsoft contrib ops, some produced by the fusion passes
+      registry.register_in_domain(MS_DOMAIN, "FusedConv", FusedConv);
+      registry.register_in_domain(MS_DOMAIN, "FusedGemm", FusedGemm);
+      registry.register_in_domain(MS_DOMAIN, "Gelu", Gelu);
+      // com.microsoft transformer ops, as emitted by ONNX Runtime's transformer optimizer
+      registry.register_in_domain(MS_DOMAIN, "BiasGelu", BiasGelu);
+      registry.register_in_domain(MS_DOMAIN, "FastGelu", FastGelu);
+      registry.register_in_domain(MS_DOMAIN, "SkipLayerNormalization", SkipLayerNormalization);
+      registry.register_in_domain(MS_DOMAIN, "EmbedLayerNormalization", EmbedLayerNormalization);
+      registry.register_in_domain(MS_DOMAIN, "Attention", Attention);
+      registry.register_in_domain(MS_DOMAIN, "MultiHeadAttention", MultiHeadAttention);
+      registry.register_in_domain(MS_DOMAIN, "GroupQueryAttention", GroupQueryAttention);
+      registry.register_in_domain(MS_DOMAIN, "RotaryEmbedding", RotaryEmbedding);
       registry
   }
 
+  /// Registers `op` for `name` in the default domain, replacing any previous operator.
   pub fn register<Op: Operator + Send + Sync + 'static>(&mut self, name: &str, op: Op) {
-      self.ops.insert(name.to_string(), Box::new(op));
+      self.register_in_domain("", name, op);
   }
 
+  /// Registers `op` for `name` in `domain`, replacing any previous operator.
+  pub fn register_in_domain<Op: Operator + Send + Sync + 'static>(&mut self, domain: &str, name: &str, op: Op) {
+      self.ops.insert((canonical(domain).to_string(), name.to_string()), Box::new(op));
+  }
+
+  /// The operator registered for `name` in the default domain.
   pub fn get(&self, name: &str) -> Option<&(dyn Operator + Send + Sync)> {
-      self.ops.get(name).map(|boxed| boxed.as_ref())
+      self.get_in_domain("", name)
+  }
+
+  /// The operator registered for `name` in `domain`, where "ai.onnx" is the default domain.
+  pub fn get_in_domain(&self, domain: &str, name: &str) -> Option<&(dyn Operator + Send + Sync)> {
+      self.ops.get(&(canonical(domain).to_string(), name.to_string())).map(|boxed| boxed.as_ref())
+  }
+
+  /// The operator running `node`, looked up by its domain and op type.
+  pub fn resolve(&self, node: &NodeProto) -> Option<&(dyn Operator + Send + Sync)> {
+      self.get_in_domain(&node.domain, &node.op_type)
+  }
+
+  /// The domains with at least one operator, sorted.
+  pub fn domains(&self) -> Vec<&str> {
+      let mut domains: Vec<&str> = self.ops.keys().map(|(domain, _)| domain.as_str()).collect();
+      domains.sort_unstable();
+      domains.dedup();
+      domains
+  }
+}
+
+impl Default for OpRegistry {
+  fn default() -> Self {
+      Self::new()
   }
 }
@@ -0,0 +1,439 @@
+//! Recurrent operators: LSTM, GRU and RNN.
+//!
+//! The three share their inputs (`X`, `W`, `R`, `B`, `sequence_lens`,
+//! `initial_h`), directions and `layout`. The input projection `X·Wᵀ + Wb`
+//! is computed for every time step at once, then each step only multiplies
+//! the previous hidden state by `Rᵀ`. Steps past the length of a sequence
+//! leave its state unchanged and its rows of `Y` at zero.
+
+use crate::ops::attributes;
+use crate::ops::matmul::MatMul;
+use crate::ops::operator::{optional_input, OpContext, Operator};
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Lstm;
+pub struct Gru;
+pub struct Rnn;
+
+/// Activation function named in the `activations` attribute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Relu,
+    Tanh,
+    Sigmoid,
+    Affine { alpha: f32, beta: f32 },
+    LeakyRelu { alpha: f32 },
+    ThresholdedRelu { alpha: f32 },
+    ScaledTanh { alpha: f32, beta: f32 },
+    HardSigmoid { alpha: f32, beta: f32 },
+    Elu { alpha: f32 },
+    Softsign,
+    Softplus,
+}
+
+impl Function {
+    /// Parses `name`, taking its parameters from `alphas`/`betas` (in the order
+    /// of the functions that have some) or the defaults of the ONNX operator.
+    fn parse(
+        node: &NodeProto,
+        name: &str,
+        alphas: &mut impl Iterator<Item = f32>,
+        betas: &mut impl Iterator<Item = f32>,
+    ) -> anyhow::Result<Self> {
+        let mut alpha = |default: f32| alphas.next().unwrap_or(default);
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "relu" => Self::Relu,
+            "tanh" => Self::Tanh,
+            "sigmoid" => Self::Sigmoid,
+            "affine" => Self::Affine { alpha: alpha(1.0), beta: betas.next().unwrap_or(0.0) },
+            "leakyrelu" => Self::LeakyRelu { alpha: alpha(0.01) },
+            "thresholdedrelu" => Self::ThresholdedRelu { alpha: alpha(1.0) },
+            "scaledtanh" => Self::ScaledTanh { alpha: alpha(1.0), beta: betas.next().unwrap_or(1.0) },
+            "hardsigmoid" => Self::HardSigmoid { alpha: alpha(0.2), beta: betas.next().unwrap_or(0.5) },
+            "elu" => Self::Elu { alpha: alpha(1.0) },
+            "softsign" => Self::Softsign,
+            "softplus" => Self::Softplus,
+            _ => return Err(anyhow::anyhow!("{} '{}': unsupported activation '{}'", node.op_type, node.name, name)),
+        })
+    }
+
+    #[inline]
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            Self::Relu => x.max(0.0),
+            Self::Tanh => x.tanh(),
+            Self::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Self::Affine { alpha, beta } => alpha * x + beta,
+            Self::LeakyRelu { alpha } => if x >= 0.0 { x } else { alpha * x },
+            Self::ThresholdedRelu { alpha } => if x > alpha { x } else { 0.0 },
+            Self::ScaledTanh { alpha, beta } => alpha * (beta * x).tanh(),
+            Self::HardSigmoid { alpha, beta } => (alpha * x + beta).clamp(0.0, 1.0),
+            Self::Elu { alpha } => if x >= 0.0 { x } else { alpha * (x.exp() - 1.0) },
+            Self::Softsign => x / (1.0 + x.abs()),
+            Self::Softplus => x.exp().ln_1p(),
+        }
+    }
+}
+
+/// Shapes, attributes and layout-normalized inputs shared by the recurrent operators.
+struct Recurrence {
+    seq_len: usize,
+    batch: usize,
+    input_size: usize,
+    hidden: usize,
+    directions: usize,
+    /// Whether direction 0 walks the sequence backwards (`direction = "reverse"`).
+    reverse: bool,
+    /// `layout = 1`: batch-major `X`, `Y` and states.
+    batch_major: bool,
+    /// `X` as `[seq_len, batch, input_size]`.
+    x: Vec<f32>,
+    lengths: Vec<usize>,
+    clip: Option<f32>,
+    /// Activation functions, `defaults.len()` per direction.
+    functions: Vec<Function>,
+}
+
+impl Recurrence {
+    /// Reads the common inputs of a node whose weights stack `gates` blocks of
+    /// `hidden_size` rows, with the given default activation functions.
+    fn new(node: &NodeProto, inputs: &[&Tensor], gates: usize, defaults: &[&str]) -> anyhow::Result<Self> {
+        let err = |message: String| anyhow::anyhow!("{} '{}': {}", node.op_type, node.name, message);
+        if inputs.len() < 3 {
+            return Err(err(format!("expected at least 3 inputs, got {}", inputs.len())));
+        }
+        let (x, w, r) = (inputs[0], inputs[1], inputs[2]);
+        let batch_major = attributes::get_int(node, "layout", 0) != 0;
+        if x.shape().len() != 3 {
+            return Err(err(format!("X must have rank 3, got {:?}", x.shape())));
+        }
+        let x = if batch_major { x.permute(&[1, 0, 2])? } else { x.clone() };
+        let (seq_len, batch, input_size) = (x.shape()[0], x.shape()[1], x.shape()[2]);
+
+        let (directions, reverse) = match attributes::get_string(node, "direction").as_str() {
+            "" | "forward" => (1, false),
+            "reverse" => (1, true),
+            "bidirectional" => (2, false),
+            other => return Err(err(format!("unknown direction '{}'", other))),
+        };
+        let hidden = match attributes::get_int(node, "hidden_size", 0) {
+            0 => r.shape().last().copied().unwrap_or(0),
+            h => h as usize,
+        };
+        if w.shape() != [directions, gates * hidden, input_size] || r.shape() != [directions, gates * hidden, hidden] {
+            return Err(err(format!(
+                "W {:?} and R {:?} do not match {} direction(s), {} gates of {} units and {} inputs",
+                w.shape(), r.shape(), directions, gates, hidden, input_size
+            )));
+        }
+        if let Some(b) = optional_input(inputs, 3).filter(|b| b.shape() != [directions, 2 * gates * hidden]) {
+            return Err(err(format!("B has shape {:?}, expected [{}, {}]", b.shape(), directions, 2 * gates * hidden)));
+        }
+
+        let lengths = match optional_input(inputs, 4) {
+            Some(lens) => lens.values().iter().map(|&l| l as usize).collect(),
+            None => vec![seq_len; batch],
+        };
+        if lengths.len() != batch || lengths.iter().any(|&l| l > seq_len) {
+            return Err(err(format!("sequence_lens {:?} do not fit a batch of {} sequences of {} steps", lengths, batch, seq_len)));
+        }
+
+        let names = attributes::get_attr(node, "activations")
+            .map(|a| a.strings.iter().map(|s| String::from_utf8_lossy(s).to_string()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let names: Vec<&str> = match names.len() {
+            0 => defaults.iter().copied().cycle().take(defaults.len() * directions).collect(),
+            // One direction's worth given for both directions
+            n if n == defaults.len() => names.iter().map(String::as_str).cycle().take(n * directions).collect(),
+            n if n == defaults.len() * directions => names.iter().map(String::as_str).collect(),
+            n => return Err(err(format!("expected {} activations per direction, got {}", defaults.len(), n))),
+        };
+        let floats = |name: &str| attributes::get_attr(node, name).map(|a| a.floats.clone()).unwrap_or_default();
+        let (alphas, betas) = (floats("activation_alpha"), floats("activation_beta"));
+        let (mut alphas, mut betas) = (alphas.into_iter(), betas.into_iter());
+        let functions = names.iter()
+            .map(|name| Function::parse(node, name, &mut alphas, &mut betas))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let clip = attributes::get_attr(node, "clip").map(|a| a.f);
+        if let Some(c) = clip.filter(|c| *c < 0.0 || c.is_nan()) {
+            return Err(err(format!("clip must be a non-negative threshold, got {}", c)));
+        }
+
+        Ok(Self {
+            seq_len,
+            batch,
+            input_size,
+            hidden,
+            directions,
+            reverse,
+            batch_major,
+            x: x.to_vec(),
+            lengths,
+            clip,
+            functions,
+        })
+    }
+
+    /// Activation functions of direction `d`.
+    fn functions(&self, d: usize) -> &[Function] {
+        let per_direction = self.functions.len() / self.directions;
+        &self.functions[d * per_direction..(d + 1) * per_direction]
+    }
+
+    /// Clips a gate input to `[-clip, clip]` when the `clip` attribute is set.
+    #[inline]
+    fn clip(&self, x: f32) -> f32 {
+        match self.clip {
+            Some(c) => x.clamp(-c, c),
+            None => x,
+        }
+    }
+
+    /// Index in `X` of step `t` of sequence `b` in direction `d`, or `None` past its length.
+    fn time(&self, d: usize, b: usize, t: usize) -> Option<usize> {
+        let len = self.lengths[b];
+        if t >= len {
+            return None;
+        }
+        Some(if d == 1 || self.reverse { len - 1 - t } else { t })
+    }
+
+    /// Initial state `[directions, batch, hidden]` from `initial_h`/`initial_c`, zeros when absent.
+    fn initial_state(&self, node: &NodeProto, state: Option<&Tensor>) -> anyhow::Result<Vec<f32>> {
+        let Some(state) = state else { return Ok(vec![0.0; self.directions * self.batch * self.hidden]) };
+        let state = if self.batch_major { state.permute(&[1, 0, 2])? } else { state.clone() };
+        if state.shape() != [self.directions, self.batch, self.hidden] {
+            return Err(anyhow::anyhow!(
+                "{} '{}': initial state has shape {:?}, expected {:?}",
+                node.op_type, node.name, state.shape(), [self.directions, self.batch, self.hidden]
+            ));
+        }
+        Ok(state.to_vec())
+    }
+
+    /// `X·W[d]ᵀ + Wb[d]`, plus `Rb[d]` for its first `recurrent_bias_gates` gates,
+    /// as `[seq_len * batch, gates * hidden]`.
+    fn input_projection(&self, ctx: &OpContext, inputs: &[&Tensor], d: usize, recurrent_bias_gates: usize) -> Vec<f32> {
+        let width = inputs[1].shape()[1];
+        let w = transposed(inputs[1], d, 0, width);
+        let mut projection = vec![0.0; self.seq_len * self.batch * width];
+        MatMul::gemm(ctx, &self.x, &w, &mut projection, self.seq_len * self.batch, self.input_size, width);
+        if let Some(b) = optional_input(inputs, 3) {
+            let b = &b.values()[d * 2 * width..(d + 1) * 2 * width];
+            let bias: Vec<f32> = (0..width)
+                .map(|j| b[j] + if j < recurrent_bias_gates * self.hidden { b[width + j] } else { 0.0 })
+                .collect();
+            for row in projection.chunks_mut(width) {
+                for (v, bias) in row.iter_mut().zip(&bias) {
+                    *v += bias;
+                }
+            }
+        }
+        projection
+    }
+
+    /// `Y` and the final states, in the layout of the node, with the hidden
+    /// and cell states truncated to the outputs the node names.
+    fn outputs(&self, node: &NodeProto, y: Vec<f32>, states: Vec<Vec<f32>>) -> anyhow::Result<Vec<Tensor>> {
+        let (s, d, b, h) = (self.seq_len, self.directions, self.batch, self.hidden);
+        let mut y = Tensor::new(y, vec![s, d, b, h]);
+        if self.batch_major {
+            y = y.permute(&[2, 0, 1, 3])?.to_contiguous();
+        }
+        let mut outputs = vec![y];
+        for state in states {
+            let mut state = Tensor::new(state, vec![d, b, h]);
+            if self.batch_major {
+                state = state.permute(&[1, 0, 2])?.to_contiguous();
+            }
+            outputs.push(state);
+        }
+        outputs.truncate(node.output.len().max(1));
+        Ok(outputs)
+    }
+}
+
+/// Rows `start..start + rows` of `W[d]` (or `R[d]`), of shape `[directions, _, cols]`,
+/// transposed into a row-major `[cols, rows]` matrix.
+fn transposed(w: &Tensor, d: usize, start: usize, rows: usize) -> Vec<f32> {
+    let (all_rows, cols) = (w.shape()[1], w.shape()[2]);
+    let values = w.values();
+    let block = &values[(d * all_rows + start) * cols..(d * all_rows + start + rows) * cols];
+    let mut out = vec![0.0; rows * cols];
+    for r in 0..rows {
+        for c in 0..cols {
+            out[c * rows + r] = block[r * cols + c];
+        }
+    }
+    out
+}
+
+/// `h·rt` for the `[batch, hidden]` state `h` and a transposed weight block.
+fn project(ctx: &OpContext, h: &[f32], rt: &[f32], batch: usize, hidden: usize) -> Vec<f32> {
+    let width = rt.len() / hidden.max(1);
+    let mut out = vec![0.0; batch * width];
+    MatMul::gemm(ctx, h, rt, &mut out, batch, hidden, width);
+    out
+}
+
+impl Operator for Lstm {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.swap_remove(0))
+    }
+
+    /// Returns `Y`, `Y_h` and `Y_c`. Gates are stacked as input, output,
+    /// forget, cell (`iofc`) and peepholes as input, output, forget.
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let rec = Recurrence::new(node, inputs, 4, &["Sigmoid", "Tanh", "Tanh"])?;
+        let (batch, h) = (rec.batch, rec.hidden);
+        let peepholes = optional_input(inputs, 7);
+        if let Some(p) = peepholes.filter(|p| p.shape() != [rec.directions, 3 * h]) {
+            return Err(anyhow::anyhow!("LSTM '{}': P has shape {:?}, expected [{}, {}]", node.name, p.shape(), rec.directions, 3 * h));
+        }
+        let peepholes = peepholes.map(|p| p.to_vec()).unwrap_or_else(|| vec![0.0; rec.directions * 3 * h]);
+        let input_forget = attributes::get_int(node, "input_forget", 0) != 0;
+
+        let mut y = vec![0.0; rec.seq_len * rec.directions * batch * h];
+        let mut y_h = rec.initial_state(node, optional_input(inputs, 5))?;
+        let mut y_c = rec.initial_state(node, optional_input(inputs, 6))?;
+        for d in 0..rec.directions {
+            let xw = rec.input_projection(ctx, inputs, d, 4);
+            let rt = transposed(inputs[2], d, 0, 4 * h);
+            let [f, g, act_h] = rec.functions(d) else { unreachable!("three functions per direction") };
+            let (f, g, act_h) = (*f, *g, *act_h);
+            let p = &peepholes[d * 3 * h..(d + 1) * 3 * h];
+            let (p_i, p_o, p_f) = (&p[..h], &p[h..2 * h], &p[2 * h..]);
+            let state = d * batch * h..(d + 1) * batch * h;
+
+            for t in 0..rec.seq_len {
+                let hr = project(ctx, &y_h[state.clone()], &rt, batch, h);
+                for b in 0..batch {
+                    let Some(time) = rec.time(d, b, t) else { continue };
+                    let gates = &xw[(time * batch + b) * 4 * h..(time * batch + b + 1) * 4 * h];
+                    let hr = &hr[b * 4 * h..(b + 1) * 4 * h];
+                    let offset = state.start + b * h;
+                    for j in 0..h {
+                        let pre = |gate: usize| gates[gate * h + j] + hr[gate * h + j];
+                        let c_prev = y_c[offset + j];
+                        let i = f.apply(rec.clip(pre(0) + p_i[j] * c_prev));
+                        let forget = if input_forget { 1.0 - i } else { f.apply(rec.clip(pre(2) + p_f[j] * c_prev)) };
+                        let candidate = g.apply(rec.clip(pre(3)));
+                        let c = forget * c_prev + i * candidate;
+                        let o = f.apply(rec.clip(pre(1) + p_o[j] * c));
+                        let hidden = o * act_h.apply(c);
+                        y_c[offset + j] = c;
+                        y_h[offset + j] = hidden;
+                        y[((time * rec.directions + d) * batch + b) * h + j] = hidden;
+                    }
+                }
+            }
+        }
+        rec.outputs(node, y, vec![y_h, y_c])
+    }
+}
+
+impl Operator for Gru {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.swap_remove(0))
+    }
+
+    /// Returns `Y` and `Y_h`. Gates are stacked as update, reset, hidden (`zrh`).
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let rec = Recurrence::new(node, inputs, 3, &["Sigmoid", "Tanh"])?;
+        let (batch, h) = (rec.batch, rec.hidden);
+        let linear_before_reset = attributes::get_int(node, "linear_before_reset", 0) != 0;
+
+        let mut y = vec![0.0; rec.seq_len * rec.directions * batch * h];
+        let mut y_h = rec.initial_state(node, optional_input(inputs, 5))?;
+        for d in 0..rec.directions {
+            // The recurrent bias of the hidden gate is applied with the reset gate
+            let xw = rec.input_projection(ctx, inputs, d, 2);
+            let rb_h: Vec<f32> = match optional_input(inputs, 3) {
+                Some(b) => b.values()[(2 * d + 1) * 3 * h + 2 * h..(2 * d + 2) * 3 * h].to_vec(),
+                None => vec![0.0; h],
+            };
+            let rt_zr = transposed(inputs[2], d, 0, 2 * h);
+            let rt_h = transposed(inputs[2], d, 2 * h, h);
+            let [f, g] = rec.functions(d) else { unreachable!("two functions per direction") };
+            let (f, g) = (*f, *g);
+            let state = d * batch * h..(d + 1) * batch * h;
+
+            for t in 0..rec.seq_len {
+                let h_prev = y_h[state.clone()].to_vec();
+                let hr_zr = project(ctx, &h_prev, &rt_zr, batch, h);
+                let mut z = vec![0.0; batch * h];
+                let mut r = vec![0.0; batch * h];
+                for b in 0..batch {
+                    let Some(time) = rec.time(d, b, t) else { continue };
+                    let gates = &xw[(time * batch + b) * 3 * h..];
+                    for j in 0..h {
+                        z[b * h + j] = f.apply(rec.clip(gates[j] + hr_zr[b * 2 * h + j]));
+                        r[b * h + j] = f.apply(rec.clip(gates[h + j] + hr_zr[b * 2 * h + h + j]));
+                    }
+                }
+                // Either r ⊙ (h·Rhᵀ + Rbh), or (r ⊙ h)·Rhᵀ + Rbh
+                let hr_h = if linear_before_reset {
+                    let mut hr_h = project(ctx, &h_prev, &rt_h, batch, h);
+                    for (i, v) in hr_h.iter_mut().enumerate() {
+                        *v = r[i] * (*v + rb_h[i % h]);
+                    }
+                    hr_h
+                } else {
+                    let reset: Vec<f32> = h_prev.iter().zip(&r).map(|(h, r)| h * r).collect();
+                    let mut hr_h = project(ctx, &reset, &rt_h, batch, h);
+                    for (i, v) in hr_h.iter_mut().enumerate() {
+                        *v += rb_h[i % h];
+                    }
+                    hr_h
+                };
+                for b in 0..batch {
+                    let Some(time) = rec.time(d, b, t) else { continue };
+                    let gates = &xw[(time * batch + b) * 3 * h..];
+                    for j in 0..h {
+                        let candidate = g.apply(rec.clip(gates[2 * h + j] + hr_h[b * h + j]));
+                        let z = z[b * h + j];
+                        let hidden = (1.0 - z) * candidate + z * h_prev[b * h + j];
+                        y_h[state.start + b * h + j] = hidden;
+                        y[((time * rec.directions + d) * batch + b) * h + j] = hidden;
+                    }
+                }
+            }
+        }
+        rec.outputs(node, y, vec![y_h])
+    }
+}
+
+impl Operator for Rnn {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(self.run_outputs(inputs, node, ctx)?.swap_remove(0))
+    }
+
+    /// Returns `Y` and `Y_h`.
+    fn run_outputs(&self, inputs: &[&Tensor], node: &NodeProto, ctx: &OpContext) -> anyhow::Result<Vec<Tensor>> {
+        let rec = Recurrence::new(node, inputs, 1, &["Tanh"])?;
+        let (batch, h) = (rec.batch, rec.hidden);
+
+        let mut y = vec![0.0; rec.seq_len * rec.directions * batch * h];
+        let mut y_h = rec.initial_state(node, optional_input(inputs, 5))?;
+        for d in 0..rec.directions {
+            let xw = rec.input_projection(ctx, inputs, d, 1);
+            let rt = transposed(inputs[2], d, 0, h);
+            let f = rec.functions(d)[0];
+            let state = d * batch * h..(d + 1) * batch * h;
+
+            for t in 0..rec.seq_len {
+                let hr = project(ctx, &y_h[state.clone()], &rt, batch, h);
+                for b in 0..batch {
+                    let Some(time) = rec.time(d, b, t) else { continue };
+                    for j in 0..h {
+                        let hidden = f.apply(rec.clip(xw[(time * batch + b) * h + j] + hr[b * h + j]));
+                        y_h[state.start + b * h + j] = hidden;
+                        y[((time * rec.directions + d) * batch + b) * h + j] = hidden;
+                    }
+                }
+            }
+        }
+        rec.outputs(node, y, vec![y_h])
+    }
+}
@@ -0,0 +1,189 @@
+//! Shape manipulation operators: Shape, Reshape, Flatten, Squeeze, Unsqueeze.
+//!
+//! Apart from Shape, these only change the dimensions: the output shares the
+//! input buffer when the input is contiguous, and is a copy otherwise.
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::attributes;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Shape;
+pub struct Reshape;
+pub struct Flatten;
+pub struct Squeeze;
+pub struct Unsqueeze;
+
+/// Maps a possibly negative `axis` into `0..rank`.
+pub(crate) fn normalize_axis(node: &NodeProto, axis: i64, rank: usize) -> anyhow::Result<usize> {
+    let normalized = if axis < 0 { axis + rank as i64 } else { axis };
+    if normalized < 0 || normalized >= rank as i64 {
+        return Err(anyhow::anyhow!("{}: axis {} out of range for rank {}", node.op_type, axis, rank));
+    }
+    Ok(normalized as usize)
+}
+
+/// Output of a Shape node applied to a tensor of shape `dims`, honoring `start`/`end` (opset 15).
+pub(crate) fn shape_of(node: &NodeProto, dims: &[usize]) -> Tensor {
+    let rank = dims.len() as i64;
+    let clamp = |v: i64| (if v < 0 { v + rank } else { v }).clamp(0, rank) as usize;
+    let start = clamp(attributes::get_int(node, "start", 0));
+    let end = clamp(attributes::get_int(node, "end", rank));
+    let values: Vec<f32> = dims[start..end.max(start)].iter().map(|&d| d as f32).collect();
+    let len = values.len();
+    Tensor::new(values, vec![len])
+}
+
+/// Reads the axes of Squeeze/Unsqueeze from the second input (opset >= 13) or the attribute.
+fn axes(inputs: &[&Tensor], node: &NodeProto) -> Vec<i64> {
+    match inputs.get(1) {
+        Some(t) => t.values().iter().map(|&v| v as i64).collect(),
+        None => attributes::get_ints(node, "axes"),
+    }
+}
+
+impl Operator for Shape {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(shape_of(node, inputs[0].shape()))
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for Reshape {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let x = inputs[0];
+        let requested = inputs.get(1)
+            .ok_or_else(|| anyhow::anyhow!("Reshape: missing shape input"))?
+            .values();
+        let allow_zero = attributes::get_int(node, "allowzero", 0) != 0;
+
+        let mut shape = Vec::with_capacity(requested.len());
+        let mut inferred = None;
+        for (i, &v) in requested.iter().enumerate() {
+            let dim = v as i64;
+            match dim {
+                -1 => {
+                    if inferred.replace(i).is_some() {
+                        return Err(anyhow::anyhow!("Reshape: more than one -1 in shape {:?}", requested));
+                    }
+                    shape.push(1);
+                }
+                // 0 copies the input dimension unless `allowzero` is set
+                0 if !allow_zero => {
+                    let d = x.shape().get(i)
+                        .ok_or_else(|| anyhow::anyhow!("Reshape: dimension {} copied from input of rank {}", i, x.shape().len()))?;
+                    shape.push(*d);
+                }
+                d if d < 0 => return Err(anyhow::anyhow!("Reshape: invalid dimension {}", d)),
+                d => shape.push(d as usize),
+            }
+        }
+
+        let len = x.len();
+        if let Some(i) = inferred {
+            let known: usize = shape.iter().product();
+            if known == 0 || !len.is_multiple_of(known) {
+                return Err(anyhow::anyhow!("Reshape: cannot reshape {:?} into {:?}", x.shape(), requested));
+            }
+            shape[i] = len / known;
+        }
+        if shape.iter().product::<usize>() != len {
+            return Err(anyhow::anyhow!("Reshape: cannot reshape {:?} into {:?}", x.shape(), shape));
+        }
+        x.reshape(shape)
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for Flatten {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let x = inputs[0];
+        let rank = x.shape().len();
+        let axis = attributes::get_int(node, "axis", 1);
+        // axis == rank is allowed and flattens everything into the first dimension
+        let axis = if axis == rank as i64 { rank } else { normalize_axis(node, axis, rank)? };
+        let outer: usize = x.shape()[..axis].iter().product();
+        let inner: usize = x.shape()[axis..].iter().product();
+        x.reshape(vec![outer, inner])
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for Squeeze {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let x = inputs[0];
+        let rank = x.shape().len();
+        let axes = axes(inputs, node)
+            .into_iter()
+            .map(|a| normalize_axis(node, a, rank))
+            .collect::<anyhow::Result<Vec<usize>>>()?;
+        for &a in &axes {
+            if x.shape()[a] != 1 {
+                return Err(anyhow::anyhow!("Squeeze: dimension {} of {:?} is not 1", a, x.shape()));
+            }
+        }
+        let shape = x.shape().iter().enumerate()
+            .filter(|&(i, &d)| if axes.is_empty() { d != 1 } else { !axes.contains(&i) })
+            .map(|(_, &d)| d)
+            .collect();
+        x.reshape(shape)
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for Unsqueeze {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let x = inputs[0];
+        let raw = axes(inputs, node);
+        let rank = x.shape().len() + raw.len();
+        let mut axes = raw
+            .into_iter()
+            .map(|a| normalize_axis(node, a, rank))
+            .collect::<anyhow::Result<Vec<usize>>>()?;
+        axes.sort_unstable();
+        if axes.windows(2).any(|w| w[0] == w[1]) {
+            return Err(anyhow::anyhow!("Unsqueeze: duplicate axes {:?}", axes));
+        }
+        let mut dims = x.shape().iter();
+        let shape = (0..rank)
+            .map(|i| if axes.contains(&i) { 1 } else { *dims.next().unwrap() })
+            .collect();
+        x.reshape(shape)
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,75 @@
+//! Slice operator implementation
+//!
+//! The output is a strided view of the input: no data is moved.
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::attributes;
+use crate::ops::shape::normalize_axis;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Slice;
+
+/// Reads `starts`, `ends`, `axes` and `steps` from the inputs (opset >= 10) or the attributes.
+fn params(inputs: &[&Tensor], node: &NodeProto) -> [Vec<i64>; 4] {
+    let read = |t: &Tensor| t.values().iter().map(|&v| v as i64).collect();
+    if inputs.len() > 1 {
+        let [starts, ends, axes, steps] = [1, 2, 3, 4].map(|i| inputs.get(i).map(|t| read(t)).unwrap_or_default());
+        [starts, ends, axes, steps]
+    } else {
+        let [starts, ends, axes] = ["starts", "ends", "axes"].map(|name| attributes::get_ints(node, name));
+        [starts, ends, axes, Vec::new()]
+    }
+}
+
+impl Operator for Slice {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let x = inputs[0];
+        let rank = x.shape().len();
+        let [starts, ends, axes, steps] = params(inputs, node);
+        if ends.len() != starts.len()
+            || (!axes.is_empty() && axes.len() != starts.len())
+            || (!steps.is_empty() && steps.len() != starts.len())
+        {
+            return Err(anyhow::anyhow!("Slice: starts, ends, axes and steps must have the same length"));
+        }
+
+        let mut seen = vec![false; rank];
+        let mut out = x.clone();
+        for (i, (&start, &end)) in starts.iter().zip(&ends).enumerate() {
+            let axis = match axes.get(i) {
+                Some(&a) => normalize_axis(node, a, rank)?,
+                None => i,
+            };
+            if axis >= rank || std::mem::replace(&mut seen[axis], true) {
+                return Err(anyhow::anyhow!("Slice: invalid or repeated axis {} for rank {}", axis, rank));
+            }
+            let step = steps.get(i).copied().unwrap_or(1);
+            if step == 0 {
+                return Err(anyhow::anyhow!("Slice: step cannot be 0"));
+            }
+
+            // Negative bounds count from the end, then get clamped to the valid range for the direction
+            let dim = x.shape()[axis] as i64;
+            let wrap = |v: i64| if v < 0 { v.saturating_add(dim) } else { v };
+            let (start, end) = if step > 0 {
+                (wrap(start).clamp(0, dim), wrap(end).clamp(0, dim))
+            } else {
+                (wrap(start).min(dim - 1).max(0), wrap(end).clamp(-1, dim - 1))
+            };
+            let span = if step > 0 { end - start } else { start - end };
+            let len = if span > 0 && dim > 0 { (span + step.abs() - 1) / step.abs() } else { 0 };
+            out = out.slice_axis(axis, start.max(0) as usize, len as usize, step as isize)
+                .map_err(|e| anyhow::anyhow!("Slice: {}", e))?;
+        }
+        Ok(out)
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
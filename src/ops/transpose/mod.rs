@@ -0,0 +1,54 @@
+//! Transpose operator implementation
+//!
+//! The output is a strided view of the input: no data is moved.
+
+use crate::ops::operator::{Operator, OpContext};
+use crate::ops::attributes;
+use crate::tensor::Tensor;
+use crate::onnx::onnx_proto::NodeProto;
+
+pub struct Transpose;
+
+/// Copies `data` into `out` so that the axes are laid out in `perm` order.
+pub(crate) fn permute(data: &[f32], shape: &[usize], perm: &[usize], out: &mut [f32]) {
+    let rank = shape.len();
+    let mut strides = vec![1usize; rank];
+    for i in (0..rank.saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1];
+    }
+    let out_shape: Vec<usize> = perm.iter().map(|&p| shape[p]).collect();
+    let out_strides: Vec<usize> = perm.iter().map(|&p| strides[p]).collect();
+
+    let mut index = vec![0usize; rank];
+    for o in out.iter_mut() {
+        let offset: usize = index.iter().zip(&out_strides).map(|(i, s)| i * s).sum();
+        *o = data[offset];
+        for axis in (0..rank).rev() {
+            index[axis] += 1;
+            if index[axis] < out_shape[axis] {
+                break;
+            }
+            index[axis] = 0;
+        }
+    }
+}
+
+impl Operator for Transpose {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let x = inputs[0];
+        let rank = x.shape().len();
+        let perm: Vec<usize> = match attributes::get_attr(node, "perm") {
+            Some(attr) => attr.ints.iter().map(|&p| p as usize).collect(),
+            None => (0..rank).rev().collect(),
+        };
+        x.permute(&perm).map_err(|e| anyhow::anyhow!("Transpose: {}", e))
+    }
+
+    fn accepts_views(&self) -> bool {
+        true
+    }
+
+    fn accepts_any_element_type(&self) -> bool {
+        true
+    }
+}
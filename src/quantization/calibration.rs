@@ -0,0 +1,203 @@
+//! Activation range collection over a calibration dataset.
+
+use crate::graph::Graph;
+use crate::runtime::{GraphOptimizationLevel, InferenceSession, SessionOptions};
+use crate::tensor::Tensor;
+use std::collections::HashMap;
+
+/// How the range of an activation is chosen from the values seen during calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CalibrationMethod {
+    /// The smallest and largest values seen.
+    MinMax,
+    /// Clips magnitudes above the given percentile (e.g. 99.99) of `|x|`.
+    Percentile(f32),
+    /// Clips magnitudes at the threshold minimizing the KL divergence between
+    /// the `|x|` histogram and its 128-level quantization.
+    Entropy,
+}
+
+/// Bins of the `|x|` histograms used by the percentile and entropy methods.
+const HISTOGRAM_BINS: usize = 2048;
+/// Quantization levels of one sign, against which entropy thresholds are scored.
+const ENTROPY_LEVELS: usize = 128;
+
+/// Histogram of absolute values over `[0, range]`, widened as larger values arrive.
+#[derive(Debug, Clone)]
+struct Histogram {
+    counts: Vec<f64>,
+    range: f32,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self { counts: vec![0.0; HISTOGRAM_BINS], range: 0.0 }
+    }
+
+    fn bin_width(&self) -> f32 {
+        self.range / HISTOGRAM_BINS as f32
+    }
+
+    fn add(&mut self, values: &[f32], max_abs: f32) {
+        if max_abs > self.range {
+            self.widen(max_abs);
+        }
+        if self.range == 0.0 {
+            self.counts[0] += values.len() as f64;
+            return;
+        }
+        let scale = HISTOGRAM_BINS as f32 / self.range;
+        for v in values.iter().filter(|v| v.is_finite()) {
+            self.counts[((v.abs() * scale) as usize).min(HISTOGRAM_BINS - 1)] += 1.0;
+        }
+    }
+
+    /// Moves the counts to the bins of `[0, range]`, by the center of their old bin.
+    fn widen(&mut self, range: f32) {
+        let old_width = self.bin_width();
+        let mut counts = vec![0.0; HISTOGRAM_BINS];
+        for (i, &count) in self.counts.iter().enumerate().filter(|(_, &c)| c > 0.0) {
+            let center = (i as f32 + 0.5) * old_width;
+            counts[((center / range * HISTOGRAM_BINS as f32) as usize).min(HISTOGRAM_BINS - 1)] += count;
+        }
+        self.counts = counts;
+        self.range = range;
+    }
+
+    /// Upper edge of the bin where the cumulative count reaches `percentile` percent.
+    fn percentile(&self, percentile: f32) -> f32 {
+        let total: f64 = self.counts.iter().sum();
+        let target = total * (percentile as f64 / 100.0).clamp(0.0, 1.0);
+        let mut seen = 0.0;
+        for (i, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return (i + 1) as f32 * self.bin_width();
+            }
+        }
+        self.range
+    }
+
+    /// Threshold whose clipped distribution loses the least information when
+    /// quantized to `ENTROPY_LEVELS` levels.
+    fn entropy_threshold(&self) -> f32 {
+        let mut best = (f64::INFINITY, HISTOGRAM_BINS);
+        for end in ENTROPY_LEVELS..=HISTOGRAM_BINS {
+            // Reference distribution: the first `end` bins, outliers folded into the last one
+            let mut p = self.counts[..end].to_vec();
+            p[end - 1] += self.counts[end..].iter().sum::<f64>();
+
+            // Candidate: the same bins merged into ENTROPY_LEVELS levels, each spread
+            // evenly over the bins that were not empty
+            let mut q = vec![0.0; end];
+            for level in 0..ENTROPY_LEVELS {
+                let start = level * end / ENTROPY_LEVELS;
+                let stop = ((level + 1) * end / ENTROPY_LEVELS).max(start + 1);
+                let bins = &self.counts[start..stop];
+                let used = bins.iter().filter(|&&c| c > 0.0).count();
+                if used > 0 {
+                    let share = bins.iter().sum::<f64>() / used as f64;
+                    for (q, _) in q[start..stop].iter_mut().zip(bins).filter(|(_, &c)| c > 0.0) {
+                        *q = share;
+                    }
+                }
+            }
+
+            let divergence = kl_divergence(&p, &q);
+            if divergence < best.0 {
+                best = (divergence, end);
+            }
+        }
+        (best.1 as f32 + 0.5) * self.bin_width()
+    }
+}
+
+/// KL(p || q) of two unnormalized distributions; bins that `q` leaves empty
+/// are smoothed rather than making the divergence infinite.
+fn kl_divergence(p: &[f64], q: &[f64]) -> f64 {
+    let (p_total, q_total) = (p.iter().sum::<f64>(), q.iter().sum::<f64>());
+    if p_total == 0.0 || q_total == 0.0 {
+        return f64::INFINITY;
+    }
+    p.iter().zip(q)
+        .filter(|(&p, _)| p > 0.0)
+        .map(|(&p, &q)| {
+            let (p, q) = (p / p_total, (q / q_total).max(1e-10));
+            p * (p / q).ln()
+        })
+        .sum()
+}
+
+/// Values seen for one activation.
+#[derive(Debug, Clone)]
+struct Statistics {
+    min: f32,
+    max: f32,
+    histogram: Option<Histogram>,
+}
+
+/// Runs calibration batches through a graph and records the range of chosen values.
+///
+/// The graph runs unoptimized, with the calibrated values added to its outputs,
+/// so that the names seen here are those of the graph being quantized.
+pub struct Calibrator {
+    session: InferenceSession,
+    values: Vec<String>,
+    method: CalibrationMethod,
+    statistics: HashMap<String, Statistics>,
+}
+
+impl Calibrator {
+    /// Prepares to record `values`, intermediate values or inputs of `graph`.
+    pub fn new(graph: &Graph, values: &[String], method: CalibrationMethod) -> anyhow::Result<Self> {
+        let mut graph = graph.clone();
+        graph.outputs.extend(values.iter().filter(|v| !graph.outputs.contains(v)).cloned().collect::<Vec<_>>());
+        let options = SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::DisableAll);
+        Ok(Self {
+            session: InferenceSession::with_options(graph, options)?,
+            values: values.to_vec(),
+            method,
+            statistics: HashMap::new(),
+        })
+    }
+
+    /// Runs one batch of graph inputs and updates the statistics.
+    pub fn collect(&mut self, inputs: &[Tensor]) -> anyhow::Result<()> {
+        let outputs = self.session.run(inputs)?;
+        let graph = &self.session.graph;
+        for name in &self.values {
+            let position = graph.outputs.iter().position(|o| o == name).expect("calibrated values are outputs");
+            let values = outputs[position].values();
+            let (min, max) = values.iter().filter(|v| v.is_finite())
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+            if min > max {
+                continue;
+            }
+            let stats = self.statistics.entry(name.clone()).or_insert_with(|| Statistics {
+                min,
+                max,
+                histogram: (self.method != CalibrationMethod::MinMax).then(Histogram::new),
+            });
+            stats.min = stats.min.min(min);
+            stats.max = stats.max.max(max);
+            if let Some(histogram) = &mut stats.histogram {
+                histogram.add(&values, min.abs().max(max.abs()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Range `(min, max)` chosen for every value seen so far.
+    pub fn ranges(&self) -> HashMap<String, (f32, f32)> {
+        self.statistics.iter()
+            .map(|(name, stats)| {
+                let threshold = match (self.method, &stats.histogram) {
+                    (CalibrationMethod::Percentile(p), Some(h)) => h.percentile(p),
+                    (CalibrationMethod::Entropy, Some(h)) => h.entropy_threshold(),
+                    _ => f32::INFINITY,
+                };
+                (name.clone(), (stats.min.max(-threshold), stats.max.min(threshold)))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,345 @@
+//! Post-training static quantization of fp32 graphs.
+//!
+//! Calibration batches are run through the graph to record the range of the
+//! activations read and produced by the quantized operators. QuantizeLinear /
+//! DequantizeLinear pairs are then inserted on those activations and the
+//! weights are stored as int8, producing a QDQ model that other runtimes can
+//! load and that `QdqFusion` runs with integer kernels:
+//!
+//! ```no_run
+//! # use neuroxyde::{graph::Graph, loader::ModelLoader, tensor::Tensor};
+//! # use neuroxyde::quantization::{quantize_static, CalibrationMethod, QuantizationOptions};
+//! # fn main() -> anyhow::Result<()> {
+//! let graph = Graph::from_model(&ModelLoader::load_from_file("model.onnx")?)?;
+//! let samples: Vec<Vec<Tensor>> = vec![vec![Tensor::load("sample.npy")?]];
+//! let options = QuantizationOptions::new().with_calibration(CalibrationMethod::Percentile(99.99));
+//! let quantized = quantize_static(&graph, samples, &options)?;
+//! ModelLoader { model: quantized.to_model()? }.save_to_file("model.int8.onnx")?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod calibration;
+
+pub use calibration::{CalibrationMethod, Calibrator};
+
+use crate::graph::{AttributeValue, Graph};
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use crate::onnx::onnx_proto::{NodeProto, TensorProto};
+use crate::ops::attributes;
+use crate::tensor::{ElementType, Tensor};
+use std::collections::{HashMap, HashSet};
+
+/// Settings of `quantize_static`.
+#[derive(Debug, Clone)]
+pub struct QuantizationOptions {
+    pub calibration: CalibrationMethod,
+    /// Element type of the quantized activations (`U8` or `I8`).
+    pub activation_type: ElementType,
+    /// Quantize weights per output channel instead of per tensor (opset 13 and later).
+    pub per_channel: bool,
+    /// Operators whose inputs, weights and first output are quantized.
+    pub op_types: Vec<String>,
+}
+
+impl Default for QuantizationOptions {
+    fn default() -> Self {
+        Self {
+            calibration: CalibrationMethod::MinMax,
+            activation_type: ElementType::U8,
+            per_channel: false,
+            op_types: vec!["Conv".to_string(), "MatMul".to_string()],
+        }
+    }
+}
+
+impl QuantizationOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_calibration(mut self, method: CalibrationMethod) -> Self {
+        self.calibration = method;
+        self
+    }
+
+    pub fn with_activation_type(mut self, element_type: ElementType) -> Self {
+        self.activation_type = element_type;
+        self
+    }
+
+    pub fn with_per_channel(mut self, enable: bool) -> Self {
+        self.per_channel = enable;
+        self
+    }
+
+    pub fn with_op_types(mut self, op_types: &[&str]) -> Self {
+        self.op_types = op_types.iter().map(|s| s.to_string()).collect();
+        self
+    }
+}
+
+/// Calibrates `graph` on `calibration_data` (one list of graph inputs per batch)
+/// and returns it with QuantizeLinear/DequantizeLinear pairs inserted.
+pub fn quantize_static<I>(graph: &Graph, calibration_data: I, options: &QuantizationOptions) -> anyhow::Result<Graph>
+where
+    I: IntoIterator<Item = Vec<Tensor>>,
+{
+    let values = calibration_values(graph, options);
+    let mut calibrator = Calibrator::new(graph, &values, options.calibration)?;
+    let mut batches = 0;
+    for inputs in calibration_data {
+        calibrator.collect(&inputs)?;
+        batches += 1;
+    }
+    if batches == 0 {
+        return Err(anyhow::anyhow!("Quantization needs at least one calibration batch"));
+    }
+    tracing::debug!(batches, values = values.len(), "calibration done");
+    quantize_with_ranges(graph, &calibrator.ranges(), options)
+}
+
+/// Whether `node` is one of the operators to quantize.
+fn is_target(node: &NodeProto, options: &QuantizationOptions) -> bool {
+    (node.domain.is_empty() || node.domain == "ai.onnx") && options.op_types.contains(&node.op_type)
+}
+
+/// Positions of the inputs of `node` holding weights, with the axis of their output channels.
+fn weight_inputs(node: &NodeProto, graph: &Graph) -> Vec<(usize, usize)> {
+    let weight = |i: usize| node.input.get(i).and_then(|name| graph.initializers.get(name));
+    match node.op_type.as_str() {
+        "Conv" => weight(1).map(|_| (1, 0)).into_iter().collect(),
+        "MatMul" => weight(1).filter(|w| w.shape().len() >= 2).map(|w| (1, w.shape().len() - 1)).into_iter().collect(),
+        "Gemm" => {
+            let axis = if attributes::get_int(node, "transB", 0) != 0 { 0 } else { 1 };
+            weight(1).filter(|w| w.shape().len() == 2).map(|_| (1, axis)).into_iter().collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Activations quantized by `quantize_with_ranges`: the computed inputs of the
+/// quantized operators and their first output, in graph order.
+pub fn calibration_values(graph: &Graph, options: &QuantizationOptions) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut values = Vec::new();
+    for node in graph.nodes.iter().filter(|n| is_target(n, options)) {
+        let weights: Vec<usize> = weight_inputs(node, graph).into_iter().map(|(i, _)| i).collect();
+        // A Conv bias becomes int32 rather than a quantized activation
+        let bias = (node.op_type == "Conv").then_some(2);
+        let inputs = node.input.iter().enumerate()
+            .filter(|(i, name)| !name.is_empty() && !weights.contains(i) && Some(*i) != bias)
+            .filter(|(_, name)| !graph.initializers.contains_key(*name))
+            .map(|(_, name)| name);
+        for name in inputs.chain(node.output.first()) {
+            if seen.insert(name.clone()) {
+                values.push(name.clone());
+            }
+        }
+    }
+    values
+}
+
+/// Scale and zero point mapping `[min, max]` (widened to include 0) onto `element_type`.
+fn activation_params(min: f32, max: f32, element_type: ElementType) -> (f32, i32) {
+    let (min, max) = (min.min(0.0), max.max(0.0));
+    let (qmin, qmax) = match element_type {
+        ElementType::I8 => (-128.0, 127.0),
+        _ => (0.0, 255.0),
+    };
+    if max == min {
+        return (1.0, qmin as i32);
+    }
+    let scale = (max - min) / (qmax - qmin);
+    (scale, (qmin - min / scale).round().clamp(qmin, qmax) as i32)
+}
+
+/// Symmetric int8 weights and their scales, per slice along `axis` or per tensor.
+fn quantize_weight(w: &Tensor, axis: Option<usize>) -> (Tensor, Tensor) {
+    let values = w.values();
+    let (channels, inner) = match axis {
+        Some(axis) => (w.shape()[axis], w.shape()[axis + 1..].iter().product::<usize>()),
+        None => (1, values.len().max(1)),
+    };
+    let channel = |i: usize| (i / inner) % channels;
+    let mut max_abs = vec![0.0f32; channels];
+    for (i, v) in values.iter().enumerate() {
+        max_abs[channel(i)] = max_abs[channel(i)].max(v.abs());
+    }
+    let scales: Vec<f32> = max_abs.iter().map(|&m| if m > 0.0 { m / 127.0 } else { 1.0 }).collect();
+    let quantized = values.iter().enumerate()
+        .map(|(i, &v)| (v / scales[channel(i)]).round_ties_even().clamp(-127.0, 127.0) as i8)
+        .collect();
+    let scale_shape = if axis.is_some() { vec![channels] } else { vec![] };
+    (Tensor::from_i8(quantized, w.shape().to_vec()), Tensor::new(scales, scale_shape))
+}
+
+/// Graph being rewritten, with the names already taken.
+struct Rewriter {
+    graph: Graph,
+    taken: HashSet<String>,
+}
+
+impl Rewriter {
+    /// Returns `base`, suffixed if needed so that it names nothing else in the graph.
+    fn fresh(&mut self, base: &str) -> String {
+        let mut name = base.to_string();
+        let mut suffix = 1;
+        while self.taken.contains(&name) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        self.taken.insert(name.clone());
+        name
+    }
+
+    /// Adds an initializer, recording its element type in the graph proto.
+    fn initializer(&mut self, base: &str, tensor: Tensor, data_type: DataType) -> String {
+        let name = self.fresh(base);
+        self.graph.proto.initializer.push(TensorProto {
+            name: name.clone(),
+            dims: tensor.shape().iter().map(|&d| d as i64).collect(),
+            data_type: data_type as i32,
+            ..Default::default()
+        });
+        self.graph.initializers.insert(name.clone(), tensor);
+        name
+    }
+
+    fn node(&mut self, op_type: &str, inputs: Vec<String>, output: &str, axis: Option<usize>) {
+        let name = self.fresh(&format!("{}_{}", output, op_type));
+        self.graph.nodes.push(NodeProto {
+            name,
+            op_type: op_type.to_string(),
+            input: inputs,
+            output: vec![output.to_string()],
+            attribute: axis.map(|a| AttributeValue::from(a as i64).to_proto("axis")).into_iter().collect(),
+            ..Default::default()
+        });
+    }
+
+    /// Inserts QuantizeLinear/DequantizeLinear on `value`. Its consumers then read
+    /// the dequantized value, under the original name when a node produces it.
+    fn quantize_activation(&mut self, value: &str, scale: f32, zero_point: i32, element_type: ElementType) {
+        let scale = self.initializer(&format!("{}_scale", value), Tensor::new(vec![scale], vec![]), DataType::Float);
+        let zero_point = match element_type {
+            ElementType::I8 => self.initializer(&format!("{}_zero_point", value), Tensor::from_i8(vec![zero_point as i8], vec![]), DataType::Int8),
+            _ => self.initializer(&format!("{}_zero_point", value), Tensor::from_u8(vec![zero_point as u8], vec![]), DataType::Uint8),
+        };
+        let quantized = self.fresh(&format!("{}_quantized", value));
+        let producer = self.graph.nodes.iter().position(|n| n.output.iter().any(|o| o == value));
+        let (float, dequantized) = match producer {
+            Some(idx) => {
+                let float = self.fresh(&format!("{}_float", value));
+                for output in self.graph.nodes[idx].output.iter_mut().filter(|o| *o == value) {
+                    *output = float.clone();
+                }
+                (float, value.to_string())
+            }
+            None => {
+                let dequantized = self.fresh(&format!("{}_dequantized", value));
+                for input in self.graph.nodes.iter_mut().flat_map(|n| n.input.iter_mut()).filter(|i| *i == value) {
+                    *input = dequantized.clone();
+                }
+                (value.to_string(), dequantized)
+            }
+        };
+        self.node("QuantizeLinear", vec![float, scale.clone(), zero_point.clone()], &quantized, None);
+        self.node("DequantizeLinear", vec![quantized, scale, zero_point], &dequantized, None);
+    }
+}
+
+/// Inserts QuantizeLinear/DequantizeLinear pairs using activation ranges
+/// already collected (e.g. by a `Calibrator` fed outside `quantize_static`).
+///
+/// Weights of the quantized operators are stored as symmetric int8 tensors and
+/// Conv biases as int32, both read through DequantizeLinear. Activations
+/// missing from `ranges` are left in float.
+pub fn quantize_with_ranges(
+    graph: &Graph,
+    ranges: &HashMap<String, (f32, f32)>,
+    options: &QuantizationOptions,
+) -> anyhow::Result<Graph> {
+    if !options.activation_type.is_integer() {
+        return Err(anyhow::anyhow!("Activations can only be quantized to u8 or i8, not {:?}", options.activation_type));
+    }
+    let opset = graph.model.opset_import.iter()
+        .find(|o| o.domain.is_empty() || o.domain == "ai.onnx")
+        .map_or(crate::graph::DEFAULT_OPSET, |o| o.version);
+    if opset < 10 {
+        return Err(anyhow::anyhow!("QuantizeLinear needs opset 10, the model imports opset {}", opset));
+    }
+    if options.per_channel && opset < 13 {
+        return Err(anyhow::anyhow!("Per-channel quantization needs opset 13, the model imports opset {}", opset));
+    }
+
+    let mut taken: HashSet<String> = graph.initializers.keys().cloned().collect();
+    taken.extend(graph.inputs.iter().cloned());
+    taken.extend(graph.nodes.iter().flat_map(|n| n.input.iter().chain(&n.output).chain(std::iter::once(&n.name))).cloned());
+    let mut rewriter = Rewriter { graph: graph.clone(), taken };
+
+    // Weights (and the biases depending on their scales) first, while node indices still match `graph`
+    let mut activation_scales = HashMap::new();
+    for value in calibration_values(graph, options) {
+        if let Some(&(min, max)) = ranges.get(&value) {
+            activation_scales.insert(value, activation_params(min, max, options.activation_type));
+        }
+    }
+    let mut dequantized_weights: HashMap<(String, usize), (String, Tensor)> = HashMap::new();
+    let mut replaced = HashSet::new();
+    for (idx, node) in graph.nodes.iter().enumerate().filter(|(_, n)| is_target(n, options)) {
+        for (input, axis) in weight_inputs(node, graph) {
+            let name = node.input[input].clone();
+            let axis = options.per_channel.then_some(axis);
+            let key = (name.clone(), axis.unwrap_or(usize::MAX));
+            if !dequantized_weights.contains_key(&key) {
+                let (quantized, scales) = quantize_weight(&graph.initializers[&name], axis);
+                let zeros = Tensor::from_i8(vec![0; scales.len()], scales.shape().to_vec());
+                let q = rewriter.initializer(&format!("{}_quantized", name), quantized, DataType::Int8);
+                let s = rewriter.initializer(&format!("{}_scale", name), scales.clone(), DataType::Float);
+                let z = rewriter.initializer(&format!("{}_zero_point", name), zeros, DataType::Int8);
+                let dequantized = rewriter.fresh(&format!("{}_dequantized", name));
+                rewriter.node("DequantizeLinear", vec![q, s, z], &dequantized, axis);
+                dequantized_weights.insert(key.clone(), (dequantized, scales));
+            }
+            let (dequantized, w_scales) = dequantized_weights[&key].clone();
+            rewriter.graph.nodes[idx].input[input] = dequantized;
+            replaced.insert(name);
+
+            // The bias is added to the int32 accumulators: scale x_scale * w_scale, zero point 0
+            let x_scale = activation_scales.get(&node.input[0]).map(|&(s, _)| s);
+            let bias = node.input.get(2).filter(|b| !b.is_empty()).and_then(|b| graph.initializers.get(b).map(|t| (b, t)));
+            if let (true, Some(x_scale), Some((bias_name, bias))) = (node.op_type == "Conv", x_scale, bias) {
+                let scales: Vec<f32> = w_scales.values().iter().map(|&s| s * x_scale).collect();
+                let values = bias.values();
+                let quantized: Vec<f32> = values.iter().enumerate()
+                    .map(|(c, &b)| (b / scales[if scales.len() == 1 { 0 } else { c }]).round_ties_even())
+                    .collect();
+                let shape = w_scales.shape().to_vec();
+                let q = rewriter.initializer(&format!("{}_quantized", bias_name), Tensor::new(quantized, bias.shape().to_vec()), DataType::Int32);
+                let s = rewriter.initializer(&format!("{}_scale", bias_name), Tensor::new(scales, shape), DataType::Float);
+                let dequantized = rewriter.fresh(&format!("{}_dequantized", bias_name));
+                rewriter.node("DequantizeLinear", vec![q, s], &dequantized, axis.map(|_| 0));
+                rewriter.graph.nodes[idx].input[2] = dequantized;
+                replaced.insert(bias_name.clone());
+            }
+        }
+    }
+
+    for value in calibration_values(graph, options) {
+        if let Some(&(scale, zero_point)) = activation_scales.get(&value) {
+            rewriter.quantize_activation(&value, scale, zero_point, options.activation_type);
+        }
+    }
+
+    // Float weights that nothing reads anymore are dropped
+    let mut graph = rewriter.graph;
+    let read: HashSet<&String> = graph.nodes.iter().flat_map(|n| &n.input).collect();
+    let unused: HashSet<String> = replaced.into_iter()
+        .filter(|name| !read.contains(name) && !graph.inputs.contains(name) && !graph.outputs.contains(name))
+        .collect();
+    graph.initializers.retain(|name, _| !unused.contains(name));
+    graph.proto.initializer.retain(|t| !unused.contains(&t.name));
+    graph.topological_sort()?;
+    Ok(graph)
+}
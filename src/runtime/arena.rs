@@ -0,0 +1,46 @@
+//! Memory arena recycling the buffers of intermediate tensors.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Free lists of f32 buffers, keyed by length.
+///
+/// Intermediate values are handed back once their last consumer has run,
+/// and the next allocation of the same size reuses them instead of going
+/// through the allocator. Buffers are kept across runs, so steady-state
+/// inference performs no large allocations.
+#[derive(Debug, Default)]
+pub struct TensorArena {
+    free: Mutex<HashMap<usize, Vec<Vec<f32>>>>,
+}
+
+impl TensorArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a zero-filled buffer of `len` elements, reusing a released one when possible.
+    pub fn alloc(&self, len: usize) -> Vec<f32> {
+        let recycled = self.free.lock().unwrap().get_mut(&len).and_then(|list| list.pop());
+        match recycled {
+            Some(mut buf) => {
+                buf.fill(0.0);
+                buf
+            }
+            None => vec![0.0; len],
+        }
+    }
+
+    /// Gives a buffer back to the arena.
+    pub fn release(&self, buf: Vec<f32>) {
+        if buf.is_empty() {
+            return;
+        }
+        self.free.lock().unwrap().entry(buf.len()).or_default().push(buf);
+    }
+
+    /// Total number of f32 elements currently cached.
+    pub fn cached_len(&self) -> usize {
+        self.free.lock().unwrap().iter().map(|(len, list)| len * list.len()).sum()
+    }
+}
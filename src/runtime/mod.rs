@@ -1,102 +1,279 @@
 //! Runtime module: executes the graph.
+//!
+//! Sessions report through `tracing`: an `optimize` span at creation, a `run`
+//! span per run and a `node` span per executed node. Verbosity is chosen by
+//! the installed subscriber, which records nothing when there is none.
 
-use crate::graph::Graph;
-use crate::tensor::Tensor;
+pub mod arena;
+pub mod options;
+pub mod profiler;
+pub mod thread_pool;
+mod scheduler;
+
+pub use arena::TensorArena;
+pub use options::{ExecutionMode, GraphOptimizationLevel, SessionOptions};
+pub use profiler::{EventKind, OpSummary, ProfileEvent, ProfileSummary, Profiler};
+pub use thread_pool::ThreadPool;
+
+use crate::graph::{implicit_inputs, Graph};
+use crate::graph::optimizer::{GraphOptimizer, PassContext};
+use crate::tensor::{ElementType, Tensor};
+use crate::kernels::{KernelBackend, Kernels};
+use crate::ops::control_flow::Bodies;
+use crate::ops::operator::{self, OpContext};
+use crate::onnx::onnx_proto::NodeProto;
+use scheduler::ParallelExecutor;
 use crate::ops::registry::OpRegistry;
 use crate::onnx::onnx_proto::{type_proto, tensor_shape_proto};
-use std::collections::HashMap;
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 
 pub struct InferenceSession {
     pub graph: Graph,
     pub registry: OpRegistry,
+    options: SessionOptions,
+    kernels: &'static Kernels,
+    intra_op_pool: ThreadPool,
+    /// Present when the session runs in `ExecutionMode::Parallel`.
+    parallel: Option<ParallelExecutor>,
+    arena: Option<TensorArena>,
+    /// Present when `SessionOptions::enable_profiling` is set.
+    profiler: Option<Profiler>,
+    /// Index of the last node reading each value, so it can be freed right after.
+    last_use: HashMap<String, usize>,
+    /// Subgraphs of the control-flow nodes, decoded once instead of on every run.
+    bodies: Bodies,
 }
 
 impl InferenceSession {
     pub fn new(graph: Graph) -> anyhow::Result<Self> {
-        if graph.inputs.len() != 1 {
-            return Err(anyhow::anyhow!("Expect model to have exactly 1 input, got {}: {:?}", graph.inputs.len(), graph.inputs));
+        Self::with_options(graph, SessionOptions::default())
+    }
+
+    pub fn with_options(graph: Graph, options: SessionOptions) -> anyhow::Result<Self> {
+        Self::with_registry(graph, options, OpRegistry::new())
+    }
+
+    /// Creates a session whose nodes, including those of subgraphs, run on
+    /// the operators of `registry`, e.g. `OpRegistry::new()` extended with
+    /// custom domains. Function calls `registry` has no kernel for are
+    /// replaced by their bodies.
+    pub fn with_registry(mut graph: Graph, options: SessionOptions, registry: OpRegistry) -> anyhow::Result<Self> {
+        let fed = graph.inputs.iter().filter(|name| !graph.initializers.contains_key(*name)).count();
+        if fed == 0 {
+            return Err(anyhow::anyhow!("Expect model to have at least 1 input, got {:?}", graph.inputs));
+        }
+        graph.inline_functions(&registry)?;
+        let kernels = options.resolve_kernel_backend().kernels()?;
+        let intra_op_pool = ThreadPool::new(options.intra_op_num_threads, &options.intra_op_affinity)?;
+        {
+            let _span = tracing::info_span!("optimize", level = ?options.graph_optimization_level, nodes = graph.nodes.len()).entered();
+            graph.topological_sort()?;
+            let ctx = PassContext {
+                registry: &registry,
+                op_ctx: OpContext::new(kernels, &intra_op_pool),
+                free_dimension_overrides: &options.free_dimension_overrides,
+            };
+            GraphOptimizer::new(options.graph_optimization_level).run(&mut graph, &ctx)?;
+        }
+        if let Some(precision) = options.reduced_precision {
+            // Only FLOAT weights: integer data (shapes, indices) must stay exact
+            for init in graph.proto.initializer.iter().filter(|t| t.data_type == DataType::Float as i32) {
+                if let Some(tensor) = graph.initializers.get_mut(&init.name) {
+                    *tensor = tensor.to_element_type(precision);
+                }
+            }
+        }
+        let parallel = match options.execution_mode {
+            ExecutionMode::Sequential => None,
+            ExecutionMode::Parallel => Some(ParallelExecutor::new(&graph, options.inter_op_num_threads)?),
+        };
+
+        let bodies = Bodies::new(&graph.nodes)?;
+        let outputs: HashSet<&String> = graph.outputs.iter().collect();
+        let mut last_use = HashMap::new();
+        for (idx, node) in graph.nodes.iter().enumerate() {
+            for name in node.input.iter().cloned().chain(implicit_inputs(node)).filter(|n| !outputs.contains(n)) {
+                last_use.insert(name, idx);
+            }
         }
+
         Ok(Self {
+            registry,
+            kernels,
+            intra_op_pool,
+            parallel,
+            arena: options.enable_mem_arena.then(TensorArena::new),
+            profiler: options.enable_profiling.then(Profiler::new),
+            last_use,
+            bodies,
+            options,
             graph,
-            registry: OpRegistry::new(),
         })
     }
 
+    /// Options the session was created with.
+    pub fn options(&self) -> &SessionOptions {
+        &self.options
+    }
+
+    /// Kernel backend selected for this session.
+    pub fn kernel_backend(&self) -> KernelBackend {
+        self.kernels.backend
+    }
+
+    /// Number of threads each operator may use.
+    pub fn intra_op_num_threads(&self) -> usize {
+        self.intra_op_pool.num_threads()
+    }
+
+    pub fn execution_mode(&self) -> ExecutionMode {
+        if self.parallel.is_some() { ExecutionMode::Parallel } else { ExecutionMode::Sequential }
+    }
+
+    /// Number of threads running independent nodes concurrently (1 in sequential mode).
+    pub fn inter_op_num_threads(&self) -> usize {
+        self.parallel.as_ref().map_or(1, |p| p.num_threads())
+    }
+
+    /// Events recorded so far, when profiling is enabled.
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Buffers recycled between nodes and runs, when the memory arena is enabled.
+    pub fn arena(&self) -> Option<&TensorArena> {
+        self.arena.as_ref()
+    }
+
     fn validate_inputs(&self, inputs: &[Tensor]) -> anyhow::Result<HashMap<String, Tensor>> {
         let mut values = HashMap::new();
-        for (idx, param) in self.graph.proto.input.iter().enumerate() {
+        let params = self.graph.proto.input.iter()
             // Skip inputs that are initializers (weights)
-            if self.graph.initializers.contains_key(&param.name) {
-                continue;
-            }
-
+            .filter(|param| !self.graph.initializers.contains_key(&param.name));
+        for (idx, param) in params.enumerate() {
             let input_tensor = inputs.get(idx).ok_or_else(|| {
                 anyhow::anyhow!("Missing input tensor at index {}", idx)
             })?;
 
             // Parse expected shape from TypeProto
-            if let Some(type_proto) = &param.r#type {
-                if let Some(type_proto::Value::TensorType(tensor_type)) = &type_proto.value {
-                    if let Some(shape_proto) = &tensor_type.shape {
-                        let expected_shape: Vec<Option<usize>> = shape_proto.dim.iter()
-                            .map(|dim| {
-                                match &dim.value {
-                                    Some(tensor_shape_proto::dimension::Value::DimValue(v)) => {
-                                        Some(*v as usize)
-                                    }
-                                    Some(tensor_shape_proto::dimension::Value::DimParam(_)) => {
-                                        // Symbolic dimension (e.g., "batch"), accept any value
-                                        None
-                                    }
-                                    None => None,
+            if let Some(type_proto::Value::TensorType(tensor_type)) = param.r#type.as_ref().and_then(|t| t.value.as_ref()) {
+                if let Some(shape_proto) = &tensor_type.shape {
+                    let expected_shape: Vec<Option<usize>> = shape_proto.dim.iter()
+                        .map(|dim| {
+                            match &dim.value {
+                                Some(tensor_shape_proto::dimension::Value::DimValue(v)) => {
+                                    Some(*v as usize)
                                 }
-                            })
-                            .collect();
+                                Some(tensor_shape_proto::dimension::Value::DimParam(name)) => {
+                                    // Symbolic dimension (e.g., "batch"): accept any value unless pinned
+                                    self.options.free_dimension_overrides.get(name).copied()
+                                }
+                                None => None,
+                            }
+                        })
+                        .collect();
 
-                        let actual_shape = input_tensor.shape();
+                    let actual_shape = input_tensor.shape();
 
-                        // Check rank
-                        if expected_shape.len() != actual_shape.len() {
-                            return Err(anyhow::anyhow!(
-                                "Input '{}': rank mismatch, expected {} dimensions, got {}",
-                                param.name, expected_shape.len(), actual_shape.len()
-                            ));
-                        }
+                    // Check rank
+                    if expected_shape.len() != actual_shape.len() {
+                        return Err(anyhow::anyhow!(
+                            "Input '{}': rank mismatch, expected {} dimensions, got {}",
+                            param.name, expected_shape.len(), actual_shape.len()
+                        ));
+                    }
 
-                        // Check each dimension
-                        for (i, (expected, actual)) in expected_shape.iter().zip(actual_shape.iter()).enumerate() {
-                            if let Some(exp) = expected {
-                                if *exp != *actual {
-                                    return Err(anyhow::anyhow!(
-                                        "Input '{}': dimension {} mismatch, expected {}, got {}",
-                                        param.name, i, exp, actual
-                                    ));
-                                }
+                    // Check each dimension
+                    for (i, (expected, actual)) in expected_shape.iter().zip(actual_shape.iter()).enumerate() {
+                        if let Some(exp) = expected {
+                            if *exp != *actual {
+                                return Err(anyhow::anyhow!(
+                                    "Input '{}': dimension {} mismatch, expected {}, got {}",
+                                    param.name, i, exp, actual
+                                ));
                             }
                         }
-                        values.insert(param.name.clone(), input_tensor.clone());
                     }
                 }
             }
+            let elem_type = match param.r#type.as_ref().and_then(|t| t.value.as_ref()) {
+                Some(type_proto::Value::TensorType(tensor_type)) => tensor_type.elem_type,
+                _ => DataType::Float as i32,
+            };
+            // Floating-point inputs are converted to the precision the model runs in
+            let precision = match DataType::try_from(elem_type).ok().and_then(ElementType::from_data_type) {
+                Some(ElementType::F32) => self.options.reduced_precision.or(Some(ElementType::F32)),
+                declared => declared,
+            };
+            let tensor = match precision {
+                Some(precision) => input_tensor.to_element_type(precision),
+                None => input_tensor.clone(),
+            };
+            values.insert(param.name.clone(), tensor);
         }
         Ok(values)
     }
 
-    pub fn run(&self, input: &[Tensor]) -> anyhow::Result<Vec<Tensor>> {
-        // 1. Context: Map names to Tensors
-        // This HashMap holds all live values (inputs + intermediate activations)
-        let mut values = self.validate_inputs(&input)?;
+    /// Dispatches a single node to its operator.
+    fn run_node(&self, node: &NodeProto, inputs: &[&Tensor]) -> anyhow::Result<Vec<Tensor>> {
+        let op = self.registry.resolve(node)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported operator: {}", node.op_type))?;
+        let ctx = OpContext {
+            kernels: self.kernels,
+            pool: &self.intra_op_pool,
+            arena: self.arena.as_ref(),
+            registry: Some(&self.registry),
+            bodies: Some(&self.bodies),
+        };
+        // Always created, the installed subscriber decides whether it is recorded
+        let span = tracing::debug_span!(
+            "node",
+            op_type = %node.op_type,
+            name = %node.name,
+            input_shapes = tracing::field::Empty,
+            output_shape = tracing::field::Empty,
+            elapsed_us = tracing::field::Empty,
+        );
+        if span.is_disabled() && self.profiler.is_none() {
+            return operator::invoke(op, inputs, node, &ctx);
+        }
 
-        // 2. Execute nodes
-        // NOTE: We assume by the spec that the graph is a valid DAG, already topologically sorted.
-        // TODO: We might want to resort the graph and raise an error if it's not a valid DAG.
-        for node in &self.graph.nodes {
-            // println!("State: {:?}", values);
-            // Gather inputs for this node
+        let input_shapes: Vec<Vec<usize>> = inputs.iter().map(|t| t.shape().to_vec()).collect();
+        span.record("input_shapes", tracing::field::debug(&input_shapes));
+        let _enter = span.enter();
+        let start = Instant::now();
+        let outputs = operator::invoke(op, inputs, node, &ctx)?;
+        let output_shapes: Vec<&[usize]> = outputs.iter().map(|t| t.shape()).collect();
+        span.record("output_shape", tracing::field::debug(output_shapes));
+        span.record("elapsed_us", start.elapsed().as_micros() as u64);
+        if let Some(profiler) = &self.profiler {
+            let bytes = outputs.iter().map(|t| t.len() * t.element_type().size()).sum();
+            profiler.record(EventKind::Node, &node.op_type, &node.name, input_shapes, start, bytes);
+        }
+        Ok(outputs)
+    }
+
+    /// Drops an intermediate value, handing its buffer back to the arena if enabled.
+    fn release(&self, tensor: Tensor) {
+        // Views and values still shared elsewhere keep their buffer
+        if let (Some(arena), Ok(buf)) = (&self.arena, tensor.into_buffer()) {
+            arena.release(buf);
+        }
+    }
+
+    fn run_sequential(&self, mut values: HashMap<String, Tensor>) -> anyhow::Result<HashMap<String, Tensor>> {
+        // The graph was topologically sorted at session creation.
+        for (idx, node) in self.graph.nodes.iter().enumerate() {
+            // Gather inputs for this node, then the values its subgraphs capture
+            let captured = implicit_inputs(node);
             let mut node_inputs = Vec::new();
-            for input_name in &node.input {
+            for input_name in node.input.iter().chain(&captured) {
                 // First check computed values (activations), then constant weights (initializers)
-                if let Some(t) = values.get(input_name) {
+                if input_name.is_empty() {
+                    node_inputs.push(operator::absent_input());
+                } else if let Some(t) = values.get(input_name) {
                     node_inputs.push(t);
                 } else if let Some(t) = self.graph.initializers.get(input_name) {
                     node_inputs.push(t);
@@ -105,26 +282,60 @@ impl InferenceSession {
                 }
             }
 
-            println!("Running operator: {:#?}", node);
-            // Dispatch to operator using the registry
-            let op = self.registry.get(&node.op_type)
-                .ok_or_else(|| anyhow::anyhow!("Unsupported operator: {}", node.op_type))?;
-            
-            let output = op.run(&node_inputs, node)?;
+            let outputs = self.run_node(node, &node_inputs)?;
 
-            // Store output (assuming single output for these simple ops)
-            if let Some(output_name) = node.output.first() {
-                values.insert(output_name.clone(), output);
+            // Free the values nobody reads anymore
+            for input_name in node.input.iter().chain(&captured) {
+                if self.last_use.get(input_name) == Some(&idx) {
+                    if let Some(t) = values.remove(input_name) {
+                        self.release(t);
+                    }
+                }
+            }
+
+            // Store the outputs; optional ones left unnamed are dropped
+            for (name, output) in node.output.iter().zip(outputs) {
+                if !name.is_empty() {
+                    values.insert(name.clone(), output);
+                }
             }
         }
+        Ok(values)
+    }
+
+    pub fn run(&self, input: &[Tensor]) -> anyhow::Result<Vec<Tensor>> {
+        let _span = tracing::info_span!("run", mode = ?self.execution_mode(), nodes = self.graph.nodes.len()).entered();
+
+        let start = Instant::now();
+
+        // 1. Context: Map names to Tensors
+        // This HashMap holds all live values (inputs + intermediate activations)
+        let values = self.validate_inputs(input)?;
+
+        // 2. Execute nodes
+        let values = match &self.parallel {
+            Some(executor) => executor.run(self, values)?,
+            None => self.run_sequential(values)?,
+        };
 
         // 3. Return requested outputs
         let mut results = Vec::new();
         for output_name in &self.graph.outputs {
             let t = values.get(output_name)
                 .ok_or_else(|| anyhow::anyhow!("Output '{}' not produced", output_name))?;
-            // We clone the result to return ownership to the caller
-            results.push(t.clone());
+            // Outputs are handed back contiguous, so callers can read them with `data()`,
+            // and in f32 when the session lowered the precision of an fp32 model
+            let t = match self.options.reduced_precision {
+                Some(_) => t.to_element_type(ElementType::F32).to_contiguous(),
+                None => t.to_contiguous(),
+            };
+            results.push(t);
+        }
+
+        if let Some(profiler) = &self.profiler {
+            let input_shapes = input.iter().map(|t| t.shape().to_vec()).collect();
+            let bytes = results.iter().map(|t| t.len() * t.element_type().size()).sum();
+            profiler.record(EventKind::Run, "run", &self.graph.proto.name, input_shapes, start, bytes);
         }
 
         Ok(results)
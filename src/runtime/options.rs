@@ -0,0 +1,169 @@
+//! Session configuration.
+
+use crate::kernels::KernelBackend;
+use crate::tensor::ElementType;
+use std::collections::HashMap;
+
+/// How the nodes of a graph are dispatched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionMode {
+    /// Nodes run one after the other, in graph order. Easiest to debug.
+    #[default]
+    Sequential,
+    /// Independent nodes run concurrently on the inter-op pool as soon as their inputs are ready.
+    Parallel,
+}
+
+/// Which graph rewrites are applied when the session is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum GraphOptimizationLevel {
+    /// Run the graph exactly as loaded.
+    DisableAll,
+    /// Semantics-preserving cleanups such as constant folding and dead node removal.
+    Basic,
+    /// Basic, plus operator fusions.
+    Extended,
+    /// Every available optimization.
+    #[default]
+    All,
+}
+
+/// Options applied when creating an `InferenceSession`.
+///
+/// Built by chaining `with_*` calls on `SessionOptions::new()`:
+///
+/// ```
+/// use neuroxyde::runtime::{ExecutionMode, SessionOptions};
+///
+/// let options = SessionOptions::new()
+///     .with_intra_op_num_threads(4)
+///     .with_execution_mode(ExecutionMode::Parallel)
+///     .with_free_dimension_override("batch", 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SessionOptions {
+    /// Kernel backend to use. `None` picks the widest one supported by the CPU.
+    pub kernel_backend: Option<KernelBackend>,
+    /// Threads used inside a single operator. 0 means one per available core.
+    pub intra_op_num_threads: usize,
+    /// Cores the intra-op workers are pinned to, round-robin. Empty leaves placement to the OS.
+    pub intra_op_affinity: Vec<usize>,
+    /// Sequential or dependency-driven parallel node execution.
+    pub execution_mode: ExecutionMode,
+    /// Threads running independent nodes in `ExecutionMode::Parallel`. 0 means one per available core.
+    pub inter_op_num_threads: usize,
+    pub graph_optimization_level: GraphOptimizationLevel,
+    /// Recycle the buffers of intermediate tensors across nodes and runs.
+    pub enable_mem_arena: bool,
+    /// Record per-node timings, see `InferenceSession::profiler`.
+    pub enable_profiling: bool,
+    /// Results reproducible across machines: selects the scalar kernels unless a backend is forced.
+    pub deterministic: bool,
+    /// Fixed values for symbolic input dimensions (e.g. `batch` -> 1).
+    pub free_dimension_overrides: HashMap<String, usize>,
+    /// Stores FLOAT weights and activations as `ElementType::F16` or `BF16`,
+    /// halving their memory. `None` runs the model in its own precision.
+    pub reduced_precision: Option<ElementType>,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            kernel_backend: None,
+            intra_op_num_threads: 0,
+            intra_op_affinity: Vec::new(),
+            execution_mode: ExecutionMode::default(),
+            inter_op_num_threads: 0,
+            graph_optimization_level: GraphOptimizationLevel::default(),
+            enable_mem_arena: true,
+            enable_profiling: false,
+            deterministic: false,
+            free_dimension_overrides: HashMap::new(),
+            reduced_precision: None,
+        }
+    }
+}
+
+impl SessionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forces a specific kernel backend (e.g. `KernelBackend::Scalar` to compare against the reference).
+    pub fn with_kernel_backend(mut self, backend: KernelBackend) -> Self {
+        self.kernel_backend = Some(backend);
+        self
+    }
+
+    /// Sets the number of intra-op threads (1 disables intra-op parallelism).
+    pub fn with_intra_op_num_threads(mut self, num_threads: usize) -> Self {
+        self.intra_op_num_threads = num_threads;
+        self
+    }
+
+    /// Pins intra-op worker `i` to core `cores[i % cores.len()]`.
+    pub fn with_intra_op_affinity(mut self, cores: Vec<usize>) -> Self {
+        self.intra_op_affinity = cores;
+        self
+    }
+
+    pub fn with_execution_mode(mut self, mode: ExecutionMode) -> Self {
+        self.execution_mode = mode;
+        self
+    }
+
+    /// Sets the number of inter-op threads used in `ExecutionMode::Parallel`.
+    pub fn with_inter_op_num_threads(mut self, num_threads: usize) -> Self {
+        self.inter_op_num_threads = num_threads;
+        self
+    }
+
+    pub fn with_graph_optimization_level(mut self, level: GraphOptimizationLevel) -> Self {
+        self.graph_optimization_level = level;
+        self
+    }
+
+    pub fn with_mem_arena(mut self, enable: bool) -> Self {
+        self.enable_mem_arena = enable;
+        self
+    }
+
+    pub fn with_profiling(mut self, enable: bool) -> Self {
+        self.enable_profiling = enable;
+        self
+    }
+
+    pub fn with_deterministic(mut self, enable: bool) -> Self {
+        self.deterministic = enable;
+        self
+    }
+
+    /// Pins the symbolic dimension `name` (a `dim_param` in the model inputs) to `value`.
+    ///
+    /// Inputs of any other size are rejected, and the graph optimizer treats
+    /// the dimension as fixed, so e.g. `Shape` of such an input is folded.
+    pub fn with_free_dimension_override(mut self, name: &str, value: usize) -> Self {
+        self.free_dimension_overrides.insert(name.to_string(), value);
+        self
+    }
+
+    /// Runs an fp32 model in reduced precision.
+    ///
+    /// FLOAT initializers are converted once, after graph optimization, and
+    /// FLOAT inputs on every run; outputs are converted back to f32. Operators
+    /// without a native reduced-precision path widen their inputs to f32,
+    /// so results are rounded to `precision` between nodes.
+    pub fn with_reduced_precision(mut self, precision: ElementType) -> Self {
+        self.reduced_precision = (precision != ElementType::F32).then_some(precision);
+        self
+    }
+
+    /// Backend the session will run with on this CPU.
+    pub(crate) fn resolve_kernel_backend(&self) -> KernelBackend {
+        match self.kernel_backend {
+            Some(backend) => backend,
+            None if self.deterministic => KernelBackend::Scalar,
+            None => KernelBackend::detect(),
+        }
+    }
+}
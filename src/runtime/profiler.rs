@@ -0,0 +1,254 @@
+//! Opt-in per-node profiler with Chrome trace-event export.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Category of a recorded event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// A whole `InferenceSession::run` call.
+    Run,
+    /// A single node execution.
+    Node,
+}
+
+/// One timed execution.
+#[derive(Debug, Clone)]
+pub struct ProfileEvent {
+    pub kind: EventKind,
+    /// Operator type (or "run" for run events).
+    pub op_type: String,
+    pub name: String,
+    pub input_shapes: Vec<Vec<usize>>,
+    /// Start time, relative to the creation of the profiler.
+    pub start: Duration,
+    pub duration: Duration,
+    /// Size of the tensors produced by the node.
+    pub bytes_allocated: usize,
+    /// Small integer identifying the executing thread.
+    pub thread_id: u64,
+    pub thread_name: String,
+}
+
+/// Aggregated statistics for one operator type.
+#[derive(Debug, Clone)]
+pub struct OpSummary {
+    pub op_type: String,
+    pub calls: usize,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub bytes_allocated: usize,
+    /// Share of the total node time, in percent.
+    pub percent: f64,
+}
+
+impl OpSummary {
+    pub fn mean(&self) -> Duration {
+        self.total / self.calls.max(1) as u32
+    }
+}
+
+/// Per-operator summary, sorted by decreasing total time.
+#[derive(Debug, Clone)]
+pub struct ProfileSummary {
+    pub ops: Vec<OpSummary>,
+    pub total: Duration,
+}
+
+impl fmt::Display for ProfileSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>12} {:>12} {:>12} {:>12} {:>7} {:>12}",
+            "op_type", "calls", "total_us", "mean_us", "min_us", "max_us", "%", "bytes"
+        )?;
+        for op in &self.ops {
+            writeln!(
+                f,
+                "{:<24} {:>8} {:>12} {:>12} {:>12} {:>12} {:>6.2}% {:>12}",
+                op.op_type,
+                op.calls,
+                op.total.as_micros(),
+                op.mean().as_micros(),
+                op.min.as_micros(),
+                op.max.as_micros(),
+                op.percent,
+                op.bytes_allocated
+            )?;
+        }
+        writeln!(f, "Total node time: {} us", self.total.as_micros())
+    }
+}
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Collects `ProfileEvent`s from every thread of a session.
+#[derive(Debug)]
+pub struct Profiler {
+    origin: Instant,
+    events: Mutex<Vec<ProfileEvent>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self { origin: Instant::now(), events: Mutex::new(Vec::new()) }
+    }
+
+    /// Records an execution that started at `start` and just finished.
+    pub(crate) fn record(
+        &self,
+        kind: EventKind,
+        op_type: &str,
+        name: &str,
+        input_shapes: Vec<Vec<usize>>,
+        start: Instant,
+        bytes_allocated: usize,
+    ) {
+        let duration = start.elapsed();
+        let thread = std::thread::current();
+        let event = ProfileEvent {
+            kind,
+            op_type: op_type.to_string(),
+            name: name.to_string(),
+            input_shapes,
+            start: start.saturating_duration_since(self.origin),
+            duration,
+            bytes_allocated,
+            thread_id: THREAD_ID.with(|id| *id),
+            thread_name: thread.name().unwrap_or("unnamed").to_string(),
+        };
+        self.events.lock().unwrap().push(event);
+    }
+
+    /// All events recorded so far, in completion order.
+    pub fn events(&self) -> Vec<ProfileEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Discards the recorded events.
+    pub fn clear(&self) {
+        self.events.lock().unwrap().clear();
+    }
+
+    /// Aggregates node events per operator type.
+    pub fn summary(&self) -> ProfileSummary {
+        let events = self.events.lock().unwrap();
+        let mut by_op: HashMap<&str, OpSummary> = HashMap::new();
+        let mut total = Duration::ZERO;
+        for e in events.iter().filter(|e| e.kind == EventKind::Node) {
+            total += e.duration;
+            let entry = by_op.entry(e.op_type.as_str()).or_insert_with(|| OpSummary {
+                op_type: e.op_type.clone(),
+                calls: 0,
+                total: Duration::ZERO,
+                min: Duration::MAX,
+                max: Duration::ZERO,
+                bytes_allocated: 0,
+                percent: 0.0,
+            });
+            entry.calls += 1;
+            entry.total += e.duration;
+            entry.min = entry.min.min(e.duration);
+            entry.max = entry.max.max(e.duration);
+            entry.bytes_allocated += e.bytes_allocated;
+        }
+
+        let mut ops: Vec<OpSummary> = by_op.into_values().collect();
+        for op in &mut ops {
+            op.percent = if total.is_zero() { 0.0 } else { 100.0 * op.total.as_secs_f64() / total.as_secs_f64() };
+        }
+        ops.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.op_type.cmp(&b.op_type)));
+        ProfileSummary { ops, total }
+    }
+
+    /// Serializes the events in the Chrome trace-event format (viewable in Perfetto or chrome://tracing).
+    pub fn to_chrome_trace(&self) -> String {
+        let events = self.events.lock().unwrap();
+        let mut out = String::from("{\"traceEvents\":[");
+        let mut threads: Vec<(u64, &str)> = Vec::new();
+        for (i, e) in events.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let (name, cat) = match e.kind {
+                EventKind::Run => ("run", "Session"),
+                EventKind::Node => (if e.name.is_empty() { e.op_type.as_str() } else { e.name.as_str() }, "Node"),
+            };
+            let shapes = e.input_shapes.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(",");
+            let _ = write!(
+                out,
+                "{{\"name\":{},\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{},\
+                 \"args\":{{\"op_type\":{},\"input_shapes\":{},\"bytes_allocated\":{}}}}}",
+                json_string(name),
+                cat,
+                e.start.as_micros(),
+                e.duration.as_micros(),
+                e.thread_id,
+                json_string(&e.op_type),
+                json_string(&shapes),
+                e.bytes_allocated
+            );
+            if !threads.iter().any(|(id, _)| *id == e.thread_id) {
+                threads.push((e.thread_id, &e.thread_name));
+            }
+        }
+        // Metadata events so that Perfetto labels the tracks with thread names
+        for (id, name) in threads {
+            if out.ends_with('[') {
+                let _ = write!(out, "{{");
+            } else {
+                let _ = write!(out, ",{{");
+            }
+            let _ = write!(
+                out,
+                "\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":{}}}}}",
+                id,
+                json_string(name)
+            );
+        }
+        out.push_str("],\"displayTimeUnit\":\"ms\"}");
+        out
+    }
+
+    /// Writes `to_chrome_trace()` to `path`.
+    pub fn write_chrome_trace<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_chrome_trace())
+    }
+}
+
+/// Quotes and escapes `s` as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
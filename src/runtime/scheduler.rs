@@ -0,0 +1,205 @@
+//! Dependency-driven inter-op scheduler.
+//!
+//! Nodes become ready once every node producing one of their inputs has
+//! finished, and ready nodes are dispatched concurrently on a dedicated
+//! worker pool. Each node computes exactly what the sequential executor
+//! would, so results do not depend on the schedule.
+
+use crate::graph::{implicit_inputs, Graph};
+use crate::ops::operator;
+use crate::runtime::InferenceSession;
+use crate::tensor::Tensor;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Producer/consumer relationships between the nodes of a graph.
+pub(crate) struct DependencyGraph {
+    /// Number of distinct producer nodes each node waits for.
+    dependencies: Vec<usize>,
+    /// Nodes to notify when a node completes.
+    consumers: Vec<Vec<usize>>,
+    /// Number of nodes reading each value that is not a graph output.
+    uses: HashMap<String, usize>,
+}
+
+impl DependencyGraph {
+    pub(crate) fn new(graph: &Graph) -> anyhow::Result<Self> {
+        let mut producers: HashMap<&str, usize> = HashMap::new();
+        for (idx, node) in graph.nodes.iter().enumerate() {
+            for output in node.output.iter().filter(|o| !o.is_empty()) {
+                if producers.insert(output.as_str(), idx).is_some() {
+                    return Err(anyhow::anyhow!("Value '{}' is produced by more than one node", output));
+                }
+            }
+        }
+
+        let mut dependencies = vec![0; graph.nodes.len()];
+        let mut consumers = vec![Vec::new(); graph.nodes.len()];
+        let mut uses: HashMap<String, usize> = HashMap::new();
+        for (idx, node) in graph.nodes.iter().enumerate() {
+            let captured = implicit_inputs(node);
+            let names: HashSet<&String> = node.input.iter().chain(&captured).collect();
+            for name in names.into_iter().filter(|n| !graph.outputs.contains(n)) {
+                *uses.entry(name.clone()).or_default() += 1;
+            }
+
+            let upstream: HashSet<usize> = node.input.iter().chain(&captured)
+                .filter_map(|name| producers.get(name.as_str()).copied())
+                .collect();
+            if upstream.contains(&idx) {
+                return Err(anyhow::anyhow!("Node '{}' consumes its own output", node.name));
+            }
+            dependencies[idx] = upstream.len();
+            for producer in upstream {
+                consumers[producer].push(idx);
+            }
+        }
+
+        Ok(Self { dependencies, consumers, uses })
+    }
+}
+
+/// Worker pool and precomputed dependencies used in `ExecutionMode::Parallel`.
+pub(crate) struct ParallelExecutor {
+    pool: rayon::ThreadPool,
+    deps: DependencyGraph,
+}
+
+/// A node input: either a value computed during this run or a borrowed initializer
+/// (or placeholder for an absent optional input).
+enum Input<'s> {
+    Value(Arc<Tensor>),
+    Initializer(&'s Tensor),
+}
+
+impl Input<'_> {
+    fn tensor(&self) -> &Tensor {
+        match self {
+            Input::Value(t) => t,
+            Input::Initializer(t) => t,
+        }
+    }
+}
+
+/// Mutable state shared by the workers during one `run`.
+struct RunState {
+    values: Mutex<HashMap<String, Arc<Tensor>>>,
+    pending: Vec<AtomicUsize>,
+    /// Readers left for each value; the value is freed when it drops to zero.
+    remaining_uses: HashMap<String, AtomicUsize>,
+    error: Mutex<Option<anyhow::Error>>,
+    /// Span of the calling `run`, re-entered on the workers so node spans nest under it.
+    parent_span: tracing::Span,
+}
+
+impl ParallelExecutor {
+    /// Creates an executor with `num_threads` workers (0 means one per available core).
+    pub(crate) fn new(graph: &Graph, num_threads: usize) -> anyhow::Result<Self> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|i| format!("neuroxyde-inter-op-{}", i))
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to create inter-op thread pool: {}", e))?;
+        Ok(Self { pool, deps: DependencyGraph::new(graph)? })
+    }
+
+    pub(crate) fn num_threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    pub(crate) fn run(
+        &self,
+        session: &InferenceSession,
+        values: HashMap<String, Tensor>,
+    ) -> anyhow::Result<HashMap<String, Tensor>> {
+        let state = RunState {
+            values: Mutex::new(values.into_iter().map(|(k, v)| (k, Arc::new(v))).collect()),
+            pending: self.deps.dependencies.iter().map(|&d| AtomicUsize::new(d)).collect(),
+            remaining_uses: self.deps.uses.iter().map(|(k, &n)| (k.clone(), AtomicUsize::new(n))).collect(),
+            error: Mutex::new(None),
+            parent_span: tracing::Span::current(),
+        };
+
+        let roots: Vec<usize> = (0..self.deps.dependencies.len())
+            .filter(|&idx| self.deps.dependencies[idx] == 0)
+            .collect();
+        let state_ref = &state;
+        self.pool.scope(|scope| {
+            for idx in roots {
+                scope.spawn(move |scope| self.execute(scope, session, state_ref, idx));
+            }
+        });
+
+        if let Some(err) = state.error.into_inner().unwrap() {
+            return Err(err);
+        }
+        Ok(state.values.into_inner().unwrap()
+            .into_iter()
+            .map(|(k, v)| (k, Arc::try_unwrap(v).unwrap_or_else(|shared| (*shared).clone())))
+            .collect())
+    }
+
+    fn execute<'s>(&'s self, scope: &rayon::Scope<'s>, session: &'s InferenceSession, state: &'s RunState, idx: usize) {
+        if state.error.lock().unwrap().is_some() {
+            return;
+        }
+        let _parent = state.parent_span.enter();
+        let node = &session.graph.nodes[idx];
+
+        // Hold the lock only long enough to grab the inputs, then the values its subgraphs capture
+        let captured = implicit_inputs(node);
+        let inputs: anyhow::Result<Vec<Input>> = {
+            let values = state.values.lock().unwrap();
+            node.input.iter().chain(&captured)
+                .map(|name| {
+                    if name.is_empty() {
+                        Ok(Input::Initializer(operator::absent_input()))
+                    } else if let Some(t) = values.get(name) {
+                        Ok(Input::Value(Arc::clone(t)))
+                    } else if let Some(t) = session.graph.initializers.get(name) {
+                        Ok(Input::Initializer(t))
+                    } else {
+                        Err(anyhow::anyhow!("Missing input '{}' for node '{}'", name, node.name))
+                    }
+                })
+                .collect()
+        };
+
+        let result = inputs.and_then(|inputs| {
+            let refs: Vec<&Tensor> = inputs.iter().map(Input::tensor).collect();
+            session.run_node(node, &refs)
+        });
+        let outputs = match result {
+            Ok(outputs) => outputs,
+            Err(err) => {
+                state.error.lock().unwrap().get_or_insert(err);
+                return;
+            }
+        };
+
+        {
+            let mut values = state.values.lock().unwrap();
+            for (name, output) in node.output.iter().zip(outputs) {
+                if !name.is_empty() {
+                    values.insert(name.clone(), Arc::new(output));
+                }
+            }
+            let names: HashSet<&String> = node.input.iter().chain(&captured).collect();
+            for name in names {
+                let last_reader = state.remaining_uses.get(name)
+                    .is_some_and(|n| n.fetch_sub(1, Ordering::AcqRel) == 1);
+                if last_reader {
+                    if let Some(t) = values.remove(name).and_then(|t| Arc::try_unwrap(t).ok()) {
+                        session.release(t);
+                    }
+                }
+            }
+        }
+        for &consumer in &self.deps.consumers[idx] {
+            if state.pending[consumer].fetch_sub(1, Ordering::AcqRel) == 1 {
+                scope.spawn(move |scope| self.execute(scope, session, state, consumer));
+            }
+        }
+    }
+}
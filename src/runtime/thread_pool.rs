@@ -0,0 +1,91 @@
+//! Intra-op thread pool shared by the kernels of one session.
+
+use rayon::prelude::*;
+
+/// Minimum number of scalar operations handed to a single task. Smaller
+/// tensors are processed inline on the calling thread.
+const GRAIN: usize = 16 * 1024;
+
+/// Work-splitting helper around a dedicated rayon pool.
+///
+/// Operators partition their output into fixed-size chunks that do not
+/// depend on the number of threads, and every chunk is computed exactly as
+/// it would be on a single thread. Results are therefore bit-identical
+/// whatever the pool size.
+pub struct ThreadPool {
+    pool: Option<rayon::ThreadPool>,
+    num_threads: usize,
+}
+
+impl ThreadPool {
+    /// Creates a pool with `num_threads` workers (0 means one per available core).
+    ///
+    /// When `affinity` is non-empty, worker `i` is pinned to core `affinity[i % affinity.len()]`.
+    pub fn new(num_threads: usize, affinity: &[usize]) -> anyhow::Result<Self> {
+        let num_threads = if num_threads == 0 {
+            std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+        } else {
+            num_threads
+        };
+        if num_threads == 1 {
+            return Ok(Self { pool: None, num_threads });
+        }
+
+        let cores: Vec<core_affinity::CoreId> = affinity.iter().map(|&id| core_affinity::CoreId { id }).collect();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|i| format!("neuroxyde-intra-op-{}", i))
+            .start_handler(move |i| {
+                if !cores.is_empty() {
+                    core_affinity::set_for_current(cores[i % cores.len()]);
+                }
+            })
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to create intra-op thread pool: {}", e))?;
+        Ok(Self { pool: Some(pool), num_threads })
+    }
+
+    /// A pool that runs everything on the calling thread.
+    pub fn single_threaded() -> Self {
+        Self { pool: None, num_threads: 1 }
+    }
+
+    pub fn num_threads(&self) -> usize {
+        self.num_threads
+    }
+
+    /// Calls `f(chunk_index, chunk)` for every `chunk_len`-sized chunk of `data`.
+    ///
+    /// `work_per_chunk` is a rough count of scalar operations per chunk, used
+    /// to batch small chunks together so that task overhead stays negligible.
+    pub fn for_each_chunk<T, F>(&self, data: &mut [T], chunk_len: usize, work_per_chunk: usize, f: F)
+    where
+        T: Send,
+        F: Fn(usize, &mut [T]) + Sync + Send,
+    {
+        let chunk_len = chunk_len.max(1);
+        let total_work = data.len().div_ceil(chunk_len).saturating_mul(work_per_chunk);
+        match &self.pool {
+            Some(pool) if total_work > GRAIN => {
+                let min_len = GRAIN.div_ceil(work_per_chunk.max(1));
+                pool.install(|| {
+                    data.par_chunks_mut(chunk_len)
+                        .with_min_len(min_len)
+                        .enumerate()
+                        .for_each(|(i, chunk)| f(i, chunk));
+                });
+            }
+            _ => {
+                for (i, chunk) in data.chunks_mut(chunk_len).enumerate() {
+                    f(i, chunk);
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ThreadPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThreadPool").field("num_threads", &self.num_threads).finish()
+    }
+}
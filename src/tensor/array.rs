@@ -0,0 +1,84 @@
+//! Conversions between `Tensor` and `ndarray` arrays (feature `ndarray`).
+//!
+//! Owned arrays in standard (row-major, contiguous) layout are moved without
+//! copying in both directions, and tensors of any layout can be viewed as
+//! `ArrayViewD`. Other array layouts, such as transposed or sliced arrays,
+//! are copied into row-major order.
+
+use super::{Storage, Tensor};
+use ndarray::{Array, ArrayD, ArrayView, ArrayViewD, Axis, Dimension, IxDyn, ShapeBuilder};
+
+impl Tensor {
+    /// Borrows the tensor as an `ArrayViewD` with the same strides, without copying.
+    ///
+    /// Returns `None` if the tensor does not hold f32 elements; `into_array`
+    /// converts those instead.
+    pub fn array_view(&self) -> Option<ArrayViewD<'_, f32>> {
+        let Storage::F32(buf) = &self.storage else {
+            return None;
+        };
+        if self.is_empty() {
+            return Some(ArrayViewD::from_shape(IxDyn(&self.shape), &[]).expect("empty view"));
+        }
+        // Slice-backed views need non-negative strides: start from the lowest
+        // address, then flip the axes that run backwards
+        let mut start = self.offset as isize;
+        for (&dim, &stride) in self.shape.iter().zip(&self.strides) {
+            if stride < 0 {
+                start += stride * (dim as isize - 1);
+            }
+        }
+        let strides: Vec<usize> = self.strides.iter().map(|s| s.unsigned_abs()).collect();
+        let mut view = ArrayViewD::from_shape(IxDyn(&self.shape).strides(IxDyn(&strides)), &buf[start as usize..])
+            .expect("tensor strides stay within its buffer");
+        for (axis, &stride) in self.strides.iter().enumerate() {
+            if stride < 0 {
+                view.invert_axis(Axis(axis));
+            }
+        }
+        Some(view)
+    }
+
+    /// Converts the tensor into a row-major f32 `ArrayD`, reusing its buffer
+    /// when it owns it and widening other element types.
+    pub fn into_array(self) -> ArrayD<f32> {
+        let shape = IxDyn(&self.shape);
+        ArrayD::from_shape_vec(shape, self.into_data()).expect("tensor data matches its shape")
+    }
+}
+
+impl From<Tensor> for ArrayD<f32> {
+    fn from(tensor: Tensor) -> Self {
+        tensor.into_array()
+    }
+}
+
+/// Moves the buffer when the array is in standard layout and owns exactly its
+/// elements; otherwise copies them in row-major order.
+impl<D: Dimension> From<Array<f32, D>> for Tensor {
+    fn from(array: Array<f32, D>) -> Self {
+        let shape = array.shape().to_vec();
+        if array.is_standard_layout() {
+            let len = array.len();
+            let (data, offset) = array.into_raw_vec_and_offset();
+            if offset.unwrap_or(0) == 0 && data.len() == len {
+                return Tensor::new(data, shape);
+            }
+            let start = offset.unwrap_or(0);
+            return Tensor::new(data[start..start + len].to_vec(), shape);
+        }
+        Tensor::new(array.iter().copied().collect(), shape)
+    }
+}
+
+/// Copies the viewed elements in row-major order, whatever the strides of the view.
+impl<D: Dimension> From<ArrayView<'_, f32, D>> for Tensor {
+    fn from(view: ArrayView<'_, f32, D>) -> Self {
+        let shape = view.shape().to_vec();
+        let data = match view.as_slice() {
+            Some(slice) => slice.to_vec(),
+            None => view.iter().copied().collect(),
+        };
+        Tensor::new(data, shape)
+    }
+}
@@ -0,0 +1,105 @@
+//! Packed little-endian encodings of tensor elements, as used by
+//! `TensorProto.raw_data` and `.npy` files.
+//!
+//! f32, f16, bf16, u8, i8 and i32 tensors are stored natively; elements of
+//! the other numeric types are decoded to f32. Half-precision values are
+//! converted by the `half`-backed routines of `storage`, like the tensors
+//! themselves.
+
+use super::storage::{downcast_bf16, downcast_f16, upcast_bf16, upcast_f16};
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use half::{bf16, f16};
+
+/// Size in bytes of one element of `data_type`, for the numeric types a tensor can hold.
+pub(crate) fn element_size(data_type: DataType) -> Option<usize> {
+    Some(match data_type {
+        DataType::Bool | DataType::Int8 | DataType::Uint8 => 1,
+        DataType::Int16 | DataType::Uint16 | DataType::Float16 | DataType::Bfloat16 => 2,
+        DataType::Float | DataType::Int32 | DataType::Uint32 => 4,
+        DataType::Double | DataType::Int64 | DataType::Uint64 => 8,
+        _ => return None,
+    })
+}
+
+/// Decodes packed little-endian elements of type `data_type`.
+pub(crate) fn decode_le(data_type: DataType, bytes: &[u8]) -> anyhow::Result<Vec<f32>> {
+    let size = element_size(data_type)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported element type {}", data_type.as_str_name()))?;
+    if !bytes.len().is_multiple_of(size) {
+        return Err(anyhow::anyhow!("{} bytes do not hold a whole number of {} elements", bytes.len(), data_type.as_str_name()));
+    }
+    let chunks = bytes.chunks_exact(size);
+    Ok(match data_type {
+        DataType::Bool => chunks.map(|b| if b[0] != 0 { 1.0 } else { 0.0 }).collect(),
+        DataType::Int8 => chunks.map(|b| b[0] as i8 as f32).collect(),
+        DataType::Uint8 => chunks.map(|b| b[0] as f32).collect(),
+        DataType::Int16 => chunks.map(|b| i16::from_le_bytes([b[0], b[1]]) as f32).collect(),
+        DataType::Uint16 => chunks.map(|b| u16::from_le_bytes([b[0], b[1]]) as f32).collect(),
+        DataType::Float16 => upcast_f16(&decode_f16_le(bytes)?),
+        DataType::Bfloat16 => upcast_bf16(&decode_bf16_le(bytes)?),
+        DataType::Float => chunks.map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect(),
+        DataType::Int32 => chunks.map(|b| i32::from_le_bytes(b.try_into().unwrap()) as f32).collect(),
+        DataType::Uint32 => chunks.map(|b| u32::from_le_bytes(b.try_into().unwrap()) as f32).collect(),
+        DataType::Double => chunks.map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32).collect(),
+        DataType::Int64 => chunks.map(|b| i64::from_le_bytes(b.try_into().unwrap()) as f32).collect(),
+        DataType::Uint64 => chunks.map(|b| u64::from_le_bytes(b.try_into().unwrap()) as f32).collect(),
+        _ => unreachable!(),
+    })
+}
+
+/// Bits of packed little-endian 16-bit elements.
+fn decode_bits_le(bytes: &[u8], name: &str) -> anyhow::Result<Vec<u16>> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(anyhow::anyhow!("odd number of bytes for {}", name));
+    }
+    Ok(bytes.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect())
+}
+
+/// Decodes packed little-endian half-precision elements.
+pub(crate) fn decode_f16_le(bytes: &[u8]) -> anyhow::Result<Vec<f16>> {
+    Ok(decode_bits_le(bytes, "FLOAT16")?.into_iter().map(f16::from_bits).collect())
+}
+
+/// Decodes packed little-endian bfloat16 elements.
+pub(crate) fn decode_bf16_le(bytes: &[u8]) -> anyhow::Result<Vec<bf16>> {
+    Ok(decode_bits_le(bytes, "BFLOAT16")?.into_iter().map(bf16::from_bits).collect())
+}
+
+/// Decodes packed little-endian int32 elements exactly, without going through f32.
+pub(crate) fn decode_i32_le(bytes: &[u8]) -> anyhow::Result<Vec<i32>> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(anyhow::anyhow!("{} bytes do not hold a whole number of INT32 elements", bytes.len()));
+    }
+    Ok(bytes.chunks_exact(4).map(|b| i32::from_le_bytes(b.try_into().unwrap())).collect())
+}
+
+/// Encodes `values` as packed little-endian elements of type `data_type`.
+///
+/// Integer types truncate toward zero and saturate, like `as` casts.
+pub(crate) fn encode_le(data_type: DataType, values: &[f32]) -> anyhow::Result<Vec<u8>> {
+    let size = element_size(data_type)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported element type {}", data_type.as_str_name()))?;
+    match data_type {
+        DataType::Float16 => return Ok(downcast_f16(values).iter().flat_map(|v| v.to_le_bytes()).collect()),
+        DataType::Bfloat16 => return Ok(downcast_bf16(values).iter().flat_map(|v| v.to_le_bytes()).collect()),
+        _ => {}
+    }
+    let mut out = Vec::with_capacity(values.len() * size);
+    for &v in values {
+        match data_type {
+            DataType::Bool => out.push(u8::from(v != 0.0)),
+            DataType::Int8 => out.push(v as i8 as u8),
+            DataType::Uint8 => out.push(v as u8),
+            DataType::Int16 => out.extend((v as i16).to_le_bytes()),
+            DataType::Uint16 => out.extend((v as u16).to_le_bytes()),
+            DataType::Float => out.extend(v.to_le_bytes()),
+            DataType::Int32 => out.extend((v as i32).to_le_bytes()),
+            DataType::Uint32 => out.extend((v as u32).to_le_bytes()),
+            DataType::Double => out.extend((v as f64).to_le_bytes()),
+            DataType::Int64 => out.extend((v as i64).to_le_bytes()),
+            DataType::Uint64 => out.extend((v as u64).to_le_bytes()),
+            _ => unreachable!(),
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,393 @@
+//! Reading and writing tensors: NumPy `.npy`/`.npz` files and serialized ONNX `TensorProto` (`.pb`) files.
+
+use super::{dtype, ElementType, Tensor};
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use crate::onnx::onnx_proto::TensorProto;
+use prost::Message;
+use std::path::Path;
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Maps a NumPy type string such as `<f4` to its element type and byte order.
+fn npy_element(descr: &str) -> anyhow::Result<(DataType, bool)> {
+    let unsupported = || anyhow::anyhow!("Unsupported .npy dtype '{}'", descr);
+    let mut chars = descr.chars();
+    let big_endian = match chars.next().ok_or_else(unsupported)? {
+        '<' | '|' | '=' => false,
+        '>' => true,
+        _ => return Err(unsupported()),
+    };
+    let data_type = match chars.as_str() {
+        "b1" => DataType::Bool,
+        "i1" => DataType::Int8,
+        "u1" => DataType::Uint8,
+        "i2" => DataType::Int16,
+        "u2" => DataType::Uint16,
+        "f2" => DataType::Float16,
+        "i4" => DataType::Int32,
+        "u4" => DataType::Uint32,
+        "f4" => DataType::Float,
+        "i8" => DataType::Int64,
+        "u8" => DataType::Uint64,
+        "f8" => DataType::Double,
+        _ => return Err(unsupported()),
+    };
+    Ok((data_type, big_endian))
+}
+
+/// NumPy type string of `data_type`, little-endian.
+fn npy_descr(data_type: DataType) -> anyhow::Result<&'static str> {
+    Ok(match data_type {
+        DataType::Bool => "|b1",
+        DataType::Int8 => "|i1",
+        DataType::Uint8 => "|u1",
+        DataType::Int16 => "<i2",
+        DataType::Uint16 => "<u2",
+        DataType::Float16 => "<f2",
+        DataType::Int32 => "<i4",
+        DataType::Uint32 => "<u4",
+        DataType::Float => "<f4",
+        DataType::Int64 => "<i8",
+        DataType::Uint64 => "<u8",
+        DataType::Double => "<f8",
+        other => return Err(anyhow::anyhow!("{} has no NumPy equivalent", other.as_str_name())),
+    })
+}
+
+/// Extracts the value of `key` from the Python dict literal of an `.npy` header.
+fn header_field<'h>(header: &'h str, key: &str) -> anyhow::Result<&'h str> {
+    let pattern = format!("'{}':", key);
+    let start = header.find(&pattern)
+        .ok_or_else(|| anyhow::anyhow!("Malformed .npy header, missing '{}': {}", key, header))?
+        + pattern.len();
+    let rest = header[start..].trim_start();
+    let end = if rest.starts_with('(') { rest.find(')').map(|i| i + 1) } else { rest.find([',', '}']) };
+    Ok(rest[..end.unwrap_or(rest.len())].trim())
+}
+
+impl Tensor {
+    /// Reads a tensor from a `.npy` or `.pb` file, depending on its extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("npy") => Self::read_npy(path),
+            Some("pb") => Self::read_pb(path),
+            _ => Err(anyhow::anyhow!("Unknown tensor file format: {}", path.display())),
+        }
+    }
+
+    /// Writes the tensor to a `.npy` or `.pb` file, depending on the extension of `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P, name: &str) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("npy") => self.write_npy(path),
+            Some("pb") => self.write_pb(path, name),
+            _ => Err(anyhow::anyhow!("Unknown tensor file format: {}", path.display())),
+        }
+    }
+
+    /// Decodes a NumPy `.npy` array of any numeric dtype, in C or Fortran order.
+    pub fn from_npy_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < 10 || !bytes.starts_with(NPY_MAGIC) {
+            return Err(anyhow::anyhow!("Not a .npy file"));
+        }
+        let (header_len, offset) = match bytes[6] {
+            1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+            2 | 3 if bytes.len() >= 12 => (u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize, 12),
+            v => return Err(anyhow::anyhow!("Unsupported .npy version {}", v)),
+        };
+        let header = bytes.get(offset..offset + header_len)
+            .ok_or_else(|| anyhow::anyhow!("Truncated .npy header"))?;
+        let header = std::str::from_utf8(header)?;
+
+        let descr = header_field(header, "descr")?.trim_matches(|c| c == '\'' || c == '"');
+        let fortran_order = match header_field(header, "fortran_order")? {
+            "False" => false,
+            "True" => true,
+            other => return Err(anyhow::anyhow!("Malformed .npy fortran_order '{}'", other)),
+        };
+        let shape = header_field(header, "shape")?
+            .trim_matches(|c| c == '(' || c == ')')
+            .split(',')
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(|d| d.parse::<usize>().map_err(|e| anyhow::anyhow!("Bad .npy dimension '{}': {}", d, e)))
+            .collect::<anyhow::Result<Vec<usize>>>()?;
+
+        let (data_type, big_endian) = npy_element(descr)?;
+        let size = dtype::element_size(data_type).unwrap();
+        let count: usize = shape.iter().product();
+        let raw = bytes.get(offset + header_len..)
+            .filter(|d| d.len() == count * size)
+            .ok_or_else(|| anyhow::anyhow!(".npy data does not match shape {:?} and dtype {}", shape, descr))?;
+        let swapped: Vec<u8>;
+        let raw = if big_endian {
+            swapped = raw.chunks_exact(size).flat_map(|c| c.iter().rev().copied()).collect();
+            &swapped
+        } else {
+            raw
+        };
+        // int32 is decoded exactly; other types go through f32
+        let decode = |shape: Vec<usize>| -> anyhow::Result<Tensor> {
+            Ok(match data_type {
+                DataType::Int32 => Tensor::from_i32(dtype::decode_i32_le(raw)?, shape),
+                _ => Tensor::new(dtype::decode_le(data_type, raw)?, shape),
+            })
+        };
+
+        let tensor = if !fortran_order || shape.len() < 2 {
+            decode(shape)?
+        } else {
+            // Column-major data of shape S is the row-major array of shape S reversed, transposed
+            let reversed: Vec<usize> = shape.iter().rev().copied().collect();
+            let perm: Vec<usize> = (0..shape.len()).rev().collect();
+            decode(reversed)?.permute(&perm)?.to_contiguous()
+        };
+        // Types held natively keep their element type (the round trip through f32 is exact)
+        Ok(match ElementType::from_data_type(data_type) {
+            Some(element_type) => tensor.to_element_type(element_type),
+            None => tensor,
+        })
+    }
+
+    /// Encodes the tensor as a version 1.0 `.npy` array of its element type,
+    /// with bfloat16 written as f32 (NumPy has no bfloat16).
+    pub fn to_npy_bytes(&self) -> Vec<u8> {
+        let data_type = match self.element_type() {
+            ElementType::BF16 => DataType::Float,
+            other => other.data_type(),
+        };
+        self.to_npy_bytes_as(data_type).unwrap()
+    }
+
+    /// Encodes the tensor as a version 1.0 `.npy` array (C order) of element type `data_type`.
+    pub fn to_npy_bytes_as(&self, data_type: DataType) -> anyhow::Result<Vec<u8>> {
+        let descr = npy_descr(data_type)?;
+        // The native element type is written as is, keeping int32 exact
+        let data = if data_type == self.element_type().data_type() {
+            self.to_proto("").raw_data
+        } else {
+            dtype::encode_le(data_type, &self.values())?
+        };
+        let dims: Vec<String> = self.shape.iter().map(|d| d.to_string()).collect();
+        let shape = match dims.len() {
+            1 => format!("({},)", dims[0]),
+            _ => format!("({})", dims.join(", ")),
+        };
+        let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}", descr, shape);
+        // The data must start on a 64-byte boundary, and the header ends with a newline
+        let unpadded = NPY_MAGIC.len() + 4 + header.len() + 1;
+        header.extend(std::iter::repeat_n(' ', unpadded.next_multiple_of(64) - unpadded));
+        header.push('\n');
+
+        let mut out = Vec::with_capacity(NPY_MAGIC.len() + 4 + header.len() + data.len());
+        out.extend_from_slice(NPY_MAGIC);
+        out.extend_from_slice(&[1, 0]);
+        out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        out.extend_from_slice(header.as_bytes());
+        out.extend_from_slice(&data);
+        Ok(out)
+    }
+
+    pub fn read_npy<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::from_npy_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn write_npy<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        Ok(std::fs::write(path, self.to_npy_bytes())?)
+    }
+
+    /// Writes the tensor as a `.npy` file of element type `data_type`.
+    pub fn write_npy_as<P: AsRef<Path>>(&self, path: P, data_type: DataType) -> anyhow::Result<()> {
+        Ok(std::fs::write(path, self.to_npy_bytes_as(data_type)?)?)
+    }
+
+    /// Reads every array of a `.npz` archive (as written by `numpy.savez` or
+    /// `numpy.savez_compressed`), in archive order, keyed without the `.npy` suffix.
+    pub fn read_npz<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<(String, Tensor)>> {
+        let archive = std::fs::read(path)?;
+        zip::entries(&archive)?
+            .into_iter()
+            .map(|(name, bytes)| {
+                let tensor = Self::from_npy_bytes(&bytes)
+                    .map_err(|e| anyhow::anyhow!("Array '{}': {}", name, e))?;
+                let key = name.strip_suffix(".npy").unwrap_or(&name).to_string();
+                Ok((key, tensor))
+            })
+            .collect()
+    }
+
+    /// Writes `arrays` as a `.npz` archive, deflated if `compressed`, with the
+    /// element types of `to_npy_bytes`.
+    pub fn write_npz<P: AsRef<Path>>(path: P, arrays: &[(&str, &Tensor)], compressed: bool) -> anyhow::Result<()> {
+        let files: Vec<(String, Vec<u8>)> = arrays.iter()
+            .map(|(name, tensor)| (format!("{}.npy", name), tensor.to_npy_bytes()))
+            .collect();
+        Ok(std::fs::write(path, zip::archive(&files, compressed)?)?)
+    }
+
+    /// Reads a serialized `TensorProto`, as found in the ONNX `test_data_set_*` directories.
+    pub fn read_pb<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let proto = TensorProto::decode(&*std::fs::read(path)?)?;
+        Self::from_proto(&proto)
+    }
+
+    /// Writes the tensor as a serialized `TensorProto` named `name`, of its own element type.
+    pub fn write_pb<P: AsRef<Path>>(&self, path: P, name: &str) -> anyhow::Result<()> {
+        Ok(std::fs::write(path, self.to_proto(name).encode_to_vec())?)
+    }
+
+    /// Writes the tensor as a serialized `TensorProto` of element type `data_type`.
+    pub fn write_pb_as<P: AsRef<Path>>(&self, path: P, name: &str, data_type: DataType) -> anyhow::Result<()> {
+        Ok(std::fs::write(path, self.to_proto_as(name, data_type as i32)?.encode_to_vec())?)
+    }
+}
+
+/// Just enough of the ZIP format for `.npz` files: stored and deflated
+/// entries, with Zip64 sizes on read (NumPy always writes Zip64 headers).
+mod zip {
+    const LOCAL_HEADER: u32 = 0x0403_4b50;
+    const CENTRAL_HEADER: u32 = 0x0201_4b50;
+    const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+    const STORED: u16 = 0;
+    const DEFLATED: u16 = 8;
+    /// 1980-01-01, the earliest date ZIP can represent.
+    const DOS_DATE: u16 = 0x0021;
+
+    fn u16_at(bytes: &[u8], at: usize) -> anyhow::Result<u16> {
+        bytes.get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or_else(|| anyhow::anyhow!("Truncated .npz archive"))
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> anyhow::Result<u32> {
+        bytes.get(at..at + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| anyhow::anyhow!("Truncated .npz archive"))
+    }
+
+    fn u64_at(bytes: &[u8], at: usize) -> anyhow::Result<u64> {
+        bytes.get(at..at + 8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| anyhow::anyhow!("Truncated .npz archive"))
+    }
+
+    /// Decompressed `(name, content)` of every file in the archive.
+    pub(super) fn entries(archive: &[u8]) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+        // The end record is the last 22 bytes, unless followed by a comment
+        let end = (0..=archive.len().saturating_sub(22)).rev()
+            .take(u16::MAX as usize + 1)
+            .find(|&i| u32_at(archive, i).ok() == Some(END_OF_CENTRAL_DIRECTORY))
+            .ok_or_else(|| anyhow::anyhow!("Not a .npz (zip) archive"))?;
+        let count = u16_at(archive, end + 10)? as usize;
+        let mut at = u32_at(archive, end + 16)? as usize;
+
+        let mut files = Vec::with_capacity(count);
+        for _ in 0..count {
+            if u32_at(archive, at)? != CENTRAL_HEADER {
+                return Err(anyhow::anyhow!("Corrupt .npz central directory"));
+            }
+            let method = u16_at(archive, at + 10)?;
+            let crc = u32_at(archive, at + 16)?;
+            let mut compressed_size = u32_at(archive, at + 20)? as u64;
+            let mut size = u32_at(archive, at + 24)? as u64;
+            let name_len = u16_at(archive, at + 28)? as usize;
+            let extra_len = u16_at(archive, at + 30)? as usize;
+            let comment_len = u16_at(archive, at + 32)? as usize;
+            let mut offset = u32_at(archive, at + 42)? as u64;
+            let name = archive.get(at + 46..at + 46 + name_len)
+                .ok_or_else(|| anyhow::anyhow!("Truncated .npz archive"))?;
+            let name = String::from_utf8_lossy(name).into_owned();
+
+            // Zip64 extra field: the values saturated in the header follow, in order
+            let mut extra = at + 46 + name_len;
+            let extra_end = extra + extra_len;
+            while extra + 4 <= extra_end {
+                let (id, len) = (u16_at(archive, extra)?, u16_at(archive, extra + 2)? as usize);
+                if id == 0x0001 {
+                    let mut field = extra + 4;
+                    for value in [&mut size, &mut compressed_size, &mut offset] {
+                        if *value == u32::MAX as u64 && field + 8 <= extra + 4 + len {
+                            *value = u64_at(archive, field)?;
+                            field += 8;
+                        }
+                    }
+                }
+                extra += 4 + len;
+            }
+            at = extra_end + comment_len;
+
+            let local = offset as usize;
+            if u32_at(archive, local)? != LOCAL_HEADER {
+                return Err(anyhow::anyhow!("Corrupt .npz entry '{}'", name));
+            }
+            let start = local + 30 + u16_at(archive, local + 26)? as usize + u16_at(archive, local + 28)? as usize;
+            let data = usize::try_from(compressed_size).ok()
+                .and_then(|len| start.checked_add(len))
+                .and_then(|end| archive.get(start..end))
+                .ok_or_else(|| anyhow::anyhow!("Truncated .npz entry '{}'", name))?;
+            // Inflate no further than the declared size, whatever the stream holds
+            let limit = usize::try_from(size).map_err(|_| anyhow::anyhow!("Corrupt .npz entry '{}'", name))?;
+            let content = match method {
+                STORED => data.to_vec(),
+                DEFLATED => miniz_oxide::inflate::decompress_to_vec_with_limit(data, limit)
+                    .map_err(|e| anyhow::anyhow!("Cannot inflate .npz entry '{}': {:?}", name, e))?,
+                other => return Err(anyhow::anyhow!("Unsupported compression method {} in .npz entry '{}'", other, name)),
+            };
+            if content.len() as u64 != size || crc32fast::hash(&content) != crc {
+                return Err(anyhow::anyhow!("Corrupt .npz entry '{}'", name));
+            }
+            files.push((name, content));
+        }
+        Ok(files)
+    }
+
+    /// Builds an archive holding `files`.
+    pub(super) fn archive(files: &[(String, Vec<u8>)], compressed: bool) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for (name, content) in files {
+            let data = if compressed { miniz_oxide::deflate::compress_to_vec(content, 6) } else { content.clone() };
+            let offset = out.len();
+            if offset > u32::MAX as usize || data.len() > u32::MAX as usize || content.len() > u32::MAX as usize {
+                return Err(anyhow::anyhow!("Arrays over 4 GiB cannot be written to .npz"));
+            }
+            // Fields shared by the local and central headers, from "version needed" to "extra length"
+            let mut common = Vec::with_capacity(26);
+            common.extend(20u16.to_le_bytes());
+            common.extend(0u16.to_le_bytes());
+            common.extend((if compressed { DEFLATED } else { STORED }).to_le_bytes());
+            common.extend(0u16.to_le_bytes());
+            common.extend(DOS_DATE.to_le_bytes());
+            common.extend(crc32fast::hash(content).to_le_bytes());
+            common.extend((data.len() as u32).to_le_bytes());
+            common.extend((content.len() as u32).to_le_bytes());
+            common.extend((name.len() as u16).to_le_bytes());
+            common.extend(0u16.to_le_bytes());
+
+            out.extend(LOCAL_HEADER.to_le_bytes());
+            out.extend(&common);
+            out.extend(name.as_bytes());
+            out.extend(&data);
+
+            central.extend(CENTRAL_HEADER.to_le_bytes());
+            central.extend(20u16.to_le_bytes());
+            central.extend(&common);
+            // Comment length, disk number, internal and external attributes
+            central.extend([0u8; 10]);
+            central.extend((offset as u32).to_le_bytes());
+            central.extend(name.as_bytes());
+        }
+
+        let directory_offset = out.len() as u32;
+        out.extend(&central);
+        out.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        out.extend([0u8; 4]);
+        out.extend((files.len() as u16).to_le_bytes());
+        out.extend((files.len() as u16).to_le_bytes());
+        out.extend((central.len() as u32).to_le_bytes());
+        out.extend(directory_offset.to_le_bytes());
+        out.extend(0u16.to_le_bytes());
+        Ok(out)
+    }
+}
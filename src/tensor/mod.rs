@@ -1,101 +1,491 @@
 //! Tensor module: defines the Tensor struct and basic tensor utilities.
 use crate::onnx::onnx_proto;
+use std::borrow::Cow;
+use std::sync::Arc;
 
+#[cfg(feature = "ndarray")]
+mod array;
+pub(crate) mod dtype;
+mod io;
+mod storage;
+
+pub use storage::{bf16, f16, ElementType};
+use storage::Storage;
+
+/// A tensor: a shape and strides over a shared, reference-counted buffer of
+/// f32, f16, bf16, u8 or i8 elements.
+///
+/// Tensors created from owned data are contiguous (row-major, no offset).
+/// View operations such as `reshape`, `permute` and `slice_axis` only adjust
+/// the shape, strides and offset, so cloning and viewing are O(1) and several
+/// tensors (or sessions) can share one buffer.
 #[derive(Debug, Clone)]
 pub struct Tensor {
     shape: Vec<usize>,
-    data: Vec<f32>,
+    /// Distance, in elements, between consecutive indices along each axis.
+    strides: Vec<isize>,
+    /// Position of the element at index zero in `storage`.
+    offset: usize,
+    storage: Storage,
+}
+
+/// Row-major strides of a dense tensor of shape `shape`.
+fn contiguous_strides(shape: &[usize]) -> Vec<isize> {
+    let mut strides = vec![1isize; shape.len()];
+    for i in (0..shape.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1] as isize;
+    }
+    strides
 }
 
 impl Tensor {
     pub fn new(data: Vec<f32>, shape: Vec<usize>) -> Self {
-        assert_eq!(data.len(), shape.iter().product::<usize>());
-        Self { data, shape }
+        Self::from_shared(Arc::new(data), shape)
+    }
+
+    /// Wraps a shared buffer as a contiguous tensor of shape `shape`, without copying it.
+    pub fn from_shared(storage: Arc<Vec<f32>>, shape: Vec<usize>) -> Self {
+        Self::from_storage(Storage::F32(storage), shape)
+    }
+
+    /// Creates a half-precision tensor.
+    pub fn from_f16(data: Vec<f16>, shape: Vec<usize>) -> Self {
+        Self::from_storage(Storage::F16(Arc::new(data)), shape)
+    }
+
+    /// Creates a bfloat16 tensor.
+    pub fn from_bf16(data: Vec<bf16>, shape: Vec<usize>) -> Self {
+        Self::from_storage(Storage::BF16(Arc::new(data)), shape)
+    }
+
+    /// Creates a u8 tensor, as used by quantized models.
+    pub fn from_u8(data: Vec<u8>, shape: Vec<usize>) -> Self {
+        Self::from_storage(Storage::U8(Arc::new(data)), shape)
+    }
+
+    /// Creates an i8 tensor, as used by quantized models.
+    pub fn from_i8(data: Vec<i8>, shape: Vec<usize>) -> Self {
+        Self::from_storage(Storage::I8(Arc::new(data)), shape)
+    }
+
+    /// Creates an int32 tensor, such as the accumulators of integer matmuls.
+    pub fn from_i32(data: Vec<i32>, shape: Vec<usize>) -> Self {
+        Self::from_storage(Storage::I32(Arc::new(data)), shape)
+    }
+
+    fn from_storage(storage: Storage, shape: Vec<usize>) -> Self {
+        assert_eq!(storage.len(), shape.iter().product::<usize>());
+        Self { strides: contiguous_strides(&shape), shape, offset: 0, storage }
     }
 
     pub fn zeros(shape: &[usize]) -> Self {
         let n = shape.iter().product::<usize>();
-        Self { data: vec![0.0; n], shape: shape.to_vec() }
+        Self::new(vec![0.0; n], shape.to_vec())
     }
 
     pub fn shape(&self) -> &[usize] {
         &self.shape
     }
 
+    pub fn element_type(&self) -> ElementType {
+        self.storage.element_type()
+    }
+
+    /// Strides of each axis, in elements; negative for axes walked backwards.
+    pub fn strides(&self) -> &[isize] {
+        &self.strides
+    }
+
+    /// Position of the first element in the underlying buffer.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Number of elements.
+    pub fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the elements are laid out densely in row-major order.
+    pub fn is_contiguous(&self) -> bool {
+        let mut expected = 1isize;
+        for (&dim, &stride) in self.shape.iter().zip(&self.strides).rev() {
+            if dim == 0 {
+                return true;
+            }
+            if dim != 1 && stride != expected {
+                return false;
+            }
+            expected *= dim as isize;
+        }
+        true
+    }
+
+    /// Whether both tensors are views of the same buffer.
+    pub fn shares_storage(&self, other: &Tensor) -> bool {
+        self.storage.ptr_eq(&other.storage)
+    }
+
+    /// Elements of a contiguous f32 tensor, in row-major order.
+    ///
+    /// Panics if the tensor is a non-contiguous view or holds another element
+    /// type; use `values`, `to_vec`, `to_contiguous` or `to_element_type` for those.
     pub fn data(&self) -> &[f32] {
-        &self.data
+        match &self.storage {
+            Storage::F32(buf) => self.dense_slice(buf),
+            other => panic!("Tensor::data called on a {:?} tensor", other.element_type()),
+        }
     }
-    
-    pub fn from_proto(tns: &onnx_proto::TensorProto) -> Self {
-        use onnx_proto::tensor_proto::DataType;
-    
-        let shape: Vec<usize> = tns.dims.iter().map(|d| *d as usize).collect();
-        let count = shape.iter().product::<usize>();
-    
-        match tns.data_type {
-            x if x == DataType::Float as i32 || x == 1 => {
-                // common: raw_data or float_data
-                if !tns.raw_data.is_empty() {
-                    let floats = tns.raw_data
-                        .chunks_exact(4)
-                        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
-                        .collect::<Vec<f32>>();
-                    assert_eq!(floats.len(), count);
-                    return Tensor::new(floats, shape);
+
+    /// Elements of a contiguous f16 tensor; panics like `data` for other tensors.
+    pub fn data_f16(&self) -> &[f16] {
+        match &self.storage {
+            Storage::F16(buf) => self.dense_slice(buf),
+            other => panic!("Tensor::data_f16 called on a {:?} tensor", other.element_type()),
+        }
+    }
+
+    /// Elements of a contiguous bf16 tensor; panics like `data` for other tensors.
+    pub fn data_bf16(&self) -> &[bf16] {
+        match &self.storage {
+            Storage::BF16(buf) => self.dense_slice(buf),
+            other => panic!("Tensor::data_bf16 called on a {:?} tensor", other.element_type()),
+        }
+    }
+
+    /// Elements of a contiguous u8 tensor; panics like `data` for other tensors.
+    pub fn data_u8(&self) -> &[u8] {
+        match &self.storage {
+            Storage::U8(buf) => self.dense_slice(buf),
+            other => panic!("Tensor::data_u8 called on a {:?} tensor", other.element_type()),
+        }
+    }
+
+    /// Elements of a contiguous i8 tensor; panics like `data` for other tensors.
+    pub fn data_i8(&self) -> &[i8] {
+        match &self.storage {
+            Storage::I8(buf) => self.dense_slice(buf),
+            other => panic!("Tensor::data_i8 called on a {:?} tensor", other.element_type()),
+        }
+    }
+
+    /// Elements of a contiguous i32 tensor; panics like `data` for other tensors.
+    pub fn data_i32(&self) -> &[i32] {
+        match &self.storage {
+            Storage::I32(buf) => self.dense_slice(buf),
+            other => panic!("Tensor::data_i32 called on a {:?} tensor", other.element_type()),
+        }
+    }
+
+    fn dense_slice<'a, T>(&self, buf: &'a [T]) -> &'a [T] {
+        assert!(self.is_contiguous(), "Tensor data requested from a non-contiguous view of shape {:?}", self.shape);
+        &buf[self.offset..self.offset + self.len()]
+    }
+
+    /// The elements of `buf` seen by this tensor, in row-major order, borrowed when contiguous.
+    fn dense<'a, T: Copy>(&self, buf: &'a [T]) -> Cow<'a, [T]> {
+        if self.is_contiguous() {
+            Cow::Borrowed(&buf[self.offset..self.offset + self.len()])
+        } else {
+            Cow::Owned(self.gather(buf))
+        }
+    }
+
+    /// Elements as f32 in row-major order, borrowed when the tensor is a contiguous f32 tensor.
+    pub fn values(&self) -> Cow<'_, [f32]> {
+        match &self.storage {
+            Storage::F32(buf) => self.dense(buf),
+            Storage::F16(buf) => Cow::Owned(storage::upcast_f16(&self.dense(buf))),
+            Storage::BF16(buf) => Cow::Owned(storage::upcast_bf16(&self.dense(buf))),
+            Storage::U8(buf) => Cow::Owned(self.dense(buf).iter().map(|&v| v as f32).collect()),
+            Storage::I8(buf) => Cow::Owned(self.dense(buf).iter().map(|&v| v as f32).collect()),
+            Storage::I32(buf) => Cow::Owned(self.dense(buf).iter().map(|&v| v as f32).collect()),
+        }
+    }
+
+    /// Copies the elements out as f32, in row-major order.
+    pub fn to_vec(&self) -> Vec<f32> {
+        self.values().into_owned()
+    }
+
+    /// Returns a contiguous tensor with the same elements: a cheap clone if this one already is.
+    pub fn to_contiguous(&self) -> Tensor {
+        if self.is_contiguous() {
+            return self.clone();
+        }
+        let storage = match &self.storage {
+            Storage::F32(buf) => Storage::F32(Arc::new(self.gather(buf))),
+            Storage::F16(buf) => Storage::F16(Arc::new(self.gather(buf))),
+            Storage::BF16(buf) => Storage::BF16(Arc::new(self.gather(buf))),
+            Storage::U8(buf) => Storage::U8(Arc::new(self.gather(buf))),
+            Storage::I8(buf) => Storage::I8(Arc::new(self.gather(buf))),
+            Storage::I32(buf) => Storage::I32(Arc::new(self.gather(buf))),
+        };
+        Tensor::from_storage(storage, self.shape.clone())
+    }
+
+    /// Converts the elements to `element_type`, rounding to nearest even when
+    /// narrowing to f16/bf16; integer types truncate and saturate like `as` casts.
+    ///
+    /// Returns a cheap clone when the tensor already has that element type.
+    pub fn to_element_type(&self, element_type: ElementType) -> Tensor {
+        if element_type == self.element_type() {
+            return self.clone();
+        }
+        let values = self.values();
+        let storage = match element_type {
+            ElementType::F32 => Storage::F32(Arc::new(values.into_owned())),
+            ElementType::F16 => Storage::F16(Arc::new(storage::downcast_f16(&values))),
+            ElementType::BF16 => Storage::BF16(Arc::new(storage::downcast_bf16(&values))),
+            ElementType::U8 => Storage::U8(Arc::new(values.iter().map(|&v| v as u8).collect())),
+            ElementType::I8 => Storage::I8(Arc::new(values.iter().map(|&v| v as i8).collect())),
+            ElementType::I32 => Storage::I32(Arc::new(values.iter().map(|&v| v as i32).collect())),
+        };
+        Tensor::from_storage(storage, self.shape.clone())
+    }
+
+    /// Consumes the tensor and returns its elements in row-major order.
+    ///
+    /// The buffer is reused when this tensor is its only owner and spans all of
+    /// it; otherwise the elements are copied.
+    pub fn into_data(self) -> Vec<f32> {
+        match self.into_buffer() {
+            Ok(data) => data,
+            Err(tensor) => tensor.to_vec(),
+        }
+    }
+
+    /// Takes the f32 buffer back if this tensor is its only owner and covers it exactly.
+    pub(crate) fn into_buffer(self) -> Result<Vec<f32>, Tensor> {
+        if self.offset != 0 || self.storage.len() != self.len() || !self.is_contiguous() {
+            return Err(self);
+        }
+        let Tensor { shape, strides, offset, storage } = self;
+        match storage {
+            Storage::F32(buf) => Arc::try_unwrap(buf)
+                .map_err(|buf| Tensor { shape, strides, offset, storage: Storage::F32(buf) }),
+            storage => Err(Tensor { shape, strides, offset, storage }),
+        }
+    }
+
+    /// Reads the elements of `buf` seen by this strided view, in row-major order.
+    fn gather<T: Copy>(&self, buf: &[T]) -> Vec<T> {
+        let len = self.len();
+        let mut out = Vec::with_capacity(len);
+        if len == 0 {
+            return out;
+        }
+        let rank = self.shape.len();
+        if rank == 0 {
+            out.push(buf[self.offset]);
+            return out;
+        }
+        let (inner, inner_stride) = (self.shape[rank - 1], self.strides[rank - 1]);
+        let mut index = vec![0usize; rank - 1];
+        let mut base = self.offset as isize;
+        loop {
+            out.extend((0..inner as isize).map(|i| buf[(base + i * inner_stride) as usize]));
+            // Advance the outer axes like an odometer
+            let mut axis = rank - 1;
+            loop {
+                if axis == 0 {
+                    return out;
                 }
-                if !tns.float_data.is_empty() {
-                    let v = tns.float_data.clone();
-                    assert_eq!(v.len(), count);
-                    return Tensor::new(v, shape);
+                axis -= 1;
+                index[axis] += 1;
+                base += self.strides[axis];
+                if index[axis] < self.shape[axis] {
+                    break;
                 }
-                panic!("No float data in TensorProto");
+                base -= self.strides[axis] * self.shape[axis] as isize;
+                index[axis] = 0;
             }
-            x if x == DataType::Double as i32 || x == 11 => {
-                // convert f64 to f32
-                if !tns.raw_data.is_empty() {
-                    let doubles = tns.raw_data
-                        .chunks_exact(8)
-                        .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32)
-                        .collect::<Vec<f32>>();
-                    assert_eq!(doubles.len(), count);
-                    return Tensor::new(doubles, shape);
+        }
+    }
+
+    /// Views the elements under a new shape with the same element count.
+    ///
+    /// This is O(1) for contiguous tensors; other views are copied first.
+    pub fn reshape(&self, shape: Vec<usize>) -> anyhow::Result<Tensor> {
+        if shape.iter().product::<usize>() != self.len() {
+            return Err(anyhow::anyhow!("Cannot reshape {:?} into {:?}", self.shape, shape));
+        }
+        let base = self.to_contiguous();
+        Ok(Tensor { strides: contiguous_strides(&shape), shape, offset: base.offset, storage: base.storage })
+    }
+
+    /// Views the tensor with its axes reordered, so that axis `i` of the result is axis `perm[i]`.
+    pub fn permute(&self, perm: &[usize]) -> anyhow::Result<Tensor> {
+        let rank = self.shape.len();
+        let mut seen = vec![false; rank];
+        if perm.len() != rank || perm.iter().any(|&p| p >= rank || std::mem::replace(&mut seen[p], true)) {
+            return Err(anyhow::anyhow!("Invalid permutation {:?} for rank {}", perm, rank));
+        }
+        Ok(Tensor {
+            shape: perm.iter().map(|&p| self.shape[p]).collect(),
+            strides: perm.iter().map(|&p| self.strides[p]).collect(),
+            offset: self.offset,
+            storage: self.storage.clone(),
+        })
+    }
+
+    /// Views `len` elements of `axis`, starting at index `start` and moving by `step`.
+    ///
+    /// A negative `step` walks the axis backwards from `start`.
+    pub fn slice_axis(&self, axis: usize, start: usize, len: usize, step: isize) -> anyhow::Result<Tensor> {
+        let dim = *self.shape.get(axis)
+            .ok_or_else(|| anyhow::anyhow!("Axis {} out of range for shape {:?}", axis, self.shape))?;
+        let last = start as isize + (len as isize - 1) * step;
+        if step == 0 || (len > 0 && (start >= dim || last < 0 || last >= dim as isize)) {
+            return Err(anyhow::anyhow!(
+                "Invalid slice (start {}, len {}, step {}) of axis {} with {} elements", start, len, step, axis, dim
+            ));
+        }
+        let mut view = self.clone();
+        view.shape[axis] = len;
+        if len > 0 {
+            view.offset = (self.offset as isize + start as isize * self.strides[axis]) as usize;
+            view.strides[axis] = self.strides[axis] * step;
+        }
+        Ok(view)
+    }
+
+    /// Encodes the tensor as a `TensorProto` named `name`, of its own element
+    /// type (FLOAT, FLOAT16, BFLOAT16, UINT8, INT8 or INT32).
+    pub fn to_proto(&self, name: &str) -> onnx_proto::TensorProto {
+        let raw_data = match &self.storage {
+            Storage::F32(buf) => self.dense(buf).iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Storage::F16(buf) => self.dense(buf).iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Storage::BF16(buf) => self.dense(buf).iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Storage::U8(buf) => self.dense(buf).into_owned(),
+            Storage::I8(buf) => self.dense(buf).iter().map(|&v| v as u8).collect(),
+            Storage::I32(buf) => self.dense(buf).iter().flat_map(|v| v.to_le_bytes()).collect(),
+        };
+        onnx_proto::TensorProto {
+            data_type: self.element_type().data_type() as i32,
+            raw_data,
+            ..self.to_proto_header(name)
+        }
+    }
+
+    /// Encodes the tensor as a `TensorProto` of element type `data_type`, converting the values.
+    ///
+    /// Every numeric type is supported; integer types truncate like `as` casts.
+    pub fn to_proto_as(&self, name: &str, data_type: i32) -> anyhow::Result<onnx_proto::TensorProto> {
+        if data_type == self.element_type().data_type() as i32 {
+            return Ok(self.to_proto(name));
+        }
+        let raw_data = onnx_proto::tensor_proto::DataType::try_from(data_type)
+            .map_err(anyhow::Error::from)
+            .and_then(|dt| dtype::encode_le(dt, &self.values()))
+            .map_err(|e| anyhow::anyhow!("Cannot encode tensor '{}' as data type {}: {}", name, data_type, e))?;
+        Ok(onnx_proto::TensorProto { data_type, raw_data, ..self.to_proto_header(name) })
+    }
+
+    fn to_proto_header(&self, name: &str) -> onnx_proto::TensorProto {
+        onnx_proto::TensorProto {
+            name: name.to_string(),
+            dims: self.shape.iter().map(|&d| d as i64).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Decodes a `TensorProto` of any numeric element type, from `raw_data` or the typed fields.
+    ///
+    /// FLOAT16, BFLOAT16, UINT8, INT8 and INT32 tensors keep their element
+    /// type; other types are converted to f32.
+    pub fn from_proto(tns: &onnx_proto::TensorProto) -> anyhow::Result<Self> {
+        use onnx_proto::tensor_proto::{DataLocation, DataType};
+
+        if tns.data_location == DataLocation::External as i32 {
+            return Err(anyhow::anyhow!("Tensor '{}': external data is not supported", tns.name));
+        }
+        let data_type = DataType::try_from(tns.data_type)
+            .map_err(|_| anyhow::anyhow!("Tensor '{}': unknown data type {}", tns.name, tns.data_type))?;
+        let shape: Vec<usize> = tns.dims.iter().map(|d| *d as usize).collect();
+        let count = shape.iter().product::<usize>();
+
+        if let DataType::Float16 | DataType::Bfloat16 = data_type {
+            // Half-precision values are stored as their bits, in raw_data or int32_data
+            let context = |e: anyhow::Error| anyhow::anyhow!("Tensor '{}': {}", tns.name, e);
+            let bits = || tns.int32_data.iter().map(|&v| v as u16);
+            let check = |len: usize| -> anyhow::Result<()> {
+                if len != count {
+                    return Err(anyhow::anyhow!("Tensor '{}': {} values for shape {:?}", tns.name, len, shape));
                 }
-                // prost has `double_data` field named `double_data`? Many models store raw.
-                panic!("Double TensorProto: only raw_data handling implemented");
+                Ok(())
+            };
+            return Ok(if data_type == DataType::Float16 {
+                let values = if tns.raw_data.is_empty() {
+                    bits().map(f16::from_bits).collect()
+                } else {
+                    dtype::decode_f16_le(&tns.raw_data).map_err(context)?
+                };
+                check(values.len())?;
+                Tensor::from_f16(values, shape)
+            } else {
+                let values = if tns.raw_data.is_empty() {
+                    bits().map(bf16::from_bits).collect()
+                } else {
+                    dtype::decode_bf16_le(&tns.raw_data).map_err(context)?
+                };
+                check(values.len())?;
+                Tensor::from_bf16(values, shape)
+            });
+        }
+
+        if let DataType::Uint8 | DataType::Int8 = data_type {
+            let bytes: Vec<u8> = if !tns.raw_data.is_empty() {
+                tns.raw_data.clone()
+            } else {
+                tns.int32_data.iter().map(|&v| v as u8).collect()
+            };
+            if bytes.len() != count {
+                return Err(anyhow::anyhow!("Tensor '{}': {} values for shape {:?}", tns.name, bytes.len(), shape));
             }
-            x if x == DataType::Int32 as i32 || x == 6 => {
-                if !tns.raw_data.is_empty() {
-                    let ints = tns.raw_data
-                        .chunks_exact(4)
-                        .map(|b| i32::from_le_bytes(b.try_into().unwrap()) as f32)
-                        .collect::<Vec<f32>>();
-                    assert_eq!(ints.len(), count);
-                    return Tensor::new(ints, shape);
-                }
-                if !tns.int32_data.is_empty() {
-                    let v = tns.int32_data.iter().map(|&i| i as f32).collect::<Vec<f32>>();
-                    return Tensor::new(v, shape);
-                }
-                panic!("No int32 data found");
+            return Ok(match data_type {
+                DataType::Uint8 => Tensor::from_u8(bytes, shape),
+                _ => Tensor::from_i8(bytes.into_iter().map(|v| v as i8).collect(), shape),
+            });
+        }
+
+        if data_type == DataType::Int32 {
+            let values = if !tns.raw_data.is_empty() {
+                dtype::decode_i32_le(&tns.raw_data).map_err(|e| anyhow::anyhow!("Tensor '{}': {}", tns.name, e))?
+            } else {
+                tns.int32_data.clone()
+            };
+            if values.len() != count {
+                return Err(anyhow::anyhow!("Tensor '{}': {} values for shape {:?}", tns.name, values.len(), shape));
             }
-            x if x == DataType::Int64 as i32 || x == 7 => {
-                if !tns.raw_data.is_empty() {
-                    let ints = tns.raw_data
-                        .chunks_exact(8)
-                        .map(|b| i64::from_le_bytes(b.try_into().unwrap()) as f32)
-                        .collect::<Vec<f32>>();
-                    assert_eq!(ints.len(), count);
-                    return Tensor::new(ints, shape);
-                }
-                if !tns.int64_data.is_empty() {
-                    let v = tns.int64_data.iter().map(|&i| i as f32).collect::<Vec<f32>>();
-                    return Tensor::new(v, shape);
+            return Ok(Tensor::from_i32(values, shape));
+        }
+
+        let data: Vec<f32> = if !tns.raw_data.is_empty() {
+            dtype::decode_le(data_type, &tns.raw_data)
+                .map_err(|e| anyhow::anyhow!("Tensor '{}': {}", tns.name, e))?
+        } else {
+            match data_type {
+                DataType::Float => tns.float_data.clone(),
+                DataType::Double => tns.double_data.iter().map(|&v| v as f32).collect(),
+                DataType::Int64 => tns.int64_data.iter().map(|&v| v as f32).collect(),
+                DataType::Uint32 | DataType::Uint64 => tns.uint64_data.iter().map(|&v| v as f32).collect(),
+                DataType::Int16 | DataType::Uint16 | DataType::Bool => {
+                    tns.int32_data.iter().map(|&v| v as f32).collect()
                 }
-                panic!("No int64 data found");
-            }
-            _ => {
-                panic!("Unsupported TensorProto data_type: {:?}", tns.data_type);
+                _ => return Err(anyhow::anyhow!("Tensor '{}': unsupported data type {}", tns.name, data_type.as_str_name())),
             }
+        };
+        if data.len() != count {
+            return Err(anyhow::anyhow!(
+                "Tensor '{}': {} values for shape {:?}",
+                tns.name, data.len(), shape
+            ));
         }
+        Ok(Tensor::new(data, shape))
     }
-}
\ No newline at end of file
+}
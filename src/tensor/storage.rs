@@ -0,0 +1,147 @@
+//! Element types and the reference-counted buffers that tensors view.
+//!
+//! Besides f32, tensors can hold IEEE half-precision (`f16`) and bfloat16
+//! (`bf16`) values natively, at half the memory, the 8-bit integers of
+//! quantized models and the int32 accumulators of their integer products.
+//! The half-precision conversion kernels below use the F16C/FP16
+//! instructions when the CPU has them.
+
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use half::slice::HalfFloatSliceExt;
+use std::sync::Arc;
+
+pub use half::{bf16, f16};
+
+/// Type of the elements stored in a tensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ElementType {
+    F32,
+    F16,
+    BF16,
+    U8,
+    I8,
+    I32,
+}
+
+impl ElementType {
+    /// Size in bytes of one element.
+    pub fn size(self) -> usize {
+        match self {
+            ElementType::F32 | ElementType::I32 => 4,
+            ElementType::F16 | ElementType::BF16 => 2,
+            ElementType::U8 | ElementType::I8 => 1,
+        }
+    }
+
+    /// The matching ONNX `TensorProto` data type.
+    pub fn data_type(self) -> DataType {
+        match self {
+            ElementType::F32 => DataType::Float,
+            ElementType::F16 => DataType::Float16,
+            ElementType::BF16 => DataType::Bfloat16,
+            ElementType::U8 => DataType::Uint8,
+            ElementType::I8 => DataType::Int8,
+            ElementType::I32 => DataType::Int32,
+        }
+    }
+
+    /// The element type storing ONNX `data_type` natively, if any.
+    ///
+    /// Other numeric types are held as f32 values.
+    pub fn from_data_type(data_type: DataType) -> Option<Self> {
+        match data_type {
+            DataType::Float => Some(ElementType::F32),
+            DataType::Float16 => Some(ElementType::F16),
+            DataType::Bfloat16 => Some(ElementType::BF16),
+            DataType::Uint8 => Some(ElementType::U8),
+            DataType::Int8 => Some(ElementType::I8),
+            DataType::Int32 => Some(ElementType::I32),
+            _ => None,
+        }
+    }
+
+    /// Whether this is one of the 8-bit integer types of quantized tensors.
+    pub fn is_integer(self) -> bool {
+        matches!(self, ElementType::U8 | ElementType::I8)
+    }
+
+    /// Whether this is f32 or one of the half-precision types.
+    pub fn is_float(self) -> bool {
+        matches!(self, ElementType::F32 | ElementType::F16 | ElementType::BF16)
+    }
+}
+
+/// A shared buffer of one of the element types.
+#[derive(Debug, Clone)]
+pub(crate) enum Storage {
+    F32(Arc<Vec<f32>>),
+    F16(Arc<Vec<f16>>),
+    BF16(Arc<Vec<bf16>>),
+    U8(Arc<Vec<u8>>),
+    I8(Arc<Vec<i8>>),
+    I32(Arc<Vec<i32>>),
+}
+
+impl Storage {
+    pub(crate) fn element_type(&self) -> ElementType {
+        match self {
+            Storage::F32(_) => ElementType::F32,
+            Storage::F16(_) => ElementType::F16,
+            Storage::BF16(_) => ElementType::BF16,
+            Storage::U8(_) => ElementType::U8,
+            Storage::I8(_) => ElementType::I8,
+            Storage::I32(_) => ElementType::I32,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Storage::F32(buf) => buf.len(),
+            Storage::F16(buf) => buf.len(),
+            Storage::BF16(buf) => buf.len(),
+            Storage::U8(buf) => buf.len(),
+            Storage::I8(buf) => buf.len(),
+            Storage::I32(buf) => buf.len(),
+        }
+    }
+
+    pub(crate) fn ptr_eq(&self, other: &Storage) -> bool {
+        match (self, other) {
+            (Storage::F32(a), Storage::F32(b)) => Arc::ptr_eq(a, b),
+            (Storage::F16(a), Storage::F16(b)) => Arc::ptr_eq(a, b),
+            (Storage::BF16(a), Storage::BF16(b)) => Arc::ptr_eq(a, b),
+            (Storage::U8(a), Storage::U8(b)) => Arc::ptr_eq(a, b),
+            (Storage::I8(a), Storage::I8(b)) => Arc::ptr_eq(a, b),
+            (Storage::I32(a), Storage::I32(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// Widens half-precision values to f32 (exact).
+pub(crate) fn upcast_f16(src: &[f16]) -> Vec<f32> {
+    let mut out = vec![0.0; src.len()];
+    src.convert_to_f32_slice(&mut out);
+    out
+}
+
+/// Rounds f32 values to the nearest half-precision value (ties to even).
+pub(crate) fn downcast_f16(src: &[f32]) -> Vec<f16> {
+    let mut out = vec![f16::ZERO; src.len()];
+    out.convert_from_f32_slice(src);
+    out
+}
+
+/// Widens bfloat16 values to f32 (exact).
+pub(crate) fn upcast_bf16(src: &[bf16]) -> Vec<f32> {
+    let mut out = vec![0.0; src.len()];
+    src.convert_to_f32_slice(&mut out);
+    out
+}
+
+/// Rounds f32 values to the nearest bfloat16 value (ties to even).
+pub(crate) fn downcast_bf16(src: &[f32]) -> Vec<bf16> {
+    let mut out = vec![bf16::ZERO; src.len()];
+    out.convert_from_f32_slice(src);
+    out
+}
@@ -0,0 +1,98 @@
+//! Graphs built in code run like loaded ones, and invalid graphs are rejected as they are built.
+
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::runtime::InferenceSession;
+use neuroxyde::tensor::{f16, ElementType, Tensor};
+
+#[test]
+fn built_graph_runs() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("built");
+    builder
+        .input("a", DataType::Float, &[-1, 2, 2])?
+        .input("b", DataType::Float, &[2, 3])?
+        .initializer_as("target", Tensor::new(vec![-1.0, 2.0], vec![2]), DataType::Int64)?
+        .initializer("bias", Tensor::new(vec![0.5, -0.5, 1.0], vec![3]))?
+        .node("Reshape", &["a", "target"], &["flat"], &[])?
+        .node("Gemm", &["flat", "b", "bias"], &["g"], &[("alpha", 2.0f32.into()), ("transB", 0i64.into())])?
+        .domain_node("com.microsoft", "Gelu", &["g"], &["y"], &[])?
+        .output("y")?
+        .output("flat")?;
+
+    let model = builder.to_model()?;
+    let domains: Vec<&str> = model.opset_import.iter().map(|o| o.domain.as_str()).collect();
+    assert_eq!(domains, ["", "com.microsoft"]);
+
+    let session = InferenceSession::new(builder.build()?)?;
+    let a = Tensor::new(vec![1.0, 0.0, 0.0, 1.0], vec![1, 2, 2]);
+    let b = Tensor::new(vec![1.0, 2.0, 3.0, -1.0, 0.5, 0.0], vec![2, 3]);
+    let outputs = session.run(&[a, b])?;
+
+    assert_eq!(outputs[1].shape(), [2, 2]);
+    assert_eq!(outputs[0].shape(), [2, 3]);
+    // g = 2 * flat @ b + bias
+    let g = [2.5, 3.5, 7.0, -1.5, 0.5, 1.0];
+    let gelu = |x: f32| 0.5 * x * (1.0 + erf(x / std::f32::consts::SQRT_2));
+    for (y, x) in outputs[0].data().iter().zip(g) {
+        assert!((y - gelu(x)).abs() < 1e-4, "{} vs {}", y, gelu(x));
+    }
+    Ok(())
+}
+
+/// Abramowitz & Stegun 7.1.26 (absolute error below 1.5e-7).
+fn erf(x: f32) -> f32 {
+    let x = x as f64;
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    (1.0 - poly * (-x * x).exp()).copysign(x) as f32
+}
+
+#[test]
+fn builder_validates_as_it_goes() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("invalid");
+    builder.input("x", DataType::Float, &[4])?;
+
+    let err = builder.node("Relu", &["missing"], &["y"], &[]).err().unwrap();
+    assert!(err.to_string().contains("'missing' is not defined"), "{}", err);
+    let err = builder.node("NotAnOp", &["x"], &["y"], &[]).err().unwrap();
+    assert!(err.to_string().contains("unsupported operator"), "{}", err);
+    let err = builder.node("Relu", &["x"], &["x"], &[]).err().unwrap();
+    assert!(err.to_string().contains("already defined"), "{}", err);
+    let err = builder.node("LeakyRelu", &["x"], &["y"], &[("alpha", 0.1f32.into()), ("alpha", 0.2f32.into())]).err().unwrap();
+    assert!(err.to_string().contains("given twice"), "{}", err);
+    // A bad later output defines none of the earlier ones
+    let err = builder.node("Dropout", &["x"], &["y", "x"], &[]).err().unwrap();
+    assert!(err.to_string().contains("already defined"), "{}", err);
+    let err = builder.node("Dropout", &["x"], &["y", "y"], &[]).err().unwrap();
+    assert!(err.to_string().contains("output 'y' given twice"), "{}", err);
+    assert!(builder.output("y").is_err());
+    assert!(builder.build().is_err());
+
+    // Failed calls leave the builder untouched
+    builder.node("Relu", &["x"], &["y"], &[])?.output("y")?;
+    let graph = builder.build()?;
+    assert_eq!(graph.nodes.len(), 1);
+    assert_eq!(graph.nodes[0].name, "Relu_0");
+    Ok(())
+}
+
+#[test]
+fn initializers_keep_their_element_type() -> anyhow::Result<()> {
+    let half = Tensor::from_f16(vec![f16::from_f32(0.5), f16::from_f32(-2.0)], vec![2]);
+    let mut builder = Graph::builder("typed");
+    builder
+        .input("x", DataType::Float, &[2])?
+        .initializer("half", half)?
+        .initializer("codes", Tensor::from_u8(vec![3, 250], vec![2]))?
+        .initializer("counts", Tensor::from_i32(vec![1 << 30, -7], vec![2]))?
+        .initializer_as("target", Tensor::new(vec![2.0], vec![1]), DataType::Int64)?
+        .node("Relu", &["x"], &["y"], &[])?
+        .output("y")?;
+
+    let initializers = builder.to_model()?.graph.unwrap().initializer;
+    let types: Vec<i32> = initializers.iter().map(|t| t.data_type).collect();
+    assert_eq!(types, [DataType::Float16 as i32, DataType::Uint8 as i32, DataType::Int32 as i32, DataType::Int64 as i32]);
+    assert_eq!(Tensor::from_proto(&initializers[0])?.element_type(), ElementType::F16);
+    assert_eq!(Tensor::from_proto(&initializers[2])?.data_i32(), [1 << 30, -7]);
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! End-to-end runs of the `neuroxyde` binary.
+
+#![cfg(feature = "cli")]
+
+use neuroxyde::graph::Graph;
+use neuroxyde::loader::ModelLoader;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::onnx::onnx_proto::NodeProto;
+use neuroxyde::tensor::Tensor;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn workdir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("neuroxyde-cli-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// y = Relu(x @ w + b), with a symbolic batch dimension
+fn save_model(path: &Path) -> anyhow::Result<()> {
+    let mut builder = Graph::builder("mlp");
+    builder
+        .input("x", DataType::Float, &[-1, 3])?
+        .initializer("w", Tensor::new(vec![1.0, -1.0, 0.5, 2.0, 0.0, -1.0], vec![3, 2]))?
+        .initializer("b", Tensor::new(vec![0.5, -0.5], vec![2]))?
+        .node("MatMul", &["x", "w"], &["xw"], &[])?
+        .node("Add", &["xw", "b"], &["z"], &[])?
+        .node("Relu", &["z"], &["y"], &[])?
+        .output("y")?;
+    ModelLoader { model: builder.to_model()? }.save_to_file(path)
+}
+
+fn neuroxyde(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_neuroxyde")).args(args).output().unwrap();
+    let text = String::from_utf8_lossy(&output.stdout).to_string() + &String::from_utf8_lossy(&output.stderr);
+    (output.status.success(), text)
+}
+
+#[test]
+fn inspect_validate_run_bench_optimize() -> anyhow::Result<()> {
+    let dir = workdir("ok");
+    let model = dir.join("mlp.onnx");
+    save_model(&model)?;
+    let model = model.to_str().unwrap();
+
+    let (ok, out) = neuroxyde(&["inspect", model]);
+    assert!(ok, "{}", out);
+    assert!(out.contains("ai.onnx v17"), "{}", out);
+    assert!(out.contains("x: FLOAT[?, 3]"), "{}", out);
+    assert!(out.lines().any(|l| l.trim_start().starts_with("MatMul") && l.trim_end().ends_with('1')), "{}", out);
+    assert!(!out.contains("UNSUPPORTED"), "{}", out);
+
+    let (ok, out) = neuroxyde(&["validate", model]);
+    assert!(ok, "{}", out);
+    assert!(out.contains(": OK"), "{}", out);
+
+    let input = dir.join("x.npy");
+    Tensor::new(vec![1.0, 2.0, 3.0, -1.0, 0.0, 1.0], vec![2, 3]).write_npy(&input)?;
+    let (ok, out) = neuroxyde(&["run", model, "-i", &format!("x={}", input.display()), "-o", dir.to_str().unwrap()]);
+    assert!(ok, "{}", out);
+    let y = Tensor::read_npy(dir.join("y.npy"))?;
+    assert_eq!(y.shape(), [2, 2]);
+    assert_eq!(y.data(), [2.5, 0.0, 0.0, 0.0]);
+
+    let archive = dir.join("inputs.npz");
+    Tensor::write_npz(&archive, &[("x", &Tensor::new(vec![0.0, 0.0, 1.0], vec![1, 3]))], true)?;
+    let (ok, out) = neuroxyde(&["run", model, "-i", archive.to_str().unwrap(), "-o", dir.to_str().unwrap(), "--format", "pb"]);
+    assert!(ok, "{}", out);
+    assert_eq!(Tensor::read_pb(dir.join("y.pb"))?.data(), [0.5, 0.0]);
+
+    let (ok, out) = neuroxyde(&["bench", model, "-n", "3", "--warmup", "1", "--dim", "4"]);
+    assert!(ok, "{}", out);
+    assert!(out.contains("Input shape: [4, 3]"), "{}", out);
+    assert!(out.contains("p99"), "{}", out);
+    assert!(out.contains("inferences/s"), "{}", out);
+
+    let optimized = dir.join("mlp.opt.onnx");
+    let (ok, out) = neuroxyde(&["optimize", model, optimized.to_str().unwrap()]);
+    assert!(ok, "{}", out);
+    let graph = Graph::from_model(&ModelLoader::load_from_file(&optimized)?)?;
+    let ops: Vec<&str> = graph.nodes.iter().map(|n| n.op_type.as_str()).collect();
+    assert_eq!(ops, ["FusedGemm"]);
+
+    let quantized = dir.join("mlp.int8.onnx");
+    let (ok, out) = neuroxyde(&["quantize", model, quantized.to_str().unwrap(), "-i", input.to_str().unwrap(), "--per-channel"]);
+    assert!(ok, "{}", out);
+    let graph = Graph::from_model(&ModelLoader::load_from_file(&quantized)?)?;
+    assert!(graph.nodes.iter().any(|n| n.op_type == "QuantizeLinear"));
+    assert_eq!(graph.initializers["w_scale"].len(), 2);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn validate_reports_problems() -> anyhow::Result<()> {
+    let dir = workdir("invalid");
+    let path = dir.join("broken.onnx");
+    save_model(&path)?;
+    let mut loader = ModelLoader::load_from_file(&path)?;
+    let graph = loader.model.graph.as_mut().unwrap();
+    graph.node.push(NodeProto {
+        op_type: "Frobnicate".to_string(),
+        name: "mystery".to_string(),
+        input: vec!["nowhere".to_string()],
+        output: vec!["y".to_string()],
+        ..Default::default()
+    });
+    // Defined as a function, so not a problem
+    graph.node.push(NodeProto {
+        op_type: "HardSwish".to_string(),
+        input: vec!["y".to_string()],
+        output: vec!["swished".to_string()],
+        ..Default::default()
+    });
+    loader.save_to_file(&path)?;
+
+    let (ok, out) = neuroxyde(&["validate", path.to_str().unwrap()]);
+    assert!(!ok, "{}", out);
+    assert!(out.contains("unsupported operator 'Frobnicate'"), "{}", out);
+    assert!(out.contains("input 'nowhere' is never defined"), "{}", out);
+    assert!(out.contains("'y' is produced by both"), "{}", out);
+    assert!(!out.contains("HardSwish"), "{}", out);
+    let (_, out) = neuroxyde(&["inspect", path.to_str().unwrap()]);
+    assert!(out.contains("UNSUPPORTED"), "{}", out);
+    assert!(out.lines().any(|l| l.contains("HardSwish") && l.ends_with("(function)")), "{}", out);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+//! Builders for the hand-written graphs of the optimizer and runtime tests.
+
+// Each test crate compiles its own copy and uses only some of the helpers
+#![allow(dead_code)]
+
+use neuroxyde::onnx::onnx_proto::{
+    attribute_proto, tensor_shape_proto, type_proto, AttributeProto, NodeProto, TensorShapeProto, TypeProto,
+    ValueInfoProto,
+};
+
+/// A single-output node, named after its output.
+pub fn node(op_type: &str, inputs: &[&str], output: &str) -> NodeProto {
+    node_with(op_type, inputs, output, Vec::new())
+}
+
+/// A single-output node with attributes, named after its output.
+pub fn node_with(op_type: &str, inputs: &[&str], output: &str, attribute: Vec<AttributeProto>) -> NodeProto {
+    NodeProto {
+        op_type: op_type.to_string(),
+        name: output.to_string(),
+        input: inputs.iter().map(|s| s.to_string()).collect(),
+        output: vec![output.to_string()],
+        attribute,
+        ..Default::default()
+    }
+}
+
+pub fn int(name: &str, value: i64) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        i: value,
+        r#type: attribute_proto::AttributeType::Int as i32,
+        ..Default::default()
+    }
+}
+
+pub fn ints(name: &str, values: &[i64]) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        ints: values.to_vec(),
+        r#type: attribute_proto::AttributeType::Ints as i32,
+        ..Default::default()
+    }
+}
+
+/// A FLOAT value of fixed `shape`.
+pub fn float_input(name: &str, shape: &[i64]) -> ValueInfoProto {
+    let dim = shape.iter()
+        .map(|&d| tensor_shape_proto::Dimension {
+            value: Some(tensor_shape_proto::dimension::Value::DimValue(d)),
+            ..Default::default()
+        })
+        .collect();
+    ValueInfoProto {
+        name: name.to_string(),
+        r#type: Some(TypeProto {
+            value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                elem_type: 1,
+                shape: Some(TensorShapeProto { dim }),
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,97 @@
+//! Constant folding must remove constant subgraphs without changing the outputs.
+
+mod common;
+
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::{attribute_proto, AttributeProto, GraphProto, TensorProto};
+use neuroxyde::runtime::{GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+use std::collections::HashMap;
+use common::{float_input, int, ints, node_with};
+
+fn constant_tensor(values: &[i64]) -> AttributeProto {
+    AttributeProto {
+        name: "value".to_string(),
+        t: Some(TensorProto {
+            dims: vec![values.len() as i64],
+            data_type: 7,
+            int64_data: values.to_vec(),
+            ..Default::default()
+        }),
+        r#type: attribute_proto::AttributeType::Tensor as i32,
+        ..Default::default()
+    }
+}
+
+/// y = Reshape(x, Concat(Unsqueeze(Gather(Shape(x), 0)), [-1])) @ Transpose(w)
+fn graph() -> Graph {
+    let nodes = vec![
+        node_with("Shape", &["x"], "shape", vec![]),
+        node_with("Constant", &[], "zero", vec![AttributeProto {
+            name: "value_int".to_string(),
+            i: 0,
+            r#type: attribute_proto::AttributeType::Int as i32,
+            ..Default::default()
+        }]),
+        node_with("Gather", &["shape", "zero"], "batch", vec![int("axis", 0)]),
+        node_with("Unsqueeze", &["batch"], "batch_1d", vec![ints("axes", &[0])]),
+        node_with("Constant", &[], "minus_one", vec![constant_tensor(&[-1])]),
+        node_with("Concat", &["batch_1d", "minus_one"], "target", vec![int("axis", 0)]),
+        node_with("Reshape", &["x", "target"], "flat", vec![]),
+        node_with("Transpose", &["w"], "w_t", vec![ints("perm", &[1, 0])]),
+        node_with("MatMul", &["flat", "w_t"], "y", vec![]),
+    ];
+    let proto = GraphProto {
+        node: nodes.clone(),
+        input: vec![float_input("x", &[2, 3, 4])],
+        ..Default::default()
+    };
+    let w: Vec<f32> = (0..5 * 12).map(|i| ((i * 7 % 11) as f32 - 5.0) * 0.25).collect();
+    Graph {
+        proto,
+        nodes,
+        initializers: HashMap::from([("w".to_string(), Tensor::new(w, vec![5, 12]))]),
+        inputs: vec!["x".to_string()],
+        outputs: vec!["y".to_string()],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn folds_constant_subgraphs() {
+    let input = vec![Tensor::new((0..24).map(|i| i as f32 * 0.5 - 3.0).collect(), vec![2, 3, 4])];
+
+    let options = SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::DisableAll);
+    let reference = InferenceSession::with_options(graph(), options).unwrap();
+    assert_eq!(reference.graph.nodes.len(), 9);
+    let expected = reference.run(&input).unwrap();
+
+    let session = InferenceSession::new(graph()).unwrap();
+    let remaining: Vec<&str> = session.graph.nodes.iter().map(|n| n.op_type.as_str()).collect();
+    assert_eq!(remaining, ["Reshape", "MatMul"]);
+    assert_eq!(session.graph.initializers["target"].data(), &[2.0, -1.0]);
+    assert_eq!(session.graph.initializers["w_t"].shape(), &[12, 5]);
+
+    let actual = session.run(&input).unwrap();
+    assert_eq!(actual[0].shape(), &[2, 5]);
+    assert_eq!(actual[0].data(), expected[0].data());
+}
+
+#[test]
+fn kernel_errors_keep_the_node() -> anyhow::Result<()> {
+    // Gather past the end of a constant: the session keeps it for the run to report
+    let mut g = graph();
+    g.initializers.insert("table".to_string(), Tensor::new(vec![1.0, 2.0, 3.0], vec![3]));
+    g.initializers.insert("index".to_string(), Tensor::new(vec![5.0], vec![]));
+    let gather = node_with("Gather", &["table", "index"], "picked", vec![int("axis", 0)]);
+    g.nodes.push(gather.clone());
+    g.proto.node.push(gather);
+    g.nodes.push(node_with("Neg", &["picked"], "picked_out", vec![]));
+    g.outputs.push("picked_out".to_string());
+
+    let session = InferenceSession::new(g)?;
+    assert!(session.graph.nodes.iter().any(|n| n.name == "picked"));
+    let err = session.run(&[Tensor::new(vec![0.0; 24], vec![2, 3, 4])]).err().unwrap();
+    assert!(err.to_string().contains("index 5 out of range"), "{}", err);
+    Ok(())
+}
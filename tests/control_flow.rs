@@ -0,0 +1,251 @@
+//! If, Loop and Scan: subgraph execution, outer-scope capture and loop-carried values.
+
+use neuroxyde::graph::{implicit_inputs, Graph};
+use neuroxyde::loader::ModelLoader;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::runtime::{ExecutionMode, GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+
+fn scalar(v: f32) -> Tensor {
+    Tensor::new(vec![v], vec![])
+}
+
+/// Every combination of optimization level and execution mode.
+fn sessions(graph: &Graph) -> anyhow::Result<Vec<InferenceSession>> {
+    let mut sessions = Vec::new();
+    for level in [GraphOptimizationLevel::DisableAll, GraphOptimizationLevel::All] {
+        for mode in [ExecutionMode::Sequential, ExecutionMode::Parallel] {
+            let options = SessionOptions::new().with_graph_optimization_level(level).with_execution_mode(mode);
+            sessions.push(InferenceSession::with_options(graph.clone(), options)?);
+        }
+    }
+    Ok(sessions)
+}
+
+#[test]
+fn if_runs_the_selected_branch_with_captured_values() -> anyhow::Result<()> {
+    // The branches read `xi` and `z_w`, which no top-level node consumes
+    let mut then_branch = Graph::builder("then");
+    then_branch
+        .capture("xi")?
+        .capture("z_w")?
+        .node("Add", &["xi", "z_w"], &["sum"], &[])?
+        .output("sum")?;
+    let mut else_branch = Graph::builder("else");
+    else_branch
+        .capture("xi")?
+        .node("Neg", &["xi"], &["negated"], &[])?
+        .output("negated")?;
+
+    let mut builder = Graph::builder("if");
+    builder
+        .input("flag", DataType::Bool, &[])?
+        .input("x", DataType::Float, &[3])?
+        .initializer("w", Tensor::new(vec![10.0, 20.0, 30.0], vec![3]))?
+        .initializer("z_w", Tensor::new(vec![10.0, 20.0, 30.0], vec![3]))?
+        .node("Identity", &["x"], &["xi"], &[])?
+        .node("Mul", &["x", "w"], &["scaled"], &[])?
+        .node("If", &["flag"], &["y"], &[
+            ("then_branch", then_branch.to_subgraph()?.into()),
+            ("else_branch", else_branch.to_subgraph()?.into()),
+        ])?
+        .output("y")?
+        .output("scaled")?;
+    let graph = builder.build()?;
+    assert_eq!(implicit_inputs(&graph.nodes[2]), ["xi", "z_w"]);
+    assert!(graph.initializers.contains_key("z_w"));
+
+    let x = Tensor::new(vec![1.0, 2.0, 3.0], vec![3]);
+    for session in sessions(&graph)? {
+        let outputs = session.run(&[scalar(1.0), x.clone()])?;
+        assert_eq!(outputs[0].data(), [11.0, 22.0, 33.0]);
+        assert_eq!(outputs[1].data(), [10.0, 40.0, 90.0]);
+        let outputs = session.run(&[scalar(0.0), x.clone()])?;
+        assert_eq!(outputs[0].data(), [-1.0, -2.0, -3.0]);
+    }
+    Ok(())
+}
+
+/// Loop body: `acc += x` (x captured), scanning the running sum, and
+/// stopping once the iteration number reaches `stop`.
+fn accumulate_body() -> anyhow::Result<Graph> {
+    let mut body = Graph::builder("accumulate");
+    body
+        .input("i", DataType::Int64, &[])?
+        .input("cond_in", DataType::Bool, &[])?
+        .input("acc", DataType::Float, &[2])?
+        .capture("x")?
+        .capture("stop")?
+        .node("Add", &["acc", "x"], &["acc_out"], &[])?
+        .node("Sub", &["stop", "i"], &["cond_out"], &[])?
+        .node("Identity", &["acc_out"], &["partial"], &[])?
+        .output("cond_out")?
+        .output("acc_out")?
+        .output("partial")?;
+    let mut builder = Graph::builder("loop");
+    builder
+        .input("trip_count", DataType::Int64, &[])?
+        .input("x", DataType::Float, &[2])?
+        .initializer_as("stop", scalar(3.0), DataType::Int64)?
+        .initializer("acc0", Tensor::new(vec![0.5, -0.5], vec![2]))?
+        .node("Loop", &["trip_count", "", "acc0"], &["acc", "partials"], &[("body", body.to_subgraph()?.into())])?
+        .output("acc")?
+        .output("partials")?;
+    builder.build()
+}
+
+#[test]
+fn loop_carries_values_and_stacks_scan_outputs() -> anyhow::Result<()> {
+    let graph = accumulate_body()?;
+    let x = Tensor::new(vec![1.0, 2.0], vec![2]);
+    for session in sessions(&graph)? {
+        // Bounded by the trip count
+        let outputs = session.run(&[scalar(2.0), x.clone()])?;
+        assert_eq!(outputs[0].data(), [2.5, 3.5]);
+        assert_eq!(outputs[1].shape(), [2, 2]);
+        assert_eq!(outputs[1].data(), [1.5, 1.5, 2.5, 3.5]);
+
+        // Stopped by the condition after iteration 3
+        let outputs = session.run(&[scalar(10.0), x.clone()])?;
+        assert_eq!(outputs[0].data(), [4.5, 7.5]);
+        assert_eq!(outputs[1].shape(), [4, 2]);
+
+        // No iteration at all: the carried values come back unchanged
+        let outputs = session.run(&[scalar(0.0), x.clone()])?;
+        assert_eq!(outputs[0].data(), [0.5, -0.5]);
+        assert_eq!(outputs[1].shape(), [0]);
+    }
+    Ok(())
+}
+
+#[test]
+fn scan_walks_axes_in_both_directions() -> anyhow::Result<()> {
+    // Running sum over the columns of x, most recent first
+    let mut body = Graph::builder("cumsum");
+    body
+        .input("state", DataType::Float, &[2])?
+        .input("column", DataType::Float, &[2])?
+        .node("Add", &["state", "column"], &["next"], &[])?
+        .node("Identity", &["next"], &["partial"], &[])?
+        .output("next")?
+        .output("partial")?;
+    let mut builder = Graph::builder("scan");
+    builder
+        .input("x", DataType::Float, &[2, 3])?
+        .initializer("init", Tensor::new(vec![0.0, 100.0], vec![2]))?
+        .node("Scan", &["init", "x"], &["total", "partials"], &[
+            ("body", body.to_subgraph()?.into()),
+            ("num_scan_inputs", 1i64.into()),
+            ("scan_input_axes", vec![-1i64].into()),
+            ("scan_input_directions", vec![1i64].into()),
+            ("scan_output_axes", vec![1i64].into()),
+        ])?
+        .output("total")?
+        .output("partials")?;
+    let graph = builder.build()?;
+
+    let x = Tensor::new(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0], vec![2, 3]);
+    for session in sessions(&graph)? {
+        let outputs = session.run(std::slice::from_ref(&x))?;
+        assert_eq!(outputs[0].data(), [6.0, 115.0]);
+        assert_eq!(outputs[1].shape(), [2, 3]);
+        assert_eq!(outputs[1].data(), [3.0, 5.0, 6.0, 106.0, 111.0, 115.0]);
+    }
+    Ok(())
+}
+
+#[test]
+fn nested_subgraphs_capture_across_scopes_and_survive_saving() -> anyhow::Result<()> {
+    // Loop body holding an If whose branches read the loop input `i` and the top-level `bias`
+    let mut then_branch = Graph::builder("then");
+    then_branch
+        .capture("acc")?
+        .capture("bias")?
+        .node("Add", &["acc", "bias"], &["biased"], &[])?
+        .output("biased")?;
+    let mut else_branch = Graph::builder("else");
+    else_branch.capture("acc")?.output("acc")?;
+
+    let mut body = Graph::builder("body");
+    body
+        .input("i", DataType::Int64, &[])?
+        .input("cond_in", DataType::Bool, &[])?
+        .input("acc", DataType::Float, &[1])?
+        .capture("bias")?
+        .capture("parity")?
+        .node("Mul", &["i", "parity"], &["odd"], &[])?
+        .node("If", &["odd"], &["acc_out"], &[
+            ("then_branch", then_branch.to_subgraph()?.into()),
+            ("else_branch", else_branch.to_subgraph()?.into()),
+        ])?
+        .output("cond_in")?
+        .output("acc_out")?;
+
+    let mut builder = Graph::builder("nested");
+    builder
+        .input("acc0", DataType::Float, &[1])?
+        .initializer_as("trips", scalar(4.0), DataType::Int64)?
+        .initializer("bias", Tensor::new(vec![1.5], vec![1]))?
+        .initializer_as("parity", scalar(1.0), DataType::Int64)?
+        .node("Loop", &["trips", "", "acc0"], &["acc"], &[("body", body.to_subgraph()?.into())])?
+        .output("acc")?;
+    let model = builder.to_model()?;
+    let graph = Graph::from_model(&ModelLoader { model: model.clone() })?;
+    assert_eq!(implicit_inputs(&graph.nodes[0]), ["parity", "bias"]);
+
+    // Every iteration but the first (i = 0) adds the bias
+    for session in sessions(&graph)? {
+        assert_eq!(session.run(&[Tensor::new(vec![1.0], vec![1])])?[0].data(), [5.5]);
+    }
+    let reloaded = Graph::from_model(&ModelLoader { model: Graph::from_model(&ModelLoader { model })?.to_model()? })?;
+    assert!(reloaded.initializers.contains_key("bias"));
+    let session = InferenceSession::new(reloaded)?;
+    assert_eq!(session.run(&[Tensor::new(vec![0.0], vec![1])])?[0].data(), [4.5]);
+    Ok(())
+}
+
+#[test]
+fn subgraph_errors_are_reported() -> anyhow::Result<()> {
+    let mut branch = Graph::builder("branch");
+    branch.capture("missing")?.node("Neg", &["missing"], &["y"], &[])?.output("y")?;
+    let mut builder = Graph::builder("broken");
+    builder.input("flag", DataType::Bool, &[])?;
+    let err = builder
+        .node("If", &["flag"], &["y"], &[("then_branch", branch.to_subgraph()?.into()), ("else_branch", branch.to_subgraph()?.into())])
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("a subgraph reads 'missing'"), "{}", err);
+
+    // A branch returning a different number of values than the node declares
+    let mut branch = Graph::builder("branch");
+    branch.capture("x")?.node("Neg", &["x"], &["a"], &[])?.node("Abs", &["x"], &["b"], &[])?.output("a")?.output("b")?;
+    let mut builder = Graph::builder("mismatch");
+    builder
+        .input("flag", DataType::Bool, &[])?
+        .input("x", DataType::Float, &[1])?
+        .node("If", &["flag"], &["y"], &[("then_branch", branch.to_subgraph()?.into()), ("else_branch", branch.to_subgraph()?.into())])?
+        .output("y")?;
+    let session = InferenceSession::new(builder.build()?)?;
+    let err = session.run(&[scalar(1.0), Tensor::new(vec![1.0], vec![1])]).err().unwrap();
+    assert!(err.to_string().contains("then_branch has 2 outputs"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn bodies_are_built_at_session_creation() -> anyhow::Result<()> {
+    // A then_branch whose two nodes feed each other fails even if it never runs
+    let mut branch = Graph::builder("branch");
+    branch.capture("x")?.node("Neg", &["x"], &["a"], &[])?.node("Abs", &["a"], &["b"], &[])?.output("b")?;
+    let mut cyclic = branch.to_subgraph()?;
+    cyclic.node[0].input[0] = "b".to_string();
+
+    let mut builder = Graph::builder("cyclic");
+    builder
+        .input("flag", DataType::Bool, &[])?
+        .input("x", DataType::Float, &[1])?
+        .node("If", &["flag"], &["y"], &[("then_branch", cyclic.into()), ("else_branch", branch.to_subgraph()?.into())])?
+        .output("y")?;
+    let err = InferenceSession::new(builder.build()?).err().unwrap();
+    assert!(err.to_string().contains("cycle"), "{}", err);
+    Ok(())
+}
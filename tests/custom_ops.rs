@@ -0,0 +1,147 @@
+//! User-defined operators: custom domains, per-domain dispatch and sessions
+//! created with a user registry.
+
+use neuroxyde::graph::Graph;
+use neuroxyde::loader::ModelLoader;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::onnx::onnx_proto::NodeProto;
+use neuroxyde::ops::operator::{OpContext, Operator};
+use neuroxyde::ops::registry::OpRegistry;
+use neuroxyde::runtime::{ExecutionMode, GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+
+/// `y = x * factor`, reading its `factor` attribute
+struct Scale;
+
+impl Operator for Scale {
+    fn run(&self, inputs: &[&Tensor], node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        let factor = node.attribute.iter().find(|a| a.name == "factor").map_or(1.0, |a| a.f);
+        Ok(Tensor::new(inputs[0].data().iter().map(|v| v * factor).collect(), inputs[0].shape().to_vec()))
+    }
+}
+
+/// Stands in for the standard Relu under another domain: `y = -x`
+struct Negate;
+
+impl Operator for Negate {
+    fn run(&self, inputs: &[&Tensor], _node: &NodeProto, _ctx: &OpContext) -> anyhow::Result<Tensor> {
+        Ok(Tensor::new(inputs[0].data().iter().map(|v| -v).collect(), inputs[0].shape().to_vec()))
+    }
+}
+
+fn registry() -> OpRegistry {
+    let mut registry = OpRegistry::new();
+    registry.register_in_domain("com.example", "Scale", Scale);
+    registry.register_in_domain("com.example", "Relu", Negate);
+    registry
+}
+
+fn sessions(graph: &Graph) -> anyhow::Result<Vec<InferenceSession>> {
+    let mut sessions = Vec::new();
+    for level in [GraphOptimizationLevel::DisableAll, GraphOptimizationLevel::All] {
+        for mode in [ExecutionMode::Sequential, ExecutionMode::Parallel] {
+            let options = SessionOptions::new().with_graph_optimization_level(level).with_execution_mode(mode);
+            sessions.push(InferenceSession::with_registry(graph.clone(), options, registry())?);
+        }
+    }
+    Ok(sessions)
+}
+
+#[test]
+fn nodes_dispatch_on_their_domain() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("custom").with_registry(registry());
+    builder
+        .input("x", DataType::Float, &[3])?
+        .domain_node("com.example", "Scale", &["x"], &["scaled"], &[("factor", 3.0f32.into())])?
+        .node("Relu", &["scaled"], &["relu"], &[])?
+        .domain_node("com.example", "Relu", &["scaled"], &["negated"], &[])?
+        .domain_node("ai.onnx", "Relu", &["negated"], &["aliased"], &[])?
+        .output("relu")?
+        .output("negated")?
+        .output("aliased")?;
+    let graph = builder.build()?;
+    assert!(graph.to_model()?.opset_import.iter().any(|o| o.domain == "com.example"));
+
+    let x = Tensor::new(vec![-1.0, 0.5, 2.0], vec![3]);
+    for session in sessions(&graph)? {
+        let outputs = session.run(std::slice::from_ref(&x))?;
+        assert_eq!(outputs[0].data(), [0.0, 1.5, 6.0]);
+        assert_eq!(outputs[1].data(), [3.0, -1.5, -6.0]);
+        assert_eq!(outputs[2].data(), [3.0, 0.0, 0.0]);
+    }
+
+    // The default session has no kernel for the custom domain
+    let session = InferenceSession::new(graph)?;
+    let err = session.run(&[x]).err().unwrap();
+    assert!(err.to_string().contains("Unsupported operator: Scale"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn custom_operators_run_inside_subgraphs() -> anyhow::Result<()> {
+    // acc = Scale<factor = 2>(acc + x), three times
+    let mut body = Graph::builder("body").with_registry(registry());
+    body
+        .input("i", DataType::Int64, &[])?
+        .input("cond_in", DataType::Bool, &[])?
+        .input("acc", DataType::Float, &[2])?
+        .capture("x")?
+        .node("Add", &["acc", "x"], &["sum"], &[])?
+        .domain_node("com.example", "Scale", &["sum"], &["acc_out"], &[("factor", 2.0f32.into())])?
+        .output("cond_in")?
+        .output("acc_out")?;
+    let mut builder = Graph::builder("loop");
+    builder
+        .input("x", DataType::Float, &[2])?
+        .initializer_as("trips", Tensor::new(vec![3.0], vec![]), DataType::Int64)?
+        .initializer("acc0", Tensor::new(vec![0.0, 1.0], vec![2]))?
+        .node("Loop", &["trips", "", "acc0"], &["acc"], &[("body", body.to_subgraph()?.into())])?
+        .output("acc")?;
+    let graph = builder.build()?;
+
+    for session in sessions(&graph)? {
+        let outputs = session.run(&[Tensor::new(vec![1.0, -1.0], vec![2])])?;
+        assert_eq!(outputs[0].data(), [14.0, -6.0]);
+    }
+    Ok(())
+}
+
+#[test]
+fn user_kernels_take_precedence_over_function_bodies() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("softsign");
+    builder.input("x", DataType::Float, &[2])?.node("Identity", &["x"], &["y"], &[])?.output("y")?;
+    let mut model = builder.to_model()?;
+    model.graph.as_mut().unwrap().node[0].op_type = "Softsign".to_string();
+    let loader = ModelLoader { model };
+
+    // Without a kernel, Softsign is inlined from its function body
+    let graph = Graph::from_model(&loader)?;
+    let inlined = InferenceSession::new(graph.clone())?;
+    assert!(inlined.graph.nodes.iter().all(|n| n.op_type != "Softsign"));
+
+    // The session's registry decides, whatever loaded the graph
+    let mut registry = OpRegistry::new();
+    registry.register("Softsign", Negate);
+    let session = InferenceSession::with_registry(graph, SessionOptions::new(), registry)?;
+    assert_eq!(session.graph.nodes.len(), 1);
+    assert_eq!(session.run(&[Tensor::new(vec![1.0, -3.0], vec![2])])?[0].data(), [-1.0, 3.0]);
+    Ok(())
+}
+
+#[test]
+fn registries_are_keyed_by_domain() -> anyhow::Result<()> {
+    let registry = OpRegistry::new();
+    assert!(registry.get_in_domain("com.microsoft", "FusedGemm").is_some());
+    assert!(registry.get("FusedGemm").is_none());
+    assert!(registry.get_in_domain("ai.onnx", "Gelu").is_some());
+    assert_eq!(registry.domains(), ["", "com.microsoft"]);
+    assert!(OpRegistry::empty().domains().is_empty());
+
+    let err = Graph::builder("strict")
+        .input("x", DataType::Float, &[1])?
+        .node("FusedGemm", &["x"], &["y"], &[])
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("unsupported operator 'FusedGemm'"), "{}", err);
+    Ok(())
+}
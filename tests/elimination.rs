@@ -0,0 +1,81 @@
+//! Dead code, no-op and duplicate initializer elimination must not change the outputs.
+
+mod common;
+
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::{GraphProto, NodeProto, TensorProto};
+use neuroxyde::runtime::{GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+use std::collections::HashMap;
+use common::{float_input, int, node, node_with};
+
+fn cast(input: &str, output: &str, to: i64) -> NodeProto {
+    node_with("Cast", &[input], output, vec![int("to", to)])
+}
+
+fn graph() -> Graph {
+    let mut dropout = node("Dropout", &["i"], "dr");
+    dropout.output.push("mask".to_string());
+    let nodes = vec![
+        node("Identity", &["x"], "i"),
+        dropout,
+        cast("dr", "c", 1),
+        // Not a no-op: float to int32
+        cast("c", "t", 6),
+        node("Add", &["t", "b1"], "a1"),
+        node("Mul", &["a1", "b2"], "m"),
+        node("Relu", &["m"], "r"),
+        node("Identity", &["r"], "y"),
+        // Dead branch
+        node("Neg", &["x"], "unused"),
+        node("Add", &["unused", "dead_weight"], "unused2"),
+    ];
+    let b = Tensor::new(vec![0.5, -1.0, 2.0, 0.25], vec![4]);
+    let initializers = HashMap::from([
+        ("b1".to_string(), b.clone()),
+        ("b2".to_string(), b),
+        ("dead_weight".to_string(), Tensor::new(vec![1.0; 4], vec![4])),
+    ]);
+    let proto = GraphProto {
+        node: nodes.clone(),
+        input: vec![float_input("x", &[2, 4])],
+        initializer: ["b1", "b2", "dead_weight"].iter()
+            .map(|name| TensorProto { name: name.to_string(), dims: vec![4], data_type: 1, ..Default::default() })
+            .collect(),
+        ..Default::default()
+    };
+    Graph {
+        proto,
+        nodes,
+        initializers,
+        inputs: vec!["x".to_string()],
+        outputs: vec!["y".to_string()],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn removes_dead_and_no_op_nodes() {
+    let input = vec![Tensor::new(vec![1.7, -2.2, 3.9, 0.4, -0.6, 5.5, -7.1, 2.0], vec![2, 4])];
+
+    let options = SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::DisableAll);
+    let reference = InferenceSession::with_options(graph(), options).unwrap();
+    let expected = reference.run(&input).unwrap();
+
+    let options = SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::Basic);
+    let session = InferenceSession::with_options(graph(), options).unwrap();
+    let ops: Vec<&str> = session.graph.nodes.iter().map(|n| n.op_type.as_str()).collect();
+    assert_eq!(ops, ["Cast", "Add", "Mul", "Relu"]);
+    assert_eq!(session.graph.nodes[0].input, ["x"]);
+    // The last Identity produced a graph output, which Relu now writes directly
+    assert_eq!(session.graph.nodes[3].output, ["y"]);
+
+    let mut initializers: Vec<&String> = session.graph.initializers.keys().collect();
+    initializers.sort();
+    assert_eq!(initializers, ["b1"]);
+    assert_eq!(session.graph.nodes[2].input, ["a1", "b1"]);
+
+    let actual = session.run(&input).unwrap();
+    assert_eq!(actual[0].data(), expected[0].data());
+    assert_eq!(actual[0].data(), &[0.75, 3.0, 10.0, 0.0625, 0.25, 0.0, 0.0, 0.5625]);
+}
@@ -0,0 +1,90 @@
+//! DOT/Mermaid rendering, subgraph extraction and the bounded Display impls.
+
+use neuroxyde::graph::Graph;
+use neuroxyde::loader::ModelLoader;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::tensor::Tensor;
+
+/// x -> Conv(w) -> Relu -> Relu -> ... (`relus` times) -> y
+fn chain(relus: usize) -> anyhow::Result<ModelLoader> {
+    let mut builder = Graph::builder("chain");
+    builder
+        .input("x", DataType::Float, &[-1, 1, 4, 4])?
+        .initializer("w", Tensor::new(vec![0.123456; 9], vec![1, 1, 3, 3]))?
+        .node("Conv", &["x", "w"], &["v0"], &[])?;
+    for i in 0..relus {
+        builder.node("Relu", &[&format!("v{}", i)], &[&format!("v{}", i + 1)], &[])?;
+    }
+    builder.node("Identity", &[&format!("v{}", relus)], &["y"], &[])?.output("y")?;
+    Ok(ModelLoader { model: builder.to_model()? })
+}
+
+#[test]
+fn dot_and_mermaid() -> anyhow::Result<()> {
+    let graph = Graph::from_model(&chain(1)?)?;
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph \"chain\" {"));
+    assert!(dot.contains("i0 [shape=ellipse"), "{}", dot);
+    assert!(dot.contains("label=\"x\\n[?,1,4,4]\""), "{}", dot);
+    assert!(dot.contains("w0 [shape=note"), "{}", dot);
+    assert!(dot.contains("label=\"w\\n[1,1,3,3]\""), "{}", dot);
+    assert!(dot.contains("n0 [shape=box, style=\"rounded,filled\", fillcolor=\"#fff2cc\", label=\"Conv\\nConv_0\"]"), "{}", dot);
+    assert!(dot.contains("w0 -> n0 [label=\"w [1,1,3,3]\", style=dashed]"), "{}", dot);
+    assert!(dot.contains("n2 -> o0 [label=\"y\"]"), "{}", dot);
+    assert!(dot.trim_end().ends_with('}'));
+    assert!(!dot.contains("0.123"));
+
+    let mermaid = graph.to_mermaid();
+    assert!(mermaid.starts_with("flowchart TD\n"));
+    assert!(mermaid.contains("w0[(\"w<br/>[1,1,3,3]\")]"), "{}", mermaid);
+    assert!(mermaid.contains("n0 -->|\"v0\"| n1"), "{}", mermaid);
+    assert!(mermaid.contains("w0 -.->|\"w [1,1,3,3]\"| n0"), "{}", mermaid);
+    assert!(mermaid.contains("class n0,n1,n2 op;"), "{}", mermaid);
+    Ok(())
+}
+
+#[test]
+fn subgraph_around_node() -> anyhow::Result<()> {
+    let graph = Graph::from_model(&chain(6)?)?;
+
+    let sub = graph.subgraph("Relu_3", 1)?;
+    let names: Vec<&str> = sub.nodes.iter().map(|n| n.name.as_str()).collect();
+    assert_eq!(names, ["Relu_2", "Relu_3", "Relu_4"]);
+    assert_eq!(sub.inputs, ["v1"]);
+    assert_eq!(sub.outputs, ["v4"]);
+    assert!(sub.initializers.is_empty());
+    assert!(sub.to_dot().contains("i0 [shape=ellipse, style=filled, fillcolor=\"#cfe2f3\", label=\"v1\"]"));
+
+    let sub = graph.subgraph("Relu_1", 2)?;
+    let names: Vec<&str> = sub.nodes.iter().map(|n| n.name.as_str()).collect();
+    assert_eq!(names, ["Conv_0", "Relu_1", "Relu_2", "Relu_3"]);
+    assert_eq!(sub.inputs, ["x"]);
+    assert_eq!(sub.initializers.keys().collect::<Vec<_>>(), ["w"]);
+
+    let sub = graph.subgraph("Identity_7", 0)?;
+    assert_eq!(sub.outputs, ["y"]);
+
+    assert!(graph.subgraph("nope", 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn display_is_bounded() -> anyhow::Result<()> {
+    let loader = chain(40)?;
+    let graph = Graph::from_model(&loader)?;
+
+    let short = graph.to_string();
+    assert!(short.contains("... 10 more (use {:#} to list all)"), "{}", short);
+    assert!(!short.contains("Identity_41"));
+    let full = format!("{:#}", graph);
+    assert!(full.contains("Identity_41"));
+    assert!(!full.contains("more (use"));
+
+    let model = loader.to_string();
+    assert!(model.contains("Initializers: 1 (36 bytes)"), "{}", model);
+    assert!(model.contains("Nodes in graph: 42"));
+    assert!(model.contains("... 10 more"));
+    assert!(!model.contains("raw_data"));
+    Ok(())
+}
@@ -0,0 +1,214 @@
+//! FunctionProto expansion: model-local functions and the standard operators
+//! defined as functions are inlined when no kernel runs them.
+
+use neuroxyde::graph::{AttributeValue, Graph};
+use neuroxyde::loader::ModelLoader;
+use neuroxyde::onnx::onnx_proto::attribute_proto::AttributeType;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::onnx::onnx_proto::{AttributeProto, FunctionProto, GraphProto, ModelProto, NodeProto, ValueInfoProto};
+use neuroxyde::runtime::{GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+
+fn node(op_type: &str, domain: &str, name: &str, inputs: &[&str], outputs: &[&str], attrs: &[(&str, AttributeValue)]) -> NodeProto {
+    NodeProto {
+        op_type: op_type.to_string(),
+        domain: domain.to_string(),
+        name: name.to_string(),
+        input: inputs.iter().map(|s| s.to_string()).collect(),
+        output: outputs.iter().map(|s| s.to_string()).collect(),
+        attribute: attrs.iter().map(|(attr, value)| value.to_proto(attr)).collect(),
+        ..Default::default()
+    }
+}
+
+/// `C = A * scale + B`, with `scale` defaulting to 2
+fn scaled_add() -> FunctionProto {
+    let mut scale = node("Constant", "", "", &[], &["S"], &[]);
+    scale.attribute.push(AttributeProto {
+        name: "value_float".to_string(),
+        r#type: AttributeType::Float as i32,
+        ref_attr_name: "scale".to_string(),
+        ..Default::default()
+    });
+    FunctionProto {
+        name: "ScaledAdd".to_string(),
+        domain: "local".to_string(),
+        input: vec!["A".to_string(), "B".to_string()],
+        output: vec!["C".to_string()],
+        attribute_proto: vec![AttributeValue::from(2.0f32).to_proto("scale")],
+        node: vec![
+            scale,
+            node("Mul", "", "", &["A", "S"], &["AS"], &[]),
+            node("Add", "", "", &["AS", "B"], &["C"], &[]),
+        ],
+        ..Default::default()
+    }
+}
+
+/// A model with inputs `x` and `y` of shape [2], whose nodes are `nodes`.
+fn model_of(nodes: Vec<NodeProto>, outputs: &[&str], functions: Vec<FunctionProto>) -> anyhow::Result<ModelProto> {
+    let mut builder = Graph::builder("functions");
+    builder
+        .input("x", DataType::Float, &[2])?
+        .input("y", DataType::Float, &[2])?
+        .node("Identity", &["x"], &["placeholder"], &[])?
+        .output("placeholder")?;
+    let mut model = builder.to_model()?;
+    let graph = model.graph.as_mut().unwrap();
+    graph.node = nodes;
+    graph.output = outputs.iter().map(|o| ValueInfoProto { name: o.to_string(), ..Default::default() }).collect();
+    model.functions = functions;
+    Ok(model)
+}
+
+/// The graph a session runs for `model`, its function calls inlined.
+fn inlined(model: ModelProto) -> anyhow::Result<Graph> {
+    let options = SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::DisableAll);
+    Ok(InferenceSession::with_options(Graph::from_model(&ModelLoader { model })?, options)?.graph)
+}
+
+fn run(graph: &Graph, inputs: &[Tensor]) -> anyhow::Result<Vec<Tensor>> {
+    let mut results = Vec::new();
+    for level in [GraphOptimizationLevel::DisableAll, GraphOptimizationLevel::All] {
+        let session = InferenceSession::with_options(graph.clone(), SessionOptions::new().with_graph_optimization_level(level))?;
+        let outputs = session.run(inputs)?;
+        if let Some(previous) = results.first() {
+            let previous: &Vec<Tensor> = previous;
+            assert!(outputs.iter().zip(previous).all(|(a, b)| a.data() == b.data()));
+        }
+        results.push(outputs);
+    }
+    Ok(results.remove(0))
+}
+
+#[test]
+fn model_local_functions_bind_attribute_references() -> anyhow::Result<()> {
+    let model = model_of(
+        vec![
+            node("ScaledAdd", "local", "tripled", &["x", "y"], &["a"], &[("scale", 3.0f32.into())]),
+            node("ScaledAdd", "local", "", &["a", "y"], &["b"], &[]),
+        ],
+        &["a", "b"],
+        vec![scaled_add()],
+    )?;
+    let graph = Graph::from_model(&ModelLoader { model: model.clone() })?;
+    assert_eq!(graph.nodes.len(), 2);
+    let graph = inlined(model)?;
+    let ops: Vec<&str> = graph.nodes.iter().map(|n| n.op_type.as_str()).collect();
+    assert_eq!(ops, ["Constant", "Mul", "Add", "Constant", "Mul", "Add"]);
+    assert_eq!(graph.nodes[0].attribute[0].f, 3.0);
+    assert_eq!(graph.nodes[0].output, ["tripled/S"]);
+    assert_eq!(graph.nodes[3].attribute[0].f, 2.0);
+    assert_eq!(graph.nodes[4].input, ["a", "ScaledAdd/S"]);
+
+    let outputs = run(&graph, &[Tensor::new(vec![1.0, 2.0], vec![2]), Tensor::new(vec![10.0, 20.0], vec![2])])?;
+    assert_eq!(outputs[0].data(), [13.0, 26.0]);
+    assert_eq!(outputs[1].data(), [36.0, 72.0]);
+
+    // The expanded graph saves and reloads as plain nodes
+    let reloaded = Graph::from_model(&ModelLoader { model: graph.to_model()? })?;
+    assert_eq!(reloaded.nodes.len(), 6);
+    assert_eq!(reloaded.model.functions.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn functions_call_functions_and_run_inside_subgraphs() -> anyhow::Result<()> {
+    // Twice(A) = ScaledAdd<scale = 1>(A, A), leaving ScaledAdd's default unused
+    let twice = FunctionProto {
+        name: "Twice".to_string(),
+        domain: "local".to_string(),
+        input: vec!["A".to_string()],
+        output: vec!["C".to_string()],
+        node: vec![node("ScaledAdd", "local", "inner", &["A", "A"], &["C"], &[("scale", 1.0f32.into())])],
+        ..Default::default()
+    };
+    let branch = GraphProto {
+        name: "branch".to_string(),
+        node: vec![node("Twice", "local", "doubled", &["x"], &["d"], &[])],
+        output: vec![ValueInfoProto { name: "d".to_string(), ..Default::default() }],
+        ..Default::default()
+    };
+
+    let model = model_of(
+        vec![
+            node("Twice", "local", "t", &["x"], &["a"], &[]),
+            node("ReduceSum", "", "flag", &["y"], &["f"], &[("keepdims", 0i64.into())]),
+            node("If", "", "branch", &["f"], &["b"], &[("then_branch", branch.clone().into()), ("else_branch", branch.into())]),
+        ],
+        &["a", "b"],
+        vec![scaled_add(), twice],
+    )?;
+    let graph = inlined(model)?;
+    assert_eq!(graph.nodes[0].name, "t/inner/Constant_0");
+    let body = graph.nodes.last().unwrap().attribute[0].g.as_ref().unwrap();
+    assert!(body.node.iter().all(|n| n.op_type != "Twice" && n.op_type != "ScaledAdd"));
+
+    let outputs = run(&graph, &[Tensor::new(vec![1.5, -2.0], vec![2]), Tensor::new(vec![1.0, 0.0], vec![2])])?;
+    assert_eq!(outputs[0].data(), [3.0, -4.0]);
+    assert_eq!(outputs[1].data(), [3.0, -4.0]);
+    Ok(())
+}
+
+#[test]
+fn standard_operators_defined_as_functions() -> anyhow::Result<()> {
+    let mut model = model_of(
+        vec![
+            node("HardSwish", "", "", &["x"], &["hs"], &[]),
+            node("HardSigmoid", "", "", &["x"], &["hsig"], &[("alpha", 0.5f32.into())]),
+            node("Softsign", "", "", &["x"], &["ss"], &[]),
+            node("Swish", "", "", &["x"], &["sw"], &[]),
+            node("Unsqueeze", "", "", &["y", "axes"], &["y2"], &[]),
+            node("MeanVarianceNormalization", "", "", &["y2"], &["mvn"], &[("axes", vec![1i64].into())]),
+        ],
+        &["hs", "hsig", "ss", "sw", "mvn"],
+        vec![],
+    )?;
+    let axes = Tensor::new(vec![0.0], vec![1]).to_proto_as("axes", DataType::Int64 as i32)?;
+    model.graph.as_mut().unwrap().initializer.push(axes);
+    let graph = Graph::from_model(&ModelLoader { model })?;
+
+    let x = [-4.0f32, 1.0];
+    let outputs = run(&graph, &[Tensor::new(x.to_vec(), vec![2]), Tensor::new(vec![1.0, 3.0], vec![2])])?;
+    let close = |actual: &[f32], expected: &[f32]| {
+        assert!(actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-5), "{:?} != {:?}", actual, expected);
+    };
+    close(outputs[0].data(), &x.map(|v| v * (v / 6.0 + 0.5).clamp(0.0, 1.0)));
+    close(outputs[1].data(), &x.map(|v| (0.5 * v + 0.5).clamp(0.0, 1.0)));
+    close(outputs[2].data(), &x.map(|v| v / (1.0 + v.abs())));
+    close(outputs[3].data(), &x.map(|v| v / (1.0 + (-v).exp())));
+    close(outputs[4].data(), &[-1.0, 1.0]);
+    Ok(())
+}
+
+#[test]
+fn expansion_errors_are_reported() -> anyhow::Result<()> {
+    // A function calling itself
+    let looping = FunctionProto {
+        name: "Forever".to_string(),
+        domain: "local".to_string(),
+        input: vec!["A".to_string()],
+        output: vec!["C".to_string()],
+        node: vec![node("Forever", "local", "", &["A"], &["C"], &[])],
+        ..Default::default()
+    };
+    let model = model_of(vec![node("Forever", "local", "f", &["x"], &["a"], &[])], &["a"], vec![looping])?;
+    let err = inlined(model).err().unwrap();
+    assert!(err.to_string().contains("nested more than"), "{}", err);
+
+    // A required attribute left out
+    let mut function = scaled_add();
+    function.attribute = vec!["scale".to_string()];
+    function.attribute_proto.clear();
+    let model = model_of(vec![node("ScaledAdd", "local", "s", &["x", "y"], &["a"], &[])], &["a"], vec![function])?;
+    let err = inlined(model).err().unwrap();
+    assert!(err.to_string().contains("requires attribute 'scale'"), "{}", err);
+
+    // A function of another domain does not define the node
+    let model = model_of(vec![node("ScaledAdd", "other", "s", &["x", "y"], &["a"], &[])], &["a"], vec![scaled_add()])?;
+    let graph = Graph::from_model(&ModelLoader { model })?;
+    let session = InferenceSession::new(graph)?;
+    let err = session.run(&[Tensor::new(vec![0.0; 2], vec![2]), Tensor::new(vec![0.0; 2], vec![2])]).err().unwrap();
+    assert!(err.to_string().contains("Unsupported operator: ScaledAdd"), "{}", err);
+    Ok(())
+}
@@ -0,0 +1,187 @@
+//! Every fusion must compute the same outputs as the unfused graph.
+
+mod common;
+
+use neuroxyde::graph::optimizer::rewrite::GraphIndex;
+use neuroxyde::graph::optimizer::{GeluFusion, RewriteRule};
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::{GraphProto, NodeProto};
+use neuroxyde::runtime::{GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+use std::collections::HashMap;
+use common::{float_input, ints, node, node_with};
+
+fn values(len: usize, seed: f32) -> Vec<f32> {
+    (0..len).map(|i| ((i as f32 + seed) * 0.731).sin() * 1.5).collect()
+}
+
+fn tensor(shape: &[usize], seed: f32) -> Tensor {
+    Tensor::new(values(shape.iter().product(), seed), shape.to_vec())
+}
+
+fn graph(nodes: Vec<NodeProto>, input: &[usize], initializers: Vec<(&str, Tensor)>) -> Graph {
+    let dims: Vec<i64> = input.iter().map(|&d| d as i64).collect();
+    let proto = GraphProto {
+        node: nodes.clone(),
+        input: vec![float_input("x", &dims)],
+        ..Default::default()
+    };
+    Graph {
+        proto,
+        nodes,
+        initializers: initializers.into_iter().map(|(k, v)| (k.to_string(), v)).collect::<HashMap<_, _>>(),
+        inputs: vec!["x".to_string()],
+        outputs: vec!["y".to_string()],
+        ..Default::default()
+    }
+}
+
+/// Runs `build()` unoptimized and at `level`, checks the optimized op types and compares the outputs.
+fn check(build: impl Fn() -> Graph, input: &[usize], level: GraphOptimizationLevel, expected_ops: &[&str]) {
+    let x = vec![tensor(input, 0.5)];
+    let options = SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::DisableAll);
+    let reference = InferenceSession::with_options(build(), options).unwrap().run(&x).unwrap();
+
+    let options = SessionOptions::new().with_graph_optimization_level(level);
+    let session = InferenceSession::with_options(build(), options).unwrap();
+    let ops: Vec<&str> = session.graph.nodes.iter().map(|n| n.op_type.as_str()).collect();
+    assert_eq!(ops, expected_ops);
+    let actual = session.run(&x).unwrap();
+
+    assert_eq!(actual[0].shape(), reference[0].shape());
+    for (a, e) in actual[0].data().iter().zip(reference[0].data()) {
+        assert!((a - e).abs() <= 1e-4 * e.abs().max(1.0), "{} vs {}", a, e);
+    }
+}
+
+fn conv_graph(activation: Option<NodeProto>) -> Graph {
+    let conv_out = if activation.is_some() { "c" } else { "y" };
+    let mut nodes = vec![node_with("Conv", &["x", "w", "b"], conv_out, vec![ints("pads", &[1, 1, 1, 1])])];
+    nodes.extend(activation);
+    graph(nodes, &[1, 3, 6, 6], vec![("w", tensor(&[4, 3, 3, 3], 1.0)), ("b", tensor(&[4], 2.0))])
+}
+
+#[test]
+fn conv_batch_norm() {
+    let build = || {
+        let nodes = vec![
+            node_with("Conv", &["x", "w"], "c", vec![ints("strides", &[2, 2])]),
+            node_with("BatchNormalization", &["c", "scale", "shift", "mean", "var"], "y", vec![]),
+        ];
+        let var = Tensor::new(vec![0.5, 1.0, 2.0, 0.25], vec![4]);
+        graph(nodes, &[2, 3, 7, 7], vec![
+            ("w", tensor(&[4, 3, 3, 3], 1.0)),
+            ("scale", tensor(&[4], 3.0)),
+            ("shift", tensor(&[4], 4.0)),
+            ("mean", tensor(&[4], 5.0)),
+            ("var", var),
+        ])
+    };
+    check(build, &[2, 3, 7, 7], GraphOptimizationLevel::Basic, &["Conv"]);
+}
+
+#[test]
+fn conv_activation() {
+    check(|| conv_graph(Some(node_with("Relu", &["c"], "y", vec![]))), &[1, 3, 6, 6], GraphOptimizationLevel::Extended, &["FusedConv"]);
+
+    let clip = || {
+        let mut g = conv_graph(Some(node_with("Clip", &["c", "lo", "hi"], "y", vec![])));
+        g.initializers.insert("lo".to_string(), Tensor::new(vec![-0.5], vec![]));
+        g.initializers.insert("hi".to_string(), Tensor::new(vec![1.0], vec![]));
+        g
+    };
+    check(clip, &[1, 3, 6, 6], GraphOptimizationLevel::Extended, &["FusedConv"]);
+    // Activation fusion is an extended optimization
+    check(clip, &[1, 3, 6, 6], GraphOptimizationLevel::Basic, &["Conv", "Clip"]);
+}
+
+#[test]
+fn matmul_add_to_gemm() {
+    let build = || {
+        let nodes = vec![
+            node_with("MatMul", &["x", "w"], "m", vec![]),
+            node_with("Add", &["bias", "m"], "a", vec![]),
+            node_with("Sigmoid", &["a"], "y", vec![]),
+        ];
+        graph(nodes, &[3, 8], vec![("w", tensor(&[8, 5], 1.0)), ("bias", tensor(&[5], 2.0))])
+    };
+    check(build, &[3, 8], GraphOptimizationLevel::Basic, &["Gemm", "Sigmoid"]);
+    check(build, &[3, 8], GraphOptimizationLevel::Extended, &["FusedGemm"]);
+
+    // The rank of a 3D input rules the rewrite out
+    let batched = || {
+        let nodes = vec![node_with("MatMul", &["x", "w"], "m", vec![]), node_with("Add", &["m", "bias"], "y", vec![])];
+        graph(nodes, &[2, 3, 8], vec![("w", tensor(&[8, 5], 1.0)), ("bias", tensor(&[5], 2.0))])
+    };
+    check(batched, &[2, 3, 8], GraphOptimizationLevel::All, &["MatMul", "Add"]);
+}
+
+#[test]
+fn erf_gelu() {
+    let scalar = |v: f32| Tensor::new(vec![v], vec![]);
+    let constants = || vec![
+        ("sqrt2", scalar(std::f32::consts::SQRT_2)),
+        ("one", scalar(1.0)),
+        ("half", scalar(0.5)),
+    ];
+    // (x * (1 + erf(x / sqrt2))) * 0.5
+    let trailing_half = || {
+        let nodes = vec![
+            node_with("Div", &["x", "sqrt2"], "d", vec![]),
+            node_with("Erf", &["d"], "e", vec![]),
+            node_with("Add", &["e", "one"], "a", vec![]),
+            node_with("Mul", &["x", "a"], "m", vec![]),
+            node_with("Mul", &["m", "half"], "y", vec![]),
+        ];
+        graph(nodes, &[2, 16], constants())
+    };
+    check(trailing_half, &[2, 16], GraphOptimizationLevel::Extended, &["Gelu"]);
+
+    // (x * 0.5) * (1 + erf(x / sqrt2))
+    let leading_half = || {
+        let nodes = vec![
+            node_with("Mul", &["half", "x"], "h", vec![]),
+            node_with("Div", &["x", "sqrt2"], "d", vec![]),
+            node_with("Erf", &["d"], "e", vec![]),
+            node_with("Add", &["one", "e"], "a", vec![]),
+            node_with("Mul", &["h", "a"], "y", vec![]),
+        ];
+        graph(nodes, &[2, 16], constants())
+    };
+    check(leading_half, &[2, 16], GraphOptimizationLevel::Extended, &["Gelu"]);
+
+    // Malformed nodes are not matched: an Erf without inputs, a Div without divisor
+    for nodes in [
+        vec![node("Erf", &[], "e"), node("Add", &["x", "e"], "y")],
+        vec![node("Div", &["x"], "d"), node("Erf", &["d"], "e"), node("Add", &["e", "x"], "y")],
+    ] {
+        let g = graph(nodes, &[2, 16], vec![]);
+        let erf = g.nodes.iter().position(|n| n.op_type == "Erf").unwrap();
+        assert!(GeluFusion.try_match(&g, &GraphIndex::new(&g), erf).unwrap().is_none());
+    }
+}
+
+#[test]
+fn layer_norm() {
+    let build = || {
+        let axes = vec![ints("axes", &[-1])];
+        let nodes = vec![
+            node_with("ReduceMean", &["x"], "mean", axes.clone()),
+            node_with("Sub", &["x", "mean"], "d", vec![]),
+            node_with("Pow", &["d", "two"], "sq", vec![]),
+            node_with("ReduceMean", &["sq"], "var", axes),
+            node_with("Add", &["var", "eps"], "ve", vec![]),
+            node_with("Sqrt", &["ve"], "std", vec![]),
+            node_with("Div", &["d", "std"], "n", vec![]),
+            node_with("Mul", &["n", "gamma"], "g", vec![]),
+            node_with("Add", &["g", "beta"], "y", vec![]),
+        ];
+        graph(nodes, &[2, 3, 8], vec![
+            ("two", Tensor::new(vec![2.0], vec![])),
+            ("eps", Tensor::new(vec![1e-5], vec![])),
+            ("gamma", tensor(&[8], 1.0)),
+            ("beta", tensor(&[8], 2.0)),
+        ])
+    };
+    check(build, &[2, 3, 8], GraphOptimizationLevel::Extended, &["LayerNormalization"]);
+}
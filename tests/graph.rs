@@ -0,0 +1,51 @@
+//! `Graph::topological_sort` puts producers before consumers, keeps the given order otherwise, and rejects cycles.
+
+mod common;
+
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::NodeProto;
+use common::node;
+
+fn graph(nodes: Vec<NodeProto>) -> Graph {
+    Graph { nodes, inputs: vec!["x".to_string()], ..Default::default() }
+}
+
+fn order(graph: &Graph) -> Vec<&str> {
+    graph.nodes.iter().map(|n| n.name.as_str()).collect()
+}
+
+#[test]
+fn producers_come_first() -> anyhow::Result<()> {
+    let mut g = graph(vec![
+        node("Add", &["a", "b"], "c"),
+        node("Neg", &["a"], "b"),
+        node("Relu", &["x"], "a"),
+    ]);
+    g.topological_sort()?;
+    assert_eq!(order(&g), ["a", "b", "c"]);
+    Ok(())
+}
+
+#[test]
+fn sorted_graphs_are_left_untouched() -> anyhow::Result<()> {
+    // Independent branches keep their relative order
+    let mut g = graph(vec![
+        node("Neg", &["x"], "b"),
+        node("Relu", &["x"], "a"),
+        node("Add", &["a", "b"], "c"),
+        node("Abs", &["x"], "d"),
+    ]);
+    g.topological_sort()?;
+    assert_eq!(order(&g), ["b", "a", "c", "d"]);
+    Ok(())
+}
+
+#[test]
+fn cycles_are_rejected() {
+    let mut g = graph(vec![
+        node("Relu", &["x", "b"], "a"),
+        node("Neg", &["a"], "b"),
+    ]);
+    let err = g.topological_sort().unwrap_err();
+    assert!(err.to_string().contains("cycle"), "{}", err);
+}
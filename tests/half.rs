@@ -0,0 +1,158 @@
+//! f16/bf16 tensors, fp16 models and running fp32 models in reduced precision.
+
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::runtime::{InferenceSession, SessionOptions};
+use neuroxyde::tensor::{bf16, f16, ElementType, Tensor};
+
+#[test]
+fn conversions_round_to_nearest_even() {
+    // 1 + 2^-11 is halfway between two halves and rounds down to the even one
+    let t = Tensor::new(vec![1.0 + 2f32.powi(-11), 1.0 + 3.0 * 2f32.powi(-11), 70000.0, -0.0], vec![4]);
+    let up = 1.0 + 2f32.powi(-9);
+    let half = t.to_element_type(ElementType::F16);
+    assert_eq!(half.element_type(), ElementType::F16);
+    assert_eq!(half.data_f16(), [f16::ONE, f16::from_f32(up), f16::INFINITY, f16::NEG_ZERO]);
+    assert_eq!(half.to_vec(), [1.0, up, f32::INFINITY, -0.0]);
+
+    let brain = t.to_element_type(ElementType::BF16);
+    assert_eq!(brain.data_bf16()[2], bf16::from_f32(70144.0));
+    assert_eq!(brain.to_element_type(ElementType::F16).data_f16()[0], f16::ONE);
+
+    // Views work on any element type
+    let h = Tensor::from_f16((0..6).map(|v| f16::from_f32(v as f32)).collect(), vec![2, 3]);
+    let t = h.permute(&[1, 0]).unwrap();
+    assert!(t.shares_storage(&h));
+    assert_eq!(t.to_contiguous().data_f16()[..3], [f16::ZERO, f16::from_f32(3.0), f16::ONE]);
+    assert_eq!(h.to_proto("h").data_type, DataType::Float16 as i32);
+    assert_eq!(h.to_proto("h").raw_data.len(), 12);
+}
+
+#[test]
+#[should_panic(expected = "F16")]
+fn data_rejects_half_tensors() {
+    let _ = Tensor::from_f16(vec![f16::ONE], vec![1]).data();
+}
+
+fn mlp(float: DataType) -> anyhow::Result<Graph> {
+    let mut builder = Graph::builder("mlp");
+    builder
+        .input("x", float, &[2, 3])?
+        .initializer_as("w", Tensor::new(vec![0.5, -1.0, 0.25, 2.0, 1.5, -0.5], vec![3, 2]), float)?
+        .initializer_as("b", Tensor::new(vec![0.125, -0.25], vec![2]), float)?
+        .initializer_as("shape", Tensor::new(vec![-1.0], vec![1]), DataType::Int64)?
+        .node("MatMul", &["x", "w"], &["xw"], &[])?
+        .node("Add", &["xw", "b"], &["h"], &[])?
+        .node("Relu", &["h"], &["r"], &[])?
+        .node("Transpose", &["r"], &["t"], &[])?
+        .node("Reshape", &["t", "shape"], &["y"], &[])?
+        .output("y")?;
+    builder.build()
+}
+
+fn x() -> Tensor {
+    Tensor::new(vec![1.0, 2.0, 3.0, -1.0, 0.5, 4.0], vec![2, 3])
+}
+
+#[test]
+fn fp16_models_run_in_half_precision() -> anyhow::Result<()> {
+    let reference = InferenceSession::new(mlp(DataType::Float)?)?.run(&[x()])?;
+    let session = InferenceSession::new(mlp(DataType::Float16)?)?;
+    assert_eq!(session.graph.initializers["w"].element_type(), ElementType::F16);
+    assert_eq!(session.graph.initializers["shape"].element_type(), ElementType::F32);
+
+    // f32 inputs are converted to the declared FLOAT16
+    let outputs = session.run(&[x()])?;
+    assert_eq!(outputs[0].element_type(), ElementType::F16);
+    assert_eq!(outputs[0].shape(), [4]);
+    for (a, e) in outputs[0].to_vec().iter().zip(reference[0].data()) {
+        assert!((a - e).abs() <= 1e-3 * e.abs().max(1.0), "{} vs {}", a, e);
+    }
+    Ok(())
+}
+
+#[test]
+fn reduced_precision_option() -> anyhow::Result<()> {
+    let reference = InferenceSession::new(mlp(DataType::Float)?)?.run(&[x()])?;
+    for precision in [ElementType::F16, ElementType::BF16] {
+        let options = SessionOptions::new().with_reduced_precision(precision);
+        let session = InferenceSession::with_options(mlp(DataType::Float)?, options)?;
+        assert_eq!(session.graph.initializers["w"].element_type(), precision);
+        assert_eq!(session.graph.initializers["shape"].element_type(), ElementType::F32);
+
+        let outputs = session.run(&[x()])?;
+        assert_eq!(outputs[0].element_type(), ElementType::F32);
+        for (a, e) in outputs[0].data().iter().zip(reference[0].data()) {
+            assert!((a - e).abs() <= 1e-2 * e.abs().max(1.0), "{:?}: {} vs {}", precision, a, e);
+        }
+    }
+
+    let options = SessionOptions::new().with_reduced_precision(ElementType::F32);
+    assert_eq!(options.reduced_precision, None);
+    Ok(())
+}
+
+#[test]
+fn cast_changes_the_element_type() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("cast");
+    builder
+        .input("x", DataType::Float, &[3])?
+        .node("Cast", &["x"], &["h"], &[("to", (DataType::Float16 as i64).into())])?
+        .node("Neg", &["h"], &["n"], &[])?
+        .node("Cast", &["n"], &["y"], &[("to", (DataType::Float as i64).into())])?
+        .output("n")?
+        .output("y")?;
+    let session = InferenceSession::new(builder.build()?)?;
+    let outputs = session.run(&[Tensor::new(vec![0.1, 1.0, 1e5], vec![3])])?;
+    assert_eq!(outputs[0].element_type(), ElementType::F16);
+    assert_eq!(outputs[1].data(), [-0.099975586, -1.0, f32::NEG_INFINITY]);
+    Ok(())
+}
+
+fn values(len: usize, seed: f32) -> Vec<f32> {
+    (0..len).map(|i| ((i as f32 + 1.0) * 0.7 + seed).sin() * 0.5).collect()
+}
+
+/// An LSTM returning `Y`, `Y_h` and `Y_c`, and an EmbedLayerNormalization
+/// returning its normalized embeddings and integer `mask_index`.
+fn multi_output(float: DataType) -> anyhow::Result<Graph> {
+    let mut builder = Graph::builder("multi_output");
+    builder
+        .input("x", float, &[3, 1, 2])?
+        .input("ids", DataType::Int32, &[1, 3])?
+        .initializer_as("w", Tensor::new(values(24, 0.0), vec![1, 12, 2]), float)?
+        .initializer_as("r", Tensor::new(values(36, 1.0), vec![1, 12, 3]), float)?
+        .initializer_as("word", Tensor::new(values(8, 2.0), vec![4, 2]), float)?
+        .initializer_as("position", Tensor::new(values(6, 3.0), vec![3, 2]), float)?
+        .initializer_as("gamma", Tensor::new(vec![1.0, 0.5], vec![2]), float)?
+        .initializer_as("beta", Tensor::new(vec![0.0, 0.25], vec![2]), float)?
+        .initializer_as("mask", Tensor::new(vec![1.0, 1.0, 0.0], vec![1, 3]), DataType::Int32)?
+        .node("LSTM", &["x", "w", "r"], &["y", "y_h", "y_c"], &[("hidden_size", 3i64.into())])?
+        .domain_node(
+            "com.microsoft", "EmbedLayerNormalization",
+            &["ids", "", "word", "position", "", "gamma", "beta", "mask"], &["embedded", "mask_index"], &[],
+        )?;
+    for output in ["y", "y_h", "y_c", "embedded", "mask_index"] {
+        builder.output(output)?;
+    }
+    builder.build()
+}
+
+#[test]
+fn every_float_output_is_narrowed() -> anyhow::Result<()> {
+    let x = Tensor::new(values(6, 4.0), vec![3, 1, 2]);
+    let ids = Tensor::new(vec![3.0, 0.0, 1.0], vec![1, 3]);
+    let reference = InferenceSession::new(multi_output(DataType::Float)?)?.run(&[x.clone(), ids.clone()])?;
+    let outputs = InferenceSession::new(multi_output(DataType::Float16)?)?.run(&[x, ids])?;
+
+    let types: Vec<ElementType> = outputs.iter().map(|t| t.element_type()).collect();
+    assert_eq!(types, [ElementType::F16, ElementType::F16, ElementType::F16, ElementType::F16, ElementType::F32]);
+    assert_eq!(outputs[4].data(), [2.0]);
+    for (actual, expected) in outputs.iter().zip(&reference) {
+        assert_eq!(actual.shape(), expected.shape());
+        for (a, e) in actual.to_vec().iter().zip(expected.data()) {
+            assert!((a - e).abs() <= 1e-2 * e.abs().max(1.0), "{} vs {}", a, e);
+        }
+    }
+    Ok(())
+}
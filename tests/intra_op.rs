@@ -0,0 +1,81 @@
+//! Operators must produce bit-identical results whatever the intra-op thread count.
+
+use neuroxyde::kernels::KernelBackend;
+use neuroxyde::onnx::onnx_proto::{AttributeProto, NodeProto};
+use neuroxyde::ops::operator::OpContext;
+use neuroxyde::ops::registry::OpRegistry;
+use neuroxyde::runtime::ThreadPool;
+use neuroxyde::tensor::Tensor;
+
+fn tensor(shape: &[usize], seed: u32) -> Tensor {
+    let n = shape.iter().product::<usize>();
+    let mut state = seed.wrapping_mul(2654435761).wrapping_add(1);
+    let data = (0..n)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % 2048) as f32 / 512.0 - 2.0
+        })
+        .collect();
+    Tensor::new(data, shape.to_vec())
+}
+
+fn ints(name: &str, values: &[i64]) -> AttributeProto {
+    AttributeProto { name: name.to_string(), ints: values.to_vec(), ..Default::default() }
+}
+
+fn run(op_type: &str, attrs: Vec<AttributeProto>, inputs: &[&Tensor], threads: usize) -> Tensor {
+    let registry = OpRegistry::new();
+    let pool = ThreadPool::new(threads, &[]).unwrap();
+    let ctx = OpContext::new(KernelBackend::detect().kernels().unwrap(), &pool);
+    let node = NodeProto { op_type: op_type.to_string(), attribute: attrs, ..Default::default() };
+    registry.get(op_type).unwrap().run(inputs, &node, &ctx).unwrap()
+}
+
+fn assert_thread_invariant(op_type: &str, attrs: Vec<AttributeProto>, inputs: &[&Tensor]) {
+    let reference = run(op_type, attrs.clone(), inputs, 1);
+    for threads in [2, 3, 8] {
+        let out = run(op_type, attrs.clone(), inputs, threads);
+        assert_eq!(reference.shape(), out.shape(), "{} with {} threads", op_type, threads);
+        assert!(
+            reference.data().iter().zip(out.data()).all(|(a, b)| a.to_bits() == b.to_bits()),
+            "{} differs with {} threads",
+            op_type,
+            threads
+        );
+    }
+}
+
+#[test]
+fn conv_is_thread_invariant() {
+    let x = tensor(&[2, 8, 32, 32], 1);
+    let w = tensor(&[16, 8, 3, 3], 2);
+    let b = tensor(&[16], 3);
+    assert_thread_invariant("Conv", vec![ints("pads", &[1, 1, 1, 1])], &[&x, &w, &b]);
+}
+
+#[test]
+fn matmul_is_thread_invariant() {
+    let a = tensor(&[3, 64, 96], 4);
+    let b = tensor(&[96, 80], 5);
+    assert_thread_invariant("MatMul", vec![], &[&a, &b]);
+}
+
+#[test]
+fn pooling_is_thread_invariant() {
+    let x = tensor(&[2, 16, 40, 40], 6);
+    let attrs = vec![ints("kernel_shape", &[3, 3]), ints("strides", &[2, 2]), ints("pads", &[1, 1, 1, 1])];
+    assert_thread_invariant("MaxPool", attrs.clone(), &[&x]);
+    assert_thread_invariant("AveragePool", attrs, &[&x]);
+    assert_thread_invariant("GlobalAveragePool", vec![], &[&x]);
+}
+
+#[test]
+fn elementwise_is_thread_invariant() {
+    let a = tensor(&[4, 64, 512], 7);
+    let b = tensor(&[64, 1], 8);
+    assert_thread_invariant("Add", vec![], &[&a, &b]);
+    assert_thread_invariant("Relu", vec![], &[&a]);
+    assert_thread_invariant("ReduceMean", vec![ints("axes", &[-1])], &[&a]);
+}
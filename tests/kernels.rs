@@ -0,0 +1,103 @@
+//! Checks every SIMD backend available on this CPU against the scalar reference.
+
+use neuroxyde::kernels::{BinaryOp, KernelBackend, ReduceOp, UnaryOp};
+
+/// Deterministic pseudo-random values in [-8, 8), including exact zeros.
+fn sample(len: usize, seed: u32) -> Vec<f32> {
+    let mut state = seed.wrapping_mul(2654435761).wrapping_add(1);
+    (0..len)
+        .map(|i| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            if i % 7 == 0 { 0.0 } else { (state % 4096) as f32 / 256.0 - 8.0 }
+        })
+        .collect()
+}
+
+// Lengths cover empty input, sub-vector sizes and every tail for 16 lanes.
+const LENGTHS: [usize; 8] = [0, 1, 3, 4, 15, 16, 33, 1000];
+
+fn assert_close(backend: KernelBackend, what: &str, expected: &[f32], actual: &[f32], tol: f32) {
+    assert_eq!(expected.len(), actual.len());
+    for (i, (e, a)) in expected.iter().zip(actual).enumerate() {
+        let bound = tol * e.abs().max(1.0);
+        assert!(e == a || (e - a).abs() <= bound, "{} {}: index {} expected {} got {}", backend, what, i, e, a);
+    }
+}
+
+#[test]
+fn unary_matches_scalar() {
+    let reference = KernelBackend::Scalar.kernels().unwrap();
+    for backend in KernelBackend::available() {
+        let kernels = backend.kernels().unwrap();
+        for len in LENGTHS {
+            let mut src = sample(len, len as u32);
+            for op in [UnaryOp::Relu, UnaryOp::Neg, UnaryOp::Abs, UnaryOp::Sqrt] {
+                if op == UnaryOp::Sqrt {
+                    src.iter_mut().for_each(|x| *x = x.abs());
+                }
+                let mut expected = vec![0.0; len];
+                let mut actual = vec![0.0; len];
+                reference.unary(op, &src, &mut expected);
+                kernels.unary(op, &src, &mut actual);
+                assert_close(backend, &format!("{:?}", op), &expected, &actual, 0.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn binary_matches_scalar() {
+    let reference = KernelBackend::Scalar.kernels().unwrap();
+    let ops = [BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Div, BinaryOp::Max, BinaryOp::Min];
+    for backend in KernelBackend::available() {
+        let kernels = backend.kernels().unwrap();
+        for len in LENGTHS {
+            let a = sample(len, 1);
+            // Keep divisors away from zero
+            let b: Vec<f32> = sample(len, 2).iter().map(|x| x + 16.5).collect();
+            for op in ops {
+                let mut expected = vec![0.0; len];
+                let mut actual = vec![0.0; len];
+
+                reference.binary(op, &a, &b, &mut expected);
+                kernels.binary(op, &a, &b, &mut actual);
+                assert_close(backend, &format!("{:?}", op), &expected, &actual, 0.0);
+
+                reference.binary_scalar_rhs(op, &a, 3.5, &mut expected);
+                kernels.binary_scalar_rhs(op, &a, 3.5, &mut actual);
+                assert_close(backend, &format!("{:?} rhs", op), &expected, &actual, 0.0);
+
+                reference.binary_scalar_lhs(op, -2.25, &b, &mut expected);
+                kernels.binary_scalar_lhs(op, -2.25, &b, &mut actual);
+                assert_close(backend, &format!("{:?} lhs", op), &expected, &actual, 0.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn reduce_matches_scalar() {
+    let reference = KernelBackend::Scalar.kernels().unwrap();
+    for backend in KernelBackend::available() {
+        let kernels = backend.kernels().unwrap();
+        for len in LENGTHS {
+            let src = sample(len, 3);
+            for op in [ReduceOp::Sum, ReduceOp::Max, ReduceOp::Min] {
+                let expected = reference.reduce(op, &src);
+                let actual = kernels.reduce(op, &src);
+                // SIMD sums are reassociated, so only Sum gets a tolerance
+                let tol = if op == ReduceOp::Sum { 1e-5 * len.max(1) as f32 } else { 0.0 };
+                assert_close(backend, &format!("{:?}", op), &[expected], &[actual], tol);
+            }
+        }
+    }
+}
+
+#[test]
+fn detected_backend_is_supported() {
+    let backend = KernelBackend::detect();
+    assert!(backend.is_supported());
+    assert!(KernelBackend::available().contains(&backend));
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "ndarray")]
+
+use ndarray::{s, Array2, Array3, ArrayD, IxDyn};
+use neuroxyde::tensor::{ElementType, Tensor};
+
+#[test]
+fn owned_standard_array_moves_its_buffer() {
+    let array = Array2::from_shape_vec((2, 3), vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+    let ptr = array.as_ptr();
+    let tensor = Tensor::from(array);
+    assert_eq!(tensor.shape(), &[2, 3]);
+    assert_eq!(tensor.data(), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    assert_eq!(tensor.data().as_ptr(), ptr);
+}
+
+#[test]
+fn tensor_into_array_moves_its_buffer() {
+    let tensor = Tensor::new((0..24).map(|v| v as f32).collect(), vec![2, 3, 4]);
+    let ptr = tensor.data().as_ptr();
+    let array: ArrayD<f32> = tensor.into();
+    assert_eq!(array.shape(), &[2, 3, 4]);
+    assert_eq!(array[[1, 2, 3]], 23.0);
+    assert_eq!(array.as_ptr(), ptr);
+}
+
+#[test]
+fn array_view_borrows_tensor_data() {
+    let tensor = Tensor::new(vec![1.0, 2.0, 3.0, 4.0], vec![2, 2]);
+    let view = tensor.array_view().unwrap();
+    assert_eq!(view.shape(), &[2, 2]);
+    assert_eq!(view[[1, 0]], 3.0);
+    assert_eq!(view.as_ptr(), tensor.data().as_ptr());
+
+    // Other element types cannot be borrowed as f32, but convert
+    let half = tensor.to_element_type(ElementType::F16);
+    assert!(half.array_view().is_none());
+    assert_eq!(half.into_array(), ndarray::arr2(&[[1.0f32, 2.0], [3.0, 4.0]]).into_dyn());
+}
+
+#[test]
+fn transposed_view_is_materialized_row_major() {
+    let array = Array2::from_shape_vec((2, 3), vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+    let tensor = Tensor::from(array.t());
+    assert_eq!(tensor.shape(), &[3, 2]);
+    assert_eq!(tensor.data(), &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+
+    let owned = Tensor::from(array.reversed_axes());
+    assert_eq!(owned.data(), &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+}
+
+#[test]
+fn sliced_arrays_are_materialized() {
+    let array = Array3::from_shape_fn((2, 3, 4), |(i, j, k)| (i * 12 + j * 4 + k) as f32);
+    let view = array.slice(s![.., 1.., ..;2]);
+    let tensor = Tensor::from(view);
+    assert_eq!(tensor.shape(), &[2, 2, 2]);
+    assert_eq!(tensor.data(), &[4.0, 6.0, 8.0, 10.0, 16.0, 18.0, 20.0, 22.0]);
+
+    let mut owned = array.clone();
+    owned.slice_collapse(s![1.., .., 1..]);
+    let tensor = Tensor::from(owned);
+    assert_eq!(tensor.shape(), &[1, 3, 3]);
+    assert_eq!(tensor.data(), &[13.0, 14.0, 15.0, 17.0, 18.0, 19.0, 21.0, 22.0, 23.0]);
+}
+
+#[test]
+fn round_trip_preserves_shape_and_data() {
+    let array = ArrayD::from_shape_fn(IxDyn(&[3, 1, 2]), |idx| (idx[0] * 2 + idx[2]) as f32 - 2.5);
+    let back = Tensor::from(array.clone()).into_array();
+    assert_eq!(back, array);
+
+    let scalar = Tensor::new(vec![7.0], vec![]);
+    assert_eq!(scalar.array_view().unwrap().ndim(), 0);
+    assert_eq!(Tensor::from(scalar.array_view().unwrap()).data(), &[7.0]);
+}
+
+#[test]
+fn strided_tensors_are_viewed_in_place() -> anyhow::Result<()> {
+    let tensor = Tensor::new((0..6).map(|v| v as f32).collect(), vec![2, 3]);
+    let view = tensor.slice_axis(1, 2, 2, -2)?.permute(&[1, 0])?;
+    let array = view.array_view().unwrap();
+    assert_eq!(array.shape(), &[2, 2]);
+    assert_eq!(array, ndarray::arr2(&[[2.0f32, 5.0], [0.0, 3.0]]).into_dyn());
+    assert!(std::ptr::eq(&array[[1, 0]], &tensor.data()[0]));
+    assert_eq!(Tensor::from(array).data(), view.to_vec());
+    Ok(())
+}
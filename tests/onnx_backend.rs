@@ -0,0 +1,321 @@
+//! ONNX backend conformance harness.
+//!
+//! Runs the node tests shipped with ONNX (`onnx/backend/test/data/node`):
+//! every directory holding a `model.onnx` and `test_data_set_*/{input,output}_*.pb`
+//! is loaded through `ModelLoader` -> `Graph` -> `InferenceSession` and the
+//! outputs are compared with per-op tolerances.
+//!
+//! Environment variables:
+//! - `ONNX_BACKEND_TEST_DIR`: directory to scan (the test is skipped when unset)
+//! - `ONNX_BACKEND_FILTER`: only run cases whose name contains this string
+//! - `ONNX_BACKEND_REPORT`: where to write the Markdown coverage report
+//!   (default `target/onnx_backend_report.md`)
+//! - `ONNX_BACKEND_STRICT=1`: fail the test when any case fails
+
+use neuroxyde::graph::Graph;
+use neuroxyde::loader::ModelLoader;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::runtime::InferenceSession;
+use neuroxyde::tensor::Tensor;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+
+/// ONNX's own defaults for backend tests.
+const DEFAULT_TOLERANCE: Tolerance = Tolerance { rtol: 1e-3, atol: 1e-7 };
+
+/// Operators whose reference outputs are computed in float64 or with
+/// transcendental functions, for which f32 evaluation drifts further.
+const TOLERANCES: &[(&str, Tolerance)] = &[
+    ("Erf", Tolerance { rtol: 1e-3, atol: 1e-5 }),
+    ("Gelu", Tolerance { rtol: 1e-3, atol: 1e-5 }),
+    ("LayerNormalization", Tolerance { rtol: 1e-3, atol: 1e-4 }),
+    ("BatchNormalization", Tolerance { rtol: 1e-3, atol: 1e-5 }),
+    ("Pow", Tolerance { rtol: 1e-3, atol: 1e-5 }),
+    ("ReduceMean", Tolerance { rtol: 1e-3, atol: 1e-6 }),
+    ("Conv", Tolerance { rtol: 1e-3, atol: 1e-5 }),
+    ("Gemm", Tolerance { rtol: 1e-3, atol: 1e-5 }),
+    ("MatMul", Tolerance { rtol: 1e-3, atol: 1e-5 }),
+];
+
+#[derive(Debug, Clone, Copy)]
+struct Tolerance {
+    rtol: f32,
+    atol: f32,
+}
+
+/// Loosest tolerance among the operators of a model.
+fn tolerance_for(op_types: &[String]) -> Tolerance {
+    op_types.iter()
+        .filter_map(|op| TOLERANCES.iter().find(|(name, _)| name == op).map(|(_, t)| *t))
+        .fold(DEFAULT_TOLERANCE, |a, b| Tolerance { rtol: a.rtol.max(b.rtol), atol: a.atol.max(b.atol) })
+}
+
+#[derive(Debug)]
+enum Outcome {
+    Pass,
+    /// Could not be loaded or the session could not be created.
+    Load(String),
+    /// `run` returned an error or panicked.
+    Run(String),
+    /// Ran, but an output differs from the reference.
+    Mismatch(String),
+}
+
+struct CaseResult {
+    name: String,
+    op_types: Vec<String>,
+    outcome: Outcome,
+}
+
+/// Test case directories under `root`, sorted by name.
+fn discover(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut cases = Vec::new();
+    for entry in std::fs::read_dir(root)? {
+        let path = entry?.path();
+        if path.join("model.onnx").is_file() {
+            cases.push(path);
+        } else if path.is_dir() {
+            cases.extend(discover(&path)?);
+        }
+    }
+    cases.sort();
+    Ok(cases)
+}
+
+/// `prefix_N.pb` files of a data set, ordered by N.
+fn numbered(dir: &Path, prefix: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files: Vec<(usize, PathBuf)> = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let index = path.file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix(prefix))
+            .and_then(|n| n.strip_suffix(".pb"))
+            .and_then(|n| n.parse().ok());
+        if let Some(index) = index {
+            files.push((index, path));
+        }
+    }
+    files.sort();
+    Ok(files.into_iter().map(|(_, p)| p).collect())
+}
+
+fn compare(name: &str, actual: &Tensor, expected: &Tensor, tolerance: Tolerance) -> Result<(), String> {
+    if actual.shape() != expected.shape() {
+        return Err(format!("{}: shape {:?}, expected {:?}", name, actual.shape(), expected.shape()));
+    }
+    let mut worst: Option<(usize, f32, f32)> = None;
+    let mut mismatches = 0;
+    for (i, (&a, &e)) in actual.values().iter().zip(expected.values().iter()).enumerate() {
+        let close = (a.is_nan() && e.is_nan()) || a == e || (a - e).abs() <= tolerance.atol + tolerance.rtol * e.abs();
+        if !close {
+            mismatches += 1;
+            if worst.is_none_or(|(_, wa, we)| (a - e).abs() > (wa - we).abs()) {
+                worst = Some((i, a, e));
+            }
+        }
+    }
+    match worst {
+        None => Ok(()),
+        Some((i, a, e)) => Err(format!(
+            "{}: {} of {} values differ, worst at {}: {} vs expected {}",
+            name, mismatches, actual.len(), i, a, e
+        )),
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic".to_string())
+}
+
+fn run_case(dir: &Path) -> CaseResult {
+    let name = dir.file_name().unwrap().to_string_lossy().into_owned();
+    let loader = match ModelLoader::load_from_file(dir.join("model.onnx")) {
+        Ok(loader) => loader,
+        Err(e) => return CaseResult { name, op_types: Vec::new(), outcome: Outcome::Load(e.to_string()) },
+    };
+    let mut op_types: Vec<String> = loader.model.graph.iter()
+        .flat_map(|g| g.node.iter().map(|n| n.op_type.clone()))
+        .collect();
+    op_types.sort();
+    op_types.dedup();
+    let outcome = evaluate(dir, &loader, tolerance_for(&op_types));
+    CaseResult { name, op_types, outcome }
+}
+
+fn evaluate(dir: &Path, loader: &ModelLoader, tolerance: Tolerance) -> Outcome {
+    let session = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        Graph::from_model(loader).and_then(InferenceSession::new)
+    }));
+    let session = match session {
+        Ok(Ok(session)) => session,
+        Ok(Err(e)) => return Outcome::Load(format!("{:#}", e)),
+        Err(panic) => return Outcome::Load(format!("panicked: {}", panic_message(panic))),
+    };
+
+    let data_sets = match std::fs::read_dir(dir) {
+        Ok(entries) => {
+            let mut sets: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.is_dir() && p.file_name().is_some_and(|n| n.to_string_lossy().starts_with("test_data_set_")))
+                .collect();
+            sets.sort();
+            sets
+        }
+        Err(e) => return Outcome::Load(e.to_string()),
+    };
+    for set in data_sets {
+        let load = |prefix: &str| -> anyhow::Result<Vec<Tensor>> {
+            numbered(&set, prefix)?.iter().map(Tensor::read_pb).collect()
+        };
+        let (inputs, expected) = match (load("input_"), load("output_")) {
+            (Ok(inputs), Ok(expected)) => (inputs, expected),
+            (Err(e), _) | (_, Err(e)) => return Outcome::Load(format!("{}: {:#}", set.display(), e)),
+        };
+        let outputs = match std::panic::catch_unwind(AssertUnwindSafe(|| session.run(&inputs))) {
+            Ok(Ok(outputs)) => outputs,
+            Ok(Err(e)) => return Outcome::Run(format!("{:#}", e)),
+            Err(panic) => return Outcome::Run(format!("panicked: {}", panic_message(panic))),
+        };
+        if outputs.len() != expected.len() {
+            return Outcome::Mismatch(format!("{} outputs, expected {}", outputs.len(), expected.len()));
+        }
+        for (i, (actual, expected)) in outputs.iter().zip(&expected).enumerate() {
+            let name = session.graph.outputs.get(i).cloned().unwrap_or_else(|| format!("output_{}", i));
+            if let Err(e) = compare(&name, actual, expected, tolerance) {
+                return Outcome::Mismatch(e);
+            }
+        }
+    }
+    Outcome::Pass
+}
+
+/// Markdown report: totals, per-operator coverage and the failing cases.
+fn report(results: &[CaseResult]) -> String {
+    let passed = results.iter().filter(|r| matches!(r.outcome, Outcome::Pass)).count();
+    let mut out = String::from("# ONNX backend conformance\n\n");
+    let _ = writeln!(out, "{} / {} cases pass ({:.1}%)\n", passed, results.len(), 100.0 * passed as f64 / results.len().max(1) as f64);
+
+    // An operator is covered by the cases whose model uses it
+    let mut by_op: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for r in results {
+        for op in &r.op_types {
+            let entry = by_op.entry(op).or_default();
+            if matches!(r.outcome, Outcome::Pass) { entry.0 += 1 } else { entry.1 += 1 }
+        }
+    }
+    out.push_str("| Operator | Passed | Failed | Status |\n|---|---:|---:|---|\n");
+    for (op, (pass, fail)) in &by_op {
+        let status = match (pass, fail) {
+            (_, 0) => "pass",
+            (0, _) => "fail",
+            _ => "partial",
+        };
+        let _ = writeln!(out, "| {} | {} | {} | {} |", op, pass, fail, status);
+    }
+
+    out.push_str("\n## Failures\n\n");
+    for r in results {
+        let (kind, detail) = match &r.outcome {
+            Outcome::Pass => continue,
+            Outcome::Load(e) => ("load", e),
+            Outcome::Run(e) => ("run", e),
+            Outcome::Mismatch(e) => ("mismatch", e),
+        };
+        let detail = detail.lines().next().unwrap_or_default().replace('|', "\\|");
+        let _ = writeln!(out, "- `{}` ({}): {}", r.name, kind, detail);
+    }
+    out
+}
+
+fn run_all(root: &Path, filter: Option<&str>) -> Vec<CaseResult> {
+    // Failures are reported, not printed by the panic hook
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let results = discover(root).unwrap_or_default()
+        .iter()
+        .filter(|dir| filter.is_none_or(|f| dir.to_string_lossy().contains(f)))
+        .map(|dir| run_case(dir))
+        .collect();
+    std::panic::set_hook(hook);
+    results
+}
+
+#[test]
+fn onnx_backend_node_tests() {
+    let Some(root) = std::env::var_os("ONNX_BACKEND_TEST_DIR") else {
+        eprintln!("ONNX_BACKEND_TEST_DIR is not set; skipping the ONNX backend tests");
+        return;
+    };
+    let filter = std::env::var("ONNX_BACKEND_FILTER").ok();
+    let results = run_all(Path::new(&root), filter.as_deref());
+    assert!(!results.is_empty(), "No test cases found under {:?}", root);
+
+    let report = report(&results);
+    let path = std::env::var_os("ONNX_BACKEND_REPORT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("onnx_backend_report.md"));
+    std::fs::write(&path, &report).unwrap();
+    eprintln!("{}", report.lines().nth(2).unwrap_or_default());
+    eprintln!("Coverage report written to {}", path.display());
+
+    if std::env::var("ONNX_BACKEND_STRICT").is_ok_and(|v| v == "1") {
+        let failed: Vec<&str> = results.iter()
+            .filter(|r| !matches!(r.outcome, Outcome::Pass))
+            .map(|r| r.name.as_str())
+            .collect();
+        assert!(failed.is_empty(), "{} case(s) failed: {:?}", failed.len(), failed);
+    }
+}
+
+/// Lays out a case the way ONNX does, from a graph built in code.
+fn write_case(root: &Path, name: &str, builder: &neuroxyde::graph::GraphBuilder, inputs: &[Tensor], outputs: &[Tensor]) {
+    let dir = root.join(name);
+    let set = dir.join("test_data_set_0");
+    std::fs::create_dir_all(&set).unwrap();
+    ModelLoader { model: builder.to_model().unwrap() }.save_to_file(dir.join("model.onnx")).unwrap();
+    for (i, t) in inputs.iter().enumerate() {
+        t.write_pb(set.join(format!("input_{}.pb", i)), &format!("input_{}", i)).unwrap();
+    }
+    for (i, t) in outputs.iter().enumerate() {
+        t.write_pb(set.join(format!("output_{}.pb", i)), &format!("output_{}", i)).unwrap();
+    }
+}
+
+/// The harness itself, on cases generated in a temporary directory.
+#[test]
+fn harness_reports_coverage() -> anyhow::Result<()> {
+    let root = std::env::temp_dir().join(format!("neuroxyde-backend-{}", std::process::id()));
+    let x = Tensor::new(vec![-1.0, 0.5, 2.0], vec![3]);
+
+    let mut relu = Graph::builder("test_relu");
+    relu.input("x", DataType::Float, &[3])?.node("Relu", &["x"], &["y"], &[])?.output("y")?;
+    write_case(&root, "test_relu", &relu, std::slice::from_ref(&x), &[Tensor::new(vec![0.0, 0.5, 2.0], vec![3])]);
+
+    let mut wrong = Graph::builder("test_abs_wrong");
+    wrong.input("x", DataType::Float, &[3])?.node("Abs", &["x"], &["y"], &[])?.output("y")?;
+    write_case(&root, "test_abs_wrong", &wrong, std::slice::from_ref(&x), &[Tensor::new(vec![1.0, 0.5, 2.5], vec![3])]);
+
+    // Within the Erf tolerance but not the default one
+    let mut erf = Graph::builder("test_erf");
+    erf.input("x", DataType::Float, &[1])?.node("Erf", &["x"], &["y"], &[])?.output("y")?;
+    write_case(&root, "test_erf", &erf, &[Tensor::new(vec![0.0], vec![1])], &[Tensor::new(vec![5e-6], vec![1])]);
+
+    let results = run_all(&root, None);
+    let filtered = run_all(&root, Some("relu"));
+    let report = report(&results);
+    std::fs::remove_dir_all(&root)?;
+
+    let outcomes: Vec<(&str, bool)> = results.iter().map(|r| (r.name.as_str(), matches!(r.outcome, Outcome::Pass))).collect();
+    assert_eq!(outcomes, [("test_abs_wrong", false), ("test_erf", true), ("test_relu", true)]);
+    assert!(report.contains("2 / 3 cases pass (66.7%)"), "{}", report);
+    assert!(report.contains("| Abs | 0 | 1 | fail |"), "{}", report);
+    assert!(report.contains("| Relu | 1 | 0 | pass |"), "{}", report);
+    assert!(report.contains("- `test_abs_wrong` (mismatch): y: 1 of 3 values differ, worst at 2: 2 vs expected 2.5"), "{}", report);
+
+    assert_eq!(filtered.len(), 1);
+    Ok(())
+}
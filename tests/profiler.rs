@@ -0,0 +1,86 @@
+//! The profiler must record every node and export valid Chrome trace JSON.
+
+mod common;
+
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::{GraphProto, NodeProto};
+use neuroxyde::runtime::{EventKind, ExecutionMode, InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+use std::collections::HashMap;
+use common::float_input;
+
+fn node(op_type: &str, inputs: &[&str], output: &str) -> NodeProto {
+    NodeProto {
+        // Quotes and backslashes must survive the JSON export
+        name: format!("{}\\\"{}\"", op_type, output),
+        ..common::node(op_type, inputs, output)
+    }
+}
+
+fn graph() -> Graph {
+    let nodes = vec![
+        node("Relu", &["x"], "a"),
+        node("Neg", &["x"], "b"),
+        node("Add", &["a", "b"], "c"),
+        node("Relu", &["c"], "y"),
+    ];
+    let proto = GraphProto {
+        node: nodes.clone(),
+        input: vec![float_input("x", &[2, 8])],
+        ..Default::default()
+    };
+    Graph {
+        proto,
+        nodes,
+        initializers: HashMap::new(),
+        inputs: vec!["x".to_string()],
+        outputs: vec!["y".to_string()],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn disabled_by_default() {
+    let session = InferenceSession::new(graph()).unwrap();
+    assert!(session.profiler().is_none());
+}
+
+#[test]
+fn records_nodes_and_exports_trace() {
+    for mode in [ExecutionMode::Sequential, ExecutionMode::Parallel] {
+        let options = SessionOptions::new()
+            .with_profiling(true)
+            .with_execution_mode(mode)
+            .with_inter_op_num_threads(2);
+        let session = InferenceSession::with_options(graph(), options).unwrap();
+        let input = vec![Tensor::new((0..16).map(|i| i as f32 - 8.0).collect(), vec![2, 8])];
+        session.run(&input).unwrap();
+        session.run(&input).unwrap();
+
+        let profiler = session.profiler().unwrap();
+        let events = profiler.events();
+        let nodes: Vec<_> = events.iter().filter(|e| e.kind == EventKind::Node).collect();
+        assert_eq!(nodes.len(), 8);
+        assert_eq!(events.iter().filter(|e| e.kind == EventKind::Run).count(), 2);
+        let add = nodes.iter().find(|e| e.op_type == "Add").unwrap();
+        assert_eq!(add.input_shapes, vec![vec![2, 8], vec![2, 8]]);
+        assert_eq!(add.bytes_allocated, 16 * 4);
+
+        let summary = profiler.summary();
+        let relu = summary.ops.iter().find(|op| op.op_type == "Relu").unwrap();
+        assert_eq!(relu.calls, 4);
+        let percent: f64 = summary.ops.iter().map(|op| op.percent).sum();
+        assert!(summary.total.is_zero() || (percent - 100.0).abs() < 1e-6);
+        assert!(summary.to_string().contains("Relu"));
+
+        let trace: serde_json::Value = serde_json::from_str(&profiler.to_chrome_trace()).unwrap();
+        let trace_events = trace["traceEvents"].as_array().unwrap();
+        let complete = trace_events.iter().filter(|e| e["ph"] == "X").count();
+        assert_eq!(complete, 10);
+        assert!(trace_events.iter().any(|e| e["ph"] == "M" && e["name"] == "thread_name"));
+        assert!(trace_events.iter().any(|e| e["name"] == "Add\\\"c\""));
+
+        profiler.clear();
+        assert!(profiler.events().is_empty());
+    }
+}
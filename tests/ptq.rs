@@ -0,0 +1,129 @@
+//! Post-training static quantization: calibration methods and the quantized graphs they produce.
+
+use neuroxyde::graph::Graph;
+use neuroxyde::loader::ModelLoader;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::quantization::{quantize_static, CalibrationMethod, Calibrator, QuantizationOptions};
+use neuroxyde::runtime::{GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::{ElementType, Tensor};
+
+fn values(len: usize, seed: f32) -> Vec<f32> {
+    (0..len).map(|i| ((i as f32 + seed) * 0.731).sin()).collect()
+}
+
+/// Conv -> Relu -> Flatten -> MatMul, small enough to check element by element.
+fn conv_mlp() -> anyhow::Result<Graph> {
+    let mut builder = Graph::builder("conv_mlp");
+    builder
+        .input("x", DataType::Float, &[-1, 2, 6, 6])?
+        .initializer("w", Tensor::new(values(4 * 2 * 3 * 3, 1.0), vec![4, 2, 3, 3]))?
+        .initializer("b", Tensor::new(vec![0.1, -0.2, 0.05, 0.0], vec![4]))?
+        .initializer("m", Tensor::new(values(64 * 3, 7.0), vec![64, 3]))?
+        .node("Conv", &["x", "w", "b"], &["c"], &[])?
+        .node("Relu", &["c"], &["r"], &[])?
+        .node("Flatten", &["r"], &["f"], &[])?
+        .node("MatMul", &["f", "m"], &["y"], &[])?
+        .output("y")?;
+    builder.build()
+}
+
+fn samples(count: usize) -> Vec<Vec<Tensor>> {
+    (0..count).map(|i| vec![Tensor::new(values(72, i as f32 * 13.0), vec![1, 2, 6, 6])]).collect()
+}
+
+fn run(graph: Graph, level: GraphOptimizationLevel, x: &Tensor) -> anyhow::Result<(Vec<String>, Tensor)> {
+    let session = InferenceSession::with_options(graph, SessionOptions::new().with_graph_optimization_level(level))?;
+    let ops = session.graph.nodes.iter().map(|n| n.op_type.clone()).collect();
+    Ok((ops, session.run(std::slice::from_ref(x))?.remove(0)))
+}
+
+#[test]
+fn quantized_model_tracks_the_float_model() -> anyhow::Result<()> {
+    for per_channel in [false, true] {
+        let options = QuantizationOptions::new().with_per_channel(per_channel);
+        let quantized = quantize_static(&conv_mlp()?, samples(8), &options)?;
+
+        let ops: Vec<&str> = quantized.nodes.iter().map(|n| n.op_type.as_str()).collect();
+        assert_eq!(ops.iter().filter(|op| **op == "QuantizeLinear").count(), 4, "{:?}", ops);
+        // One per activation, plus the weights and the Conv bias
+        assert_eq!(ops.iter().filter(|op| **op == "DequantizeLinear").count(), 7, "{:?}", ops);
+        assert_eq!(quantized.initializers["w_quantized"].element_type(), ElementType::I8);
+        assert!(!quantized.initializers.contains_key("w"));
+        let scales = quantized.initializers["w_scale"].len();
+        assert_eq!(scales, if per_channel { 4 } else { 1 });
+
+        // Saved and reloaded, with the weights stored as int8 and the bias as int32
+        let model = quantized.to_model()?;
+        let data_type = |name: &str| model.graph.as_ref().unwrap().initializer.iter().find(|t| t.name == name).map(|t| t.data_type);
+        assert_eq!(data_type("w_quantized"), Some(DataType::Int8 as i32));
+        assert_eq!(data_type("b_quantized"), Some(DataType::Int32 as i32));
+        assert_eq!(data_type("c_zero_point"), Some(DataType::Uint8 as i32));
+        let reloaded = Graph::from_model(&ModelLoader { model })?;
+
+        let x = samples(9).remove(8).remove(0);
+        let (_, expected) = run(conv_mlp()?, GraphOptimizationLevel::DisableAll, &x)?;
+        let (_, simulated) = run(reloaded.clone(), GraphOptimizationLevel::DisableAll, &x)?;
+        let (fused_ops, fused) = run(reloaded, GraphOptimizationLevel::All, &x)?;
+        assert!(fused_ops.iter().any(|op| op == "QLinearConv"), "{:?}", fused_ops);
+        assert!(fused_ops.iter().any(|op| op == "QLinearMatMul"), "{:?}", fused_ops);
+
+        let range = expected.data().iter().fold(0.0f32, |m, v| m.max(v.abs()));
+        for output in [&simulated, &fused] {
+            for (a, e) in output.data().iter().zip(expected.data()) {
+                assert!((a - e).abs() <= 0.05 * range, "{:?} vs {:?}", output.data(), expected.data());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn calibration_methods_clip_outliers() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("identity");
+    builder
+        .input("x", DataType::Float, &[-1])?
+        .node("Relu", &["x"], &["y"], &[])?
+        .output("y")?;
+    let graph = builder.build()?;
+
+    // Mostly within [-1, 1], with a single far outlier
+    let mut data: Vec<f32> = (0..10_000).map(|i| ((i as f32) * 0.37).sin()).collect();
+    data[17] = 100.0;
+    let x = Tensor::new(data, vec![10_000]);
+
+    let range = |method| -> anyhow::Result<(f32, f32)> {
+        let mut calibrator = Calibrator::new(&graph, &["x".to_string(), "y".to_string()], method)?;
+        calibrator.collect(std::slice::from_ref(&x))?;
+        calibrator.collect(&[Tensor::new(vec![-0.5, 0.25], vec![2])])?;
+        Ok(calibrator.ranges()["x"])
+    };
+    let (min, max) = range(CalibrationMethod::MinMax)?;
+    assert!((min + 1.0).abs() < 1e-3 && max == 100.0, "{} {}", min, max);
+    let (min, max) = range(CalibrationMethod::Percentile(99.9))?;
+    assert!((min + 1.0).abs() < 1e-3 && (1.0..1.2).contains(&max), "{} {}", min, max);
+    // The threshold spans at least 128 of the 2048 bins over [0, 100]
+    let (min, max) = range(CalibrationMethod::Entropy)?;
+    assert!((min + 1.0).abs() < 1e-3 && (1.0..10.0).contains(&max), "{} {}", min, max);
+    Ok(())
+}
+
+#[test]
+fn signed_activations_and_opset_checks() -> anyhow::Result<()> {
+    let options = QuantizationOptions::new().with_activation_type(ElementType::I8).with_calibration(CalibrationMethod::Entropy);
+    let quantized = quantize_static(&conv_mlp()?, samples(4), &options)?;
+    assert_eq!(quantized.initializers["x_zero_point"].element_type(), ElementType::I8);
+    let x = samples(1).remove(0).remove(0);
+    let (ops, _) = run(quantized, GraphOptimizationLevel::All, &x)?;
+    assert!(ops.iter().any(|op| op == "QLinearConv"), "{:?}", ops);
+
+    assert!(quantize_static(&conv_mlp()?, Vec::new(), &QuantizationOptions::new()).is_err());
+    let f16 = QuantizationOptions::new().with_activation_type(ElementType::F16);
+    assert!(quantize_static(&conv_mlp()?, samples(1), &f16).is_err());
+
+    let mut old = conv_mlp()?;
+    old.model.opset_import[0].version = 11;
+    assert!(quantize_static(&old, samples(1), &QuantizationOptions::new()).is_ok());
+    let err = quantize_static(&old, samples(1), &QuantizationOptions::new().with_per_channel(true)).err().unwrap();
+    assert!(err.to_string().contains("opset 13"), "{}", err);
+    Ok(())
+}
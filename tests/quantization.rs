@@ -0,0 +1,261 @@
+//! Quantized operators, checked against the ONNX reference examples, and the QDQ fusion pass.
+
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::runtime::{GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::{ElementType, Tensor};
+
+fn u8s(values: &[u8], shape: &[usize]) -> Tensor {
+    Tensor::from_u8(values.to_vec(), shape.to_vec())
+}
+
+fn scalar(v: f32) -> Tensor {
+    Tensor::new(vec![v], vec![])
+}
+
+fn unoptimized() -> SessionOptions {
+    SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::DisableAll)
+}
+
+#[test]
+fn quantize_and_dequantize() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("qdq");
+    builder
+        .input("x", DataType::Float, &[2, 3])?
+        .initializer("scale", scalar(0.5))?
+        .initializer("zero", u8s(&[128], &[]))?
+        .initializer("channel_scales", Tensor::new(vec![1.0, 0.5, 0.25], vec![3]))?
+        .initializer("channel_zeros", Tensor::from_i8(vec![0, -10, 10], vec![3]))?
+        .node("QuantizeLinear", &["x", "scale", "zero"], &["q"], &[])?
+        .node("DequantizeLinear", &["q", "scale", "zero"], &["dq"], &[])?
+        .node("QuantizeLinear", &["x", "channel_scales", "channel_zeros"], &["qc"], &[("axis", (-1i64).into())])?
+        .node("DequantizeLinear", &["qc", "channel_scales", "channel_zeros"], &["dqc"], &[("axis", 1i64.into())])?
+        .node("QuantizeLinear", &["x", "scale"], &["q_default"], &[])?
+        .output("q")?
+        .output("dq")?
+        .output("qc")?
+        .output("dqc")?
+        .output("q_default")?;
+    let session = InferenceSession::new(builder.build()?)?;
+
+    let x = Tensor::new(vec![0.25, 0.75, -1.0, 100.0, -100.0, 3.1], vec![2, 3]);
+    let outputs = session.run(&[x])?;
+
+    // Ties round to even: 0.5 -> 0 and 1.5 -> 2; out-of-range values saturate
+    assert_eq!(outputs[0].element_type(), ElementType::U8);
+    assert_eq!(outputs[0].data_u8(), [128, 130, 126, 255, 0, 134]);
+    assert_eq!(outputs[1].data(), [0.0, 1.0, -1.0, 63.5, -64.0, 3.0]);
+
+    assert_eq!(outputs[2].element_type(), ElementType::I8);
+    assert_eq!(outputs[2].data_i8(), [0, -8, 6, 100, -128, 22]);
+    assert_eq!(outputs[3].data(), [0.0, 1.0, -1.0, 100.0, -59.0, 3.0]);
+
+    // Without a zero point the output is uint8
+    assert_eq!(outputs[4].data_u8(), [0, 2, 0, 200, 0, 6]);
+    Ok(())
+}
+
+#[test]
+fn dynamic_quantize_linear() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("dynamic");
+    builder
+        .input("x", DataType::Float, &[6])?
+        .node("DynamicQuantizeLinear", &["x"], &["y", "scale", "zero"], &[])?
+        .output("y")?
+        .output("scale")?
+        .output("zero")?;
+    let session = InferenceSession::new(builder.build()?)?;
+
+    let outputs = session.run(&[Tensor::new(vec![0.0, 2.0, -3.0, -2.5, 1.34, 0.5], vec![6])])?;
+    assert_eq!(outputs[0].data_u8(), [153, 255, 0, 26, 221, 179]);
+    assert!((outputs[1].data()[0] - 5.0 / 255.0).abs() < 1e-7);
+    assert_eq!((outputs[2].shape(), outputs[2].data_u8()), ([].as_slice(), [153].as_slice()));
+    Ok(())
+}
+
+#[test]
+fn matmul_integer() -> anyhow::Result<()> {
+    let a = [11u8, 7, 3, 10, 6, 2, 9, 5, 1, 8, 4, 0];
+    let b = [1u8, 4, 2, 5, 3, 6];
+    let mut builder = Graph::builder("matmul_integer");
+    builder
+        .input("a", DataType::Uint8, &[4, 3])?
+        .initializer("b", u8s(&b, &[3, 2]))?
+        .initializer("a_zero", u8s(&[12], &[]))?
+        .initializer("b_zero", u8s(&[0], &[]))?
+        .initializer("b_columns", u8s(&[1, 0], &[2]))?
+        .node("MatMulInteger", &["a", "b", "a_zero", "b_zero"], &["y"], &[])?
+        // An absent a_zero_point followed by per-column zero points for B
+        .node("MatMulInteger", &["a", "b", "", "b_columns"], &["y_columns"], &[])?
+        .output("y")?
+        .output("y_columns")?;
+    let session = InferenceSession::new(builder.build()?)?;
+
+    let outputs = session.run(&[u8s(&a, &[4, 3])])?;
+    assert_eq!(outputs[0].shape(), [4, 2]);
+    assert_eq!(outputs[0].element_type(), ElementType::I32);
+    assert_eq!(outputs[0].data_i32(), [-38, -83, -44, -98, -50, -113, -56, -128]);
+    // A @ (B - [1, 0])
+    assert_eq!(outputs[1].data_i32(), [13, 97, 10, 82, 7, 67, 4, 52]);
+
+    // A transformer-wide dot product, past the integers f32 holds exactly
+    let mut builder = Graph::builder("wide");
+    builder
+        .input("a", DataType::Uint8, &[1, 768])?
+        .initializer("b", u8s(&[255; 768], &[768, 1]))?
+        .node("MatMulInteger", &["a", "b"], &["y"], &[])?
+        .output("y")?;
+    let mut a = vec![255u8; 768];
+    a[0] = 254;
+    let y = InferenceSession::new(builder.build()?)?.run(&[u8s(&a, &[1, 768])])?.remove(0);
+    assert_eq!(y.data_i32(), [767 * 255 * 255 + 254 * 255]);
+    assert!(y.data_i32()[0] > 1 << 24);
+    Ok(())
+}
+
+#[test]
+fn qlinear_matmul() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("qlinear_matmul");
+    builder
+        .input("a", DataType::Uint8, &[2, 4])?
+        .initializer("a_scale", scalar(0.0066))?
+        .initializer("a_zero", u8s(&[113], &[]))?
+        .initializer("b", u8s(&[152, 51, 244, 60, 26, 255, 0, 127, 246, 127, 254, 247], &[4, 3]))?
+        .initializer("b_scale", scalar(0.00705))?
+        .initializer("b_zero", u8s(&[114], &[]))?
+        .initializer("y_scale", scalar(0.0107))?
+        .initializer("y_zero", u8s(&[118], &[]))?
+        .node("QLinearMatMul", &["a", "a_scale", "a_zero", "b", "b_scale", "b_zero", "y_scale", "y_zero"], &["y"], &[])?
+        .output("y")?;
+    let session = InferenceSession::new(builder.build()?)?;
+
+    let a = u8s(&[208, 236, 0, 238, 3, 214, 255, 29], &[2, 4]);
+    let y = session.run(&[a])?.remove(0);
+    assert_eq!(y.shape(), [2, 3]);
+    assert_eq!(y.data_u8(), [168, 115, 255, 1, 66, 151]);
+    Ok(())
+}
+
+#[test]
+fn qlinear_conv() -> anyhow::Result<()> {
+    // 1x1 convolution: y = round(x_scale * w_scale / y_scale * (x - x_zero) * (w - w_zero)) + y_zero
+    let mut builder = Graph::builder("qlinear_conv");
+    builder
+        .input("x", DataType::Uint8, &[1, 1, 2, 2])?
+        .initializer("x_scale", scalar(0.5))?
+        .initializer("x_zero", u8s(&[10], &[]))?
+        .initializer("w", Tensor::from_i8(vec![2, -3], vec![2, 1, 1, 1]))?
+        .initializer("w_scale", Tensor::new(vec![1.0, 0.5], vec![2]))?
+        .initializer("w_zero", Tensor::from_i8(vec![0, 0], vec![2]))?
+        .initializer("y_scale", scalar(0.25))?
+        .initializer("y_zero", Tensor::from_i8(vec![-5], vec![]))?
+        .initializer_as("bias", Tensor::new(vec![4.0, -2.0], vec![2]), DataType::Int32)?
+        .node(
+            "QLinearConv",
+            &["x", "x_scale", "x_zero", "w", "w_scale", "w_zero", "y_scale", "y_zero", "bias"],
+            &["y"],
+            &[("kernel_shape", vec![1i64, 1].into())],
+        )?
+        .output("y")?;
+    let session = InferenceSession::new(builder.build()?)?;
+
+    let y = session.run(&[u8s(&[10, 12, 8, 50], &[1, 1, 2, 2])])?.remove(0);
+    assert_eq!(y.element_type(), ElementType::I8);
+    assert_eq!(y.shape(), [1, 2, 2, 2]);
+    // Channel 0: (2 * (x - 10) + 4) * 2 - 5; channel 1: (-3 * (x - 10) - 2) * 1 - 5
+    assert_eq!(y.data_i8(), [3, 11, -5, 127, -7, -13, -1, -127]);
+    Ok(())
+}
+
+/// A QDQ model: activations quantized per tensor, weights per output channel.
+fn qdq_conv_matmul() -> anyhow::Result<Graph> {
+    let weights: Vec<i8> = (0..4 * 2 * 3 * 3).map(|i| ((i * 37) % 255) as i8).collect();
+    let w_scales = vec![0.01, 0.02, 0.015, 0.005];
+    let x_scale = 0.05;
+    let matrix: Vec<u8> = (0..4 * 3 * 3 * 5).map(|i| ((i * 53) % 256) as u8).collect();
+
+    let mut builder = Graph::builder("qdq");
+    builder
+        .input("x", DataType::Float, &[1, 2, 5, 5])?
+        .initializer("x_scale", scalar(x_scale))?
+        .initializer("x_zero", u8s(&[120], &[]))?
+        .initializer("w", Tensor::from_i8(weights, vec![4, 2, 3, 3]))?
+        .initializer("w_scale", Tensor::new(w_scales.clone(), vec![4]))?
+        .initializer_as("bias", Tensor::new(vec![100.0, -40.0, 0.0, 7.0], vec![4]), DataType::Int32)?
+        .initializer("bias_scale", Tensor::new(w_scales.iter().map(|s| s * x_scale).collect(), vec![4]))?
+        .initializer("c_scale", scalar(0.1))?
+        .initializer("c_zero", u8s(&[128], &[]))?
+        .initializer("m", u8s(&matrix, &[36, 5]))?
+        .initializer("m_scale", scalar(0.003))?
+        .initializer("m_zero", u8s(&[100], &[]))?
+        .initializer("y_scale", scalar(0.2))?
+        .initializer_as("shape", Tensor::new(vec![1.0, 36.0], vec![2]), DataType::Int64)?
+        .node("QuantizeLinear", &["x", "x_scale", "x_zero"], &["xq"], &[])?
+        .node("DequantizeLinear", &["xq", "x_scale", "x_zero"], &["xd"], &[])?
+        .node("DequantizeLinear", &["w", "w_scale"], &["wd"], &[("axis", 0i64.into())])?
+        .node("DequantizeLinear", &["bias", "bias_scale"], &["bd"], &[("axis", 0i64.into())])?
+        .node("Conv", &["xd", "wd", "bd"], &["c"], &[("pads", vec![0i64, 0, 0, 0].into())])?
+        .node("QuantizeLinear", &["c", "c_scale", "c_zero"], &["cq"], &[])?
+        .node("Reshape", &["cq", "shape"], &["flat"], &[])?
+        .node("DequantizeLinear", &["flat", "c_scale", "c_zero"], &["fd"], &[])?
+        .node("DequantizeLinear", &["m", "m_scale", "m_zero"], &["md"], &[])?
+        .node("MatMul", &["fd", "md"], &["mm"], &[])?
+        .node("QuantizeLinear", &["mm", "y_scale"], &["yq"], &[])?
+        .node("DequantizeLinear", &["yq", "y_scale"], &["y"], &[])?
+        .output("y")?;
+    builder.build()
+}
+
+#[test]
+fn qdq_fusion_matches_the_unfused_graph() -> anyhow::Result<()> {
+    let reference = InferenceSession::with_options(qdq_conv_matmul()?, unoptimized())?;
+    let options = SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::Extended);
+    let fused = InferenceSession::with_options(qdq_conv_matmul()?, options)?;
+
+    let ops: Vec<&str> = fused.graph.nodes.iter().map(|n| n.op_type.as_str()).collect();
+    assert_eq!(ops, ["QuantizeLinear", "QLinearConv", "Reshape", "QLinearMatMul", "DequantizeLinear"]);
+    // The missing output zero point of the MatMul was added as a uint8 initializer
+    let model = fused.graph.to_model()?;
+    let initializer = |name: &str| model.graph.as_ref().unwrap().initializer.iter().find(|t| t.name == name).cloned();
+    assert_eq!(initializer("yq_zero_point").map(|t| t.data_type), Some(DataType::Uint8 as i32));
+    assert_eq!(initializer("m").map(|t| t.data_type), Some(DataType::Uint8 as i32));
+    assert_eq!(initializer("bias").map(|t| t.data_type), Some(DataType::Int32 as i32));
+
+    let x = Tensor::new((0..50).map(|i| ((i as f32) * 0.37).sin() * 4.0).collect(), vec![1, 2, 5, 5]);
+    let expected = reference.run(std::slice::from_ref(&x))?.remove(0);
+    let actual = fused.run(&[x])?.remove(0);
+    assert_eq!(actual.shape(), [1, 5]);
+    // Requantizing integer accumulators can land one step away from the float path
+    for (a, e) in actual.data().iter().zip(expected.data()) {
+        assert!((a - e).abs() <= 0.2 + 1e-5, "{:?} vs {:?}", actual.data(), expected.data());
+    }
+    Ok(())
+}
+
+#[test]
+fn qdq_fusion_keeps_shared_and_float_patterns() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("shared");
+    builder
+        .input("x", DataType::Uint8, &[2, 3])?
+        .initializer("scale", scalar(0.1))?
+        .initializer("w", Tensor::new(vec![0.5; 6], vec![3, 2]))?
+        .initializer("wq", u8s(&[1, 2, 3, 4, 5, 6], &[3, 2]))?
+        .node("DequantizeLinear", &["x", "scale"], &["xd"], &[])?
+        .node("DequantizeLinear", &["wq", "scale"], &["wd"], &[])?
+        .node("MatMul", &["xd", "wd"], &["a"], &[])?
+        .node("QuantizeLinear", &["a", "scale"], &["aq"], &[])?
+        // A float weight is not quantized, so this MatMul stays
+        .node("MatMul", &["xd", "w"], &["b"], &[])?
+        .node("QuantizeLinear", &["b", "scale"], &["bq"], &[])?
+        .output("aq")?
+        .output("bq")?;
+    let session = InferenceSession::new(builder.build()?)?;
+    let ops: Vec<&str> = session.graph.nodes.iter().map(|n| n.op_type.as_str()).collect();
+    // xd is still read by the second MatMul
+    assert_eq!(ops, ["DequantizeLinear", "QLinearMatMul", "MatMul", "QuantizeLinear"]);
+
+    let outputs = session.run(&[u8s(&[10, 20, 30, 0, 5, 255], &[2, 3])])?;
+    assert_eq!(outputs[0].data_u8(), [22, 28, 129, 155]);
+    assert_eq!(outputs[1].data_u8(), [30, 30, 130, 130]);
+    Ok(())
+}
@@ -0,0 +1,330 @@
+//! LSTM, GRU and RNN against scalar references.
+//!
+//! Most cases hold a single value per gate block of the weights, so all the
+//! hidden units of a sequence follow the same scalar recurrence. The
+//! `*_reference` cases use distinct weights everywhere instead, on the shapes
+//! of the ONNX `test_lstm_with_peepholes` and `test_gru_seq_length` cases.
+
+use neuroxyde::graph::{AttributeValue, Graph};
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::runtime::{GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+
+const HIDDEN: usize = 3;
+const INPUT: usize = 2;
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// `[directions, gates * HIDDEN, cols]` weights, direction by direction, holding `gates[d][g]` in gate block `g`.
+fn weights(gates: &[&[f32]], cols: usize) -> Tensor {
+    let data: Vec<f32> = gates.iter()
+        .flat_map(|direction| direction.iter().flat_map(|&v| std::iter::repeat_n(v, HIDDEN * cols)))
+        .collect();
+    Tensor::new(data, vec![gates.len(), gates[0].len() * HIDDEN, cols])
+}
+
+/// `[directions, 2 * gates * HIDDEN]` biases: `Wb` then `Rb`, each per gate block.
+fn biases(wb: &[f32], rb: &[f32]) -> Tensor {
+    let data: Vec<f32> = wb.iter().chain(rb).flat_map(|&v| std::iter::repeat_n(v, HIDDEN)).collect();
+    Tensor::new(data, vec![1, 2 * wb.len() * HIDDEN])
+}
+
+/// `[seq, batch, INPUT]` inputs.
+fn sequence(seq: usize, batch: usize) -> Tensor {
+    Tensor::new((0..seq * batch * INPUT).map(|i| ((i as f32) * 0.37).sin()).collect(), vec![seq, batch, INPUT])
+}
+
+/// `len` distinct weights in [-11/64, 11/64], exact in f32; `salt` varies them between tensors.
+fn pattern(len: usize, salt: usize) -> Vec<f32> {
+    (0..len).map(|k| ((k * 37 + salt * 11) % 23) as f32 / 64.0 - 11.0 / 64.0).collect()
+}
+
+fn run(op: &str, inputs: &[(&str, Option<Tensor>)], outputs: &[&str], attributes: &[(&str, AttributeValue)]) -> anyhow::Result<Vec<Tensor>> {
+    let mut builder = Graph::builder("recurrent");
+    let x = inputs[0].1.clone().unwrap();
+    builder.input("x", DataType::Float, &x.shape().iter().map(|&d| d as i64).collect::<Vec<_>>())?;
+    for (name, tensor) in &inputs[1..] {
+        if let Some(tensor) = tensor {
+            let data_type = if *name == "sequence_lens" { DataType::Int32 } else { DataType::Float };
+            builder.initializer_as(name, tensor.clone(), data_type)?;
+        }
+    }
+    let names: Vec<&str> = inputs.iter().map(|(name, t)| if t.is_some() { *name } else { "" }).collect();
+    builder.node(op, &names, outputs, attributes)?;
+    for output in outputs.iter().filter(|o| !o.is_empty()) {
+        builder.output(output)?;
+    }
+    let options = SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::DisableAll);
+    InferenceSession::with_options(builder.build()?, options)?.run(&[x])
+}
+
+fn assert_close(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len(), "{:?} vs {:?}", actual, expected);
+    for (a, e) in actual.iter().zip(expected) {
+        assert!((a - e).abs() < 1e-5, "{:?} vs {:?}", actual, expected);
+    }
+}
+
+/// Unit 0 of `Y` (`[seq, directions, batch, HIDDEN]`) at every step, for one direction and sequence.
+fn units(y: &Tensor, direction: usize, b: usize) -> Vec<f32> {
+    let (seq, directions, batch) = (y.shape()[0], y.shape()[1], y.shape()[2]);
+    (0..seq).map(|t| y.data()[((t * directions + direction) * batch + b) * HIDDEN]).collect()
+}
+
+fn sums(x: &Tensor, b: usize) -> Vec<f32> {
+    let (seq, batch) = (x.shape()[0], x.shape()[1]);
+    (0..seq).map(|t| x.data()[(t * batch + b) * INPUT..(t * batch + b + 1) * INPUT].iter().sum()).collect()
+}
+
+#[test]
+fn rnn_directions_layout_and_activations() -> anyhow::Result<()> {
+    let x = sequence(4, 2);
+    let (w, r) = (0.3, -0.2);
+    let reference = |sums: &[f32], h0: f32, f: &dyn Fn(f32) -> f32| -> Vec<f32> {
+        let mut h = h0;
+        sums.iter().map(|s| { h = f(w * s + r * HIDDEN as f32 * h + 0.1); h }).collect()
+    };
+    let inputs = |directions: usize| -> Vec<(&str, Option<Tensor>)> {
+        let gates: Vec<&[f32]> = vec![&[0.3]; directions];
+        let recurrent: Vec<&[f32]> = vec![&[-0.2]; directions];
+        let b = Tensor::new(vec![0.05; 2 * HIDDEN * directions], vec![directions, 2 * HIDDEN]);
+        vec![("x", Some(x.clone())), ("w", Some(weights(&gates, INPUT))), ("r", Some(weights(&recurrent, HIDDEN))), ("b", Some(b))]
+    };
+
+    let outputs = run("RNN", &inputs(1), &["y", "y_h"], &[])?;
+    assert_eq!(outputs[0].shape(), [4, 1, 2, HIDDEN]);
+    for b in 0..2 {
+        let expected = reference(&sums(&x, b), 0.0, &|v| v.tanh());
+        assert_close(&units(&outputs[0], 0, b), &expected);
+        assert_close(&outputs[1].data()[b * HIDDEN..(b + 1) * HIDDEN], &[expected[3]; HIDDEN]);
+    }
+
+    // Bidirectional: the second direction reads the sequence backwards and ends at step 0
+    let outputs = run("RNN", &inputs(2), &["y", "y_h"], &[("direction", "bidirectional".into())])?;
+    assert_eq!(outputs[0].shape(), [4, 2, 2, HIDDEN]);
+    let mut backwards = sums(&x, 1);
+    backwards.reverse();
+    let mut expected = reference(&backwards, 0.0, &|v| v.tanh());
+    expected.reverse();
+    assert_close(&units(&outputs[0], 1, 1), &expected);
+    assert_close(&outputs[1].data()[3 * HIDDEN..], &[expected[0]; HIDDEN]);
+
+    // Batch-major layout: same values, transposed
+    let mut batch_major = inputs(1);
+    batch_major[0].1 = Some(x.permute(&[1, 0, 2])?.to_contiguous());
+    let transposed = run("RNN", &batch_major, &["y", "y_h"], &[("layout", 1i64.into())])?;
+    let forward = run("RNN", &inputs(1), &["y", "y_h"], &[])?;
+    assert_eq!(transposed[0].shape(), [2, 4, 1, HIDDEN]);
+    assert_eq!(transposed[0].to_vec(), forward[0].permute(&[2, 0, 1, 3])?.to_vec());
+    assert_eq!(transposed[1].shape(), [2, 1, HIDDEN]);
+
+    // Parameterized activation and clipped gate inputs
+    let outputs = run("RNN", &inputs(1), &["y"], &[
+        ("activations", AttributeValue::Strings(vec!["HardSigmoid".to_string()])),
+        ("activation_alpha", vec![0.3f32].into()),
+        ("activation_beta", vec![0.4f32].into()),
+        ("clip", 0.5f32.into()),
+    ])?;
+    let expected = reference(&sums(&x, 0), 0.0, &|v| (0.3 * v.clamp(-0.5, 0.5) + 0.4).clamp(0.0, 1.0));
+    assert_close(&units(&outputs[0], 0, 0), &expected);
+    Ok(())
+}
+
+#[test]
+fn lstm_with_peepholes_initial_states_and_sequence_lens() -> anyhow::Result<()> {
+    let x = sequence(3, 2);
+    // Gate blocks are stacked input, output, forget, cell
+    let (w, r) = ([0.2, -0.1, 0.3, 0.5], [0.1, 0.2, -0.3, 0.05]);
+    let (wb, rb) = ([0.1, 0.0, 0.2, -0.1], [0.0, 0.05, 0.0, 0.1]);
+    let p = [0.3, -0.2, 0.1];
+    let (h0, c0) = (0.25, -0.5);
+    let inputs: Vec<(&str, Option<Tensor>)> = vec![
+        ("x", Some(x.clone())),
+        ("w", Some(weights(&[&w], INPUT))),
+        ("r", Some(weights(&[&r], HIDDEN))),
+        ("b", Some(biases(&wb, &rb))),
+        ("sequence_lens", Some(Tensor::new(vec![3.0, 1.0], vec![2]))),
+        ("initial_h", Some(Tensor::new(vec![h0; 2 * HIDDEN], vec![1, 2, HIDDEN]))),
+        ("initial_c", Some(Tensor::new(vec![c0; 2 * HIDDEN], vec![1, 2, HIDDEN]))),
+        ("p", Some(Tensor::new(p.iter().flat_map(|&v| [v; HIDDEN]).collect(), vec![1, 3 * HIDDEN]))),
+    ];
+    let outputs = run("LSTM", &inputs, &["y", "y_h", "y_c"], &[])?;
+
+    let reference = |sums: &[f32]| -> (Vec<f32>, f32) {
+        let (mut h, mut c) = (h0, c0);
+        let hidden = HIDDEN as f32;
+        let ys = sums.iter()
+            .map(|s| {
+                let pre = |g: usize| w[g] * s + r[g] * hidden * h + wb[g] + rb[g];
+                let i = sigmoid(pre(0) + p[0] * c);
+                let f = sigmoid(pre(2) + p[2] * c);
+                c = f * c + i * pre(3).tanh();
+                h = sigmoid(pre(1) + p[1] * c) * c.tanh();
+                h
+            })
+            .collect();
+        (ys, c)
+    };
+    let (expected, c) = reference(&sums(&x, 0));
+    assert_close(&units(&outputs[0], 0, 0), &expected);
+    assert_close(&outputs[1].data()[..HIDDEN], &[expected[2]; HIDDEN]);
+    assert_close(&outputs[2].data()[..HIDDEN], &[c; HIDDEN]);
+
+    // The second sequence stops after one step: later rows of Y stay zero
+    let (expected, c) = reference(&sums(&x, 1)[..1]);
+    assert_close(&units(&outputs[0], 0, 1), &[expected[0], 0.0, 0.0]);
+    assert_close(&outputs[1].data()[HIDDEN..], &[expected[0]; HIDDEN]);
+    assert_close(&outputs[2].data()[HIDDEN..], &[c; HIDDEN]);
+
+    // Outputs can be left out, and `input_forget` couples the forget gate to the input gate
+    let only_state = run("LSTM", &inputs, &["", "y_h"], &[])?;
+    assert_eq!(only_state[0].to_vec(), outputs[1].to_vec());
+    let coupled = run("LSTM", &inputs, &["y"], &[("input_forget", 1i64.into())])?;
+    assert_ne!(coupled[0].to_vec(), outputs[0].to_vec());
+    Ok(())
+}
+
+#[test]
+fn gru_with_and_without_linear_before_reset() -> anyhow::Result<()> {
+    let x = sequence(3, 1);
+    // Gate blocks are stacked update, reset, hidden
+    let (w, r) = ([0.2, -0.4, 0.6], [0.3, 0.1, -0.5]);
+    let (wb, rb) = ([0.1, -0.1, 0.2], [0.05, 0.1, 0.3]);
+    let inputs: Vec<(&str, Option<Tensor>)> = vec![
+        ("x", Some(x.clone())),
+        ("w", Some(weights(&[&w], INPUT))),
+        ("r", Some(weights(&[&r], HIDDEN))),
+        ("b", Some(biases(&wb, &rb))),
+        ("sequence_lens", None),
+        ("initial_h", Some(Tensor::new(vec![0.5; HIDDEN], vec![1, 1, HIDDEN]))),
+    ];
+    let hidden = HIDDEN as f32;
+    for linear_before_reset in [0i64, 1] {
+        let outputs = run("GRU", &inputs, &["y", "y_h"], &[("linear_before_reset", linear_before_reset.into())])?;
+        let mut h = 0.5f32;
+        let expected: Vec<f32> = sums(&x, 0).iter()
+            .map(|s| {
+                let z = sigmoid(w[0] * s + r[0] * hidden * h + wb[0] + rb[0]);
+                let reset = sigmoid(w[1] * s + r[1] * hidden * h + wb[1] + rb[1]);
+                let recurrent = if linear_before_reset == 1 {
+                    reset * (r[2] * hidden * h + rb[2])
+                } else {
+                    r[2] * hidden * (reset * h) + rb[2]
+                };
+                let candidate = (w[2] * s + recurrent + wb[2]).tanh();
+                h = (1.0 - z) * candidate + z * h;
+                h
+            })
+            .collect();
+        assert_close(&units(&outputs[0], 0, 0), &expected);
+        assert_close(outputs[1].data(), &[expected[2]; HIDDEN]);
+    }
+
+    // Reverse direction over the same sequence ends where the sequence starts
+    let reverse = run("GRU", &inputs, &["y"], &[("direction", "reverse".into())])?;
+    let y = units(&reverse[0], 0, 0);
+    assert!(y[0] != 0.0 && y[2] != 0.0);
+    Ok(())
+}
+
+#[test]
+fn lstm_with_peepholes_reference() -> anyhow::Result<()> {
+    // Expected values computed in f64 with the equations of the ONNX reference implementation
+    let hidden = 3;
+    let x = Tensor::new((1..=8).map(|v| v as f32).collect(), vec![1, 2, 4]);
+    let inputs: Vec<(&str, Option<Tensor>)> = vec![
+        ("x", Some(x)),
+        ("w", Some(Tensor::new(pattern(4 * hidden * 4, 1), vec![1, 4 * hidden, 4]))),
+        ("r", Some(Tensor::new(pattern(4 * hidden * hidden, 2), vec![1, 4 * hidden, hidden]))),
+        ("b", Some(Tensor::new(pattern(8 * hidden, 3), vec![1, 8 * hidden]))),
+        ("sequence_lens", None),
+        ("initial_h", Some(Tensor::new(pattern(2 * hidden, 8), vec![1, 2, hidden]))),
+        ("initial_c", Some(Tensor::new(pattern(2 * hidden, 9), vec![1, 2, hidden]))),
+        ("p", Some(Tensor::new(pattern(3 * hidden, 4), vec![1, 3 * hidden]))),
+    ];
+    let outputs = run("LSTM", &inputs, &["y", "y_h", "y_c"], &[("hidden_size", (hidden as i64).into())])?;
+
+    let y = [-0.1061013, 0.0264015, -0.1433113, -0.1303676, 0.0189748, -0.292759];
+    assert_eq!(outputs[0].shape(), [1, 1, 2, hidden]);
+    assert_close(outputs[0].data(), &y);
+    assert_close(outputs[1].data(), &y);
+    assert_close(outputs[2].data(), &[-0.2645157, 0.0700281, -0.2455213, -0.3675738, 0.0742211, -0.4471212]);
+    Ok(())
+}
+
+#[test]
+fn gru_seq_length_reference() -> anyhow::Result<()> {
+    // Expected values computed in f64 with the equations of the ONNX reference implementation
+    let hidden = 5;
+    let x = Tensor::new((1..=18).map(|v| v as f32).collect(), vec![2, 3, 3]);
+    let inputs: Vec<(&str, Option<Tensor>)> = vec![
+        ("x", Some(x)),
+        ("w", Some(Tensor::new(pattern(3 * hidden * 3, 5), vec![1, 3 * hidden, 3]))),
+        ("r", Some(Tensor::new(pattern(3 * hidden * hidden, 6), vec![1, 3 * hidden, hidden]))),
+        ("b", Some(Tensor::new(pattern(6 * hidden, 7), vec![1, 6 * hidden]))),
+        // The second sequence stops after one step
+        ("sequence_lens", Some(Tensor::new(vec![2.0, 1.0, 2.0], vec![3]))),
+    ];
+    let expected: [([f32; 30], [f32; 15]); 2] = [
+        (
+            [
+                0.1885193, -0.1407302, 0.2356374, -0.1133094, -0.0416072,
+                0.4184654, -0.2094601, 0.4383823, -0.2436411, 0.0357904,
+                0.6161185, -0.2634021, 0.6201623, -0.3520719, 0.0625719,
+                0.7859862, -0.3889747, 0.7986926, -0.4828264, 0.0277227,
+                0.0, 0.0, 0.0, 0.0, 0.0,
+                0.950916, -0.5093084, 0.9522882, -0.6908777, 0.0989647,
+            ],
+            [
+                0.7859862, -0.3889747, 0.7986926, -0.4828264, 0.0277227,
+                0.4184654, -0.2094601, 0.4383823, -0.2436411, 0.0357904,
+                0.950916, -0.5093084, 0.9522882, -0.6908777, 0.0989647,
+            ],
+        ),
+        (
+            [
+                0.1806744, -0.1183851, 0.2131784, -0.1017853, -0.0119422,
+                0.4117659, -0.1950517, 0.4179998, -0.2313017, 0.0568928,
+                0.6115479, -0.2550803, 0.6048026, -0.3409243, 0.0748021,
+                0.7843382, -0.3691523, 0.7841246, -0.474618, 0.0597341,
+                0.0, 0.0, 0.0, 0.0, 0.0,
+                0.9514883, -0.499157, 0.9474216, -0.6899318, 0.1114794,
+            ],
+            [
+                0.7843382, -0.3691523, 0.7841246, -0.474618, 0.0597341,
+                0.4117659, -0.1950517, 0.4179998, -0.2313017, 0.0568928,
+                0.9514883, -0.499157, 0.9474216, -0.6899318, 0.1114794,
+            ],
+        ),
+    ];
+    for (linear_before_reset, (y, y_h)) in expected.iter().enumerate() {
+        let attributes = [("hidden_size", (hidden as i64).into()), ("linear_before_reset", (linear_before_reset as i64).into())];
+        let outputs = run("GRU", &inputs, &["y", "y_h"], &attributes)?;
+        assert_eq!(outputs[0].shape(), [2, 1, 3, hidden]);
+        assert_close(outputs[0].data(), y);
+        assert_close(outputs[1].data(), y_h);
+    }
+    Ok(())
+}
+
+#[test]
+fn recurrent_shape_errors() -> anyhow::Result<()> {
+    let x = sequence(2, 1);
+    let inputs: Vec<(&str, Option<Tensor>)> = vec![
+        ("x", Some(x.clone())),
+        ("w", Some(weights(&[&[0.1, 0.2, 0.3]], INPUT))),
+        ("r", Some(weights(&[&[0.1, 0.2, 0.3]], HIDDEN))),
+    ];
+    // GRU-shaped weights given to an LSTM
+    let err = run("LSTM", &inputs, &["y"], &[]).err().unwrap();
+    assert!(format!("{:#}", err).contains("do not match 1 direction(s), 4 gates"), "{:#}", err);
+    let err = run("GRU", &inputs, &["y"], &[("activations", AttributeValue::Strings(vec!["Swish".to_string(); 2]))]).err().unwrap();
+    assert!(format!("{:#}", err).contains("unsupported activation 'Swish'"), "{:#}", err);
+    for clip in [-1.0f32, f32::NAN] {
+        let err = run("GRU", &inputs, &["y"], &[("clip", clip.into())]).err().unwrap();
+        assert!(format!("{:#}", err).contains("clip must be a non-negative threshold"), "{:#}", err);
+    }
+    Ok(())
+}
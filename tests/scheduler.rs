@@ -0,0 +1,70 @@
+//! The parallel scheduler must produce the same results as the sequential executor.
+
+mod common;
+
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::GraphProto;
+use neuroxyde::runtime::{ExecutionMode, InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+use std::collections::HashMap;
+use common::{float_input, node};
+
+/// x fans out into four independent branches that are joined pairwise.
+fn branched_graph() -> Graph {
+    let nodes = vec![
+        node("Relu", &["x"], "a"),
+        node("Neg", &["x"], "b"),
+        node("Abs", &["x"], "c"),
+        node("Sqrt", &["c"], "d"),
+        node("Add", &["a", "b"], "ab"),
+        node("Mul", &["c", "d"], "cd"),
+        node("Sub", &["ab", "cd"], "y"),
+    ];
+    let proto = GraphProto {
+        node: nodes.clone(),
+        input: vec![float_input("x", &[4, 1024])],
+        ..Default::default()
+    };
+    Graph {
+        proto,
+        nodes,
+        initializers: HashMap::new(),
+        inputs: vec!["x".to_string()],
+        outputs: vec!["y".to_string(), "ab".to_string()],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn parallel_matches_sequential() {
+    let data: Vec<f32> = (0..4096).map(|i| (i as f32 * 0.37).sin() * 5.0).collect();
+    let input = vec![Tensor::new(data, vec![4, 1024])];
+
+    let sequential = InferenceSession::new(branched_graph()).unwrap();
+    assert_eq!(sequential.execution_mode(), ExecutionMode::Sequential);
+    let expected = sequential.run(&input).unwrap();
+
+    for threads in [1, 2, 4] {
+        let options = SessionOptions::new()
+            .with_execution_mode(ExecutionMode::Parallel)
+            .with_inter_op_num_threads(threads);
+        let parallel = InferenceSession::with_options(branched_graph(), options).unwrap();
+        assert_eq!(parallel.inter_op_num_threads(), threads);
+        let outputs = parallel.run(&input).unwrap();
+        assert_eq!(outputs.len(), expected.len());
+        for (e, o) in expected.iter().zip(&outputs) {
+            assert_eq!(e.shape(), o.shape());
+            assert_eq!(e.data(), o.data());
+        }
+    }
+}
+
+#[test]
+fn parallel_reports_operator_errors() {
+    let mut graph = branched_graph();
+    graph.nodes[3].op_type = "NotAnOp".to_string();
+    let options = SessionOptions::new().with_execution_mode(ExecutionMode::Parallel);
+    let session = InferenceSession::with_options(graph, options).unwrap();
+    let err = session.run(&[Tensor::zeros(&[4, 1024])]).unwrap_err();
+    assert!(err.to_string().contains("NotAnOp"), "{}", err);
+}
@@ -0,0 +1,116 @@
+//! An optimized graph must survive `to_model` -> `save_to_file` -> `load_from_file`.
+
+mod common;
+
+use neuroxyde::graph::Graph;
+use neuroxyde::loader::ModelLoader;
+use neuroxyde::onnx::onnx_proto::{
+    attribute_proto, AttributeProto, GraphProto, ModelProto, OperatorSetIdProto, StringStringEntryProto, TensorProto,
+};
+use neuroxyde::runtime::{GraphOptimizationLevel, InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+use common::{float_input, node_with};
+
+fn initializer(name: &str, shape: &[i64], seed: f32) -> TensorProto {
+    let len = shape.iter().product::<i64>() as usize;
+    let values: Vec<f32> = (0..len).map(|i| ((i as f32 + seed) * 0.613).sin()).collect();
+    Tensor::new(values, shape.iter().map(|&d| d as usize).collect()).to_proto(name)
+}
+
+/// Conv -> BatchNormalization -> Relu -> Reshape -> MatMul -> Add, with an int64 reshape target.
+fn model() -> ModelProto {
+    let target = TensorProto {
+        name: "target".to_string(),
+        dims: vec![2],
+        data_type: 7,
+        int64_data: vec![1, -1],
+        ..Default::default()
+    };
+    let graph = GraphProto {
+        name: "roundtrip".to_string(),
+        node: vec![
+            node_with("Conv", &["x", "w"], "c", vec![AttributeProto {
+                name: "pads".to_string(),
+                ints: vec![1, 1, 1, 1],
+                r#type: attribute_proto::AttributeType::Ints as i32,
+                ..Default::default()
+            }]),
+            node_with("BatchNormalization", &["c", "scale", "shift", "mean", "var"], "bn", vec![]),
+            node_with("Relu", &["bn"], "r", vec![]),
+            node_with("Identity", &["target"], "target_copy", vec![]),
+            node_with("Reshape", &["r", "target_copy"], "flat", vec![]),
+            node_with("MatMul", &["flat", "fc"], "m", vec![]),
+            node_with("Add", &["m", "fc_bias"], "y", vec![]),
+        ],
+        initializer: vec![
+            initializer("w", &[3, 2, 3, 3], 1.0),
+            initializer("scale", &[3], 2.0),
+            initializer("shift", &[3], 3.0),
+            initializer("mean", &[3], 4.0),
+            Tensor::new(vec![0.5, 1.5, 2.5], vec![3]).to_proto("var"),
+            initializer("fc", &[48, 4], 5.0),
+            initializer("fc_bias", &[4], 6.0),
+            target,
+        ],
+        input: vec![float_input("x", &[1, 2, 4, 4])],
+        output: vec![float_input("y", &[1, 4])],
+        ..Default::default()
+    };
+    ModelProto {
+        ir_version: 8,
+        producer_name: "neuroxyde-tests".to_string(),
+        opset_import: vec![OperatorSetIdProto { domain: String::new(), version: 13 }],
+        metadata_props: vec![StringStringEntryProto { key: "author".to_string(), value: "tests".to_string() }],
+        graph: Some(graph),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn optimized_graph_round_trips() {
+    let input = vec![Tensor::new((0..32).map(|i| (i as f32 * 0.3).cos()).collect(), vec![1, 2, 4, 4])];
+    let loader = ModelLoader { model: model() };
+
+    let reference = InferenceSession::with_options(
+        Graph::from_model(&loader).unwrap(),
+        SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::DisableAll),
+    ).unwrap();
+    let expected = reference.run(&input).unwrap();
+
+    let optimized = InferenceSession::new(Graph::from_model(&loader).unwrap()).unwrap();
+    let saved = ModelLoader { model: optimized.graph.to_model().unwrap() };
+    let path = std::env::temp_dir().join(format!("neuroxyde-roundtrip-{}.onnx", std::process::id()));
+    saved.save_to_file(&path).unwrap();
+    let reloaded = ModelLoader::load_from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let model = &reloaded.model;
+    assert_eq!(model.ir_version, 8);
+    assert_eq!(model.producer_name, "neuroxyde-tests");
+    assert_eq!(model.metadata_props, loader.model.metadata_props);
+    let domains: Vec<(&str, i64)> = model.opset_import.iter().map(|o| (o.domain.as_str(), o.version)).collect();
+    assert_eq!(domains, [("", 13), ("com.microsoft", 1)]);
+
+    let graph = Graph::from_model(&reloaded).unwrap();
+    assert_eq!(graph.nodes, optimized.graph.nodes);
+    let ops: Vec<&str> = graph.nodes.iter().map(|n| n.op_type.as_str()).collect();
+    assert_eq!(ops, ["FusedConv", "Reshape", "Gemm"]);
+    assert_eq!(graph.initializers.len(), optimized.graph.initializers.len());
+    for (name, tensor) in &optimized.graph.initializers {
+        assert_eq!(graph.initializers[name].shape(), tensor.shape());
+        assert_eq!(graph.initializers[name].data(), tensor.data());
+    }
+    // The reshape target keeps its int64 type
+    let target = graph.proto.initializer.iter().find(|t| t.name == "target").unwrap();
+    assert_eq!(target.data_type, 7);
+
+    // No need to optimize again: the reloaded graph runs as is
+    let session = InferenceSession::with_options(
+        graph,
+        SessionOptions::new().with_graph_optimization_level(GraphOptimizationLevel::DisableAll),
+    ).unwrap();
+    let actual = session.run(&input).unwrap();
+    for (a, e) in actual[0].data().iter().zip(expected[0].data()) {
+        assert!((a - e).abs() <= 1e-4 * e.abs().max(1.0), "{} vs {}", a, e);
+    }
+}
@@ -0,0 +1,83 @@
+//! Each session option changes what it documents: shapes the optimizer sees, buffer reuse, kernel choice.
+
+use neuroxyde::graph::Graph;
+use neuroxyde::kernels::KernelBackend;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::onnx::onnx_proto::{tensor_shape_proto, type_proto};
+use neuroxyde::runtime::{InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+
+/// `y = Neg(Reshape(Relu(x), Shape(x)))` with `x: [batch, 3, 4]`.
+fn graph() -> anyhow::Result<Graph> {
+    let mut builder = Graph::builder("options");
+    builder
+        .input("x", DataType::Float, &[-1, 3, 4])?
+        .node("Shape", &["x"], &["s"], &[])?
+        .node("Relu", &["x"], &["r"], &[])?
+        .node("Reshape", &["r", "s"], &["flat"], &[])?
+        .node("Neg", &["flat"], &["y"], &[])?
+        .output("y")?;
+    let mut graph = builder.build()?;
+    let Some(type_proto::Value::TensorType(tensor_type)) = graph.proto.input[0].r#type.as_mut().and_then(|t| t.value.as_mut()) else {
+        unreachable!()
+    };
+    tensor_type.shape.as_mut().unwrap().dim[0].value = Some(tensor_shape_proto::dimension::Value::DimParam("batch".to_string()));
+    Ok(graph)
+}
+
+fn input(batch: usize) -> Tensor {
+    Tensor::new((0..batch * 12).map(|i| i as f32 - 6.0).collect(), vec![batch, 3, 4])
+}
+
+fn has_shape_node(session: &InferenceSession) -> bool {
+    session.graph.nodes.iter().any(|n| n.op_type == "Shape")
+}
+
+#[test]
+fn free_dimension_override_reaches_constant_folding() -> anyhow::Result<()> {
+    let session = InferenceSession::new(graph()?)?;
+    assert!(has_shape_node(&session));
+    assert_eq!(session.run(&[input(3)])?[0].shape(), [3, 3, 4]);
+
+    let options = SessionOptions::new().with_free_dimension_override("batch", 2);
+    let session = InferenceSession::with_options(graph()?, options)?;
+    assert!(!has_shape_node(&session));
+    let outputs = session.run(&[input(2)])?;
+    assert_eq!(outputs[0].shape(), [2, 3, 4]);
+    assert_eq!(outputs[0].data()[..3], [-0.0, -0.0, -0.0]);
+    assert_eq!(outputs[0].data()[23], -17.0);
+
+    let err = session.run(&[input(3)]).err().unwrap();
+    assert!(err.to_string().contains("'x'"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn mem_arena_keeps_intermediate_buffers() -> anyhow::Result<()> {
+    let session = InferenceSession::new(graph()?)?;
+    let arena = session.arena().expect("the arena is enabled by default");
+    assert_eq!(arena.cached_len(), 0);
+    session.run(&[input(2)])?;
+    assert!(arena.cached_len() >= 24, "{}", arena.cached_len());
+
+    let session = InferenceSession::with_options(graph()?, SessionOptions::new().with_mem_arena(false))?;
+    assert!(session.arena().is_none());
+    assert_eq!(session.run(&[input(2)])?[0].shape(), [2, 3, 4]);
+    Ok(())
+}
+
+#[test]
+fn deterministic_selects_scalar_kernels_unless_forced() -> anyhow::Result<()> {
+    let session = InferenceSession::with_options(graph()?, SessionOptions::new().with_deterministic(true))?;
+    assert_eq!(session.kernel_backend(), KernelBackend::Scalar);
+
+    let detected = KernelBackend::detect();
+    let session = InferenceSession::with_options(graph()?, SessionOptions::new().with_deterministic(false))?;
+    assert_eq!(session.kernel_backend(), detected);
+
+    // An explicit backend wins over the deterministic default
+    let options = SessionOptions::new().with_deterministic(true).with_kernel_backend(detected);
+    let session = InferenceSession::with_options(graph()?, options)?;
+    assert_eq!(session.kernel_backend(), detected);
+    Ok(())
+}
@@ -0,0 +1,193 @@
+//! .npy/.npz and TensorProto .pb round trips, element types and memory orders.
+
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::onnx::onnx_proto::TensorProto;
+use neuroxyde::tensor::{ElementType, Tensor};
+use std::path::PathBuf;
+
+fn temp(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("neuroxyde-io-{}-{}", std::process::id(), name))
+}
+
+/// Builds a version 1.0 .npy file by hand, as NumPy lays it out.
+fn npy(descr: &str, fortran: bool, shape: &str, payload: &[u8]) -> Vec<u8> {
+    let mut header = format!("{{'descr': '{}', 'fortran_order': {}, 'shape': {}, }}", descr, if fortran { "True" } else { "False" }, shape);
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+    let mut out = b"\x93NUMPY\x01\x00".to_vec();
+    out.extend((header.len() as u16).to_le_bytes());
+    out.extend(header.as_bytes());
+    out.extend(payload);
+    out
+}
+
+#[test]
+fn npy_dtypes_and_orders() -> anyhow::Result<()> {
+    let t = Tensor::from_npy_bytes(&npy("|u1", false, "(3,)", &[0, 7, 255]))?;
+    assert_eq!((t.shape(), t.data_u8()), ([3].as_slice(), [0, 7, 255].as_slice()));
+    assert_eq!(t.to_npy_bytes(), npy("|u1", false, "(3,)", &[0, 7, 255]));
+
+    let t = Tensor::from_npy_bytes(&npy("|b1", false, "(2,)", &[1, 0]))?;
+    assert_eq!(t.data(), [1.0, 0.0]);
+
+    let payload: Vec<u8> = [-2i64, 40].iter().flat_map(|v| v.to_be_bytes()).collect();
+    let t = Tensor::from_npy_bytes(&npy(">i8", false, "(2,)", &payload))?;
+    assert_eq!(t.data(), [-2.0, 40.0]);
+
+    // 1.0, -2.5 and 65504 (largest finite half) in binary16
+    let payload: Vec<u8> = [0x3c00u16, 0xc100, 0x7bff].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let t = Tensor::from_npy_bytes(&npy("<f2", false, "(3,)", &payload))?;
+    assert_eq!(t.element_type(), ElementType::F16);
+    assert_eq!(t.to_vec(), [1.0, -2.5, 65504.0]);
+    assert_eq!(t.to_npy_bytes(), npy("<f2", false, "(3,)", &payload));
+
+    // Scalar
+    let t = Tensor::from_npy_bytes(&npy("<f4", false, "()", &1.5f32.to_le_bytes()))?;
+    assert_eq!((t.shape(), t.data()), ([].as_slice(), [1.5].as_slice()));
+
+    // [[0, 1, 2], [3, 4, 5]] stored column by column
+    let payload: Vec<u8> = [0i32, 3, 1, 4, 2, 5].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let t = Tensor::from_npy_bytes(&npy("<i4", true, "(2, 3)", &payload))?;
+    assert_eq!(t.shape(), [2, 3]);
+    assert_eq!(t.data_i32(), [0, 1, 2, 3, 4, 5]);
+
+    // int32 stays exact past 2^24, where f32 cannot hold every integer
+    let payload: Vec<u8> = [(1 << 24) + 1, i32::MIN].iter().flat_map(|v: &i32| v.to_be_bytes()).collect();
+    let t = Tensor::from_npy_bytes(&npy(">i4", false, "(2,)", &payload))?;
+    assert_eq!(t.data_i32(), [(1 << 24) + 1, i32::MIN]);
+    assert_eq!(Tensor::from_npy_bytes(&t.to_npy_bytes())?.data_i32(), t.data_i32());
+    assert_eq!(Tensor::from_proto(&t.to_proto("t"))?.data_i32(), t.data_i32());
+
+    assert!(Tensor::from_npy_bytes(&npy("<c8", false, "(1,)", &[0; 8])).is_err());
+    assert!(Tensor::from_npy_bytes(&npy("<f4", false, "(3,)", &[0; 8])).is_err());
+    Ok(())
+}
+
+#[test]
+fn npy_round_trip() -> anyhow::Result<()> {
+    let t = Tensor::new(vec![0.5, -1.0, 3.0, 1e-3, 250.0, -7.25], vec![2, 1, 3]);
+    let path = temp("t.npy");
+    t.write_npy(&path)?;
+    let back = Tensor::load(&path)?;
+    assert_eq!((back.shape(), back.data()), (t.shape(), t.data()));
+
+    for (data_type, expected) in [
+        (DataType::Int8, vec![0.0, -1.0, 3.0, 0.0, 127.0, -7.0]),
+        (DataType::Uint16, vec![0.0, 0.0, 3.0, 0.0, 250.0, 0.0]),
+        (DataType::Float16, vec![0.5, -1.0, 3.0, 0.0010004044, 250.0, -7.25]),
+        (DataType::Double, t.data().to_vec()),
+    ] {
+        t.write_npy_as(&path, data_type)?;
+        let back = Tensor::read_npy(&path)?;
+        assert_eq!(back.shape(), t.shape());
+        assert_eq!(back.to_vec(), expected, "{:?}", data_type);
+        // Half-precision, 8-bit and 32-bit integer tensors keep their element type
+        let native = matches!(data_type, DataType::Float16 | DataType::Bfloat16 | DataType::Int8 | DataType::Uint8 | DataType::Int32);
+        assert_eq!(back.element_type() != ElementType::F32, native, "{:?}", data_type);
+    }
+    assert!(t.to_npy_bytes_as(DataType::Bfloat16).is_err());
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn npz_archives() -> anyhow::Result<()> {
+    // Written by Python's zipfile the way numpy.savez_compressed does (deflated, Zip64 headers)
+    let path = temp("numpy.npz");
+    std::fs::write(&path, include_bytes!("data/numpy_style.npz"))?;
+    let arrays = Tensor::read_npz(&path)?;
+    assert_eq!(arrays.len(), 2);
+    assert_eq!(arrays[0].0, "a");
+    assert_eq!((arrays[0].1.shape(), arrays[0].1.data()), ([2, 3].as_slice(), [0.0, 1.0, 2.0, 3.0, 4.0, 5.0].as_slice()));
+    assert_eq!(arrays[1].0, "b");
+    assert_eq!((arrays[1].1.shape(), arrays[1].1.data()), ([2, 2].as_slice(), [1.0, 2.0, 3.0, 4.0].as_slice()));
+
+    let x = Tensor::new((0..100).map(|i| i as f32).collect(), vec![10, 10]);
+    let y = Tensor::new(vec![-1.0], vec![1]);
+    for compressed in [false, true] {
+        Tensor::write_npz(&path, &[("x", &x), ("nested/y", &y)], compressed)?;
+        let arrays = Tensor::read_npz(&path)?;
+        let names: Vec<&str> = arrays.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["x", "nested/y"]);
+        assert_eq!(arrays[0].1.data(), x.data());
+        assert_eq!(arrays[1].1.shape(), [1]);
+    }
+
+    let mut corrupt = std::fs::read(&path)?;
+    corrupt[40] ^= 0xff;
+    std::fs::write(&path, &corrupt)?;
+    assert!(Tensor::read_npz(&path).is_err());
+
+    // Sizes from a corrupt central directory: a Zip64 compressed size running
+    // past the end of memory, and an entry inflating beyond its declared size
+    let zeros = Tensor::new(vec![0.0; 1 << 20], vec![1 << 20]);
+    Tensor::write_npz(&path, &[("zeros", &zeros)], true)?;
+    let archive = std::fs::read(&path)?;
+    let central = archive.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    let name_end = central + 46 + u16::from_le_bytes([archive[central + 28], archive[central + 29]]) as usize;
+    let mut overflowing = archive.clone();
+    overflowing[central + 20..central + 24].copy_from_slice(&u32::MAX.to_le_bytes());
+    overflowing[central + 30..central + 32].copy_from_slice(&12u16.to_le_bytes());
+    let zip64 = [&1u16.to_le_bytes()[..], &8u16.to_le_bytes(), &u64::MAX.to_le_bytes()].concat();
+    overflowing.splice(name_end..name_end, zip64);
+    let mut bomb = archive;
+    bomb[central + 24..central + 28].copy_from_slice(&128u32.to_le_bytes());
+    for (corrupt, message) in [(overflowing, "Truncated .npz entry"), (bomb, "Cannot inflate .npz entry")] {
+        std::fs::write(&path, &corrupt)?;
+        let err = Tensor::read_npz(&path).err().unwrap();
+        assert!(err.to_string().contains(message), "{}", err);
+    }
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn tensor_proto_all_types() -> anyhow::Result<()> {
+    let t = Tensor::new(vec![1.0, -2.0, 3.5, 0.0], vec![2, 2]);
+    for data_type in [
+        DataType::Float, DataType::Double, DataType::Float16, DataType::Bfloat16, DataType::Int8, DataType::Int16,
+        DataType::Int32, DataType::Int64, DataType::Uint8, DataType::Uint16, DataType::Uint32, DataType::Uint64,
+        DataType::Bool,
+    ] {
+        let proto = t.to_proto_as("t", data_type as i32)?;
+        assert_eq!(proto.dims, [2, 2]);
+        let back = Tensor::from_proto(&proto)?;
+        let expected: Vec<f32> = t.data().iter()
+            .map(|&v| match data_type {
+                DataType::Bool => (v != 0.0) as u8 as f32,
+                DataType::Uint8 | DataType::Uint16 | DataType::Uint32 | DataType::Uint64 => v.max(0.0).trunc(),
+                DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => v.trunc(),
+                _ => v,
+            })
+            .collect();
+        assert_eq!(back.to_vec(), expected, "{:?}", data_type);
+        // Half-precision, 8-bit and 32-bit integer tensors keep their element type
+        let native = matches!(data_type, DataType::Float16 | DataType::Bfloat16 | DataType::Int8 | DataType::Uint8 | DataType::Int32);
+        assert_eq!(back.element_type() != ElementType::F32, native, "{:?}", data_type);
+    }
+    assert!(t.to_proto_as("t", DataType::String as i32).is_err());
+
+    // Typed fields instead of raw_data
+    let typed = TensorProto {
+        dims: vec![2],
+        data_type: DataType::Float16 as i32,
+        int32_data: vec![0x3c00, 0xbc00],
+        ..Default::default()
+    };
+    let half = Tensor::from_proto(&typed)?;
+    assert_eq!(half.element_type(), ElementType::F16);
+    assert_eq!(half.to_vec(), [1.0, -1.0]);
+    let typed = TensorProto { dims: vec![2], data_type: DataType::Uint64 as i32, uint64_data: vec![3, 9], ..Default::default() };
+    assert_eq!(Tensor::from_proto(&typed)?.data(), [3.0, 9.0]);
+    let wrong = TensorProto { dims: vec![3], data_type: DataType::Double as i32, double_data: vec![1.0], ..Default::default() };
+    assert!(Tensor::from_proto(&wrong).is_err());
+
+    let path = temp("t.pb");
+    t.write_pb_as(&path, "input_0", DataType::Int64)?;
+    let back = Tensor::load(&path)?;
+    assert_eq!(back.data(), [1.0, -2.0, 3.0, 0.0]);
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! Sessions emit `optimize`, `run` and per-node `tracing` spans to whatever subscriber is installed.
+
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::runtime::{InferenceSession, SessionOptions};
+use neuroxyde::tensor::Tensor;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+/// A span as seen by the subscriber: its name and every field recorded on it.
+#[derive(Debug, Default)]
+struct Recorded {
+    name: &'static str,
+    fields: HashMap<String, String>,
+}
+
+impl Visit for Recorded {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.fields.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+/// Keeps every span up to `max_level`.
+struct Recorder {
+    max_level: Level,
+    spans: Arc<Mutex<Vec<Recorded>>>,
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.max_level
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let mut span = Recorded { name: attrs.metadata().name(), ..Default::default() };
+        attrs.record(&mut span);
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span);
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, id: &Id, values: &Record<'_>) {
+        values.record(&mut self.spans.lock().unwrap()[id.into_u64() as usize - 1]);
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+    fn event(&self, _event: &Event<'_>) {}
+    fn enter(&self, _span: &Id) {}
+    fn exit(&self, _span: &Id) {}
+}
+
+/// Runs a two-node graph with default options under a `Recorder`.
+fn record_run(max_level: Level) -> anyhow::Result<Vec<Recorded>> {
+    let mut builder = Graph::builder("traced");
+    builder
+        .input("x", DataType::Float, &[2, 3])?
+        .node("Relu", &["x"], &["r"], &[])?
+        .node("Neg", &["r"], &["y"], &[])?
+        .output("y")?;
+    let graph = builder.build()?;
+
+    let spans = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Recorder { max_level, spans: spans.clone() };
+    tracing::subscriber::with_default(recorder, || -> anyhow::Result<()> {
+        let session = InferenceSession::with_options(graph, SessionOptions::new())?;
+        session.run(&[Tensor::new(vec![1.0, -2.0, 3.0, -4.0, 5.0, -6.0], vec![2, 3])])?;
+        Ok(())
+    })?;
+    let spans = std::mem::take(&mut *spans.lock().unwrap());
+    Ok(spans)
+}
+
+#[test]
+fn node_spans_carry_shapes_and_timing() -> anyhow::Result<()> {
+    let spans = record_run(Level::TRACE)?;
+    let nodes: Vec<&Recorded> = spans.iter().filter(|s| s.name == "node").collect();
+    assert_eq!(nodes.len(), 2);
+    assert_eq!(nodes[0].fields["op_type"], "Relu");
+    assert_eq!(nodes[1].fields["op_type"], "Neg");
+    for node in nodes {
+        assert_eq!(node.fields["input_shapes"], "[[2, 3]]");
+        assert_eq!(node.fields["output_shape"], "[[2, 3]]");
+        assert!(node.fields.contains_key("elapsed_us"), "{:?}", node.fields);
+    }
+    Ok(())
+}
+
+#[test]
+fn session_spans_are_emitted_with_default_options() -> anyhow::Result<()> {
+    let spans = record_run(Level::TRACE)?;
+    let names: Vec<&str> = spans.iter().map(|s| s.name).filter(|&n| n != "pass").collect();
+    assert_eq!(names, ["optimize", "run", "node", "node"]);
+    Ok(())
+}
+
+#[test]
+fn subscriber_filters_node_spans() -> anyhow::Result<()> {
+    let spans = record_run(Level::INFO)?;
+    let names: Vec<&str> = spans.iter().map(|s| s.name).collect();
+    assert_eq!(names, ["optimize", "run"]);
+    Ok(())
+}
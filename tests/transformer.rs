@@ -0,0 +1,364 @@
+//! com.microsoft transformer operators, against scalar references and
+//! against each other: Attention, MultiHeadAttention and GroupQueryAttention
+//! agree wherever their inputs describe the same computation.
+
+use neuroxyde::graph::{AttributeValue, Graph};
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::runtime::InferenceSession;
+use neuroxyde::tensor::Tensor;
+
+const MS: &str = "com.microsoft";
+
+/// Deterministic values in [-1, 1].
+fn values(len: usize, seed: f32) -> Vec<f32> {
+    (0..len).map(|i| ((i as f32 + 1.0) * 0.7 + seed).sin()).collect()
+}
+
+/// Runs a single com.microsoft node; `None` inputs are left out.
+fn run(op: &str, inputs: &[(&str, Option<&Tensor>)], outputs: &[&str], attrs: &[(&str, AttributeValue)]) -> anyhow::Result<Vec<Tensor>> {
+    let mut builder = Graph::builder(op);
+    let mut names = Vec::new();
+    let mut feeds = Vec::new();
+    for (name, tensor) in inputs {
+        match tensor {
+            Some(t) => {
+                let shape: Vec<i64> = t.shape().iter().map(|&d| d as i64).collect();
+                builder.input(name, DataType::Float, &shape)?;
+                names.push(*name);
+                feeds.push((*t).clone());
+            }
+            None => names.push(""),
+        }
+    }
+    builder.domain_node(MS, op, &names, outputs, attrs)?;
+    for output in outputs {
+        builder.output(output)?;
+    }
+    InferenceSession::new(builder.build()?)?.run(&feeds)
+}
+
+fn assert_close(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len());
+    for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+        assert!((a - e).abs() < 1e-4, "element {}: {} != {}\n{:?}\n{:?}", i, a, e, actual, expected);
+    }
+}
+
+fn matmul(a: &[f32], b: &[f32], m: usize, k: usize, n: usize) -> Vec<f32> {
+    let mut out = vec![0.0; m * n];
+    for i in 0..m {
+        for j in 0..n {
+            out[i * n + j] = (0..k).map(|kk| a[i * k + kk] * b[kk * n + j]).sum();
+        }
+    }
+    out
+}
+
+/// Columns `start..start + width` of a matrix with `cols` columns.
+fn columns(x: &[f32], cols: usize, start: usize, width: usize) -> Vec<f32> {
+    x.chunks(cols).flat_map(|row| row[start..start + width].to_vec()).collect()
+}
+
+#[test]
+fn gelu_variants_and_skip_layer_normalization() -> anyhow::Result<()> {
+    let gelu = |x: f32| 0.5 * x * (1.0 + erf(x / std::f32::consts::SQRT_2));
+    let x = Tensor::new(vec![-1.5, 0.0, 0.5, 2.0, -0.25, 1.0], vec![2, 3]);
+    let bias = Tensor::new(vec![0.5, -0.5, 1.0], vec![3]);
+    let expected: Vec<f32> = x.data().iter().enumerate().map(|(i, v)| gelu(v + bias.data()[i % 3])).collect();
+    assert_close(run("BiasGelu", &[("x", Some(&x)), ("b", Some(&bias))], &["y"], &[])?[0].data(), &expected);
+    let fast = run("FastGelu", &[("x", Some(&x)), ("b", Some(&bias))], &["y"], &[])?;
+    assert!(fast[0].data().iter().zip(&expected).all(|(a, e)| (a - e).abs() < 1e-3));
+
+    // The skip repeats over the batch; every output requested
+    let input = Tensor::new(values(12, 0.0), vec![2, 2, 3]);
+    let skip = Tensor::new(values(6, 1.0), vec![2, 3]);
+    let gamma = Tensor::new(vec![1.0, 2.0, 0.5], vec![3]);
+    let beta = Tensor::new(vec![0.0, 0.1, -0.1], vec![3]);
+    let outputs = run(
+        "SkipLayerNormalization",
+        &[("input", Some(&input)), ("skip", Some(&skip)), ("gamma", Some(&gamma)), ("beta", Some(&beta)), ("bias", Some(&bias))],
+        &["y", "mean", "inv_std", "sum"],
+        &[("epsilon", 1e-5f32.into())],
+    )?;
+    let sum: Vec<f32> = input.data().iter().enumerate().map(|(i, v)| v + skip.data()[i % 6] + bias.data()[i % 3]).collect();
+    assert_close(outputs[3].data(), &sum);
+    assert_eq!(outputs[1].shape(), [2, 2, 1]);
+    for (r, row) in sum.chunks(3).enumerate() {
+        let mean = row.iter().sum::<f32>() / 3.0;
+        let inv_std = 1.0 / (row.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / 3.0 + 1e-5).sqrt();
+        assert_close(&outputs[1].data()[r..r + 1], &[mean]);
+        assert_close(&outputs[2].data()[r..r + 1], &[inv_std]);
+        let expected: Vec<f32> = (0..3).map(|j| (row[j] - mean) * inv_std * gamma.data()[j] + beta.data()[j]).collect();
+        assert_close(&outputs[0].data()[r * 3..r * 3 + 3], &expected);
+    }
+    Ok(())
+}
+
+/// erf, by the Abramowitz-Stegun approximation (error below 1.5e-7).
+fn erf(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs() as f64);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let y = 1.0 - poly * (-(x as f64) * (x as f64)).exp();
+    (if x < 0.0 { -y } else { y }) as f32
+}
+
+#[test]
+fn embed_layer_normalization_sums_the_three_embeddings() -> anyhow::Result<()> {
+    let ids = Tensor::new(vec![3.0, 1.0, 0.0, 2.0], vec![2, 2]);
+    let segments = Tensor::new(vec![0.0, 1.0, 1.0, 1.0], vec![2, 2]);
+    let word = Tensor::new(values(8, 0.0), vec![4, 2]);
+    let position = Tensor::new(values(6, 2.0), vec![3, 2]);
+    let segment = Tensor::new(values(4, 4.0), vec![2, 2]);
+    let gamma = Tensor::new(vec![1.0, 1.0], vec![2]);
+    let beta = Tensor::new(vec![0.0, 0.0], vec![2]);
+    let mask = Tensor::new(vec![1.0, 1.0, 1.0, 0.0], vec![2, 2]);
+    let outputs = run(
+        "EmbedLayerNormalization",
+        &[
+            ("input_ids", Some(&ids)), ("segment_ids", Some(&segments)), ("word", Some(&word)), ("position", Some(&position)),
+            ("segment", Some(&segment)), ("gamma", Some(&gamma)), ("beta", Some(&beta)), ("mask", Some(&mask)),
+        ],
+        &["y", "mask_index", "sum"],
+        &[("epsilon", 1e-5f32.into())],
+    )?;
+    assert_eq!(outputs[1].data(), [2.0, 1.0]);
+    for token in 0..4 {
+        let (id, seg, pos) = (ids.data()[token] as usize, segments.data()[token] as usize, token % 2);
+        let sum: Vec<f32> = (0..2).map(|j| word.data()[id * 2 + j] + position.data()[pos * 2 + j] + segment.data()[seg * 2 + j]).collect();
+        assert_close(&outputs[2].data()[token * 2..token * 2 + 2], &sum);
+        // Two values normalize to about -1 and 1
+        let half = (sum[0] - sum[1]) / 2.0;
+        let normalized = half / (half * half + 1e-5).sqrt();
+        assert_close(&outputs[0].data()[token * 2..token * 2 + 2], &[normalized, -normalized]);
+    }
+
+    let out_of_range = Tensor::new(vec![7.0, 0.0, 0.0, 0.0], vec![2, 2]);
+    let err = run(
+        "EmbedLayerNormalization",
+        &[
+            ("input_ids", Some(&out_of_range)), ("segment_ids", None), ("word", Some(&word)), ("position", Some(&position)),
+            ("segment", None), ("gamma", Some(&gamma)), ("beta", Some(&beta)),
+        ],
+        &["y"],
+        &[],
+    ).err().unwrap();
+    assert!(err.to_string().contains("word index 7 out of range"), "{}", err);
+    Ok(())
+}
+
+/// Scaled dot-product attention of one batch entry, `q` [seq, heads * size]
+/// and `k`/`v` [total, heads * size], `bias(n, i, j)` added to the scores.
+fn reference(q: &[f32], k: &[f32], v: &[f32], heads: usize, size: usize, bias: &dyn Fn(usize, usize, usize) -> f32) -> Vec<f32> {
+    let width = heads * size;
+    let (seq, total) = (q.len() / width, k.len() / width);
+    let mut out = vec![0.0; seq * width];
+    for n in 0..heads {
+        for i in 0..seq {
+            let scores: Vec<f32> = (0..total)
+                .map(|j| (0..size).map(|d| q[i * width + n * size + d] * k[j * width + n * size + d]).sum::<f32>() / (size as f32).sqrt() + bias(n, i, j))
+                .collect();
+            let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let exps: Vec<f32> = scores.iter().map(|s| (s - max).exp()).collect();
+            let sum: f32 = exps.iter().sum();
+            for d in 0..size {
+                out[i * width + n * size + d] = (0..total).map(|j| exps[j] / sum * v[j * width + n * size + d]).sum();
+            }
+        }
+    }
+    out
+}
+
+/// x [2, 3, 4] projected by w [4, 12] and b [12] into two heads of size 2.
+struct Projection {
+    x: Tensor,
+    w: Tensor,
+    b: Tensor,
+    q: Vec<f32>,
+    k: Vec<f32>,
+    v: Vec<f32>,
+}
+
+fn projection() -> Projection {
+    let x = Tensor::new(values(24, 0.0), vec![2, 3, 4]);
+    let w = Tensor::new(values(48, 3.0), vec![4, 12]);
+    let b = Tensor::new(values(12, 5.0), vec![12]);
+    let qkv: Vec<f32> = matmul(x.data(), w.data(), 6, 4, 12).iter().enumerate().map(|(i, v)| v + b.data()[i % 12]).collect();
+    let (q, k, v) = (columns(&qkv, 12, 0, 4), columns(&qkv, 12, 4, 4), columns(&qkv, 12, 8, 4));
+    Projection { x, w, b, q, k, v }
+}
+
+#[test]
+fn attention_projects_masks_and_caches() -> anyhow::Result<()> {
+    let p = projection();
+    let mask = Tensor::new(vec![1.0, 1.0, 1.0, 1.0, 1.0, 0.0], vec![2, 3]);
+    let heads = [("num_heads", 2i64.into())];
+    let outputs = run("Attention", &[("x", Some(&p.x)), ("w", Some(&p.w)), ("b", Some(&p.b)), ("mask", Some(&mask))], &["y"], &heads)?;
+    assert_eq!(outputs[0].shape(), [2, 3, 4]);
+    for batch in 0..2 {
+        let rows = batch * 12..(batch + 1) * 12;
+        let expected = reference(&p.q[rows.clone()], &p.k[rows.clone()], &p.v[rows.clone()], 2, 2, &|_, _, j| {
+            if mask.data()[batch * 3 + j] == 0.0 { -10000.0 } else { 0.0 }
+        });
+        assert_close(&outputs[0].data()[rows], &expected);
+    }
+
+    // Causal attention over the first two tokens, then the third one on the cached state
+    let causal = [("num_heads", 2i64.into()), ("unidirectional", 1i64.into())];
+    let full = run("Attention", &[("x", Some(&p.x)), ("w", Some(&p.w)), ("b", Some(&p.b))], &["y", "present"], &causal)?;
+    assert_eq!(full[1].shape(), [2, 2, 2, 3, 2]);
+    let prompt = p.x.slice_axis(1, 0, 2, 1)?.to_contiguous();
+    let step = p.x.slice_axis(1, 2, 1, 1)?.to_contiguous();
+    let first = run("Attention", &[("x", Some(&prompt)), ("w", Some(&p.w)), ("b", Some(&p.b))], &["y", "present"], &causal)?;
+    let second = run(
+        "Attention",
+        &[("x", Some(&step)), ("w", Some(&p.w)), ("b", Some(&p.b)), ("mask", None), ("past", Some(&first[1]))],
+        &["y", "present"],
+        &causal,
+    )?;
+    assert_close(second[1].data(), full[1].data());
+    let last = full[0].slice_axis(1, 2, 1, 1)?.to_contiguous();
+    assert_close(second[0].data(), last.data());
+    Ok(())
+}
+
+#[test]
+fn attention_with_uneven_heads_has_no_present() -> anyhow::Result<()> {
+    // Key heads of 1 value, value heads of 4: fine for `y`, but they cannot be stacked into `present`
+    let p = projection();
+    let attrs = [("num_heads", 2i64.into()), ("qkv_hidden_sizes", vec![2i64, 2, 8].into())];
+    let inputs = [("x", Some(&p.x)), ("w", Some(&p.w)), ("b", Some(&p.b))];
+    assert_eq!(run("Attention", &inputs, &["y"], &attrs)?[0].shape(), [2, 3, 8]);
+    let err = run("Attention", &inputs, &["y", "present"], &attrs).err().unwrap();
+    assert!(format!("{:#}", err).contains("key and value heads of one size"), "{:#}", err);
+    Ok(())
+}
+
+#[test]
+fn multi_head_attention_matches_attention_in_every_layout() -> anyhow::Result<()> {
+    let p = projection();
+    let mask = Tensor::new(vec![1.0, 1.0, 0.0, 1.0, 1.0, 1.0], vec![2, 3]);
+    let heads = [("num_heads", 2i64.into())];
+    let expected = run("Attention", &[("x", Some(&p.x)), ("w", Some(&p.w)), ("b", Some(&p.b)), ("mask", Some(&mask))], &["y"], &heads)?;
+
+    // Separate projections, with the bias left to MultiHeadAttention
+    let unbiased = matmul(p.x.data(), p.w.data(), 6, 4, 12);
+    let part = |start| Tensor::new(columns(&unbiased, 12, start, 4), vec![2, 3, 4]);
+    let (q, k, v) = (part(0), part(4), part(8));
+    let separate = run(
+        "MultiHeadAttention",
+        &[("q", Some(&q)), ("k", Some(&k)), ("v", Some(&v)), ("b", Some(&p.b)), ("mask", Some(&mask))],
+        &["y", "present_key", "present_value"],
+        &heads,
+    )?;
+    assert_close(separate[0].data(), expected[0].data());
+    assert_eq!(separate[1].shape(), [2, 2, 3, 2]);
+
+    // Packed [batch, seq, heads, 3, size], biased beforehand
+    let mut packed = Vec::new();
+    for t in 0..6 {
+        for n in 0..2 {
+            for source in [&p.q, &p.k, &p.v] {
+                packed.extend_from_slice(&source[t * 4 + n * 2..t * 4 + n * 2 + 2]);
+            }
+        }
+    }
+    let packed = Tensor::new(packed, vec![2, 3, 2, 3, 2]);
+    let outputs = run("MultiHeadAttention", &[("qkv", Some(&packed)), ("k", None), ("v", None), ("b", None), ("mask", Some(&mask))], &["y"], &heads)?;
+    assert_close(outputs[0].data(), expected[0].data());
+
+    // Keys and values already split into heads, as a cross-attention cache
+    let q = Tensor::new(p.q.clone(), vec![2, 3, 4]);
+    let outputs = run(
+        "MultiHeadAttention",
+        &[("q", Some(&q)), ("k", Some(&separate[1])), ("v", Some(&separate[2])), ("b", None), ("mask", Some(&mask))],
+        &["y"],
+        &heads,
+    )?;
+    assert_close(outputs[0].data(), expected[0].data());
+    Ok(())
+}
+
+/// cos/sin caches for 8 positions and a rotary dimension of 2.
+fn rotary_caches() -> (Tensor, Tensor) {
+    let angles: Vec<f32> = (0..8).map(|p| p as f32 * 0.3).collect();
+    (
+        Tensor::new(angles.iter().map(|a| a.cos()).collect(), vec![8, 1]),
+        Tensor::new(angles.iter().map(|a| a.sin()).collect(), vec![8, 1]),
+    )
+}
+
+#[test]
+fn rotary_embedding_rotates_each_head() -> anyhow::Result<()> {
+    let (cos, sin) = rotary_caches();
+    // Two heads of size 2 per token, positions counted from 1
+    let x = Tensor::new(vec![1.0, 0.0, 0.0, 2.0, 1.0, 1.0, -1.0, 0.5], vec![1, 2, 4]);
+    let start = Tensor::new(vec![1.0], vec![1]);
+    let outputs = run(
+        "RotaryEmbedding",
+        &[("x", Some(&x)), ("positions", Some(&start)), ("cos", Some(&cos)), ("sin", Some(&sin))],
+        &["y"],
+        &[("num_heads", 2i64.into())],
+    )?;
+    let turn = |x: f32, y: f32, p: f32| [x * (0.3 * p).cos() - y * (0.3 * p).sin(), y * (0.3 * p).cos() + x * (0.3 * p).sin()];
+    let expected: Vec<f32> = [turn(1.0, 0.0, 1.0), turn(0.0, 2.0, 1.0), turn(1.0, 1.0, 2.0), turn(-1.0, 0.5, 2.0)].concat();
+    assert_close(outputs[0].data(), &expected);
+    Ok(())
+}
+
+#[test]
+fn group_query_attention_decodes_incrementally() -> anyhow::Result<()> {
+    // Four query heads sharing two key/value heads, all of size 2
+    let query = Tensor::new(values(24, 0.0), vec![1, 3, 8]);
+    let key = Tensor::new(values(12, 1.0), vec![1, 3, 4]);
+    let value = Tensor::new(values(12, 2.0), vec![1, 3, 4]);
+    let attrs = [("num_heads", 4i64.into()), ("kv_num_heads", 2i64.into())];
+    let lens = |total: f32| (Tensor::new(vec![total - 1.0], vec![1]), Tensor::new(vec![total], vec![]));
+    let gqa = |q: &Tensor, k: &Tensor, v: &Tensor, past: Option<(&Tensor, &Tensor)>, total: f32, extra: &[(&str, AttributeValue)]| {
+        let (seqlens, total) = lens(total);
+        let (cos, sin) = rotary_caches();
+        let mut attributes = attrs.to_vec();
+        attributes.extend_from_slice(extra);
+        run(
+            "GroupQueryAttention",
+            &[
+                ("q", Some(q)), ("k", Some(k)), ("v", Some(v)), ("past_key", past.map(|p| p.0)), ("past_value", past.map(|p| p.1)),
+                ("seqlens_k", Some(&seqlens)), ("total", Some(&total)), ("cos", Some(&cos)), ("sin", Some(&sin)),
+            ],
+            &["y", "present_key", "present_value"],
+            &attributes,
+        )
+    };
+    let full = gqa(&query, &key, &value, None, 3.0, &[])?;
+    assert_eq!(full[1].shape(), [1, 2, 3, 2]);
+
+    // Causal MultiHeadAttention with each key/value head repeated for its two query heads
+    let repeat = |t: &Tensor| Tensor::new(t.data().chunks(2).flat_map(|h| [h, h].concat()).collect(), vec![1, 3, 8]);
+    let (keys, vals) = (repeat(&key), repeat(&value));
+    let expected = run(
+        "MultiHeadAttention",
+        &[("q", Some(&query)), ("k", Some(&keys)), ("v", Some(&vals))],
+        &["y"],
+        &[("num_heads", 4i64.into()), ("unidirectional", 1i64.into())],
+    )?;
+    assert_close(full[0].data(), expected[0].data());
+
+    // Two tokens, then the third one on the cache
+    let first_two = |t: &Tensor| t.slice_axis(1, 0, 2, 1).map(|t| t.to_contiguous());
+    let third = |t: &Tensor| t.slice_axis(1, 2, 1, 1).map(|t| t.to_contiguous());
+    let prompt = gqa(&first_two(&query)?, &first_two(&key)?, &first_two(&value)?, None, 2.0, &[])?;
+    let step = gqa(&third(&query)?, &third(&key)?, &third(&value)?, Some((&prompt[1], &prompt[2])), 3.0, &[])?;
+    assert_close(step[0].data(), third(&full[0])?.data());
+    assert_close(step[1].data(), full[1].data());
+
+    // do_rotary is RotaryEmbedding applied to the query and key first
+    let rotated = gqa(&query, &key, &value, None, 3.0, &[("do_rotary", 1i64.into())])?;
+    let (cos, sin) = rotary_caches();
+    let start = Tensor::new(vec![0.0], vec![1]);
+    let rotate = |t: &Tensor, heads: i64| -> anyhow::Result<Tensor> {
+        let inputs = [("x", Some(t)), ("positions", Some(&start)), ("cos", Some(&cos)), ("sin", Some(&sin))];
+        Ok(run("RotaryEmbedding", &inputs, &["y"], &[("num_heads", heads.into())])?.remove(0))
+    };
+    let unrotated = gqa(&rotate(&query, 4)?, &rotate(&key, 2)?, &value, None, 3.0, &[])?;
+    assert_close(rotated[0].data(), unrotated[0].data());
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! Strided views share their buffer, and view operators run without copying.
+
+use neuroxyde::graph::Graph;
+use neuroxyde::onnx::onnx_proto::tensor_proto::DataType;
+use neuroxyde::runtime::InferenceSession;
+use neuroxyde::tensor::Tensor;
+
+fn iota(shape: &[usize]) -> Tensor {
+    let len = shape.iter().product();
+    Tensor::new((0..len).map(|v| v as f32).collect(), shape.to_vec())
+}
+
+#[test]
+fn permute_and_slice_are_views() -> anyhow::Result<()> {
+    let x = iota(&[2, 3, 4]);
+
+    let t = x.permute(&[2, 0, 1])?;
+    assert!(t.shares_storage(&x));
+    assert!(!t.is_contiguous());
+    assert_eq!(t.shape(), [4, 2, 3]);
+    assert_eq!(t.strides(), [1, 12, 4]);
+    assert_eq!(&t.to_vec()[..6], [0.0, 4.0, 8.0, 12.0, 16.0, 20.0]);
+
+    // x[1, ::-2, 1:3]
+    let s = x.slice_axis(0, 1, 1, 1)?.slice_axis(1, 2, 2, -2)?.slice_axis(2, 1, 2, 1)?;
+    assert!(s.shares_storage(&x));
+    assert_eq!(s.shape(), [1, 2, 2]);
+    assert_eq!(s.offset(), 21);
+    assert_eq!(s.to_vec(), [21.0, 22.0, 13.0, 14.0]);
+
+    assert!(x.permute(&[0, 0, 1]).is_err());
+    assert!(x.slice_axis(1, 1, 3, 1).is_err());
+    assert!(x.slice_axis(1, 0, 2, 0).is_err());
+    Ok(())
+}
+
+#[test]
+fn reshape_shares_contiguous_buffers_only() -> anyhow::Result<()> {
+    let x = iota(&[2, 6]);
+    let r = x.reshape(vec![3, 4])?;
+    assert!(r.shares_storage(&x));
+    assert_eq!(r.data(), x.data());
+
+    // A row slice is still contiguous, with an offset
+    let row = x.slice_axis(0, 1, 1, 1)?.reshape(vec![6])?;
+    assert!(row.shares_storage(&x));
+    assert_eq!(row.data(), [6.0, 7.0, 8.0, 9.0, 10.0, 11.0]);
+
+    let t = x.permute(&[1, 0])?.reshape(vec![12])?;
+    assert!(!t.shares_storage(&x));
+    assert_eq!(&t.data()[..4], [0.0, 6.0, 1.0, 7.0]);
+
+    assert!(x.reshape(vec![5]).is_err());
+    Ok(())
+}
+
+#[test]
+fn into_data_reuses_unique_buffers() {
+    let x = iota(&[4]);
+    let ptr = x.data().as_ptr();
+    let data = x.into_data();
+    assert_eq!(data.as_ptr(), ptr);
+
+    let x = iota(&[4]);
+    let shared = x.clone();
+    assert!(shared.shares_storage(&x));
+    assert_eq!(x.into_data(), shared.data());
+
+    let col = iota(&[2, 2]).slice_axis(1, 1, 1, 1).unwrap();
+    assert_eq!(col.into_data(), [1.0, 3.0]);
+}
+
+#[test]
+#[should_panic(expected = "non-contiguous")]
+fn data_rejects_strided_views() {
+    let x = iota(&[2, 3]).permute(&[1, 0]).unwrap();
+    let _ = x.data();
+}
+
+#[test]
+fn view_operators_feed_regular_kernels() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("views");
+    builder
+        .input("x", DataType::Float, &[2, 3, 4])?
+        .initializer_as("starts", Tensor::new(vec![-1.0, 1.0], vec![2]), DataType::Int64)?
+        .initializer_as("ends", Tensor::new(vec![-1e10, 4.0], vec![2]), DataType::Int64)?
+        .initializer_as("axes", Tensor::new(vec![0.0, 2.0], vec![2]), DataType::Int64)?
+        .initializer_as("steps", Tensor::new(vec![-2.0, 2.0], vec![2]), DataType::Int64)?
+        .initializer_as("flat", Tensor::new(vec![8.0], vec![1]), DataType::Int64)?
+        .node("Transpose", &["x"], &["t"], &[("perm", vec![1i64, 0, 2].into())])?
+        .node("Slice", &["t", "starts", "ends", "axes", "steps"], &["s"], &[])?
+        .node("Reshape", &["s", "flat"], &["r"], &[])?
+        .node("Neg", &["r"], &["y"], &[])?
+        .output("y")?
+        .output("s")?;
+    let session = InferenceSession::new(builder.build()?)?;
+
+    let outputs = session.run(&[iota(&[2, 3, 4])])?;
+    // t[::-2, :, 1::2] of shape [2, 2, 2], taken from rows 2 and 0 of each batch
+    assert_eq!(outputs[1].shape(), [2, 2, 2]);
+    assert_eq!(outputs[1].data(), [9.0, 11.0, 21.0, 23.0, 1.0, 3.0, 13.0, 15.0]);
+    assert_eq!(outputs[0].data(), [-9.0, -11.0, -21.0, -23.0, -1.0, -3.0, -13.0, -15.0]);
+
+    // Strided inputs are accepted too
+    let input = iota(&[3, 2, 4]).permute(&[1, 0, 2])?;
+    let copied = session.run(&[input.to_contiguous()])?;
+    let strided = session.run(&[input])?;
+    assert_eq!(strided[0].data(), copied[0].data());
+    Ok(())
+}
+
+#[test]
+fn sessions_share_initializers() -> anyhow::Result<()> {
+    let mut builder = Graph::builder("shared");
+    builder
+        .input("x", DataType::Float, &[1, 2])?
+        .initializer("w", Tensor::new(vec![1.0, 2.0, 3.0, 4.0], vec![2, 2]))?
+        .node("MatMul", &["x", "w"], &["y"], &[])?
+        .output("y")?;
+    let graph = builder.build()?;
+
+    let first = InferenceSession::new(graph.clone())?;
+    let second = InferenceSession::new(graph)?;
+    assert!(first.graph.initializers["w"].shares_storage(&second.graph.initializers["w"]));
+
+    let x = Tensor::new(vec![1.0, 1.0], vec![1, 2]);
+    let y = first.run(std::slice::from_ref(&x))?;
+    assert_eq!(y[0].data(), second.run(&[x])?[0].data());
+    Ok(())
+}